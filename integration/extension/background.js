@@ -129,7 +129,7 @@ if (typeof chrome !== 'undefined') {
         debugLog("处理音频下载", { url, filename });
       }
       
-      if (!url || (!url.startsWith("http://") && !url.startsWith("https://"))) {
+      if (!url || !/^(https?|ftps?):\/\//i.test(url)) {
         debugLog("无效的URL，跳过下载", { url });
         return;
       }
@@ -174,6 +174,14 @@ if (typeof chrome !== 'undefined') {
         // 测试与本地主机的连接
         testConnection(sendResponse);
         return true; // 表示会异步响应
+      } else if (message.action === "list_active") {
+        // 弹窗请求活动下载列表
+        requestNativeHost({ action: "list", active: true }, sendResponse);
+        return true;
+      } else if (["pause", "resume", "cancel"].includes(message.action)) {
+        // 弹窗中的暂停/继续/取消
+        requestNativeHost({ action: message.action, id: message.id }, sendResponse);
+        return true;
       } else if (message.action === "export_logs") {
         // 导出日志
         exportLogs();
@@ -187,97 +195,104 @@ if (typeof chrome !== 'undefined') {
   console.error("Chrome 扩展 API 不可用");
 }
 
-// 测试与本地主机的连接
-function testConnection(sendResponse) {
-  debugLog("测试与本地主机的连接");
-  
-  try {
-    if (chrome.runtime && chrome.runtime.connectNative) {
-      debugLog("尝试连接本地主机", { hostName: HOST_NAME });
-      const port = chrome.runtime.connectNative(HOST_NAME);
-      
-      debugLog("连接成功，发送测试消息");
-      
-      if (port && port.onMessage) {
-        port.onMessage.addListener((response) => {
-          debugLog("收到测试连接响应", { response });
-          sendResponse({ success: true, message: response?.message || "连接成功" });
-          port.disconnect();
-        });
-      }
-      
-      if (port && port.onDisconnect) {
-        port.onDisconnect.addListener(() => {
-          if (chrome.runtime.lastError) {
-            debugLog("测试连接断开（错误）", { error: chrome.runtime.lastError });
-            sendResponse({ success: false, message: chrome.runtime.lastError.message });
-          } else {
-            debugLog("测试连接正常关闭");
-            // 连接已正常关闭，但可能还没有收到响应
-          }
-        });
-      }
-      
-      if (port && port.postMessage) {
-        const message = { action: "test_connection" };
-        debugLog("发送测试消息", { message });
-        port.postMessage(message);
-      }
+// 与 Native Host 的长连接：命令按 seq 对应回复，任务事件转发给弹窗
+let nativePort = null;
+let nextSeq = 1;
+const pendingRequests = new Map();
+
+function getNativePort() {
+  if (nativePort) {
+    return nativePort;
+  }
+  if (!(chrome.runtime && chrome.runtime.connectNative)) {
+    throw new Error("Chrome runtime API 不可用");
+  }
+  debugLog("尝试连接本地主机", { hostName: HOST_NAME });
+  const port = chrome.runtime.connectNative(HOST_NAME);
+
+  port.onMessage.addListener((msg) => {
+    if (msg && msg.type === "event") {
+      handleNativeEvent(msg);
+      return;
+    }
+    const callback = pendingRequests.get(msg?.seq);
+    if (callback) {
+      pendingRequests.delete(msg.seq);
+      callback(msg);
     } else {
-      debugLog("Chrome runtime API 不可用");
-      sendResponse({ success: false, message: "Chrome runtime API 不可用" });
+      debugLog("收到未对应请求的响应", { msg });
+    }
+  });
+
+  port.onDisconnect.addListener(() => {
+    const error = chrome.runtime.lastError;
+    debugLog("Native Host 连接断开", { error });
+    if (error) {
+      console.warn("Multidown 扩展: Native Host 连接断开。", error);
+    }
+    nativePort = null;
+    for (const callback of pendingRequests.values()) {
+      callback({ success: false, message: error?.message || "Native Host 连接已断开" });
     }
+    pendingRequests.clear();
+  });
+
+  nativePort = port;
+  // 连接建立后订阅任务事件；主程序版本过旧时订阅失败，不影响其他命令
+  requestNativeHost({ action: "subscribe" }, (response) => {
+    debugLog("订阅任务事件", { response });
+  });
+  return port;
+}
+
+// 发送一条命令并在收到对应响应时回调
+function requestNativeHost(message, callback) {
+  const seq = nextSeq++;
+  try {
+    const port = getNativePort();
+    pendingRequests.set(seq, callback || (() => {}));
+    debugLog("发送消息数据", { message: { ...message, seq } });
+    port.postMessage({ ...message, seq });
   } catch (e) {
-    debugLog("测试连接失败", { error: e });
-    sendResponse({ success: false, message: e.message });
+    pendingRequests.delete(seq);
+    debugLog("连接 Native Host 失败", { error: e });
+    console.warn("Multidown 扩展: 无法连接 Native Host。请确保已安装 Multidown 并已注册 Native Messaging Host。", e);
+    if (callback) {
+      callback({ success: false, message: e.message });
+    }
+  }
+}
+
+// 任务事件：转发给打开着的弹窗，下载完成或失败时在控制台提示
+function handleNativeEvent(event) {
+  if (event.event === "finished") {
+    console.log("Multidown: 下载完成", event.filename);
+  } else if (event.event === "failed") {
+    console.warn("Multidown: 下载失败", event.filename, event.error);
   }
+  chrome.runtime.sendMessage({ type: "multidown_event", event }).catch(() => {
+    // 弹窗未打开时没有接收方
+  });
+}
+
+// 测试与本地主机的连接
+function testConnection(sendResponse) {
+  debugLog("测试与本地主机的连接");
+  requestNativeHost({ action: "test_connection" }, (response) => {
+    debugLog("收到测试连接响应", { response });
+    sendResponse({ success: !!response?.success, message: response?.message || "连接成功" });
+  });
 }
 
 // 发送消息到本地主机
 function sendToNativeHost(action, data) {
   debugLog("发送消息到本地主机", { action, data });
-  
-  try {
-    if (chrome.runtime && chrome.runtime.connectNative) {
-      debugLog("尝试连接本地主机", { hostName: HOST_NAME });
-      const port = chrome.runtime.connectNative(HOST_NAME);
-      
-      debugLog("连接成功，设置消息监听器");
-      
-      if (port && port.onMessage) {
-        port.onMessage.addListener((response) => {
-          debugLog("收到本地主机响应", { response });
-          if (response && response.success) {
-            console.log("Multidown:", response.message);
-          } else {
-            console.warn("Multidown:", response?.message || "未知错误");
-          }
-        });
-      }
-      
-      if (port && port.onDisconnect) {
-        port.onDisconnect.addListener(() => {
-          if (chrome.runtime.lastError) {
-            debugLog("Native Host 连接断开", { error: chrome.runtime.lastError });
-            console.warn("Multidown 扩展: Native Host 连接断开。", chrome.runtime.lastError);
-          } else {
-            debugLog("Native Host 连接正常关闭");
-          }
-        });
-      }
-      
-      if (port && port.postMessage) {
-        const message = { action, ...data };
-        debugLog("发送消息数据", { message });
-        port.postMessage(message);
-        
-        debugLog("消息发送完成");
-      }
+  requestNativeHost({ action, ...data }, (response) => {
+    debugLog("收到本地主机响应", { response });
+    if (response && response.success) {
+      console.log("Multidown:", response.message);
     } else {
-      debugLog("Chrome runtime API 不可用");
+      console.warn("Multidown:", response?.message || "未知错误");
     }
-  } catch (e) {
-    debugLog("连接 Native Host 失败", { error: e });
-    console.warn("Multidown 扩展: 无法连接 Native Host。请确保已安装 Multidown 并已注册 Native Messaging Host。", e);
-  }
+  });
 }
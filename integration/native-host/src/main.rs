@@ -1,16 +1,27 @@
 //! Chrome Native Messaging Host for Multidown.
-//! 从 Chrome 扩展接收链接，通过 TCP 转发给主程序。
+//! 从 Chrome 扩展接收命令，通过本机 IPC 长连接转发给主程序，并把任务事件推回扩展。
 //! 与IDM通信方式对齐，支持更多下载参数和命令结构。
 //! 
 //! 协议：stdin 读 4 字节 (little-endian 长度) + N 字节 JSON；
-//!       stdout 写 4 字节长度 + JSON 响应。
+//!       stdout 写 4 字节长度 + JSON。扩展发来的 `seq` 原样带回对应的
+//!       `{"type":"response",...}`；任务事件为 `{"type":"event","event":...}`。
+//! 与主程序：每行一个 JSON，连接后先用 native_host_token.txt 中的令牌握手并协商协议版本，
+//!       命令按顺序回复，订阅后事件穿插在回复之间。
 
-use std::io::{Read, Write};
-use std::net::TcpStream;
+mod transport;
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use transport::AppStream;
 use std::fs::OpenOptions;
 use std::io::BufWriter;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
-const APP_ID: &str = "com.multidown.app";
+/// 支持的主程序通信协议版本
+const PROTOCOL_VERSIONS: [u32; 2] = [1, 2];
+/// 主程序单行回复上限，与 Native Messaging 发给扩展的单条消息上限（1 MB）一致
+const MAX_LINE: usize = 1024 * 1024;
 
 // 调试日志函数
 fn debug_log(message: &str, data: Option<&str>) {
@@ -47,48 +58,25 @@ fn debug_log(message: &str, data: Option<&str>) {
     }
 }
 
-// 详细日志函数（用于更详细的调试信息）
-fn debug_log_detailed(message: &str, details: &str) {
-    debug_log(message, Some(details));
-}
-
 // 日志文件路径
 fn log_file_path() -> Option<std::path::PathBuf> {
-    #[cfg(target_os = "windows")]
-    {
-        std::env::var("APPDATA").ok().map(|d| {
-            std::path::PathBuf::from(d).join("com.multidown.app").join("native_host.log")
-        })
-    }
-    #[cfg(target_os = "macos")]
-    {
-        std::env::var("HOME").ok().map(|d| {
-            std::path::PathBuf::from(d)
-                .join("Library")
-                .join("Application Support")
-                .join("com.multidown.app")
-                .join("native_host.log")
-        })
-    }
-    #[cfg(target_os = "linux")]
-    {
-        let dir = std::env::var("XDG_CONFIG_HOME")
-            .ok()
-            .map(std::path::PathBuf::from)
-            .or_else(|| std::env::var("HOME").ok().map(|h| std::path::PathBuf::from(h).join(".config")))?;
-        Some(dir.join("com.multidown.app").join("native_host.log"))
-    }
-    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
-    {
-        None
-    }
+    app_data_file("native_host.log")
 }
 
 fn port_file_path() -> Option<std::path::PathBuf> {
+    app_data_file("native_host_port.txt")
+}
+
+fn token_file_path() -> Option<std::path::PathBuf> {
+    app_data_file("native_host_token.txt")
+}
+
+/// 主程序应用数据目录（与 Tauri app_data_dir 一致）下的文件
+fn app_data_file(name: &str) -> Option<std::path::PathBuf> {
     #[cfg(target_os = "windows")]
     {
         std::env::var("APPDATA").ok().map(|d| {
-            std::path::PathBuf::from(d).join("com.multidown.app").join("native_host_port.txt")
+            std::path::PathBuf::from(d).join("com.multidown.app").join(name)
         })
     }
     #[cfg(target_os = "macos")]
@@ -98,7 +86,7 @@ fn port_file_path() -> Option<std::path::PathBuf> {
                 .join("Library")
                 .join("Application Support")
                 .join("com.multidown.app")
-                .join("native_host_port.txt")
+                .join(name)
         })
     }
     #[cfg(target_os = "linux")]
@@ -107,10 +95,11 @@ fn port_file_path() -> Option<std::path::PathBuf> {
             .ok()
             .map(std::path::PathBuf::from)
             .or_else(|| std::env::var("HOME").ok().map(|h| std::path::PathBuf::from(h).join(".config")))?;
-        Some(dir.join("com.multidown.app").join("native_host_port.txt"))
+        Some(dir.join("com.multidown.app").join(name))
     }
     #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
     {
+        let _ = name;
         None
     }
 }
@@ -125,22 +114,257 @@ fn write_u32_le(w: &mut impl Write, n: u32) -> std::io::Result<()> {
     w.write_all(&n.to_le_bytes())
 }
 
-fn send_response(stdout: &mut impl Write, ok: bool, message: &str) {
-    let body = serde_json::json!({ "success": ok, "message": message });
+/// 向扩展发送一条消息；主线程与读线程都会调用，锁住 stdout 保证消息完整
+fn send_message(body: &serde_json::Value) {
     let bytes = body.to_string().into_bytes();
-    let len = bytes.len() as u32;
-    let _ = write_u32_le(stdout, len);
+    let mut stdout = std::io::stdout().lock();
+    let _ = write_u32_le(&mut stdout, bytes.len() as u32);
     let _ = stdout.write_all(&bytes);
     let _ = stdout.flush();
 }
 
-fn handle_download_message(msg: &serde_json::Value, stdout: &mut impl Write) -> bool {
+fn send_response(seq: &serde_json::Value, ok: bool, message: &str) {
+    send_message(&serde_json::json!({ "type": "response", "seq": seq, "success": ok, "message": message }));
+}
+
+/// 读取主程序回复的一行（握手阶段逐字节读取，避免多读走后续数据）
+fn read_line(stream: &mut AppStream) -> Result<String, String> {
+    let mut buf = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        match stream.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) if buf.len() >= MAX_LINE => return Err("主程序响应过长".to_string()),
+            Ok(_) => buf.push(byte[0]),
+            Err(e) => return Err(format!("读取主程序响应失败: {}", e)),
+        }
+    }
+    if buf.is_empty() {
+        return Err("未收到主程序响应".to_string());
+    }
+    String::from_utf8(buf).map_err(|_| "主程序响应无效".to_string())
+}
+
+fn write_line(stream: &mut AppStream, body: &serde_json::Value) -> Result<(), String> {
+    let line = format!("{}\n", body);
+    stream
+        .write_all(line.as_bytes())
+        .and_then(|_| stream.flush())
+        .map_err(|_| "发送失败".to_string())
+}
+
+/// 等待主程序回复的命令
+struct Pending {
+    seq: serde_json::Value,
+    action: String,
+}
+
+/// 与主程序的长连接：写入在主线程，回复与事件由读线程转发给扩展
+struct AppConnection {
+    stream: AppStream,
+    version: u32,
+    pending: Arc<Mutex<VecDeque<Pending>>>,
+    alive: Arc<AtomicBool>,
+}
+
+impl AppConnection {
+    /// 连接主程序并完成握手，然后启动读线程
+    fn connect() -> Result<Self, String> {
+        const NOT_RUNNING: &str = "Multidown 未运行或未就绪，请先启动 Multidown";
+        let token = token_file_path()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default();
+        if token.is_empty() {
+            debug_log("令牌文件缺失，主程序未运行", None);
+            return Err(NOT_RUNNING.to_string());
+        }
+
+        // 优先本机 IPC；主程序开启了 TCP 回退时再试 TCP
+        let mut stream = match AppStream::connect_ipc() {
+            Ok(s) => s,
+            Err(e) => {
+                debug_log("本机 IPC 连接失败", Some(&e.to_string()));
+                AppStream::connect_tcp().map_err(|e| {
+                    debug_log("TCP 连接失败", Some(&e.to_string()));
+                    NOT_RUNNING.to_string()
+                })?
+            }
+        };
+        stream
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .ok();
+
+        write_line(
+            &mut stream,
+            &serde_json::json!({ "action": "hello", "token": token, "versions": PROTOCOL_VERSIONS }),
+        )?;
+        let reply: serde_json::Value =
+            serde_json::from_str(&read_line(&mut stream)?).map_err(|_| "主程序响应解析失败".to_string())?;
+        if !reply.get("ok").and_then(|v| v.as_bool()).unwrap_or(false) {
+            let error = reply.get("error").and_then(|v| v.as_str()).unwrap_or("握手失败");
+            debug_log("握手失败", Some(error));
+            return Err(format!("握手失败: {}", error));
+        }
+        let version = reply.get("version").and_then(|v| v.as_u64()).unwrap_or(1) as u32;
+        debug_log("握手成功", Some(&format!("协议版本: {}", version)));
+
+        // 之后的读取不设超时：订阅后连接可能长时间没有数据
+        stream.set_read_timeout(None).ok();
+        let reader = stream.try_clone().map_err(|e| format!("无法连接 Multidown: {}", e))?;
+        let pending = Arc::new(Mutex::new(VecDeque::new()));
+        let alive = Arc::new(AtomicBool::new(true));
+        {
+            let pending = pending.clone();
+            let alive = alive.clone();
+            std::thread::spawn(move || read_loop(reader, pending, alive));
+        }
+        Ok(Self {
+            stream,
+            version,
+            pending,
+            alive,
+        })
+    }
+
+    fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    /// 发送命令；回复由读线程按顺序对应到 seq 后发给扩展
+    fn send(&mut self, action: &str, seq: serde_json::Value, body: &serde_json::Value) -> Result<(), String> {
+        debug_log("发送给主程序的消息", Some(action));
+        // 先登记再发送，读线程收到回复时一定能找到对应的命令
+        self.pending.lock().unwrap().push_back(Pending {
+            seq,
+            action: action.to_string(),
+        });
+        if let Err(e) = write_line(&mut self.stream, body) {
+            self.alive.store(false, Ordering::SeqCst);
+            self.pending.lock().unwrap().pop_back();
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AppConnection {
+    fn drop(&mut self) {
+        self.stream.shutdown();
+    }
+}
+
+/// 成功回复的提示文字
+fn success_message(action: &str) -> &'static str {
+    match action {
+        "download" => "已加入下载",
+        "open_window" => "已打开下载窗口",
+        "pause" => "已暂停",
+        "resume" => "已继续",
+        "cancel" => "已取消",
+        "subscribe" => "已订阅任务事件",
+        _ => "",
+    }
+}
+
+/// 读线程：带 event 字段的行是任务事件，其余为命令回复；连接断开时让等待中的命令全部失败
+fn read_loop(stream: AppStream, pending: Arc<Mutex<VecDeque<Pending>>>, alive: Arc<AtomicBool>) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut buf = Vec::new();
+        match (&mut reader).take(MAX_LINE as u64).read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if buf.last() != Some(&b'\n') => {
+                // 过长的只会是命令回复（如 list）：丢弃该行，对应命令回复失败，连接继续使用
+                if !matches!(skip_line(&mut reader), Ok(true)) {
+                    break;
+                }
+                debug_log("主程序回复过长，已丢弃", None);
+                if let Some(Pending { seq, .. }) = pending.lock().unwrap().pop_front() {
+                    send_response(&seq, false, "回复过长，请分页获取");
+                }
+                continue;
+            }
+            Ok(_) => {}
+        }
+        let line = String::from_utf8_lossy(&buf);
+        let Ok(mut value) = serde_json::from_str::<serde_json::Value>(line.trim()) else {
+            debug_log("主程序消息解析失败", Some(line.trim()));
+            continue;
+        };
+        if value.get("event").is_some() {
+            if let Some(obj) = value.as_object_mut() {
+                obj.insert("type".to_string(), "event".into());
+            }
+            send_message(&value);
+            continue;
+        }
+
+        let Some(Pending { seq, action }) = pending.lock().unwrap().pop_front() else {
+            debug_log("收到未对应命令的回复", Some(line.trim()));
+            continue;
+        };
+        debug_log("收到主程序响应", Some(line.trim()));
+        let ok = value.get("ok").and_then(|v| v.as_bool()).unwrap_or(false);
+        let message = match value.get("error").and_then(|v| v.as_str()) {
+            Some(e) => e.to_string(),
+            None if ok => success_message(&action).to_string(),
+            None => "操作失败".to_string(),
+        };
+        let mut response = serde_json::json!({ "type": "response", "seq": seq, "success": ok, "message": message });
+        for key in ["task_id", "tasks", "total"] {
+            if let Some(v) = value.get(key) {
+                response[key] = v.clone();
+            }
+        }
+        send_message(&response);
+        // 新任务加入队列：供扩展在收到后续进度前先显示该任务
+        if let (true, Some(id)) = (action == "download", value.get("task_id")) {
+            send_message(&serde_json::json!({ "type": "event", "event": "queued", "id": id }));
+        }
+    }
+
+    alive.store(false, Ordering::SeqCst);
+    debug_log("与主程序的连接已断开", None);
+    for Pending { seq, .. } in pending.lock().unwrap().drain(..) {
+        send_response(&seq, false, "与 Multidown 的连接已断开");
+    }
+    send_message(&serde_json::json!({ "type": "event", "event": "disconnected" }));
+}
+
+/// 丢弃当前行余下的部分；读到换行返回 true，连接关闭返回 false
+fn skip_line(reader: &mut impl BufRead) -> std::io::Result<bool> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(false);
+        }
+        match buf.iter().position(|&b| b == b'\n') {
+            Some(i) => {
+                reader.consume(i + 1);
+                return Ok(true);
+            }
+            None => {
+                let n = buf.len();
+                reader.consume(n);
+            }
+        }
+    }
+}
+
+/// 把扩展的下载消息整理成发给主程序的命令
+fn download_command(msg: &serde_json::Value) -> Result<serde_json::Value, String> {
     debug_log("开始处理下载消息", None);
     
     let url = msg
         .get("url")
         .and_then(|v| v.as_str())
-        .filter(|s| s.starts_with("http://") || s.starts_with("https://"));
+        .filter(|s| {
+            ["http://", "https://", "ftp://", "ftps://"]
+                .iter()
+                .any(|p| s.starts_with(p))
+        });
 
     let url = match url {
         Some(u) => {
@@ -149,8 +373,7 @@ fn handle_download_message(msg: &serde_json::Value, stdout: &mut impl Write) ->
         }
         None => {
             debug_log("缺少或无效的URL", None);
-            send_response(stdout, false, "missing or invalid url");
-            return false;
+            return Err("missing or invalid url".to_string());
         }
     };
 
@@ -161,61 +384,17 @@ fn handle_download_message(msg: &serde_json::Value, stdout: &mut impl Write) ->
     let post_data = msg.get("post_data").and_then(|v| v.as_str()).unwrap_or("");
     let save_path = msg.get("save_path").and_then(|v| v.as_str()).unwrap_or("");
     let open_window = msg.get("open_window").and_then(|v| v.as_bool()).unwrap_or(true);
+    // 同一文件的其他镜像地址，由主程序探测校验
+    let mirrors: Vec<&str> = msg
+        .get("mirrors")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|m| m.as_str()).collect())
+        .unwrap_or_default();
     
     debug_log("下载参数", Some(&format!("filename: {}, referer: {}, open_window: {}", filename, referer, open_window)));
 
-    let port = match port_file_path() {
-        Some(p) => {
-            debug_log("读取端口文件", Some(&p.to_string_lossy()));
-            match std::fs::read_to_string(p) {
-                Ok(s) => {
-                    let port = s.trim().parse::<u16>().unwrap_or(0);
-                    debug_log("获取到端口", Some(&port.to_string()));
-                    port
-                }
-                Err(e) => {
-                    debug_log("读取端口文件失败", Some(&e.to_string()));
-                    0
-                }
-            }
-        }
-        None => {
-            debug_log("无法获取端口文件路径", None);
-            0
-        }
-    };
-    
-    if port == 0 {
-        debug_log("端口为0，主程序未运行", None);
-        send_response(
-            stdout,
-            false,
-            "Multidown 未运行或未就绪，请先启动 Multidown",
-        );
-        return false;
-    }
-
-    let addr = format!("127.0.0.1:{}", port);
-    debug_log("尝试连接主程序", Some(&addr));
-    
-    let mut stream = match TcpStream::connect(&addr) {
-        Ok(s) => {
-            debug_log("连接主程序成功", None);
-            s
-        }
-        Err(e) => {
-            debug_log("连接主程序失败", Some(&e.to_string()));
-            send_response(
-                stdout,
-                false,
-                &format!("无法连接 Multidown: {}", e),
-            );
-            return false;
-        }
-    };
-
     // 与IDM对齐的消息结构
-    let body = serde_json::json!({ 
+    Ok(serde_json::json!({ 
         "action": "download",
         "url": url,
         "filename": filename,
@@ -224,167 +403,62 @@ fn handle_download_message(msg: &serde_json::Value, stdout: &mut impl Write) ->
         "cookie": cookie,
         "post_data": post_data,
         "save_path": save_path,
-        "open_window": open_window
-    });
-    
-    let body_str = body.to_string();
-    debug_log("发送给主程序的消息", Some(&body_str));
-    
-    let line = format!("{}\n", body_str);
-    if stream.write_all(line.as_bytes()).is_err() || stream.flush().is_err() {
-        debug_log("发送消息失败", None);
-        send_response(stdout, false, "发送失败");
-        return false;
-    }
-    
-    debug_log("消息发送成功，等待主程序响应", None);
+        "open_window": open_window,
+        "mirrors": mirrors
+    }))
+}
 
-    // 读取主程序返回的一行 JSON：{"ok":true} 或 {"ok":false,"error":"..."}
-    stream
-        .set_read_timeout(Some(std::time::Duration::from_secs(5)))
-        .ok();
-    let mut buf = vec![0u8; 1024];
-    let mut n = 0usize;
-    while n < buf.len() {
-        match stream.read(&mut buf[n..n + 1]) {
-            Ok(0) => break,
-            Ok(1) => {
-                if buf[n] == b'\n' {
-                    n += 1;
-                    break;
+/// 把扩展消息整理成发给主程序的命令，以及该命令需要的最低协议版本
+fn app_command(action: &str, msg: &serde_json::Value) -> Result<(serde_json::Value, u32), String> {
+    match action {
+        "download" => Ok((download_command(msg)?, 1)),
+        "open_window" => {
+            let url = msg.get("url").and_then(|v| v.as_str()).unwrap_or("");
+            Ok((serde_json::json!({ "action": "open_window", "url": url }), 1))
+        }
+        "pause" | "resume" | "cancel" => {
+            let id = msg
+                .get("id")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| "missing task id".to_string())?;
+            Ok((serde_json::json!({ "action": action, "id": id }), 2))
+        }
+        "list" => {
+            let active = msg.get("active").and_then(|v| v.as_bool()).unwrap_or(false);
+            let mut command = serde_json::json!({ "action": "list", "active": active });
+            for key in ["offset", "limit"] {
+                if let Some(n) = msg.get(key).and_then(|v| v.as_u64()) {
+                    command[key] = n.into();
                 }
-                n += 1;
             }
-            Ok(_) => {}
-            Err(_) => break,
+            Ok((command, 2))
         }
+        "subscribe" => Ok((serde_json::json!({ "action": "subscribe" }), 2)),
+        _ => Err("unknown action".to_string()),
     }
-    
-    let response_msg = if n == 0 {
-        debug_log("未收到主程序响应", None);
-        send_response(stdout, false, "未收到主程序响应");
-        return false;
-    } else {
-        match std::str::from_utf8(&buf[..n]) {
-            Ok(s) => {
-                let trimmed = s.trim().to_string();
-                debug_log("收到主程序响应", Some(&trimmed));
-                trimmed
-            }
-            Err(_) => {
-                debug_log("主程序响应无效", None);
-                send_response(stdout, false, "主程序响应无效");
-                return false;
-            }
-        }
-    };
-    
-    let response: serde_json::Value = match serde_json::from_str(&response_msg) {
-        Ok(v) => v,
-        Err(_) => {
-            debug_log("主程序响应解析失败", None);
-            send_response(stdout, false, "主程序响应解析失败");
-            return false;
-        }
-    };
-    
-    let ok = response.get("ok").and_then(|v| v.as_bool()).unwrap_or(false);
-    let message = response
-        .get("error")
-        .and_then(|v| v.as_str())
-        .unwrap_or(if ok { "已加入下载" } else { "添加失败" });
-    
-    debug_log("处理响应完成", Some(&format!("ok: {}, message: {}", ok, message)));
-    
-    send_response(stdout, ok, message);
-    true
 }
 
-fn handle_open_window_message(msg: &serde_json::Value, stdout: &mut impl Write) -> bool {
-    let url = msg.get("url").and_then(|v| v.as_str()).unwrap_or("");
-    
-    let port = match port_file_path().and_then(|p| std::fs::read_to_string(p).ok()) {
-        Some(s) => s.trim().parse::<u16>().unwrap_or(0),
-        None => 0,
-    };
-    if port == 0 {
-        send_response(
-            stdout,
-            false,
-            "Multidown 未运行或未就绪，请先启动 Multidown",
-        );
-        return false;
-    }
-
-    let addr = format!("127.0.0.1:{}", port);
-    let mut stream = match TcpStream::connect(&addr) {
-        Ok(s) => s,
-        Err(e) => {
-            send_response(
-                stdout,
-                false,
-                &format!("无法连接 Multidown: {}", e),
-            );
-            return false;
+/// 读取扩展发来的一条消息；扩展断开（stdin 关闭）时返回 None
+fn read_message(stdin: &mut impl Read) -> Option<Result<serde_json::Value, String>> {
+    let len = match read_u32_le(stdin) {
+        Ok(n) if n > 1024 * 1024 => {
+            debug_log("消息长度过大", Some(&n.to_string()));
+            // 无法跳过超长消息，之后的数据已不可信
+            return None;
         }
+        Ok(n) => n as usize,
+        Err(_) => return None,
     };
-
-    let body = serde_json::json!({ 
-        "action": "open_window",
-        "url": url
-    });
-    let line = format!("{}\n", body.to_string());
-    if stream.write_all(line.as_bytes()).is_err() || stream.flush().is_err() {
-        send_response(stdout, false, "发送失败");
-        return false;
-    }
-
-    // 读取主程序返回的响应
-    stream
-        .set_read_timeout(Some(std::time::Duration::from_secs(5)))
-        .ok();
-    let mut buf = vec![0u8; 512];
-    let mut n = 0usize;
-    while n < buf.len() {
-        match stream.read(&mut buf[n..n + 1]) {
-            Ok(0) => break,
-            Ok(1) => {
-                if buf[n] == b'\n' {
-                    n += 1;
-                    break;
-                }
-                n += 1;
-            }
-            Ok(_) => {}
-            Err(_) => break,
-        }
+    let mut payload = vec![0u8; len];
+    if stdin.read_exact(&mut payload).is_err() {
+        debug_log("读取消息内容失败", None);
+        return None;
     }
-    let response_msg = if n == 0 {
-        send_response(stdout, false, "未收到主程序响应");
-        return false;
-    } else {
-        match std::str::from_utf8(&buf[..n]) {
-            Ok(s) => s.trim().to_string(),
-            Err(_) => {
-                send_response(stdout, false, "主程序响应无效");
-                return false;
-            }
-        }
-    };
-    let response: serde_json::Value = match serde_json::from_str(&response_msg) {
-        Ok(v) => v,
-        Err(_) => {
-            send_response(stdout, false, "主程序响应解析失败");
-            return false;
-        }
-    };
-    let ok = response.get("ok").and_then(|v| v.as_bool()).unwrap_or(false);
-    let message = response
-        .get("error")
-        .and_then(|v| v.as_str())
-        .unwrap_or(if ok { "已打开下载窗口" } else { "打开窗口失败" });
-    send_response(stdout, ok, message);
-    true
+    Some(serde_json::from_slice(&payload).map_err(|e| {
+        debug_log("JSON解析失败", Some(&e.to_string()));
+        "invalid json".to_string()
+    }))
 }
 
 fn main() {
@@ -392,63 +466,54 @@ fn main() {
     
     let stdin = std::io::stdin();
     let mut stdin = stdin.lock();
-    let mut stdout = std::io::stdout().lock();
-
-    debug_log("读取消息长度", None);
-    let len = match read_u32_le(&mut stdin) {
-        Ok(n) if n > 1024 * 1024 => {
-            debug_log("消息长度过大", Some(&n.to_string()));
-            send_response(&mut stdout, false, "message too large");
-            return;
-        }
-        Ok(n) => {
-            debug_log("消息长度", Some(&n.to_string()));
-            n as usize
-        }
-        Err(e) => {
-            debug_log("读取消息长度失败", Some(&e.to_string()));
-            return;
-        }
-    };
+    let mut app: Option<AppConnection> = None;
 
-    debug_log("读取消息内容", Some(&format!("长度: {}", len)));
-    let mut payload = vec![0u8; len];
-    if stdin.read_exact(&mut payload).is_err() {
-        debug_log("读取消息内容失败", None);
-        return;
-    }
+    // 扩展保持连接期间持续处理消息，直到 stdin 关闭
+    while let Some(msg) = read_message(&mut stdin) {
+        let msg = match msg {
+            Ok(m) => m,
+            Err(e) => {
+                send_response(&serde_json::Value::Null, false, &e);
+                continue;
+            }
+        };
+        let seq = msg.get("seq").cloned().unwrap_or(serde_json::Value::Null);
+        let action = msg.get("action").and_then(|v| v.as_str()).unwrap_or("download").to_string();
+        debug_log("处理命令", Some(&action));
 
-    debug_log("解析JSON消息", None);
-    let msg: serde_json::Value = match serde_json::from_slice::<serde_json::Value>(&payload) {
-        Ok(m) => {
-            debug_log("JSON解析成功", Some(&m.to_string()));
-            m
-        }
-        Err(e) => {
-            debug_log("JSON解析失败", Some(&e.to_string()));
-            send_response(&mut stdout, false, "invalid json");
-            return;
+        // 连接断开后下一条命令时重连
+        if !app.as_ref().is_some_and(|a| a.is_alive()) {
+            app = None;
+            match AppConnection::connect() {
+                Ok(c) => app = Some(c),
+                Err(e) => {
+                    send_response(&seq, false, &e);
+                    continue;
+                }
+            }
         }
-    };
-
-    // 处理不同类型的命令
-    let action = msg.get("action").and_then(|v| v.as_str()).unwrap_or("download");
-    debug_log("处理命令", Some(action));
+        let Some(conn) = app.as_mut() else { continue };
 
-    match action {
-        "download" => {
-            debug_log("处理下载命令", None);
-            handle_download_message(&msg, &mut stdout);
+        if action == "test_connection" {
+            send_response(&seq, true, &format!("已连接 Multidown（协议版本 {}）", conn.version));
+            continue;
         }
-        "open_window" => {
-            debug_log("处理打开窗口命令", None);
-            handle_open_window_message(&msg, &mut stdout);
+        let (body, min_version) = match app_command(&action, &msg) {
+            Ok(c) => c,
+            Err(e) => {
+                debug_log("无效命令", Some(&e));
+                send_response(&seq, false, &e);
+                continue;
+            }
+        };
+        if conn.version < min_version {
+            send_response(&seq, false, "Multidown 版本过旧，请更新主程序");
+            continue;
         }
-        _ => {
-            debug_log("未知命令", Some(action));
-            send_response(&mut stdout, false, "unknown action");
+        if let Err(e) = conn.send(&action, seq.clone(), &body) {
+            send_response(&seq, false, &e);
         }
     }
     
-    debug_log("处理完成", None);
+    debug_log("扩展已断开，退出", None);
 }
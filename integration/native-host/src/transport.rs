@@ -0,0 +1,262 @@
+//! 与主程序的传输：优先本机 IPC（Unix 域套接字 / Windows 命名管道），
+//! 主程序开启 TCP 回退时再尝试端口文件中的 TCP 端口。
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+pub enum AppStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixStream),
+    #[cfg(windows)]
+    Pipe(pipe::PipeStream),
+}
+
+impl AppStream {
+    /// 连接本机 IPC 端点；端点不存在或无人监听时返回错误
+    pub fn connect_ipc() -> std::io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{FileTypeExt, MetadataExt};
+            let path = crate::app_data_file("ipc/native_host.sock")
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "无法获取应用数据目录"))?;
+            let meta = std::fs::metadata(&path)?;
+            // 只连接与令牌文件同一属主、其他用户不可访问的套接字
+            let owner = crate::token_file_path().and_then(|p| std::fs::metadata(p).ok()).map(|m| m.uid());
+            if !meta.file_type().is_socket() || owner != Some(meta.uid()) || meta.mode() & 0o077 != 0 {
+                return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "套接字属主或权限不符"));
+            }
+            // 崩溃遗留的套接字文件在这里得到 ConnectionRefused
+            std::os::unix::net::UnixStream::connect(&path).map(AppStream::Unix)
+        }
+        #[cfg(windows)]
+        {
+            pipe::PipeStream::connect().map(AppStream::Pipe)
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "不支持本机 IPC"))
+        }
+    }
+
+    /// 连接端口文件中的 TCP 端口（主程序开启 TCP 回退时才有该文件）
+    pub fn connect_tcp() -> std::io::Result<Self> {
+        let port = crate::port_file_path()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|s| s.trim().parse::<u16>().ok())
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "端口文件不存在"))?;
+        TcpStream::connect(("127.0.0.1", port)).map(AppStream::Tcp)
+    }
+
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        match self {
+            AppStream::Tcp(s) => s.try_clone().map(AppStream::Tcp),
+            #[cfg(unix)]
+            AppStream::Unix(s) => s.try_clone().map(AppStream::Unix),
+            #[cfg(windows)]
+            AppStream::Pipe(s) => Ok(AppStream::Pipe(s.clone())),
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            AppStream::Tcp(s) => s.set_read_timeout(timeout),
+            #[cfg(unix)]
+            AppStream::Unix(s) => s.set_read_timeout(timeout),
+            #[cfg(windows)]
+            AppStream::Pipe(s) => {
+                s.set_read_timeout(timeout);
+                Ok(())
+            }
+        }
+    }
+
+    /// 关闭连接，使读线程退出
+    pub fn shutdown(&self) {
+        match self {
+            AppStream::Tcp(s) => {
+                let _ = s.shutdown(std::net::Shutdown::Both);
+            }
+            #[cfg(unix)]
+            AppStream::Unix(s) => {
+                let _ = s.shutdown(std::net::Shutdown::Both);
+            }
+            // 管道在两端句柄都释放后关闭
+            #[cfg(windows)]
+            AppStream::Pipe(_) => {}
+        }
+    }
+}
+
+impl Read for AppStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            AppStream::Tcp(s) => s.read(buf),
+            #[cfg(unix)]
+            AppStream::Unix(s) => s.read(buf),
+            #[cfg(windows)]
+            AppStream::Pipe(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for AppStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            AppStream::Tcp(s) => s.write(buf),
+            #[cfg(unix)]
+            AppStream::Unix(s) => s.write(buf),
+            #[cfg(windows)]
+            AppStream::Pipe(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            AppStream::Tcp(s) => s.flush(),
+            #[cfg(unix)]
+            AppStream::Unix(s) => s.flush(),
+            #[cfg(windows)]
+            AppStream::Pipe(s) => s.flush(),
+        }
+    }
+}
+
+/// 命名管道客户端。同步句柄上的读写会互相阻塞（读线程等待事件时主线程无法发送），
+/// 因此用 tokio 的异步管道，读写两半各自在调用线程上 block_on
+#[cfg(windows)]
+mod pipe {
+    use std::os::windows::io::{AsRawHandle, RawHandle};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+    use tokio::net::windows::named_pipe::{ClientOptions, NamedPipeClient};
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{EqualSid, GetTokenInformation, TokenUser, TOKEN_QUERY, TOKEN_USER};
+    use windows_sys::Win32::System::Pipes::GetNamedPipeServerProcessId;
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    /// ERROR_PIPE_BUSY：所有管道实例都在使用中，稍后重试
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    #[derive(Clone)]
+    pub struct PipeStream {
+        rt: Arc<tokio::runtime::Runtime>,
+        reader: Arc<Mutex<ReadHalf<NamedPipeClient>>>,
+        writer: Arc<Mutex<WriteHalf<NamedPipeClient>>>,
+        read_timeout: Arc<Mutex<Option<Duration>>>,
+    }
+
+    /// 与主程序一致：管道名含当前用户名
+    fn pipe_name() -> String {
+        let user: String = std::env::var("USERNAME")
+            .unwrap_or_default()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!(r"\\.\pipe\multidown-native-host-{}", user)
+    }
+
+    /// 读取进程令牌中的 TOKEN_USER；SID 指针指向返回的缓冲区内部
+    fn process_user(process: HANDLE) -> std::io::Result<Vec<u64>> {
+        unsafe {
+            let mut token: HANDLE = std::ptr::null_mut();
+            if OpenProcessToken(process, TOKEN_QUERY, &mut token) == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let mut len = 0u32;
+            GetTokenInformation(token, TokenUser, std::ptr::null_mut(), 0, &mut len);
+            let mut buf = vec![0u64; len as usize / 8 + 1];
+            let ok = GetTokenInformation(token, TokenUser, buf.as_mut_ptr().cast(), len, &mut len);
+            let err = std::io::Error::last_os_error();
+            CloseHandle(token);
+            if ok == 0 {
+                return Err(err);
+            }
+            Ok(buf)
+        }
+    }
+
+    /// 管道服务端进程是否与本进程属于同一用户
+    fn server_is_current_user(pipe: RawHandle) -> std::io::Result<bool> {
+        unsafe {
+            let me = process_user(GetCurrentProcess())?;
+            let mut pid = 0u32;
+            if GetNamedPipeServerProcessId(pipe, &mut pid) == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process.is_null() {
+                return Err(std::io::Error::last_os_error());
+            }
+            let server = process_user(process);
+            CloseHandle(process);
+            let server = server?;
+            let sid = |buf: &Vec<u64>| (*buf.as_ptr().cast::<TOKEN_USER>()).User.Sid;
+            Ok(EqualSid(sid(&server), sid(&me)) != 0)
+        }
+    }
+
+    impl PipeStream {
+        pub fn connect() -> std::io::Result<Self> {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .enable_all()
+                .build()?;
+            let name = pipe_name();
+            let client: NamedPipeClient = rt.block_on(async {
+                let mut attempts = 0;
+                loop {
+                    match ClientOptions::new().open(&name) {
+                        Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) && attempts < 10 => {
+                            attempts += 1;
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                        }
+                        other => return other,
+                    }
+                }
+            })?;
+            // 管道名可预测，其他用户可能抢先创建同名管道：只与当前用户的进程通信
+            if !server_is_current_user(client.as_raw_handle())? {
+                return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "管道服务端不属于当前用户"));
+            }
+            let (reader, writer) = tokio::io::split(client);
+            Ok(Self {
+                rt: Arc::new(rt),
+                reader: Arc::new(Mutex::new(reader)),
+                writer: Arc::new(Mutex::new(writer)),
+                read_timeout: Arc::new(Mutex::new(None)),
+            })
+        }
+
+        pub fn set_read_timeout(&self, timeout: Option<Duration>) {
+            *self.read_timeout.lock().unwrap() = timeout;
+        }
+
+        pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let timeout = *self.read_timeout.lock().unwrap();
+            let mut reader = self.reader.lock().unwrap();
+            self.rt.block_on(async {
+                match timeout {
+                    Some(t) => tokio::time::timeout(t, reader.read(buf))
+                        .await
+                        .unwrap_or_else(|_| Err(std::io::ErrorKind::TimedOut.into())),
+                    None => reader.read(buf).await,
+                }
+            })
+        }
+
+        pub fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
+            let mut writer = self.writer.lock().unwrap();
+            self.rt.block_on(writer.write(buf))
+        }
+
+        pub fn flush(&self) -> std::io::Result<()> {
+            let mut writer = self.writer.lock().unwrap();
+            self.rt.block_on(writer.flush())
+        }
+    }
+}
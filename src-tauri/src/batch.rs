@@ -0,0 +1,231 @@
+//! 批量地址模式：`[001-120]` 数字范围（起始值有前导 0 时按其宽度补 0）、`[0-100:5]` 带步长、
+//! `[a-z]` 字母范围、`{a,b,c}` 列表；一个地址中有多处时按笛卡尔积展开，前面的变化最慢。
+//! `[]` 中不是范围的内容按原样保留（如 IPv6 地址 `http://[::1]/`）
+
+use std::collections::HashSet;
+
+/// 一次展开的地址数上限，防止误写的范围生成海量任务
+pub const MAX_EXPANDED: usize = 10_000;
+
+enum Part {
+    Literal(String),
+    Choices(Vec<String>),
+}
+
+/// 展开单个模式；不含模式时原样返回
+pub fn expand(pattern: &str) -> Result<Vec<String>, String> {
+    let parts = parse(pattern)?;
+    let count = parts.iter().try_fold(1usize, |n, p| match p {
+        Part::Literal(_) => Some(n),
+        Part::Choices(c) => n.checked_mul(c.len()),
+    });
+    match count {
+        Some(n) if n <= MAX_EXPANDED => {}
+        _ => return Err(format!("展开后超过 {} 个地址", MAX_EXPANDED)),
+    }
+    let mut out = vec![String::new()];
+    for part in &parts {
+        out = match part {
+            Part::Literal(s) => out.into_iter().map(|prefix| prefix + s).collect(),
+            Part::Choices(choices) => out
+                .iter()
+                .flat_map(|prefix| choices.iter().map(move |c| format!("{}{}", prefix, c)))
+                .collect(),
+        };
+    }
+    Ok(out)
+}
+
+/// 展开多行文本（每行一个地址或模式，空行忽略），去掉重复地址；出错时指出行号
+pub fn expand_lines(text: &str) -> Result<Vec<String>, String> {
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        for url in expand(line).map_err(|e| format!("第 {} 行：{}", i + 1, e))? {
+            if seen.insert(url.clone()) {
+                out.push(url);
+            }
+        }
+        if out.len() > MAX_EXPANDED {
+            return Err(format!("展开后超过 {} 个地址", MAX_EXPANDED));
+        }
+    }
+    Ok(out)
+}
+
+fn parse(pattern: &str) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut rest = pattern;
+    while let Some(pos) = rest.find(['[', '{']) {
+        literal.push_str(&rest[..pos]);
+        let open = rest.as_bytes()[pos];
+        let close = if open == b'[' { ']' } else { '}' };
+        let after = &rest[pos + 1..];
+        let Some(end) = after.find(close) else {
+            if open == b'{' {
+                return Err("缺少与 { 对应的 }".to_string());
+            }
+            literal.push_str(&rest[pos..]);
+            rest = "";
+            break;
+        };
+        let body = &after[..end];
+        let choices = if open == b'[' {
+            range(body)?
+        } else {
+            Some(body.split(',').map(String::from).collect())
+        };
+        match choices {
+            Some(choices) => {
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(Part::Choices(choices));
+            }
+            None => {
+                literal.push_str(&rest[pos..pos + end + 2]);
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+    Ok(parts)
+}
+
+/// `[]` 内的范围；不是 `起-止` 或 `起-止:步长` 形式时返回 None（按原样保留）
+fn range(body: &str) -> Result<Option<Vec<String>>, String> {
+    let (span, step) = match body.rsplit_once(':') {
+        Some((span, step)) if !step.is_empty() && step.bytes().all(|b| b.is_ascii_digit()) => (span, Some(step)),
+        _ => (body, None),
+    };
+    let Some((from, to)) = span.split_once('-') else {
+        return Ok(None);
+    };
+    let step = match step {
+        Some(s) => s.parse::<u64>().map_err(|_| format!("步长无效: {}", s))?,
+        None => 1,
+    };
+    let numeric = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let letter = |s: &str| s.len() == 1 && s.as_bytes()[0].is_ascii_alphabetic();
+    if numeric(from) && numeric(to) {
+        if step == 0 {
+            return Err(format!("[{}]：步长不能为 0", body));
+        }
+        let start: u64 = from.parse().map_err(|_| format!("[{}]：数字过大", body))?;
+        let end: u64 = to.parse().map_err(|_| format!("[{}]：数字过大", body))?;
+        if start > end {
+            return Err(format!("[{}]：起始值大于结束值", body));
+        }
+        if (end - start) / step >= MAX_EXPANDED as u64 {
+            return Err(format!("展开后超过 {} 个地址", MAX_EXPANDED));
+        }
+        // 起始值写成 001 时按 3 位补 0
+        let width = if from.len() > 1 && from.starts_with('0') { from.len() } else { 0 };
+        let values = (start..=end)
+            .step_by(usize::try_from(step).unwrap_or(usize::MAX))
+            .map(|n| format!("{:0width$}", n, width = width))
+            .collect();
+        return Ok(Some(values));
+    }
+    if letter(from) && letter(to) {
+        let (a, b) = (from.as_bytes()[0], to.as_bytes()[0]);
+        if a.is_ascii_lowercase() != b.is_ascii_lowercase() {
+            return Err(format!("[{}]：字母范围两端大小写不一致", body));
+        }
+        if a > b {
+            return Err(format!("[{}]：起始值大于结束值", body));
+        }
+        if step == 0 {
+            return Err(format!("[{}]：步长不能为 0", body));
+        }
+        let values = (a..=b)
+            .step_by(usize::try_from(step).unwrap_or(usize::MAX))
+            .map(|c| (c as char).to_string())
+            .collect();
+        return Ok(Some(values));
+    }
+    if numeric(from) || numeric(to) || letter(from) || letter(to) {
+        return Err(format!("[{}]：范围两端须同为数字或同为字母", body));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_range_pads_to_start_width() {
+        assert_eq!(expand("http://h/[1-3].jpg").unwrap(), ["http://h/1.jpg", "http://h/2.jpg", "http://h/3.jpg"]);
+        assert_eq!(expand("http://h/[08-11]").unwrap(), ["http://h/08", "http://h/09", "http://h/10", "http://h/11"]);
+        let padded = expand("http://h/[001-120].jpg").unwrap();
+        assert_eq!(padded.len(), 120);
+        assert_eq!(padded[0], "http://h/001.jpg");
+        assert_eq!(padded[119], "http://h/120.jpg");
+        // 只有一位的 0 不算前导 0
+        assert_eq!(expand("http://h/[0-10]").unwrap()[10], "http://h/10");
+    }
+
+    #[test]
+    fn step_applies_to_numbers_and_letters() {
+        assert_eq!(expand("h/[0-10:5]").unwrap(), ["h/0", "h/5", "h/10"]);
+        assert_eq!(expand("h/[00-12:4]").unwrap(), ["h/00", "h/04", "h/08", "h/12"]);
+        assert_eq!(expand("h/[a-e:2]").unwrap(), ["h/a", "h/c", "h/e"]);
+        assert!(expand("h/[0-10:0]").is_err());
+        assert!(expand("h/[a-c:0]").is_err());
+    }
+
+    #[test]
+    fn letter_ranges() {
+        assert_eq!(expand("h/[x-z]").unwrap(), ["h/x", "h/y", "h/z"]);
+        assert_eq!(expand("h/[A-C]").unwrap(), ["h/A", "h/B", "h/C"]);
+        assert!(expand("h/[a-C]").is_err());
+        assert!(expand("h/[c-a]").is_err());
+        assert!(expand("h/[1-c]").is_err());
+        assert!(expand("h/[5-1]").is_err());
+    }
+
+    #[test]
+    fn lists_and_cartesian_order() {
+        assert_eq!(expand("h/{a,b,c}.bin").unwrap(), ["h/a.bin", "h/b.bin", "h/c.bin"]);
+        assert_eq!(expand("h/{x,}y").unwrap(), ["h/xy", "h/y"]);
+        assert_eq!(expand("h/{a,b}/[1-2]").unwrap(), ["h/a/1", "h/a/2", "h/b/1", "h/b/2"]);
+        assert!(expand("h/{a,b").is_err());
+    }
+
+    #[test]
+    fn non_range_brackets_are_kept() {
+        assert_eq!(expand("http://[::1]:8080/f.bin").unwrap(), ["http://[::1]:8080/f.bin"]);
+        assert_eq!(expand("http://[2001:db8::1]/f.bin").unwrap(), ["http://[2001:db8::1]/f.bin"]);
+        assert_eq!(expand("http://[::1]/[1-2].bin").unwrap(), ["http://[::1]/1.bin", "http://[::1]/2.bin"]);
+        assert_eq!(expand("h/[abc]/[x").unwrap(), ["h/[abc]/[x"]);
+        assert_eq!(expand("http://h/plain.bin").unwrap(), ["http://h/plain.bin"]);
+    }
+
+    #[test]
+    fn expansion_limit() {
+        assert_eq!(expand("h/[1-10000]").unwrap().len(), MAX_EXPANDED);
+        assert!(expand("h/[0-10000]").is_err());
+        assert!(expand("h/[0-99999999999999999999]").is_err());
+        assert_eq!(expand("h/[1-100]/[1-100]").unwrap().len(), MAX_EXPANDED);
+        assert!(expand("h/[1-100]/[1-100]{a,b}").is_err());
+        let lines = format!("h/[1-{}]\nh/extra", MAX_EXPANDED);
+        assert!(expand_lines(&lines).is_err());
+    }
+
+    #[test]
+    fn lines_skip_blanks_dedupe_and_report_line() {
+        let urls = expand_lines("h/[1-2]\n\n  h/2  \nh/{2,3}\n").unwrap();
+        assert_eq!(urls, ["h/1", "h/2", "h/3"]);
+        let err = expand_lines("h/1\nh/[3-1]").unwrap_err();
+        assert!(err.starts_with("第 2 行"), "{}", err);
+    }
+}
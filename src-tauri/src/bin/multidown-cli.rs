@@ -0,0 +1,710 @@
+//! 命令行版本：不依赖界面，直接驱动同一个下载引擎（适用于 CI 与服务器）
+
+use multidown_lib::engine::scheduler::Scheduler;
+use multidown_lib::engine::{piece_dir, ChannelEvents, EngineEvent, TaskId, TaskInfo, TaskStatus};
+use multidown_lib::instance;
+use multidown_lib::network::proxy::parse_proxy_url;
+use multidown_lib::network::{BypassList, NetworkOptions, ProxyMode};
+use serde_json::json;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const USAGE: &str = "用法: multidown-cli [--json] [--store 任务文件] [网络选项] <命令> [参数]
+
+网络选项:
+  --proxy <URL|direct>
+                     使用代理（如 socks5h://127.0.0.1:1080，缺省 scheme 为 http）；
+                     direct 为直连。省略时沿用环境变量 HTTP_PROXY / HTTPS_PROXY / ALL_PROXY / NO_PROXY
+  --no-proxy <列表>  不使用代理的地址，逗号分隔
+  --timeout <秒>     连接与读取超时，默认 30
+
+命令:
+  add <URL>... [-d 目录] [-o 文件名] [-m 镜像地址]... [-c 连接数] [--queue]
+                     新建任务并下载到结束；--queue 只加入队列
+  list               列出全部任务
+  pause <ID>...      暂停任务，wait 不再下载它
+  resume <ID>... [--url 新地址] [-c 连接数]
+                     继续下载到结束；--url 先换用新地址（链接过期时）
+  rm <ID>... [--delete]
+                     删除任务；--delete 同时删除已下载的文件
+  wait [ID]... [-c 连接数]
+                     下载指定任务或队列中全部等待的任务，直到结束
+
+ID 可只写开头几位。下载中按 Ctrl-C 暂停并保存进度。
+同一任务文件同时只能由一个进程使用（另一个命令行或桌面版正在使用时报错退出）。
+退出码: 0 全部完成；1 有任务失败；2 参数错误；3 有任务链接过期，需要新地址；130 被中断";
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NEEDS_REFRESH: i32 = 3;
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// 非终端输出时进度行的间隔
+const PLAIN_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+enum Command {
+    Add {
+        urls: Vec<String>,
+        dir: String,
+        filename: Option<String>,
+        mirrors: Vec<String>,
+        connections: Option<usize>,
+        queue: bool,
+    },
+    List,
+    Pause {
+        ids: Vec<String>,
+    },
+    Resume {
+        ids: Vec<String>,
+        url: Option<String>,
+        connections: Option<usize>,
+    },
+    Rm {
+        ids: Vec<String>,
+        delete: bool,
+    },
+    Wait {
+        ids: Vec<String>,
+        connections: Option<usize>,
+    },
+}
+
+struct Cli {
+    json: bool,
+    store: PathBuf,
+    network: NetworkOptions,
+    command: Command,
+}
+
+/// 某个命令的参数：带值选项与开关分开记录
+#[derive(Default)]
+struct Parsed {
+    positional: Vec<String>,
+    values: Vec<(String, String)>,
+    switches: Vec<String>,
+}
+
+impl Parsed {
+    fn parse(args: &[String], value_opts: &[&str], switch_opts: &[&str]) -> Result<Self, String> {
+        let mut parsed = Parsed::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if value_opts.contains(&arg.as_str()) {
+                let value = iter.next().ok_or_else(|| format!("选项 {} 缺少参数", arg))?;
+                parsed.values.push((arg.clone(), value.clone()));
+            } else if switch_opts.contains(&arg.as_str()) {
+                parsed.switches.push(arg.clone());
+            } else if arg.starts_with('-') && arg.len() > 1 {
+                return Err(format!("未知选项 {}", arg));
+            } else {
+                parsed.positional.push(arg.clone());
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn value(&self, name: &str) -> Option<String> {
+        self.values.iter().rev().find(|(k, _)| k == name).map(|(_, v)| v.clone())
+    }
+
+    fn all(&self, name: &str) -> Vec<String> {
+        self.values.iter().filter(|(k, _)| k == name).map(|(_, v)| v.clone()).collect()
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.switches.iter().any(|s| s == name)
+    }
+
+    fn connections(&self) -> Result<Option<usize>, String> {
+        self.value("-c")
+            .map(|c| c.parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(|| format!("无效的连接数: {}", c)))
+            .transpose()
+    }
+}
+
+fn default_store() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("multidown-cli")
+        .join("multidown_tasks.json")
+}
+
+fn parse_cli(args: Vec<String>) -> Result<Cli, String> {
+    let mut json = false;
+    let mut store = std::env::var_os("MULTIDOWN_STORE").map(PathBuf::from);
+    let mut network = NetworkOptions::default();
+    let mut rest = args.into_iter();
+    let command = loop {
+        match rest.next().as_deref() {
+            Some("--json") => json = true,
+            Some("--store") => store = Some(rest.next().ok_or("选项 --store 缺少参数")?.into()),
+            Some("--proxy") => {
+                let raw = rest.next().ok_or("选项 --proxy 缺少参数")?;
+                network.proxy.mode = if raw.eq_ignore_ascii_case("direct") {
+                    ProxyMode::Direct
+                } else {
+                    ProxyMode::Manual(parse_proxy_url(&raw, "http").ok_or_else(|| format!("无效的代理地址: {}", raw))?)
+                };
+            }
+            Some("--no-proxy") => network.proxy.bypass = BypassList::parse(&rest.next().ok_or("选项 --no-proxy 缺少参数")?),
+            Some("--timeout") => {
+                let raw = rest.next().ok_or("选项 --timeout 缺少参数")?;
+                network.timeout_secs = raw
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| format!("无效的超时: {}", raw))?;
+            }
+            Some("-h") | Some("--help") => return Err(String::new()),
+            None => return Err("缺少命令".to_string()),
+            Some(cmd) => break cmd.to_string(),
+        }
+    };
+    let args: Vec<String> = rest.collect();
+    let command = match command.as_str() {
+        "add" => {
+            let p = Parsed::parse(&args, &["-d", "-o", "-m", "-c"], &["--queue"])?;
+            if p.positional.is_empty() {
+                return Err("add 需要至少一个 URL".to_string());
+            }
+            let filename = p.value("-o");
+            if filename.is_some() && p.positional.len() > 1 {
+                return Err("-o 只能用于单个 URL".to_string());
+            }
+            Command::Add {
+                dir: p.value("-d").unwrap_or_else(|| ".".to_string()),
+                filename,
+                mirrors: p.all("-m"),
+                connections: p.connections()?,
+                queue: p.has("--queue"),
+                urls: p.positional,
+            }
+        }
+        "list" => {
+            Parsed::parse(&args, &[], &[])?;
+            Command::List
+        }
+        "pause" | "rm" => {
+            let p = Parsed::parse(&args, &[], &["--delete"])?;
+            if p.positional.is_empty() {
+                return Err(format!("{} 需要任务 ID", command));
+            }
+            if command == "pause" {
+                if p.has("--delete") {
+                    return Err("未知选项 --delete".to_string());
+                }
+                Command::Pause { ids: p.positional }
+            } else {
+                Command::Rm {
+                    delete: p.has("--delete"),
+                    ids: p.positional,
+                }
+            }
+        }
+        "resume" => {
+            let p = Parsed::parse(&args, &["--url", "-c"], &[])?;
+            if p.positional.is_empty() {
+                return Err("resume 需要任务 ID".to_string());
+            }
+            let url = p.value("--url");
+            if url.is_some() && p.positional.len() > 1 {
+                return Err("--url 只能用于单个任务".to_string());
+            }
+            Command::Resume {
+                url,
+                connections: p.connections()?,
+                ids: p.positional,
+            }
+        }
+        "wait" => {
+            let p = Parsed::parse(&args, &["-c"], &[])?;
+            Command::Wait {
+                connections: p.connections()?,
+                ids: p.positional,
+            }
+        }
+        other => return Err(format!("未知命令 {}", other)),
+    };
+    Ok(Cli {
+        json,
+        store: store.unwrap_or_else(default_store),
+        network,
+        command,
+    })
+}
+
+fn format_bytes(n: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if n < 1024 {
+        return format!("{} B", n);
+    }
+    let mut value = n as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn status_text(status: TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Pending => "等待中",
+        TaskStatus::Downloading => "下载中",
+        TaskStatus::Paused => "已暂停",
+        TaskStatus::Completed => "完成",
+        TaskStatus::Failed => "失败",
+        TaskStatus::Cancelled => "已取消",
+        TaskStatus::NeedsRefresh => "链接过期",
+    }
+}
+
+fn short_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+/// 输出：JSON 模式下每行一个 JSON 对象，否则为可读文本
+struct Output {
+    json: bool,
+    /// stderr 为终端时进度在同一行刷新
+    live: bool,
+    progress_shown: bool,
+}
+
+impl Output {
+    fn new(json: bool) -> Self {
+        Self {
+            json,
+            live: !json && std::io::stderr().is_terminal(),
+            progress_shown: false,
+        }
+    }
+
+    fn clear_progress(&mut self) {
+        if self.progress_shown {
+            eprint!("\r\x1b[2K");
+            self.progress_shown = false;
+        }
+    }
+
+    fn line(&mut self, text: String, value: serde_json::Value) {
+        self.clear_progress();
+        if self.json {
+            println!("{}", value);
+        } else {
+            println!("{}", text);
+        }
+    }
+
+    fn error(&mut self, text: String) {
+        self.clear_progress();
+        if self.json {
+            println!("{}", json!({ "event": "error", "message": text }));
+        } else {
+            eprintln!("错误: {}", text);
+        }
+    }
+
+    fn progress(&mut self, tasks: &[TaskInfo]) {
+        if self.json {
+            for t in tasks.iter().filter(|t| t.status == TaskStatus::Downloading) {
+                println!(
+                    "{}",
+                    json!({
+                        "event": "progress",
+                        "id": t.id,
+                        "downloaded_bytes": t.downloaded_bytes,
+                        "total_bytes": t.total_bytes,
+                        "speed_bps": t.speed_bps,
+                    })
+                );
+            }
+            return;
+        }
+        let active: Vec<&TaskInfo> = tasks.iter().filter(|t| t.status == TaskStatus::Downloading).collect();
+        if active.is_empty() {
+            return;
+        }
+        let done: u64 = active.iter().map(|t| t.downloaded_bytes).sum();
+        let speed: u64 = active.iter().filter_map(|t| t.speed_bps).sum();
+        let total = active.iter().map(|t| t.total_bytes).sum::<Option<u64>>();
+        let size = match total {
+            Some(total) if total > 0 => format!(
+                "{:5.1}%  {} / {}",
+                done as f64 * 100.0 / total as f64,
+                format_bytes(done),
+                format_bytes(total)
+            ),
+            _ => format_bytes(done),
+        };
+        let text = format!("下载中 {} 个任务  {}  {}/s", active.len(), size, format_bytes(speed));
+        if self.live {
+            eprint!("\r\x1b[2K{}", text);
+            let _ = std::io::stderr().flush();
+            self.progress_shown = true;
+        } else {
+            eprintln!("{}", text);
+        }
+    }
+}
+
+/// 按 ID 前缀查找任务，前缀须唯一
+async fn resolve_ids(sched: &Scheduler, prefixes: &[String]) -> Result<Vec<TaskId>, String> {
+    let tasks = sched.list_downloads().await;
+    let mut ids = Vec::new();
+    for prefix in prefixes {
+        let matched: Vec<&TaskInfo> = tasks.iter().filter(|t| t.id.starts_with(prefix.as_str())).collect();
+        match matched.as_slice() {
+            [t] => {
+                if !ids.contains(&t.id) {
+                    ids.push(t.id.clone());
+                }
+            }
+            [] => return Err(format!("任务不存在: {}", prefix)),
+            _ => return Err(format!("ID 前缀 {} 匹配多个任务", prefix)),
+        }
+    }
+    Ok(ids)
+}
+
+/// 启动任务并等待全部结束；按结果返回退出码
+async fn run_until_done(
+    sched: &Arc<Scheduler>,
+    ids: Vec<TaskId>,
+    connections: Option<usize>,
+    network: &NetworkOptions,
+    rx: &mut mpsc::UnboundedReceiver<EngineEvent>,
+    out: &mut Output,
+) -> i32 {
+    let mut started = Vec::new();
+    let mut code = EXIT_OK;
+    for id in ids {
+        let status = sched.get_task(&id).await.map(|t| t.status);
+        if status == Some(TaskStatus::Completed) {
+            started.push(id);
+            continue;
+        }
+        match sched
+            .start_download(
+                &id,
+                Some(sched.clone()),
+                connections,
+                Some(network.clone()),
+            )
+            .await
+        {
+            Ok(()) => started.push(id),
+            Err(e) => {
+                out.error(format!("{}: {}", short_id(&id), e));
+                code = EXIT_FAILED;
+            }
+        }
+    }
+
+    let mut tick = tokio::time::interval(if out.live || out.json {
+        Duration::from_millis(500)
+    } else {
+        PLAIN_PROGRESS_INTERVAL
+    });
+    let began = Instant::now();
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                for id in &started {
+                    let _ = sched.pause_task(id).await;
+                }
+                // 等各连接放回未完成区间后再保存
+                for id in &started {
+                    sched.wait_idle(id).await;
+                }
+                sched.save_tasks().await;
+                out.line("已中断，进度已保存".to_string(), json!({ "event": "interrupted" }));
+                return EXIT_INTERRUPTED;
+            }
+            Some(event) = rx.recv() => {
+                // 任务结束时立即检查，不必等到下一次刷新
+                let finished = matches!(
+                    event,
+                    EngineEvent::Finished { .. } | EngineEvent::Failed { .. } | EngineEvent::NeedsRefresh { .. }
+                );
+                report_event(event, out);
+                if !finished {
+                    continue;
+                }
+            }
+            _ = tick.tick() => {}
+        }
+        let mut infos = Vec::new();
+        for id in &started {
+            if let Some(info) = sched.get_task(id).await {
+                infos.push(info);
+            }
+        }
+        if infos.iter().all(|t| t.status != TaskStatus::Downloading) {
+            // 收取结束前发出的剩余事件
+            while let Ok(event) = rx.try_recv() {
+                report_event(event, out);
+            }
+            return summarize(&infos, code, began.elapsed(), out);
+        }
+        out.progress(&infos);
+    }
+}
+
+fn report_event(event: EngineEvent, out: &mut Output) {
+    match event {
+        EngineEvent::Progress { .. } | EngineEvent::StatusChanged { .. } => {}
+        EngineEvent::Finished { id, filename } => out.line(
+            format!("完成 {} {}", short_id(&id), filename),
+            json!({ "event": "completed", "id": id, "filename": filename }),
+        ),
+        EngineEvent::Failed { id, filename, error } => out.line(
+            format!("失败 {} {}: {}", short_id(&id), filename, error),
+            json!({ "event": "failed", "id": id, "filename": filename, "error": error }),
+        ),
+        EngineEvent::NeedsRefresh { id, filename, url } => out.line(
+            format!(
+                "链接过期 {} {}，用 resume {} --url <新地址> 继续",
+                short_id(&id),
+                filename,
+                short_id(&id)
+            ),
+            json!({ "event": "needs_refresh", "id": id, "filename": filename, "url": url }),
+        ),
+    }
+}
+
+fn summarize(infos: &[TaskInfo], code: i32, elapsed: Duration, out: &mut Output) -> i32 {
+    let count = |s: TaskStatus| infos.iter().filter(|t| t.status == s).count();
+    let completed = count(TaskStatus::Completed);
+    let needs_refresh = count(TaskStatus::NeedsRefresh);
+    let failed = infos.len() - completed - needs_refresh;
+    out.line(
+        format!(
+            "共 {} 个任务：完成 {}，失败 {}，链接过期 {}，用时 {:.1}s",
+            infos.len(),
+            completed,
+            failed,
+            needs_refresh,
+            elapsed.as_secs_f64()
+        ),
+        json!({
+            "event": "summary",
+            "completed": completed,
+            "failed": failed,
+            "needs_refresh": needs_refresh,
+        }),
+    );
+    if code != EXIT_OK || failed > 0 {
+        EXIT_FAILED
+    } else if needs_refresh > 0 {
+        EXIT_NEEDS_REFRESH
+    } else {
+        EXIT_OK
+    }
+}
+
+async fn run(cli: Cli) -> i32 {
+    // 与桌面版相同的单实例锁，放在任务文件所在目录：同一任务文件同时只由一个进程读写
+    let store_dir = cli.store.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let _lock = match instance::try_lock(store_dir) {
+        Ok(Some(lock)) => lock,
+        Ok(None) => {
+            eprintln!("错误: 任务文件 {} 正被另一个 Multidown 进程使用", cli.store.display());
+            return EXIT_FAILED;
+        }
+        Err(e) => {
+            eprintln!("错误: 无法锁定任务文件目录 {}: {}", store_dir.display(), e);
+            return EXIT_FAILED;
+        }
+    };
+    let scheduler = if cli.store.exists() {
+        match Scheduler::load_from(&cli.store) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("错误: 无法读取任务文件 {}: {}", cli.store.display(), e);
+                return EXIT_FAILED;
+            }
+        }
+    } else {
+        Scheduler::new(Some(cli.store.clone()))
+    };
+    let (events, mut rx) = ChannelEvents::channel();
+    let sched = Arc::new(scheduler.with_events(events));
+    let mut out = Output::new(cli.json);
+
+    match cli.command {
+        Command::Add {
+            urls,
+            dir,
+            filename,
+            mirrors,
+            connections,
+            queue,
+        } => {
+            let mut ids = Vec::new();
+            let mut code = EXIT_OK;
+            for url in urls {
+                match sched
+                    .create_task_with_mirrors(
+                        url.clone(),
+                        mirrors.clone(),
+                        dir.clone(),
+                        filename.clone(),
+                        None,
+                        &cli.network,
+                    )
+                    .await
+                {
+                    Ok(id) => {
+                        let name = sched.get_task(&id).await.map(|t| t.filename).unwrap_or_default();
+                        out.line(
+                            format!("已添加 {} {}", id, name),
+                            json!({ "event": "added", "id": id, "url": url, "filename": name }),
+                        );
+                        ids.push(id);
+                    }
+                    Err(e) => {
+                        out.error(format!("{}: {}", url, e));
+                        code = EXIT_FAILED;
+                    }
+                }
+            }
+            if queue || ids.is_empty() {
+                return code;
+            }
+            let result = run_until_done(&sched, ids, connections, &cli.network, &mut rx, &mut out).await;
+            if code == EXIT_OK {
+                result
+            } else {
+                code
+            }
+        }
+        Command::List => {
+            let mut tasks = sched.list_downloads().await;
+            tasks.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+            if cli.json {
+                println!("{}", serde_json::to_string(&tasks).unwrap_or_else(|_| "[]".to_string()));
+                return EXIT_OK;
+            }
+            for t in &tasks {
+                let progress = match t.total_bytes {
+                    Some(total) if total > 0 => format!("{:.1}%", t.downloaded_bytes as f64 * 100.0 / total as f64),
+                    _ => format_bytes(t.downloaded_bytes),
+                };
+                println!("{}  {:<6}  {:>8}  {}", short_id(&t.id), status_text(t.status), progress, t.filename);
+                if let Some(err) = &t.error_message {
+                    println!("          {}", err);
+                }
+            }
+            EXIT_OK
+        }
+        Command::Pause { ids } => {
+            let ids = match resolve_ids(&sched, &ids).await {
+                Ok(ids) => ids,
+                Err(e) => {
+                    out.error(e);
+                    return EXIT_FAILED;
+                }
+            };
+            let mut code = EXIT_OK;
+            for id in ids {
+                match sched.pause_task(&id).await {
+                    Ok(()) => out.line(format!("已暂停 {}", short_id(&id)), json!({ "event": "paused", "id": id })),
+                    Err(e) => {
+                        out.error(format!("{}: {}", short_id(&id), e));
+                        code = EXIT_FAILED;
+                    }
+                }
+            }
+            code
+        }
+        Command::Resume { ids, url, connections } => {
+            let ids = match resolve_ids(&sched, &ids).await {
+                Ok(ids) => ids,
+                Err(e) => {
+                    out.error(e);
+                    return EXIT_FAILED;
+                }
+            };
+            if let (Some(url), Some(id)) = (url, ids.first()) {
+                if let Err(e) = sched.rebind_task_url(id, url, &cli.network).await {
+                    out.error(format!("{}: {}", short_id(id), e));
+                    return EXIT_FAILED;
+                }
+            }
+            run_until_done(&sched, ids, connections, &cli.network, &mut rx, &mut out).await
+        }
+        Command::Rm { ids, delete } => {
+            let ids = match resolve_ids(&sched, &ids).await {
+                Ok(ids) => ids,
+                Err(e) => {
+                    out.error(e);
+                    return EXIT_FAILED;
+                }
+            };
+            let mut code = EXIT_OK;
+            for id in ids {
+                let path = sched.get_task(&id).await.map(|t| t.save_path);
+                if let Err(e) = sched.remove_task(&id).await {
+                    out.error(format!("{}: {}", short_id(&id), e));
+                    code = EXIT_FAILED;
+                    continue;
+                }
+                if let (true, Some(path)) = (delete, path) {
+                    let _ = std::fs::remove_file(&path);
+                    let _ = std::fs::remove_dir_all(piece_dir(&path));
+                }
+                out.line(format!("已删除 {}", short_id(&id)), json!({ "event": "removed", "id": id }));
+            }
+            code
+        }
+        Command::Wait { ids, connections } => {
+            let ids = if ids.is_empty() {
+                let mut pending: Vec<TaskInfo> = sched
+                    .list_downloads()
+                    .await
+                    .into_iter()
+                    .filter(|t| t.status == TaskStatus::Pending)
+                    .collect();
+                pending.sort_by_key(|t| t.created_at);
+                pending.into_iter().map(|t| t.id).collect()
+            } else {
+                match resolve_ids(&sched, &ids).await {
+                    Ok(ids) => ids,
+                    Err(e) => {
+                        out.error(e);
+                        return EXIT_FAILED;
+                    }
+                }
+            };
+            if ids.is_empty() {
+                out.line("没有等待中的任务".to_string(), json!({ "event": "summary", "completed": 0, "failed": 0, "needs_refresh": 0 }));
+                return EXIT_OK;
+            }
+            run_until_done(&sched, ids, connections, &cli.network, &mut rx, &mut out).await
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let code = match parse_cli(std::env::args().skip(1).collect()) {
+        Ok(cli) => run(cli).await,
+        Err(e) => {
+            if e.is_empty() {
+                println!("{}", USAGE);
+                EXIT_OK
+            } else {
+                eprintln!("错误: {}\n\n{}", e, USAGE);
+                EXIT_USAGE
+            }
+        }
+    };
+    std::process::exit(code);
+}
@@ -0,0 +1,273 @@
+//! 本机 IPC 传输：Unix 下为应用数据目录中仅当前用户可访问的域套接字，Windows 下为按用户命名的命名管道
+//!
+//! 端点随进程存在：崩溃后遗留的套接字文件在下次启动时经探测确认无人监听后删除；命名管道随进程退出自动消失。
+//! 管道名可被其他用户抢先创建，因此管道只允许当前用户访问，客户端连接后还要核对服务端进程的用户。
+
+use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
+
+#[cfg(unix)]
+pub type IpcStream = tokio::net::UnixStream;
+#[cfg(windows)]
+pub type IpcStream = tokio::net::windows::named_pipe::NamedPipeServer;
+
+#[cfg(unix)]
+pub type ClientStream = tokio::net::UnixStream;
+#[cfg(windows)]
+pub type ClientStream = tokio::net::windows::named_pipe::NamedPipeClient;
+
+/// 套接字所在目录（权限 0700），相对应用数据目录
+#[cfg(unix)]
+pub const SOCKET_DIR: &str = "ipc";
+#[cfg(unix)]
+pub const SOCKET_FILENAME: &str = "native_host.sock";
+
+#[cfg(unix)]
+pub fn socket_path(app_data: &Path) -> PathBuf {
+    app_data.join(SOCKET_DIR).join(SOCKET_FILENAME)
+}
+
+/// 命名管道名含当前用户名，不同用户互不冲突
+#[cfg(windows)]
+pub fn pipe_name() -> String {
+    let user: String = std::env::var("USERNAME")
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!(r"\\.\pipe\multidown-native-host-{}", user)
+}
+
+/// 连接已运行实例的端点（单实例转交启动参数时使用）
+pub async fn connect(app_data: &Path) -> std::io::Result<ClientStream> {
+    #[cfg(unix)]
+    {
+        tokio::net::UnixStream::connect(socket_path(app_data)).await
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawHandle;
+        let _ = app_data;
+        let client = tokio::net::windows::named_pipe::ClientOptions::new().open(pipe_name())?;
+        if !security::server_is_current_user(client.as_raw_handle())? {
+            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "管道服务端不属于当前用户"));
+        }
+        Ok(client)
+    }
+}
+
+/// 探测端点上是否已有进程在监听
+pub async fn probe(app_data: &Path) -> bool {
+    match connect(app_data).await {
+        Ok(_) => true,
+        // 231 = ERROR_PIPE_BUSY：管道存在但实例都在使用中
+        #[cfg(windows)]
+        Err(e) => e.raw_os_error() == Some(231),
+        #[cfg(not(windows))]
+        Err(_) => false,
+    }
+}
+
+pub struct IpcListener {
+    #[cfg(unix)]
+    listener: tokio::net::UnixListener,
+    #[cfg(unix)]
+    path: PathBuf,
+    /// 只接受与本进程同一用户的连接
+    #[cfg(unix)]
+    uid: u32,
+    #[cfg(windows)]
+    name: String,
+    #[cfg(windows)]
+    security: security::OwnerOnly,
+    /// 等待下一个客户端的管道实例
+    #[cfg(windows)]
+    next: tokio::net::windows::named_pipe::NamedPipeServer,
+}
+
+impl IpcListener {
+    /// 创建端点；已有实例在监听时返回 AddrInUse
+    pub async fn bind(app_data: &Path) -> std::io::Result<Self> {
+        if probe(app_data).await {
+            return Err(std::io::Error::new(std::io::ErrorKind::AddrInUse, "已有 Multidown 实例在监听"));
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+            let path = socket_path(app_data);
+            let dir = app_data.join(SOCKET_DIR);
+            std::fs::DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+            // 探测无人监听，说明是崩溃遗留的套接字文件
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            let listener = tokio::net::UnixListener::bind(&path)?;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+            let uid = std::fs::metadata(&path)?.uid();
+            Ok(Self { listener, path, uid })
+        }
+        #[cfg(windows)]
+        {
+            use tokio::net::windows::named_pipe::ServerOptions;
+            let _ = app_data;
+            let name = pipe_name();
+            let mut security = security::OwnerOnly::new()?;
+            // first_pipe_instance：同名管道已被其他进程创建时失败，防止抢注
+            let next = security.create(ServerOptions::new().first_pipe_instance(true).reject_remote_clients(true), &name)?;
+            Ok(Self { name, security, next })
+        }
+    }
+
+    /// 等待下一个连接；Unix 下其他用户的连接直接断开
+    pub async fn accept(&mut self) -> std::io::Result<IpcStream> {
+        #[cfg(unix)]
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            match stream.peer_cred() {
+                Ok(cred) if cred.uid() == self.uid => return Ok(stream),
+                _ => continue,
+            }
+        }
+        #[cfg(windows)]
+        {
+            use tokio::net::windows::named_pipe::ServerOptions;
+            self.next.connect().await?;
+            let next = self.security.create(ServerOptions::new().reject_remote_clients(true), &self.name)?;
+            Ok(std::mem::replace(&mut self.next, next))
+        }
+    }
+
+    /// 退出时删除套接字文件
+    pub fn cleanup(&self) {
+        #[cfg(unix)]
+        {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// 命名管道的访问控制：只允许当前用户的 DACL，以及核对服务端进程的用户 SID
+#[cfg(windows)]
+mod security {
+    use std::io;
+    use std::os::windows::io::RawHandle;
+    use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+    use windows_sys::Win32::Foundation::{CloseHandle, LocalFree, HANDLE};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+    };
+    use windows_sys::Win32::Security::{
+        EqualSid, GetTokenInformation, TokenUser, PSECURITY_DESCRIPTOR, PSID, SECURITY_ATTRIBUTES, TOKEN_QUERY, TOKEN_USER,
+    };
+    use windows_sys::Win32::System::Pipes::GetNamedPipeServerProcessId;
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    /// 进程令牌中的 TOKEN_USER；SID 指针指向缓冲区内部
+    struct ProcessUser(Vec<u64>);
+
+    impl ProcessUser {
+        fn of(process: HANDLE) -> io::Result<Self> {
+            unsafe {
+                let mut token: HANDLE = std::ptr::null_mut();
+                if OpenProcessToken(process, TOKEN_QUERY, &mut token) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let mut len = 0u32;
+                GetTokenInformation(token, TokenUser, std::ptr::null_mut(), 0, &mut len);
+                let mut buf = vec![0u64; len as usize / 8 + 1];
+                let ok = GetTokenInformation(token, TokenUser, buf.as_mut_ptr().cast(), len, &mut len);
+                let err = io::Error::last_os_error();
+                CloseHandle(token);
+                if ok == 0 {
+                    return Err(err);
+                }
+                Ok(Self(buf))
+            }
+        }
+
+        fn current() -> io::Result<Self> {
+            Self::of(unsafe { GetCurrentProcess() })
+        }
+
+        fn sid(&self) -> PSID {
+            unsafe { (*self.0.as_ptr().cast::<TOKEN_USER>()).User.Sid }
+        }
+    }
+
+    /// 管道服务端进程是否与当前进程属于同一用户
+    pub fn server_is_current_user(pipe: RawHandle) -> io::Result<bool> {
+        let me = ProcessUser::current()?;
+        unsafe {
+            let mut pid = 0u32;
+            if GetNamedPipeServerProcessId(pipe, &mut pid) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let server = ProcessUser::of(process);
+            CloseHandle(process);
+            Ok(EqualSid(server?.sid(), me.sid()) != 0)
+        }
+    }
+
+    /// 只授予当前用户完全访问权限的安全描述符，创建每个管道实例时使用
+    pub struct OwnerOnly {
+        descriptor: PSECURITY_DESCRIPTOR,
+    }
+
+    // 描述符创建后只读，由 LocalFree 释放
+    unsafe impl Send for OwnerOnly {}
+    unsafe impl Sync for OwnerOnly {}
+
+    impl OwnerOnly {
+        pub fn new() -> io::Result<Self> {
+            let me = ProcessUser::current()?;
+            unsafe {
+                let mut text: *mut u16 = std::ptr::null_mut();
+                if ConvertSidToStringSidW(me.sid(), &mut text) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let len = (0..).take_while(|&i| *text.add(i) != 0).count();
+                let sid = String::from_utf16_lossy(std::slice::from_raw_parts(text, len));
+                LocalFree(text.cast());
+                // P：不继承父级 ACE；GA：完全访问
+                let sddl: Vec<u16> = format!("D:P(A;;GA;;;{})", sid).encode_utf16().chain(Some(0)).collect();
+                let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+                if ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                    sddl.as_ptr(),
+                    SDDL_REVISION_1,
+                    &mut descriptor,
+                    std::ptr::null_mut(),
+                ) == 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(Self { descriptor })
+            }
+        }
+
+        pub fn create(&mut self, options: &mut ServerOptions, name: &str) -> io::Result<NamedPipeServer> {
+            let mut attrs = SECURITY_ATTRIBUTES {
+                nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: self.descriptor,
+                bInheritHandle: 0,
+            };
+            unsafe { options.create_with_security_attributes_raw(name, (&mut attrs as *mut SECURITY_ATTRIBUTES).cast()) }
+        }
+    }
+
+    impl Drop for OwnerOnly {
+        fn drop(&mut self) {
+            unsafe {
+                LocalFree(self.descriptor);
+            }
+        }
+    }
+}
@@ -0,0 +1,454 @@
+//! 浏览器集成协议：Native Host 经本机 IPC（见 `ipc`，可选开启 TCP 回退）与主程序通信
+//!
+//! 每行一个 JSON。连接后第一条必须是握手 `{"action":"hello","token":"…","versions":[1]}`，
+//! 令牌取自应用数据目录下的 `native_host_token.txt`（主程序每次启动重新生成）；
+//! 主程序回复 `{"ok":true,"version":2}` 后，同一连接上可发送多条命令，按顺序每条回复一行。
+//! 版本 2 起可暂停、继续、取消、分页列出任务，并在 `subscribe` 后于同一连接上收到任务事件（带 `event` 字段的行）。
+
+pub mod ipc;
+
+use crate::engine::scheduler::Scheduler;
+use crate::engine::{BroadcastEvents, EngineEvent, TaskId, TaskInfo, TaskStatus};
+use crate::search::TaskQuery;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// 当前协议版本
+pub const PROTOCOL_VERSION: u32 = 2;
+/// 仍兼容的最低协议版本
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+pub const PORT_FILENAME: &str = "native_host_port.txt";
+pub const TOKEN_FILENAME: &str = "native_host_token.txt";
+
+/// 任一传输上的连接，便于 IPC 与 TCP 共用同一套处理
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// 单行消息上限
+const MAX_LINE: u64 = 64 * 1024;
+/// list 每页最多的任务数，使回复远小于 Native Messaging 单条消息 1 MB 的上限
+pub const LIST_PAGE_MAX: usize = 100;
+/// 连接后须在此时间内完成握手
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+/// 订阅后进度合并推送的间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 握手
+#[derive(Debug, Deserialize)]
+pub struct Hello {
+    pub token: String,
+    /// 客户端支持的协议版本
+    pub versions: Vec<u32>,
+}
+
+/// 下载请求；空字符串字段视为未提供
+#[derive(Debug, Clone, Deserialize)]
+pub struct DownloadRequest {
+    pub url: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub filename: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub referer: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub user_agent: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub cookie: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub post_data: Option<String>,
+    /// 保存目录，须位于允许的目录之内
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub save_path: Option<String>,
+    #[serde(default = "default_open_window")]
+    pub open_window: bool,
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+}
+
+impl DownloadRequest {
+    /// 只有地址的请求（命令行、协议链接等来源）
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            filename: None,
+            referer: None,
+            user_agent: None,
+            cookie: None,
+            post_data: None,
+            save_path: None,
+            open_window: true,
+            mirrors: Vec::new(),
+        }
+    }
+}
+
+fn default_open_window() -> bool {
+    true
+}
+
+fn empty_as_none<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    let s: Option<String> = Option::deserialize(d)?;
+    Ok(s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()))
+}
+
+/// 握手之后的命令
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Command {
+    Download(DownloadRequest),
+    OpenWindow {
+        #[serde(default, deserialize_with = "empty_as_none")]
+        url: Option<String>,
+    },
+    Pause {
+        id: TaskId,
+    },
+    Resume {
+        id: TaskId,
+    },
+    Cancel {
+        id: TaskId,
+    },
+    /// 列出任务，按创建时间分页；active 为 true 时只列出等待、下载中与暂停的任务
+    List {
+        #[serde(default)]
+        active: bool,
+        #[serde(default)]
+        offset: usize,
+        /// 省略或超过 LIST_PAGE_MAX 时为 LIST_PAGE_MAX
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// 开始在本连接上推送任务事件，由 serve_connection 处理，不交给 handler
+    Subscribe,
+    /// 再次启动的进程转交的启动参数
+    Launch(crate::instance::LaunchArgs),
+}
+
+impl Command {
+    /// 需要的最低协议版本
+    fn min_version(&self) -> u32 {
+        match self {
+            Command::Download(_) | Command::OpenWindow { .. } => 1,
+            _ => 2,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum HelloMessage {
+    Hello(Hello),
+}
+
+/// 每条消息的回复
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 握手回复中为协商出的版本
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    /// 握手失败时列出主程序支持的版本
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supported: Option<Vec<u32>>,
+    /// download 新建或认领的任务
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<TaskId>,
+    /// list 的结果
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tasks: Option<Vec<TaskInfo>>,
+    /// list 中符合条件的任务总数（分页前）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            version: None,
+            supported: None,
+            task_id: None,
+            tasks: None,
+            total: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            version: None,
+            supported: None,
+            task_id: None,
+            tasks: None,
+            total: None,
+        }
+    }
+}
+
+impl From<Result<TaskId, String>> for Response {
+    fn from(r: Result<TaskId, String>) -> Self {
+        match r {
+            Ok(id) => Self {
+                task_id: Some(id),
+                ..Self::ok()
+            },
+            Err(e) => Self::error(e),
+        }
+    }
+}
+
+impl From<Result<(), String>> for Response {
+    fn from(r: Result<(), String>) -> Self {
+        match r {
+            Ok(()) => Self::ok(),
+            Err(e) => Self::error(e),
+        }
+    }
+}
+
+/// list 的一页：按创建时间升序，limit 不超过 LIST_PAGE_MAX
+pub fn list_page(tasks: Vec<TaskInfo>, active: bool, offset: usize, limit: Option<usize>) -> Response {
+    let query = TaskQuery {
+        status: if active {
+            vec![TaskStatus::Pending, TaskStatus::Downloading, TaskStatus::Paused]
+        } else {
+            Vec::new()
+        },
+        offset,
+        limit: Some(limit.unwrap_or(LIST_PAGE_MAX).min(LIST_PAGE_MAX)),
+        ..TaskQuery::default()
+    };
+    let page = query.apply(tasks);
+    Response {
+        tasks: Some(page.tasks),
+        total: Some(page.total),
+        ..Response::ok()
+    }
+}
+
+/// 取双方都支持的最高版本
+pub fn negotiate(client_versions: &[u32]) -> Option<u32> {
+    client_versions
+        .iter()
+        .copied()
+        .filter(|v| (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(v))
+        .max()
+}
+
+/// 生成新令牌写入应用数据目录（仅当前用户可读），返回令牌
+pub fn write_token_file(app_data: &Path) -> std::io::Result<String> {
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    std::fs::create_dir_all(app_data)?;
+    let path = app_data.join(TOKEN_FILENAME);
+    let _ = std::fs::remove_file(&path);
+    let mut opts = std::fs::OpenOptions::new();
+    opts.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    std::io::Write::write_all(&mut opts.open(&path)?, token.as_bytes())?;
+    Ok(token)
+}
+
+/// 校验扩展指定的保存目录：须为绝对路径、不含 `..`，且解析符号链接后位于某个允许的目录之内
+pub fn check_save_dir(dir: &str, allowed: &[PathBuf]) -> Result<String, String> {
+    let path = Path::new(dir);
+    if !path.is_absolute() {
+        return Err("保存目录须为绝对路径".to_string());
+    }
+    if path.components().any(|c| c == Component::ParentDir) {
+        return Err("保存目录不能包含 ..".to_string());
+    }
+    let resolved = resolve_existing_prefix(path);
+    let inside = allowed
+        .iter()
+        .filter_map(|a| a.canonicalize().ok())
+        .any(|a| resolved.starts_with(&a));
+    if inside {
+        Ok(dir.to_string())
+    } else {
+        Err("保存目录不在允许的范围内".to_string())
+    }
+}
+
+/// 对最深的已存在祖先目录做 canonicalize，再接上尚不存在的部分
+fn resolve_existing_prefix(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(p) = existing.canonicalize() {
+            return rest.iter().rev().fold(p, |acc, c| acc.join(c));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// 文件名不能带路径
+pub fn check_filename(name: &str) -> Result<(), String> {
+    if name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err("文件名不能包含路径".to_string());
+    }
+    Ok(())
+}
+
+/// 读取一行，超过上限视为错误；连接关闭时返回 None
+async fn read_line<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> std::io::Result<Option<String>> {
+    let mut line = String::new();
+    let n = (&mut *reader).take(MAX_LINE).read_line(&mut line).await?;
+    if n == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') && n as u64 >= MAX_LINE {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "消息过长"));
+    }
+    Ok(Some(line))
+}
+
+async fn write_json<W: AsyncWrite + Unpin, T: Serialize>(w: &mut W, value: &T) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(value).unwrap_or_else(|_| "{\"ok\":false}".to_string());
+    line.push('\n');
+    w.write_all(line.as_bytes()).await?;
+    w.flush().await
+}
+
+async fn reply<W: AsyncWrite + Unpin>(w: &mut W, resp: &Response) -> std::io::Result<()> {
+    write_json(w, resp).await
+}
+
+/// 未订阅时永不返回，便于放在 select 中
+async fn recv_event(rx: &mut Option<broadcast::Receiver<EngineEvent>>) -> Result<EngineEvent, RecvError> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 处理一个连接：校验握手后逐行解析命令交给 handler，直到对方关闭。
+/// 订阅后状态类事件立即推送，进度按任务合并、每秒推送一次 `{"event":"progress","task":TaskInfo}`
+pub async fn serve_connection<S, H, F>(
+    stream: S,
+    token: &str,
+    scheduler: &Scheduler,
+    events: &BroadcastEvents,
+    handler: H,
+) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    H: Fn(Command) -> F,
+    F: Future<Output = Response>,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+
+    let first = match tokio::time::timeout(HELLO_TIMEOUT, read_line(&mut reader)).await {
+        Ok(Ok(Some(line))) => line,
+        Ok(Ok(None)) => return Ok(()),
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return reply(&mut writer, &Response::error("握手超时")).await,
+    };
+    let hello = match serde_json::from_str::<HelloMessage>(first.trim()) {
+        Ok(HelloMessage::Hello(h)) => h,
+        Err(_) => return reply(&mut writer, &Response::error("需要先握手")).await,
+    };
+    if !crate::control::token_matches(&hello.token, token) {
+        return reply(&mut writer, &Response::error("令牌无效")).await;
+    }
+    let Some(version) = negotiate(&hello.versions) else {
+        let mut resp = Response::error("协议版本不兼容");
+        resp.supported = Some((MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).collect());
+        return reply(&mut writer, &resp).await;
+    };
+    let mut resp = Response::ok();
+    resp.version = Some(version);
+    reply(&mut writer, &resp).await?;
+
+    let mut subscription: Option<broadcast::Receiver<EngineEvent>> = None;
+    let mut pending: HashSet<TaskId> = HashSet::new();
+    let mut tick = tokio::time::interval(PROGRESS_INTERVAL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // 读到一半的行；fill_buf 可安全取消，已读的数据不会因 select 选中其他分支而丢失
+    let mut partial: Vec<u8> = Vec::new();
+    loop {
+        tokio::select! {
+            buf = reader.fill_buf() => {
+                let buf = buf?;
+                if buf.is_empty() {
+                    return Ok(());
+                }
+                let (n, complete) = match buf.iter().position(|b| *b == b'\n') {
+                    Some(i) => (i + 1, true),
+                    None => (buf.len(), false),
+                };
+                partial.extend_from_slice(&buf[..n]);
+                reader.consume(n);
+                if partial.len() as u64 > MAX_LINE {
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "消息过长"));
+                }
+                if !complete {
+                    continue;
+                }
+                let line = String::from_utf8_lossy(&std::mem::take(&mut partial)).trim().to_string();
+                if line.is_empty() {
+                    continue;
+                }
+                let resp = match serde_json::from_str::<Command>(&line) {
+                    Ok(cmd) if cmd.min_version() > version => {
+                        Response::error(format!("该命令需要协议版本 {}", cmd.min_version()))
+                    }
+                    Ok(Command::Subscribe) => {
+                        subscription.get_or_insert_with(|| events.subscribe());
+                        Response::ok()
+                    }
+                    Ok(cmd) => handler(cmd).await,
+                    Err(e) => Response::error(format!("消息格式无效: {}", e)),
+                };
+                reply(&mut writer, &resp).await?;
+            }
+            ev = recv_event(&mut subscription) => match ev {
+                Ok(EngineEvent::Progress { id }) => {
+                    pending.insert(id);
+                }
+                Ok(ev) => {
+                    if let EngineEvent::StatusChanged { id, .. }
+                    | EngineEvent::Finished { id, .. }
+                    | EngineEvent::Failed { id, .. }
+                    | EngineEvent::NeedsRefresh { id, .. } = &ev
+                    {
+                        pending.remove(id);
+                    }
+                    write_json(&mut writer, &ev).await?;
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    write_json(&mut writer, &serde_json::json!({ "event": "lagged", "skipped": skipped })).await?;
+                }
+                Err(RecvError::Closed) => subscription = None,
+            },
+            _ = tick.tick(), if !pending.is_empty() => {
+                for id in std::mem::take(&mut pending) {
+                    if let Some(task) = scheduler.get_task(&id).await {
+                        write_json(&mut writer, &serde_json::json!({ "event": "progress", "task": task })).await?;
+                    }
+                }
+            }
+        }
+    }
+}
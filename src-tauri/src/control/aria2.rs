@@ -0,0 +1,532 @@
+//! aria2 兼容的 JSON-RPC 子集（POST /jsonrpc 或 WebSocket /jsonrpc），供 AriaNg 等现有工具直接使用
+//!
+//! 与 aria2 一致：参数为位置参数，首个参数为 `"token:<令牌>"`；GID 取任务 ID 的前 16 位十六进制；
+//! 数值一律以字符串返回。不支持的任务选项忽略。
+
+use super::rpc::{Methods, RpcError, METHOD_NOT_FOUND};
+use super::ControlContext;
+use crate::engine::{EngineEvent, TaskInfo, TaskSource, TaskStatus};
+use crate::intake::{DuplicatePolicy, IntakeItem, IntakeKind, IntakeOptions, IntakeResult};
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use std::sync::OnceLock;
+
+/// aria2 除协议错误外统一使用的错误码
+const ARIA2_ERROR: i64 = 1;
+
+const METHODS: &[&str] = &[
+    "aria2.addUri",
+    "aria2.remove",
+    "aria2.forceRemove",
+    "aria2.pause",
+    "aria2.pauseAll",
+    "aria2.forcePause",
+    "aria2.forcePauseAll",
+    "aria2.unpause",
+    "aria2.unpauseAll",
+    "aria2.tellStatus",
+    "aria2.getUris",
+    "aria2.getFiles",
+    "aria2.getPeers",
+    "aria2.getServers",
+    "aria2.tellActive",
+    "aria2.tellWaiting",
+    "aria2.tellStopped",
+    "aria2.getOption",
+    "aria2.changeOption",
+    "aria2.getGlobalOption",
+    "aria2.changeGlobalOption",
+    "aria2.getGlobalStat",
+    "aria2.purgeDownloadResult",
+    "aria2.removeDownloadResult",
+    "aria2.getVersion",
+    "aria2.getSessionInfo",
+    "aria2.saveSession",
+    "system.multicall",
+    "system.listMethods",
+    "system.listNotifications",
+];
+
+const NOTIFICATIONS: &[&str] = &[
+    "aria2.onDownloadStart",
+    "aria2.onDownloadPause",
+    "aria2.onDownloadStop",
+    "aria2.onDownloadComplete",
+    "aria2.onDownloadError",
+];
+
+/// 本次运行的会话 ID：接口启动时随机生成，与令牌无关；格式同 aria2（40 位十六进制）
+pub(super) fn session_id() -> &'static str {
+    static ID: OnceLock<String> = OnceLock::new();
+    ID.get_or_init(|| {
+        let mut id = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        id.truncate(40);
+        id
+    })
+}
+
+pub(super) struct Aria2<'a>(pub &'a ControlContext);
+
+#[async_trait]
+impl Methods for Aria2<'_> {
+    async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let mut params = match params {
+            Value::Array(a) => a,
+            Value::Null => Vec::new(),
+            _ => return Err(error("params 须为数组")),
+        };
+        match method {
+            "system.listMethods" => return Ok(json!(METHODS)),
+            "system.listNotifications" => return Ok(json!(NOTIFICATIONS)),
+            "system.multicall" => return self.multicall(params).await,
+            _ => {}
+        }
+        self.check_token(&mut params)?;
+        self.dispatch(method, Args(params)).await
+    }
+}
+
+/// 任务 ID（UUID）对应的 GID
+fn gid(id: &str) -> String {
+    let hex: String = id.chars().filter(|c| *c != '-').collect();
+    hex.chars().take(16).collect()
+}
+
+fn error(message: impl Into<String>) -> RpcError {
+    RpcError::new(ARIA2_ERROR, message)
+}
+
+fn status_name(s: TaskStatus) -> &'static str {
+    match s {
+        TaskStatus::Downloading => "active",
+        TaskStatus::Pending => "waiting",
+        TaskStatus::Paused => "paused",
+        TaskStatus::Completed => "complete",
+        TaskStatus::Failed | TaskStatus::NeedsRefresh => "error",
+        TaskStatus::Cancelled => "removed",
+    }
+}
+
+fn is_waiting(s: TaskStatus) -> bool {
+    matches!(s, TaskStatus::Pending | TaskStatus::Paused)
+}
+
+fn is_stopped(s: TaskStatus) -> bool {
+    matches!(
+        s,
+        TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled | TaskStatus::NeedsRefresh
+    )
+}
+
+fn dir_of(info: &TaskInfo) -> String {
+    std::path::Path::new(&info.save_path)
+        .parent()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn uris(info: &TaskInfo) -> Value {
+    if info.sources.is_empty() {
+        json!([{ "uri": info.url, "status": "used" }])
+    } else {
+        Value::Array(
+            info.sources
+                .iter()
+                .map(|s| json!({ "uri": s.url, "status": if s.disabled.is_none() { "used" } else { "waiting" } }))
+                .collect(),
+        )
+    }
+}
+
+fn files(info: &TaskInfo) -> Value {
+    json!([{
+        "index": "1",
+        "path": info.save_path,
+        "length": info.total_bytes.unwrap_or(0).to_string(),
+        "completedLength": info.downloaded_bytes.to_string(),
+        "selected": "true",
+        "uris": uris(info),
+    }])
+}
+
+/// aria2 的 tellStatus 结构；keys 非空时只保留指定字段
+fn status(info: &TaskInfo, keys: &[String]) -> Value {
+    let active = info.status == TaskStatus::Downloading;
+    let connections = if !active {
+        0
+    } else if info.sources.is_empty() {
+        1
+    } else {
+        info.sources.iter().filter(|s| s.disabled.is_none()).count()
+    };
+    let mut v = json!({
+        "gid": gid(&info.id),
+        "status": status_name(info.status),
+        "totalLength": info.total_bytes.unwrap_or(0).to_string(),
+        "completedLength": info.downloaded_bytes.to_string(),
+        "uploadLength": "0",
+        "downloadSpeed": if active { info.speed_bps.unwrap_or(0) } else { 0 }.to_string(),
+        "uploadSpeed": "0",
+        "connections": connections.to_string(),
+        "dir": dir_of(info),
+        "files": files(info),
+    });
+    if let (Value::Object(m), Some(msg)) = (&mut v, &info.error_message) {
+        if matches!(info.status, TaskStatus::Failed | TaskStatus::NeedsRefresh) {
+            m.insert("errorCode".into(), json!("1"));
+            m.insert("errorMessage".into(), json!(msg));
+        }
+    }
+    if let (Value::Object(m), false) = (&mut v, keys.is_empty()) {
+        m.retain(|k, _| keys.iter().any(|key| key == k));
+    }
+    v
+}
+
+/// aria2 的分页：offset 为负时从末尾倒数，结果按倒序返回
+fn page<T>(list: Vec<T>, offset: i64, num: i64) -> Vec<T> {
+    if num <= 0 {
+        return Vec::new();
+    }
+    if offset >= 0 {
+        return list.into_iter().skip(offset as usize).take(num as usize).collect();
+    }
+    let start = list.len() as i64 + offset;
+    if start < 0 {
+        return Vec::new();
+    }
+    let mut picked: Vec<T> = list.into_iter().take(start as usize + 1).collect();
+    picked.reverse();
+    picked.truncate(num as usize);
+    picked
+}
+
+/// 调度器事件对应的 aria2 通知（仅 WebSocket 推送）
+pub(super) fn notification(ev: &EngineEvent) -> Option<Value> {
+    let (method, id) = match ev {
+        EngineEvent::StatusChanged { id, status } => match status {
+            TaskStatus::Downloading => ("aria2.onDownloadStart", id),
+            TaskStatus::Paused => ("aria2.onDownloadPause", id),
+            TaskStatus::Cancelled => ("aria2.onDownloadStop", id),
+            _ => return None,
+        },
+        EngineEvent::Finished { id, .. } => ("aria2.onDownloadComplete", id),
+        EngineEvent::Failed { id, .. } | EngineEvent::NeedsRefresh { id, .. } => ("aria2.onDownloadError", id),
+        EngineEvent::Progress { .. } => return None,
+    };
+    Some(json!({ "jsonrpc": "2.0", "method": method, "params": [{ "gid": gid(id) }] }))
+}
+
+/// 去掉令牌后的位置参数
+struct Args(Vec<Value>);
+
+impl Args {
+    fn get(&self, i: usize) -> Option<&Value> {
+        self.0.get(i).filter(|v| !v.is_null())
+    }
+
+    fn str(&self, i: usize, name: &str) -> Result<&str, RpcError> {
+        self.get(i)
+            .and_then(Value::as_str)
+            .ok_or_else(|| error(format!("缺少参数 {}", name)))
+    }
+
+    fn int(&self, i: usize, name: &str) -> Result<i64, RpcError> {
+        self.get(i)
+            .and_then(|v| v.as_i64().or_else(|| v.as_str().and_then(|s| s.parse().ok())))
+            .ok_or_else(|| error(format!("缺少参数 {}", name)))
+    }
+
+    fn options(&self, i: usize) -> Result<Map<String, Value>, RpcError> {
+        match self.get(i) {
+            None => Ok(Map::new()),
+            Some(Value::Object(m)) => Ok(m.clone()),
+            Some(_) => Err(error("选项须为对象")),
+        }
+    }
+
+    fn keys(&self, i: usize) -> Vec<String> {
+        self.get(i)
+            .and_then(Value::as_array)
+            .map(|a| a.iter().filter_map(|k| k.as_str().map(String::from)).collect())
+            .unwrap_or_default()
+    }
+}
+
+fn option_str<'m>(opts: &'m Map<String, Value>, key: &str) -> Option<&'m str> {
+    opts.get(key).and_then(Value::as_str).filter(|s| !s.is_empty())
+}
+
+fn parse_num<T: std::str::FromStr>(key: &str, v: &Value) -> Result<T, RpcError> {
+    let s = match v {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    s.trim().parse().map_err(|_| error(format!("选项 {} 的值无效: {}", key, s)))
+}
+
+impl Aria2<'_> {
+    fn check_token(&self, params: &mut Vec<Value>) -> Result<(), RpcError> {
+        let given = match params.first().and_then(Value::as_str).and_then(|s| s.strip_prefix("token:")) {
+            Some(t) => t.to_string(),
+            None => return Err(error("Unauthorized")),
+        };
+        if !super::token_matches(&given, &self.0.token) {
+            return Err(error("Unauthorized"));
+        }
+        params.remove(0);
+        Ok(())
+    }
+
+    async fn multicall(&self, params: Vec<Value>) -> Result<Value, RpcError> {
+        let calls = match params.into_iter().next() {
+            Some(Value::Array(calls)) => calls,
+            _ => return Err(error("system.multicall 参数须为调用数组")),
+        };
+        let mut results = Vec::with_capacity(calls.len());
+        for call in calls {
+            let method = call.get("methodName").and_then(Value::as_str).unwrap_or_default();
+            let params = call.get("params").cloned().unwrap_or(Value::Null);
+            let r = if method == "system.multicall" {
+                Err(error("不能嵌套 system.multicall"))
+            } else {
+                self.call(method, params).await
+            };
+            // 成功结果包一层数组，失败为错误对象
+            results.push(match r {
+                Ok(v) => json!([v]),
+                Err(e) => e.to_json(),
+            });
+        }
+        Ok(Value::Array(results))
+    }
+
+    /// 按 GID 查找任务
+    async fn find(&self, gid_str: &str) -> Result<TaskInfo, RpcError> {
+        self.0.scheduler.list_downloads()
+            .await
+            .into_iter()
+            .find(|t| gid(&t.id) == gid_str)
+            .ok_or_else(|| error(format!("GID {} is not found", gid_str)))
+    }
+
+    async fn resume(&self, id: &str) -> Result<(), RpcError> {
+        let sched = &self.0.scheduler;
+        let settings = self.0.settings();
+        let net = settings.network_options().await.map_err(error)?;
+        sched
+            .resume_task(
+                id,
+                Some(sched.clone()),
+                Some(settings.max_connections_per_task as usize),
+                Some(net),
+            )
+            .await
+            .map_err(error)
+    }
+
+    async fn dispatch(&self, method: &str, args: Args) -> Result<Value, RpcError> {
+        let ctx = self.0;
+        let sched = &ctx.scheduler;
+        match method {
+            "aria2.addUri" => {
+                let uris: Vec<String> = args
+                    .get(0)
+                    .and_then(Value::as_array)
+                    .map(|a| a.iter().filter_map(|u| u.as_str().map(String::from)).collect())
+                    .unwrap_or_default();
+                let Some((url, mirrors)) = uris.split_first() else {
+                    return Err(error("uris 不能为空"));
+                };
+                if !sched.protocols().supports(url) {
+                    return Err(error(format!("不支持的下载地址: {}", url)));
+                }
+                let opts = args.options(1)?;
+                let item = IntakeItem {
+                    url: url.clone(),
+                    mirrors: mirrors.to_vec(),
+                    save_dir: option_str(&opts, "dir").map(String::from),
+                    filename: option_str(&opts, "out").map(String::from),
+                    probe: None,
+                    headers: Vec::new(),
+                    kind: IntakeKind::Auto,
+                };
+                let options = IntakeOptions {
+                    duplicate: Some(DuplicatePolicy::from_setting(&ctx.settings().duplicate_action).unattended()),
+                    start: option_str(&opts, "pause") != Some("true"),
+                    source: TaskSource::Control,
+                    ..IntakeOptions::default()
+                };
+                let id = match ctx.intake.submit_one(item, options).await {
+                    IntakeResult::Skipped { existing } | IntakeResult::Duplicate { existing } => existing,
+                    result => result.into_id().map_err(error)?,
+                };
+                Ok(json!(gid(&id)))
+            }
+            "aria2.remove" | "aria2.forceRemove" => {
+                let info = self.find(args.str(0, "gid")?).await?;
+                sched.cancel_task(&info.id).await.map_err(error)?;
+                Ok(json!(gid(&info.id)))
+            }
+            "aria2.pause" | "aria2.forcePause" => {
+                let info = self.find(args.str(0, "gid")?).await?;
+                sched.pause_task(&info.id).await.map_err(error)?;
+                Ok(json!(gid(&info.id)))
+            }
+            "aria2.pauseAll" | "aria2.forcePauseAll" => {
+                for t in self.0.scheduler.list_downloads().await {
+                    if matches!(t.status, TaskStatus::Downloading | TaskStatus::Pending) {
+                        sched.pause_task(&t.id).await.map_err(error)?;
+                    }
+                }
+                Ok(json!("OK"))
+            }
+            "aria2.unpause" => {
+                let info = self.find(args.str(0, "gid")?).await?;
+                if info.status != TaskStatus::Paused {
+                    return Err(error(format!("GID {} cannot be unpaused now", gid(&info.id))));
+                }
+                self.resume(&info.id).await?;
+                Ok(json!(gid(&info.id)))
+            }
+            "aria2.unpauseAll" => {
+                for t in self.0.scheduler.list_downloads().await {
+                    if t.status == TaskStatus::Paused {
+                        self.resume(&t.id).await?;
+                    }
+                }
+                Ok(json!("OK"))
+            }
+            "aria2.tellStatus" => {
+                let info = self.find(args.str(0, "gid")?).await?;
+                Ok(status(&info, &args.keys(1)))
+            }
+            "aria2.getUris" => Ok(uris(&self.find(args.str(0, "gid")?).await?)),
+            "aria2.getFiles" => Ok(files(&self.find(args.str(0, "gid")?).await?)),
+            "aria2.getPeers" | "aria2.getServers" => {
+                self.find(args.str(0, "gid")?).await?;
+                Ok(json!([]))
+            }
+            "aria2.tellActive" => {
+                let keys = args.keys(0);
+                let list = self.0.scheduler.list_downloads().await;
+                Ok(Value::Array(
+                    list.iter()
+                        .filter(|t| t.status == TaskStatus::Downloading)
+                        .map(|t| status(t, &keys))
+                        .collect(),
+                ))
+            }
+            "aria2.tellWaiting" | "aria2.tellStopped" => {
+                let (offset, num, keys) = (args.int(0, "offset")?, args.int(1, "num")?, args.keys(2));
+                let filter: fn(TaskStatus) -> bool = if method == "aria2.tellWaiting" { is_waiting } else { is_stopped };
+                let list: Vec<TaskInfo> = self.0.scheduler.list_downloads().await.into_iter().filter(|t| filter(t.status)).collect();
+                Ok(Value::Array(page(list, offset, num).iter().map(|t| status(t, &keys)).collect()))
+            }
+            "aria2.getOption" => {
+                let info = self.find(args.str(0, "gid")?).await?;
+                Ok(json!({ "dir": dir_of(&info), "out": info.filename }))
+            }
+            "aria2.changeOption" => {
+                // 仅支持 dir / out（移动或重命名，下载中不可改）；其余选项忽略
+                let info = self.find(args.str(0, "gid")?).await?;
+                let opts = args.options(1)?;
+                let dir = option_str(&opts, "dir");
+                let out = option_str(&opts, "out");
+                if dir.is_some() || out.is_some() {
+                    if info.status == TaskStatus::Downloading {
+                        return Err(error("下载中的任务不能修改保存位置"));
+                    }
+                    let dir = dir.map(String::from).unwrap_or_else(|| dir_of(&info));
+                    let path = std::path::Path::new(&dir).join(out.unwrap_or(&info.filename));
+                    sched
+                        .update_task_save_path(&info.id, path.to_string_lossy().into_owned())
+                        .await
+                        .map_err(error)?;
+                }
+                Ok(json!("OK"))
+            }
+            "aria2.getGlobalOption" => {
+                let s = ctx.settings();
+                Ok(json!({
+                    "dir": ctx.intake.default_save_dir(&s),
+                    "max-concurrent-downloads": s.max_concurrent_tasks.to_string(),
+                    "max-connection-per-server": s.max_connections_per_task.to_string(),
+                    "split": s.max_connections_per_task.to_string(),
+                    "user-agent": s.user_agent,
+                    "timeout": s.timeout_secs.to_string(),
+                }))
+            }
+            "aria2.changeGlobalOption" => {
+                let opts = args.options(0)?;
+                let mut s = ctx.settings();
+                for (k, v) in &opts {
+                    match k.as_str() {
+                        "dir" => s.default_save_path = v.as_str().unwrap_or_default().to_string(),
+                        "max-concurrent-downloads" => s.max_concurrent_tasks = parse_num(k, v)?,
+                        "max-connection-per-server" | "split" => s.max_connections_per_task = parse_num(k, v)?,
+                        "user-agent" => s.user_agent = v.as_str().unwrap_or_default().to_string(),
+                        "timeout" => s.timeout_secs = parse_num(k, v)?,
+                        _ => {}
+                    }
+                }
+                ctx.intake.save_settings(&s).await.map_err(error)?;
+                Ok(json!("OK"))
+            }
+            "aria2.getGlobalStat" => {
+                let list = sched.list_downloads().await;
+                let count = |f: fn(TaskStatus) -> bool| list.iter().filter(|t| f(t.status)).count().to_string();
+                let speed: u64 = list
+                    .iter()
+                    .filter(|t| t.status == TaskStatus::Downloading)
+                    .filter_map(|t| t.speed_bps)
+                    .sum();
+                Ok(json!({
+                    "downloadSpeed": speed.to_string(),
+                    "uploadSpeed": "0",
+                    "numActive": count(|s| s == TaskStatus::Downloading),
+                    "numWaiting": count(is_waiting),
+                    "numStopped": count(is_stopped),
+                    "numStoppedTotal": count(is_stopped),
+                }))
+            }
+            "aria2.purgeDownloadResult" => {
+                // 链接过期的任务保留，便于更换地址后续传
+                for t in sched.list_downloads().await {
+                    if matches!(t.status, TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled) {
+                        sched.remove_task(&t.id).await.map_err(error)?;
+                    }
+                }
+                Ok(json!("OK"))
+            }
+            "aria2.removeDownloadResult" => {
+                let info = self.find(args.str(0, "gid")?).await?;
+                if !is_stopped(info.status) {
+                    return Err(error(format!("Could not remove download result of GID#{}", gid(&info.id))));
+                }
+                sched.remove_task(&info.id).await.map_err(error)?;
+                Ok(json!("OK"))
+            }
+            "aria2.getVersion" => Ok(json!({ "version": env!("CARGO_PKG_VERSION"), "enabledFeatures": [] })),
+            "aria2.getSessionInfo" => Ok(json!({ "sessionId": session_id() })),
+            "aria2.saveSession" => {
+                sched.save_tasks().await;
+                Ok(json!("OK"))
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("No such method: {}", method))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_id_is_random_hex_and_stable() {
+        let id = session_id();
+        assert_eq!(id.len(), 40);
+        assert!(id.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_eq!(session_id(), id);
+    }
+}
@@ -0,0 +1,143 @@
+//! 控制接口用的最小 HTTP/1.1：读取单个请求、写出响应；每个连接只处理一个请求
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// 请求头上限
+const MAX_HEAD: usize = 16 * 1024;
+/// 请求体上限
+const MAX_BODY: usize = 1024 * 1024;
+const MAX_HEADERS: usize = 32;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// 按名称取请求头（不区分大小写）
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 取查询参数（已解码）
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        self.query.as_deref()?.split('&').find_map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            if k == name {
+                urlencoding::decode(v).ok().map(|v| v.into_owned())
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// 读取一个请求；失败时返回应回复的状态码
+pub async fn read_request<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Request, u16> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = reader.read(&mut chunk).await.map_err(|_| 400u16)?;
+        if n == 0 {
+            return Err(400);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut req = httparse::Request::new(&mut headers);
+        let head_len = match req.parse(&buf) {
+            Ok(httparse::Status::Complete(len)) => len,
+            Ok(httparse::Status::Partial) if buf.len() < MAX_HEAD => continue,
+            Ok(httparse::Status::Partial) => return Err(431),
+            Err(httparse::Error::TooManyHeaders) => return Err(431),
+            Err(_) => return Err(400),
+        };
+
+        let target = req.path.unwrap_or("/");
+        let (path, query) = match target.split_once('?') {
+            Some((p, q)) => (p.to_string(), Some(q.to_string())),
+            None => (target.to_string(), None),
+        };
+        let mut request = Request {
+            method: req.method.unwrap_or("").to_string(),
+            path,
+            query,
+            headers: req
+                .headers
+                .iter()
+                .map(|h| (h.name.to_string(), String::from_utf8_lossy(h.value).into_owned()))
+                .collect(),
+            body: Vec::new(),
+        };
+
+        if request.header("transfer-encoding").is_some() {
+            return Err(411);
+        }
+        let body_len = match request.header("content-length") {
+            Some(v) => v.trim().parse::<usize>().map_err(|_| 400u16)?,
+            None => 0,
+        };
+        if body_len > MAX_BODY {
+            return Err(413);
+        }
+        let mut body = buf.split_off(head_len);
+        if body.len() < body_len {
+            let start = body.len();
+            body.resize(body_len, 0);
+            reader.read_exact(&mut body[start..]).await.map_err(|_| 400u16)?;
+        }
+        body.truncate(body_len);
+        request.body = body;
+        return Ok(request);
+    }
+}
+
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+/// 写出完整响应并关闭连接
+pub async fn respond<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    status: u16,
+    content_type: &str,
+    extra_headers: &[(&str, &str)],
+    body: &[u8],
+) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {} {}\r\n", status, reason(status));
+    if !body.is_empty() {
+        head.push_str(&format!("Content-Type: {}\r\n", content_type));
+    }
+    for (k, v) in extra_headers {
+        head.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(body).await?;
+    writer.shutdown().await
+}
+
+/// 以 JSON 错误体回复
+pub async fn respond_error<W: AsyncWrite + Unpin>(writer: &mut W, status: u16, extra_headers: &[(&str, &str)]) -> std::io::Result<()> {
+    let body = serde_json::json!({ "error": reason(status) }).to_string();
+    respond(writer, status, "application/json", extra_headers, body.as_bytes()).await
+}
@@ -0,0 +1,282 @@
+//! 本地控制接口：仅监听 127.0.0.1 的 HTTP 服务，供脚本远程管理正在运行的 Multidown
+//!
+//! - `POST /rpc`：JSON-RPC 2.0，方法见 `rpc`
+//! - `GET /events`：SSE 事件流（任务进度、状态变化、完成、失败、链接过期）
+//! - `/jsonrpc`：可选的 aria2 兼容接口（HTTP POST 或 WebSocket），见 `aria2`
+//!
+//! 请求需携带访问令牌：`Authorization: Bearer <token>`；事件流也可用 `?token=`（EventSource 无法设置请求头）；
+//! aria2 兼容接口按 aria2 的方式在参数中传 `token:<token>`。接口说明见 docs/控制接口.md。
+
+mod aria2;
+mod http;
+mod rpc;
+mod ws;
+
+pub use rpc::API_VERSION;
+
+use crate::engine::scheduler::Scheduler;
+use crate::engine::{BroadcastEvents, EngineEvent, TaskId};
+use crate::intake::Intake;
+use crate::settings::AppSettings;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+
+/// aria2 兼容接口允许任意来源的网页调用（AriaNg 等），认证依赖参数中的令牌
+const CORS_HEADERS: &[(&str, &str)] = &[
+    ("Access-Control-Allow-Origin", "*"),
+    ("Access-Control-Allow-Methods", "POST, GET, OPTIONS"),
+    ("Access-Control-Allow-Headers", "Content-Type"),
+    ("Access-Control-Max-Age", "1728000"),
+];
+
+/// 读取请求的超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// 事件流中进度合并推送的间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// 事件流空闲时发送心跳的间隔（按进度间隔计数）
+const HEARTBEAT_TICKS: u32 = 15;
+
+pub struct ControlContext {
+    pub scheduler: Arc<Scheduler>,
+    /// 新建任务（add、aria2.addUri）的入口，需与 scheduler 为同一调度器；
+    /// 设置经它读取与保存，新建、继续任务时按调用时的设置取连接数与网络选项
+    pub intake: Arc<Intake>,
+    /// 调度器事件的广播端，需已挂到调度器上
+    pub events: Arc<BroadcastEvents>,
+    pub token: String,
+    /// 是否开放 aria2 兼容接口
+    pub aria2_rpc: bool,
+}
+
+impl ControlContext {
+    fn settings(&self) -> AppSettings {
+        self.intake.settings()
+    }
+}
+
+/// 生成新的访问令牌
+pub fn generate_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+/// 绑定 127.0.0.1:port
+pub async fn bind(port: u16) -> std::io::Result<TcpListener> {
+    TcpListener::bind(("127.0.0.1", port)).await
+}
+
+/// 持续接受连接，每个连接在独立任务中处理；令牌为空时拒绝全部请求
+pub async fn serve(listener: TcpListener, ctx: Arc<ControlContext>) {
+    aria2::session_id();
+    loop {
+        let stream = match listener.accept().await {
+            Ok((s, _)) => s,
+            Err(_) => {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            let _ = handle_connection(stream, ctx).await;
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, ctx: Arc<ControlContext>) -> std::io::Result<()> {
+    let req = match tokio::time::timeout(REQUEST_TIMEOUT, http::read_request(&mut stream)).await {
+        Ok(Ok(req)) => req,
+        Ok(Err(status)) => return http::respond_error(&mut stream, status, &[]).await,
+        Err(_) => return http::respond_error(&mut stream, 408, &[]).await,
+    };
+
+    if req.path == "/jsonrpc" {
+        return handle_aria2(stream, req, ctx).await;
+    }
+
+    let bearer = req
+        .header("authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|t| t.trim().to_string());
+    let token = match req.path.as_str() {
+        "/events" => bearer.or_else(|| req.query_param("token")),
+        _ => bearer,
+    };
+    if !token.is_some_and(|t| token_matches(&t, &ctx.token)) {
+        return http::respond_error(&mut stream, 401, &[("WWW-Authenticate", "Bearer")]).await;
+    }
+
+    match (req.method.as_str(), req.path.as_str()) {
+        ("POST", "/rpc") => {
+            let is_json = req
+                .header("content-type")
+                .map(|v| v.trim_start().starts_with("application/json"))
+                .unwrap_or(false);
+            if !is_json {
+                return http::respond_error(&mut stream, 415, &[]).await;
+            }
+            match rpc::handle(&rpc::Native(&ctx), &req.body).await {
+                Some(resp) => {
+                    http::respond(&mut stream, 200, "application/json", &[], resp.to_string().as_bytes()).await
+                }
+                None => http::respond(&mut stream, 204, "", &[], b"").await,
+            }
+        }
+        ("GET", "/events") => stream_events(stream, ctx).await,
+        (_, "/rpc") => http::respond_error(&mut stream, 405, &[("Allow", "POST")]).await,
+        (_, "/events") => http::respond_error(&mut stream, 405, &[("Allow", "GET")]).await,
+        _ => http::respond_error(&mut stream, 404, &[]).await,
+    }
+}
+
+/// aria2 兼容接口：未开放时与不存在的路径一样返回 404
+async fn handle_aria2(mut stream: TcpStream, req: http::Request, ctx: Arc<ControlContext>) -> std::io::Result<()> {
+    if !ctx.aria2_rpc {
+        return http::respond_error(&mut stream, 404, &[]).await;
+    }
+    match req.method.as_str() {
+        "OPTIONS" => http::respond(&mut stream, 204, "", CORS_HEADERS, b"").await,
+        "POST" => match rpc::handle(&aria2::Aria2(&ctx), &req.body).await {
+            Some(resp) => {
+                http::respond(&mut stream, 200, "application/json", CORS_HEADERS, resp.to_string().as_bytes()).await
+            }
+            None => http::respond(&mut stream, 204, "", CORS_HEADERS, b"").await,
+        },
+        "GET" => {
+            let upgrade = req
+                .header("upgrade")
+                .map(|v| v.eq_ignore_ascii_case("websocket"))
+                .unwrap_or(false);
+            match req.header("sec-websocket-key") {
+                Some(key) if upgrade => {
+                    ws::accept(&mut stream, key).await?;
+                    aria2_websocket(stream, ctx).await
+                }
+                _ => http::respond_error(&mut stream, 400, CORS_HEADERS).await,
+            }
+        }
+        _ => http::respond_error(&mut stream, 405, &[("Allow", "POST, GET, OPTIONS")]).await,
+    }
+}
+
+/// WebSocket 会话：读取交给独立任务（读取不可被 select 取消），本循环负责回复请求与推送通知
+async fn aria2_websocket(stream: TcpStream, ctx: Arc<ControlContext>) -> std::io::Result<()> {
+    let (mut reader, mut writer) = stream.into_split();
+    let mut events = ctx.events.subscribe();
+    let (msg_tx, mut msg_rx) = tokio::sync::mpsc::channel(16);
+    let read_task = tokio::spawn(async move {
+        loop {
+            let msg = ws::read_message(&mut reader).await;
+            let stop = !matches!(msg, Ok(ws::Message::Text(_)) | Ok(ws::Message::Ping(_)));
+            if msg_tx.send(msg).await.is_err() || stop {
+                break;
+            }
+        }
+    });
+
+    let result = async {
+        loop {
+            tokio::select! {
+                msg = msg_rx.recv() => match msg {
+                    Some(Ok(ws::Message::Text(text))) => {
+                        if let Some(resp) = rpc::handle(&aria2::Aria2(&ctx), text.as_bytes()).await {
+                            ws::write_text(&mut writer, &resp.to_string()).await?;
+                        }
+                    }
+                    Some(Ok(ws::Message::Ping(payload))) => ws::write_pong(&mut writer, &payload).await?,
+                    Some(Ok(ws::Message::Close)) => {
+                        let _ = ws::write_close(&mut writer).await;
+                        return Ok(());
+                    }
+                    Some(Err(_)) | None => return Ok(()),
+                },
+                ev = events.recv() => match ev {
+                    Ok(ev) => {
+                        if let Some(n) = aria2::notification(&ev) {
+                            ws::write_text(&mut writer, &n.to_string()).await?;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+            }
+        }
+    }
+    .await;
+    read_task.abort();
+    result
+}
+
+/// 等长逐字节比较，避免按前缀泄露令牌
+pub(crate) fn token_matches(given: &str, expected: &str) -> bool {
+    !expected.is_empty()
+        && given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// SSE：状态类事件立即推送；进度按任务合并，每秒推送一次完整的任务信息
+async fn stream_events(mut stream: TcpStream, ctx: Arc<ControlContext>) -> std::io::Result<()> {
+    let mut rx = ctx.events.subscribe();
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        )
+        .await?;
+    write_sse(&mut stream, "hello", &serde_json::json!({ "api": API_VERSION })).await?;
+
+    let mut pending: HashSet<TaskId> = HashSet::new();
+    let mut tick = tokio::time::interval(PROGRESS_INTERVAL);
+    let mut idle_ticks = 0u32;
+    loop {
+        tokio::select! {
+            ev = rx.recv() => match ev {
+                Ok(EngineEvent::Progress { id }) => {
+                    pending.insert(id);
+                }
+                Ok(ev) => {
+                    if let EngineEvent::StatusChanged { id, .. }
+                    | EngineEvent::Finished { id, .. }
+                    | EngineEvent::Failed { id, .. }
+                    | EngineEvent::NeedsRefresh { id, .. } = &ev
+                    {
+                        pending.remove(id);
+                    }
+                    let data = serde_json::to_value(&ev).unwrap_or_default();
+                    let name = data.get("event").and_then(|v| v.as_str()).unwrap_or("event").to_string();
+                    write_sse(&mut stream, &name, &data).await?;
+                    idle_ticks = 0;
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    // 客户端跟不上：告知丢弃数量，需要时可调用 list 重新同步
+                    write_sse(&mut stream, "lagged", &serde_json::json!({ "skipped": skipped })).await?;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            _ = tick.tick() => {
+                if pending.is_empty() {
+                    idle_ticks += 1;
+                    if idle_ticks >= HEARTBEAT_TICKS {
+                        stream.write_all(b": ping\n\n").await?;
+                        idle_ticks = 0;
+                    }
+                    continue;
+                }
+                idle_ticks = 0;
+                for id in std::mem::take(&mut pending) {
+                    if let Some(info) = ctx.scheduler.get_task(&id).await {
+                        let data = serde_json::to_value(&info).unwrap_or_default();
+                        write_sse(&mut stream, "progress", &data).await?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn write_sse<W: AsyncWrite + Unpin>(w: &mut W, event: &str, data: &serde_json::Value) -> std::io::Result<()> {
+    w.write_all(format!("event: {}\ndata: {}\n\n", event, data).as_bytes()).await?;
+    w.flush().await
+}
@@ -0,0 +1,266 @@
+//! JSON-RPC 2.0：请求解析、批量与通知处理，以及本接口的方法（与桌面端 Tauri 命令对应：add / list / query / get / pause / resume / cancel / remove / settings.*）
+
+use super::ControlContext;
+use crate::engine::TaskSource;
+use crate::intake::{DuplicatePolicy, IntakeItem, IntakeKind, IntakeOptions, IntakeResult};
+use crate::search::{self, TaskQuery};
+use crate::settings::AppSettings;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// 接口版本；不兼容的改动才递增
+pub const API_VERSION: u32 = 1;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+pub(super) const METHOD_NOT_FOUND: i64 = -32601;
+pub(super) const INVALID_PARAMS: i64 = -32602;
+/// 调度器拒绝了操作（任务不存在、状态不允许等），message 为原因
+const OPERATION_FAILED: i64 = -32000;
+
+/// settings.get 返回的代理密码；settings.set 收到它时不修改密码
+const PASSWORD_PLACEHOLDER: &str = "********";
+
+pub(super) struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    pub(super) fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl RpcError {
+    pub(super) fn to_json(&self) -> Value {
+        json!({ "code": self.code, "message": self.message })
+    }
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        Self::new(OPERATION_FAILED, message)
+    }
+}
+
+/// 一组 JSON-RPC 方法
+#[async_trait]
+pub(super) trait Methods: Send + Sync {
+    async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError>;
+}
+
+/// 处理请求体；全部为通知（无 id）时返回 None
+pub(super) async fn handle(methods: &dyn Methods, body: &[u8]) -> Option<Value> {
+    match serde_json::from_slice(body) {
+        Ok(value) => handle_value(methods, value).await,
+        Err(e) => Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+    }
+}
+
+/// 处理已解析的单个或批量请求
+async fn handle_value(methods: &dyn Methods, value: Value) -> Option<Value> {
+    match value {
+        Value::Array(calls) if calls.is_empty() => {
+            Some(error_response(Value::Null, RpcError::new(INVALID_REQUEST, "空的批量请求")))
+        }
+        Value::Array(calls) => {
+            let mut responses = Vec::new();
+            for call in calls {
+                if let Some(r) = handle_call(methods, call).await {
+                    responses.push(r);
+                }
+            }
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        call => handle_call(methods, call).await,
+    }
+}
+
+async fn handle_call(methods: &dyn Methods, call: Value) -> Option<Value> {
+    let Value::Object(mut call) = call else {
+        return Some(error_response(Value::Null, RpcError::new(INVALID_REQUEST, "请求须为对象")));
+    };
+    let id = call.remove("id");
+    let method = match (call.get("jsonrpc").and_then(Value::as_str), call.get("method").and_then(Value::as_str)) {
+        (Some("2.0"), Some(m)) => m.to_string(),
+        _ => {
+            return Some(error_response(
+                id.unwrap_or(Value::Null),
+                RpcError::new(INVALID_REQUEST, "缺少 jsonrpc: \"2.0\" 或 method"),
+            ))
+        }
+    };
+    let params = call.remove("params").unwrap_or(Value::Null);
+    let result = methods.call(&method, params).await;
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => error_response(id, e),
+    })
+}
+
+fn error_response(id: Value, e: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": e.to_json() })
+}
+
+/// 解析命名参数；省略 params 视为空对象
+pub(super) fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+pub(super) fn to_value<T: serde::Serialize>(v: T) -> Result<Value, RpcError> {
+    serde_json::to_value(v).map_err(|e| RpcError::new(OPERATION_FAILED, e.to_string()))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskParams {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AddParams {
+    url: String,
+    save_dir: Option<String>,
+    filename: Option<String>,
+    #[serde(default)]
+    mirrors: Vec<String>,
+    #[serde(default = "default_start")]
+    start: bool,
+}
+
+fn default_start() -> bool {
+    true
+}
+
+/// 本接口的方法（POST /rpc）
+pub(super) struct Native<'a>(pub &'a ControlContext);
+
+#[async_trait]
+impl Methods for Native<'_> {
+    async fn call(&self, method: &str, p: Value) -> Result<Value, RpcError> {
+        dispatch(self.0, method, p).await
+    }
+}
+
+async fn dispatch(ctx: &ControlContext, method: &str, p: Value) -> Result<Value, RpcError> {
+    let sched = &ctx.scheduler;
+    match method {
+        "version" => Ok(json!({ "version": env!("CARGO_PKG_VERSION"), "api": API_VERSION })),
+        "add" => {
+            let p: AddParams = params(p)?;
+            if !sched.protocols().supports(&p.url) {
+                return Err(RpcError::new(INVALID_PARAMS, "不支持的下载地址"));
+            }
+            if let Some(bad) = p.mirrors.iter().find(|m| !sched.protocols().supports(m)) {
+                return Err(RpcError::new(INVALID_PARAMS, format!("不支持的镜像地址: {}", bad)));
+            }
+            let item = IntakeItem {
+                url: p.url,
+                mirrors: p.mirrors,
+                save_dir: p.save_dir,
+                filename: p.filename,
+                probe: None,
+                headers: Vec::new(),
+                kind: IntakeKind::Auto,
+            };
+            let options = IntakeOptions {
+                duplicate: Some(DuplicatePolicy::from_setting(&ctx.settings().duplicate_action).unattended()),
+                start: p.start,
+                source: TaskSource::Control,
+                ..IntakeOptions::default()
+            };
+            let (id, result) = match ctx.intake.submit_one(item, options).await {
+                IntakeResult::Added { id } => (id, "added"),
+                IntakeResult::Started { id } => (id, "started"),
+                IntakeResult::Queued { id } => (id, "queued"),
+                IntakeResult::Skipped { existing } | IntakeResult::Duplicate { existing } => (existing, "skipped"),
+                IntakeResult::Rejected { error } => return Err(RpcError::new(INVALID_PARAMS, error)),
+                IntakeResult::Failed { error } => return Err(error.into()),
+            };
+            Ok(json!({ "id": id, "result": result }))
+        }
+        "list" => to_value(sched.list_downloads().await),
+        "query" => {
+            let q: TaskQuery = params(p)?;
+            to_value(search::query(sched, &q).await)
+        }
+        "get" => {
+            let p: TaskParams = params(p)?;
+            match sched.get_task(&p.id).await {
+                Some(info) => to_value(info),
+                None => Err("任务不存在".to_string().into()),
+            }
+        }
+        "pause" => {
+            let p: TaskParams = params(p)?;
+            sched.pause_task(&p.id).await?;
+            Ok(Value::Null)
+        }
+        "resume" => {
+            let p: TaskParams = params(p)?;
+            let settings = ctx.settings();
+            let net = settings.network_options().await?;
+            sched
+                .resume_task(
+                    &p.id,
+                    Some(sched.clone()),
+                    Some(settings.max_connections_per_task as usize),
+                    Some(net),
+                )
+                .await?;
+            Ok(Value::Null)
+        }
+        "cancel" => {
+            let p: TaskParams = params(p)?;
+            sched.cancel_task(&p.id).await?;
+            Ok(Value::Null)
+        }
+        "remove" => {
+            let p: TaskParams = params(p)?;
+            sched.remove_task(&p.id).await?;
+            Ok(Value::Null)
+        }
+        "settings.get" => to_value(redacted(ctx.settings())),
+        "settings.set" => {
+            // 只需给出要修改的字段，其余保持不变
+            let Value::Object(patch) = p else {
+                return Err(RpcError::new(INVALID_PARAMS, "params 须为设置字段组成的对象"));
+            };
+            let mut merged = to_value(ctx.settings())?;
+            if let Value::Object(current) = &mut merged {
+                for (k, v) in patch {
+                    if !current.contains_key(&k) {
+                        return Err(RpcError::new(INVALID_PARAMS, format!("未知设置项: {}", k)));
+                    }
+                    // 原样传回 settings.get 的占位符时保留已保存的密码
+                    if k == "proxy_password" && v == PASSWORD_PLACEHOLDER {
+                        continue;
+                    }
+                    current.insert(k, v);
+                }
+            }
+            let settings: AppSettings = params(merged)?;
+            settings.proxy_config().map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            ctx.intake.save_settings(&settings).await?;
+            to_value(redacted(settings))
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("未知方法: {}", method))),
+    }
+}
+
+/// 不向接口返回已保存的代理密码，只表明是否已设置
+fn redacted(mut settings: AppSettings) -> AppSettings {
+    if !settings.proxy_password.is_empty() {
+        settings.proxy_password = PASSWORD_PLACEHOLDER.to_string();
+    }
+    settings
+}
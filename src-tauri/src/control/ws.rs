@@ -0,0 +1,127 @@
+//! 最小 WebSocket 服务端（RFC 6455）：握手、读取文本消息、发送文本帧；aria2 兼容接口用它收发请求与通知
+
+use base64::Engine as _;
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// 单条消息上限（含分片合并后）
+const MAX_MESSAGE: usize = 1024 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+pub enum Message {
+    Text(String),
+    Ping(Vec<u8>),
+    Close,
+}
+
+/// 回复握手；key 为请求头 Sec-WebSocket-Key
+pub async fn accept<W: AsyncWrite + Unpin>(w: &mut W, key: &str) -> std::io::Result<()> {
+    let mut sha = Sha1::new();
+    sha.update(key.trim().as_bytes());
+    sha.update(ACCEPT_GUID.as_bytes());
+    let accept = base64::engine::general_purpose::STANDARD.encode(sha.finalize());
+    let head = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    );
+    w.write_all(head.as_bytes()).await?;
+    w.flush().await
+}
+
+/// 读取一条完整消息；客户端帧必须带掩码，分片按顺序合并，pong 忽略
+pub async fn read_message<R: AsyncRead + Unpin>(r: &mut R) -> std::io::Result<Message> {
+    let mut data: Vec<u8> = Vec::new();
+    let mut started = false;
+    loop {
+        let mut head = [0u8; 2];
+        r.read_exact(&mut head).await?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0F;
+        if head[1] & 0x80 == 0 {
+            return Err(invalid("客户端帧未加掩码"));
+        }
+        let len = match head[1] & 0x7F {
+            126 => {
+                let mut b = [0u8; 2];
+                r.read_exact(&mut b).await?;
+                u16::from_be_bytes(b) as u64
+            }
+            127 => {
+                let mut b = [0u8; 8];
+                r.read_exact(&mut b).await?;
+                u64::from_be_bytes(b)
+            }
+            n => n as u64,
+        };
+        if len as usize > MAX_MESSAGE || data.len() + len as usize > MAX_MESSAGE {
+            return Err(invalid("消息过大"));
+        }
+        let mut mask = [0u8; 4];
+        r.read_exact(&mut mask).await?;
+        let mut payload = vec![0u8; len as usize];
+        r.read_exact(&mut payload).await?;
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+
+        match opcode {
+            OP_CLOSE => return Ok(Message::Close),
+            OP_PING => return Ok(Message::Ping(payload)),
+            OP_PONG => continue,
+            OP_TEXT | OP_BINARY if !started => {
+                started = true;
+                data = payload;
+            }
+            OP_CONTINUATION if started => data.extend_from_slice(&payload),
+            _ => return Err(invalid("帧顺序错误")),
+        }
+        if fin {
+            return String::from_utf8(data)
+                .map(Message::Text)
+                .map_err(|_| invalid("消息不是 UTF-8 文本"));
+        }
+    }
+}
+
+pub async fn write_text<W: AsyncWrite + Unpin>(w: &mut W, text: &str) -> std::io::Result<()> {
+    write_frame(w, OP_TEXT, text.as_bytes()).await
+}
+
+pub async fn write_pong<W: AsyncWrite + Unpin>(w: &mut W, payload: &[u8]) -> std::io::Result<()> {
+    write_frame(w, OP_PONG, payload).await
+}
+
+pub async fn write_close<W: AsyncWrite + Unpin>(w: &mut W) -> std::io::Result<()> {
+    write_frame(w, OP_CLOSE, &[]).await
+}
+
+/// 服务端帧不加掩码
+async fn write_frame<W: AsyncWrite + Unpin>(w: &mut W, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        n if n < 126 => frame.push(n as u8),
+        n if n <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(127);
+            frame.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    w.write_all(&frame).await?;
+    w.flush().await
+}
+
+fn invalid(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
@@ -0,0 +1,2033 @@
+//! 桌面应用：Tauri 命令、托盘、浏览器扩展通信，驱动下载引擎
+
+use crate::engine::scheduler::Scheduler;
+use crate::engine::{BroadcastEvents, ChannelEvents, EngineEvents, FanoutEvents, TaskId, TaskSource, TaskStatus};
+use crate::history::{History, HistoryPage, HistoryQuery, HistoryStats};
+use crate::import::{ImportFormat, ImportPreview};
+use crate::intake::{self, DuplicatePolicy, Intake, IntakeItem, IntakeKind, IntakeOptions, IntakeOutcome, IntakeResult};
+use crate::network::{NetworkOptions, ProbeResult};
+use crate::search::{TaskPage, TaskQuery};
+use crate::settings::{settings_path, AppSettings};
+use crate::{batch, browser, control, engine, grabber, history, import, instance, migrate, network, search};
+use std::sync::Arc;
+use tauri::{Emitter, Manager, State};
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::image::Image;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use arboard::Clipboard;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use chrono::Local;
+
+// 全局变量，用于存储TCP服务器的停止标志
+use std::sync::atomic::AtomicBool;
+use std::sync::Mutex;
+static TCP_SHUTDOWN_FLAG: AtomicBool = AtomicBool::new(false);
+static TCP_SHUTDOWN_TX: Mutex<Option<tokio::sync::oneshot::Sender<()>>> = Mutex::new(None);
+
+// 调试日志函数
+fn debug_log(app: &tauri::AppHandle, message: &str, data: Option<&str>) {
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+    let log_message = match data {
+        Some(d) => format!("[{}] [Multidown Main] {}: {}", timestamp, message, d),
+        None => format!("[{}] [Multidown Main] {}", timestamp, message),
+    };
+    
+    // 输出到标准错误
+    eprintln!("{}", log_message);
+    
+    // 写入日志文件
+    if let Ok(app_data) = app.path().app_data_dir() {
+        let log_path = app_data.join("multidown.log");
+        
+        // 确保日志目录存在
+        if let Some(parent) = log_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        
+        if let Ok(file) = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+        {
+            let mut writer = BufWriter::new(file);
+            let _ = writeln!(writer, "{}", log_message);
+        } else {
+            // 日志文件打开失败时，输出错误信息
+            eprintln!("无法打开日志文件: {:?}", log_path);
+        }
+    } else {
+        // 无法获取应用数据目录时，输出错误信息
+        eprintln!("无法获取应用数据目录，无法写入日志文件");
+    }
+}
+
+// 详细日志函数（用于更详细的调试信息）
+fn debug_log_detailed(app: &tauri::AppHandle, message: &str, details: &str) {
+    debug_log(app, message, Some(details));
+}
+
+// 错误日志函数
+fn error_log(app: &tauri::AppHandle, message: &str, error: &str) {
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+    let log_message = format!("[{}] [Multidown Main] [ERROR] {}: {}", timestamp, message, error);
+    
+    // 输出到标准错误
+    eprintln!("{}", log_message);
+    
+    // 写入日志文件
+    if let Ok(app_data) = app.path().app_data_dir() {
+        let log_path = app_data.join("multidown.log");
+        
+        // 确保日志目录存在
+        if let Some(parent) = log_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        
+        if let Ok(file) = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+        {
+            let mut writer = BufWriter::new(file);
+            let _ = writeln!(writer, "{}", log_message);
+        }
+    }
+}
+
+/// 当前设置：取任务入口中的缓存，与 set_settings 保存的一致
+fn current_settings(app: &tauri::AppHandle) -> AppSettings {
+    app.try_state::<Arc<Intake>>().map(|intake| intake.settings()).unwrap_or_default()
+}
+
+/// 浏览器扩展可指定的保存目录：默认保存路径、设置中额外允许的目录与系统下载目录
+fn browser_allowed_dirs(app: &tauri::AppHandle) -> Vec<std::path::PathBuf> {
+    let mut dirs: Vec<std::path::PathBuf> = Vec::new();
+    let settings = current_settings(app);
+    if !settings.default_save_path.is_empty() {
+        dirs.push(settings.default_save_path.into());
+    }
+    dirs.extend(settings.browser_allowed_dirs.into_iter().filter(|d| !d.is_empty()).map(Into::into));
+    if let Ok(d) = app.path().download_dir() {
+        dirs.push(d);
+    }
+    dirs
+}
+
+/// 校验浏览器发来的下载请求：地址协议、文件名不带路径、保存目录在允许范围内
+fn validate_browser_download(app: &tauri::AppHandle, req: &browser::DownloadRequest) -> Result<(), String> {
+    if !network::is_supported_url(&req.url) {
+        return Err("missing or invalid url".to_string());
+    }
+    if let Some(name) = &req.filename {
+        browser::check_filename(name)?;
+    }
+    if let Some(dir) = &req.save_path {
+        browser::check_save_dir(dir, &browser_allowed_dirs(app))?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_settings(intake: State<'_, Arc<Intake>>) -> Result<AppSettings, String> {
+    Ok(intake.settings())
+}
+
+#[tauri::command]
+async fn set_settings(app: tauri::AppHandle, settings: AppSettings, intake: State<'_, Arc<Intake>>) -> Result<(), String> {
+    settings.proxy_config()?;
+    intake.save_settings(&settings).await?;
+    // 保留期限或条数改小后立即清理历史
+    if let Some(history) = app.try_state::<Arc<History>>() {
+        history.apply_retention(&settings).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn probe_download(
+    url: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<ProbeResult, String> {
+    let opts = network_options_from_app(&app).await?;
+    state
+        .probe_with_options(&url, &opts)
+        .await
+        .map_err(|e: network::NetworkError| e.to_string())
+}
+
+/// 引擎事件转为前端事件
+struct TauriEvents(tauri::AppHandle);
+
+impl EngineEvents for TauriEvents {
+    fn progress(&self, _id: &str) {
+        let _ = self.0.emit("download-progress", ());
+    }
+
+    fn status_changed(&self, _id: &str, _status: TaskStatus) {
+        let _ = self.0.emit("download-progress", ());
+    }
+
+    fn finished(&self, id: &str, filename: &str) {
+        let _ = self.0.emit("download-finished", (id, "completed", filename));
+    }
+
+    fn failed(&self, id: &str, filename: &str, _error: &str) {
+        let _ = self.0.emit("download-finished", (id, "failed", filename));
+    }
+
+    fn needs_refresh(&self, id: &str, filename: &str, url: &str) {
+        let _ = self.0.emit("download-needs-refresh", (id, filename, url));
+    }
+}
+
+async fn network_options_from_app(app: &tauri::AppHandle) -> Result<NetworkOptions, String> {
+    current_settings(app).network_options().await
+}
+
+/// 经任务入口新建；重复链接且设置为询问时弹窗让用户选择覆盖、重命名或跳过，整批只问一次
+async fn submit_with_prompt(
+    app: &tauri::AppHandle,
+    intake: &Intake,
+    items: Vec<IntakeItem>,
+    options: IntakeOptions,
+) -> Vec<IntakeOutcome> {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult};
+    let mut outcomes = intake.submit(items.clone(), options).await;
+    let duplicates: Vec<usize> = outcomes
+        .iter()
+        .enumerate()
+        .filter(|(_, o)| matches!(o.result, IntakeResult::Duplicate { .. }))
+        .map(|(i, _)| i)
+        .collect();
+    if duplicates.is_empty() {
+        return outcomes;
+    }
+    let mut message = format!("{} 个链接与已有任务重复：", duplicates.len());
+    for &i in duplicates.iter().take(5) {
+        message.push_str(&format!("\n{}", outcomes[i].url));
+    }
+    if duplicates.len() > 5 {
+        message.push_str("\n…");
+    }
+    let (tx, rx) = oneshot::channel();
+    app.dialog()
+        .message(message)
+        .title("Multidown")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::YesNoCancelCustom(
+            "覆盖".to_string(),
+            "重命名".to_string(),
+            "跳过".to_string(),
+        ))
+        .show_with_result(move |r| {
+            let _ = tx.send(r);
+        });
+    let policy = match rx.await {
+        Ok(MessageDialogResult::Custom(label)) if label == "覆盖" => DuplicatePolicy::Overwrite,
+        Ok(MessageDialogResult::Custom(label)) if label == "重命名" => DuplicatePolicy::Rename,
+        _ => DuplicatePolicy::Skip,
+    };
+    let retry: Vec<IntakeItem> = duplicates.iter().map(|&i| items[i].clone()).collect();
+    let options = IntakeOptions {
+        duplicate: Some(policy),
+        ..options
+    };
+    for (i, outcome) in duplicates.into_iter().zip(intake.submit(retry, options).await) {
+        outcomes[i] = outcome;
+    }
+    outcomes
+}
+
+/// mirrors 为同一文件的其他镜像地址（可选），探测一致后与主地址共同分担分段
+#[tauri::command]
+async fn create_download(
+    url: String,
+    save_dir: String,
+    filename: Option<String>,
+    mirrors: Option<Vec<String>>,
+    app: tauri::AppHandle,
+    intake: State<'_, Arc<Intake>>,
+) -> Result<String, String> {
+    create_download_with_probe(url, save_dir, filename, None, mirrors, app, intake).await
+}
+
+#[tauri::command]
+async fn create_download_with_probe(
+    url: String,
+    save_dir: String,
+    filename: Option<String>,
+    probe_result: Option<ProbeResult>,
+    mirrors: Option<Vec<String>>,
+    app: tauri::AppHandle,
+    intake: State<'_, Arc<Intake>>,
+) -> Result<String, String> {
+    let item = IntakeItem {
+        url,
+        mirrors: mirrors.unwrap_or_default(),
+        save_dir: Some(save_dir),
+        filename,
+        probe: probe_result,
+        headers: Vec::new(),
+        kind: IntakeKind::Auto,
+    };
+    submit_with_prompt(&app, &intake, vec![item], IntakeOptions::default())
+        .await
+        .pop()
+        .ok_or_else(|| "internal".to_string())
+        .and_then(|o| o.result.into_id())
+}
+
+/// 展开批量地址模式（每行一个，如 `https://x/img[001-120].jpg`），供批量添加预览与提交
+#[tauri::command]
+fn expand_batch_urls(text: String) -> Result<Vec<String>, String> {
+    batch::expand_lines(&text)
+}
+
+/// 进行中的站点抓取：抓取 ID → 取消标记
+#[derive(Default)]
+struct GrabJobs(Mutex<std::collections::HashMap<String, Arc<std::sync::atomic::AtomicBool>>>);
+
+/// 站点抓取，进度以 grab-progress 事件发送；取消后返回已得到的结果。结果由用户勾选后经 add_downloads 新建
+#[tauri::command]
+async fn grab_site(
+    id: String,
+    options: grabber::GrabOptions,
+    app: tauri::AppHandle,
+    intake: State<'_, Arc<Intake>>,
+    jobs: State<'_, GrabJobs>,
+) -> Result<grabber::GrabResult, String> {
+    let net = intake.settings().network_options().await?;
+    let cancel = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    jobs.0.lock().unwrap().insert(id.clone(), cancel.clone());
+    let on_progress = |progress: grabber::GrabProgress| {
+        let _ = app.emit("grab-progress", serde_json::json!({ "id": id, "progress": progress }));
+    };
+    let result = grabber::grab(intake.scheduler().protocols(), &net, &options, &cancel, &on_progress).await;
+    jobs.0.lock().unwrap().remove(&id);
+    result
+}
+
+#[tauri::command]
+fn cancel_grab(id: String, jobs: State<'_, GrabJobs>) {
+    if let Some(cancel) = jobs.0.lock().unwrap().get(&id) {
+        cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+/// 批量新建，结果与请求一一对应
+#[tauri::command]
+async fn add_downloads(
+    items: Vec<IntakeItem>,
+    options: Option<IntakeOptions>,
+    app: tauri::AppHandle,
+    intake: State<'_, Arc<Intake>>,
+) -> Result<Vec<IntakeOutcome>, String> {
+    Ok(submit_with_prompt(&app, &intake, items, options.unwrap_or_default()).await)
+}
+
+/// 列出 HLS 主播放列表的码率变体，供用户选择分辨率
+#[tauri::command]
+async fn probe_hls_variants(
+    url: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<Vec<engine::HlsVariant>, String> {
+    let opts = network_options_from_app(&app).await?;
+    state.hls_variants(&url, &opts).await
+}
+
+#[tauri::command]
+async fn create_hls_download(
+    url: String,
+    save_dir: String,
+    filename: Option<String>,
+    options: Option<engine::HlsOptions>,
+    app: tauri::AppHandle,
+    intake: State<'_, Arc<Intake>>,
+) -> Result<String, String> {
+    let item = IntakeItem {
+        url,
+        save_dir: Some(save_dir),
+        filename,
+        kind: IntakeKind::Hls(options.unwrap_or_default()),
+        ..IntakeItem::default()
+    };
+    submit_with_prompt(&app, &intake, vec![item], IntakeOptions::default())
+        .await
+        .pop()
+        .ok_or_else(|| "internal".to_string())
+        .and_then(|o| o.result.into_id())
+}
+
+/// 列出 DASH 清单中的视频/音频表示，供用户选择
+#[tauri::command]
+async fn probe_dash_representations(
+    url: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<Vec<engine::DashRepresentation>, String> {
+    let opts = network_options_from_app(&app).await?;
+    state.dash_representations(&url, &opts).await
+}
+
+#[tauri::command]
+async fn create_dash_download(
+    url: String,
+    save_dir: String,
+    filename: Option<String>,
+    options: Option<engine::DashOptions>,
+    app: tauri::AppHandle,
+    intake: State<'_, Arc<Intake>>,
+) -> Result<String, String> {
+    let item = IntakeItem {
+        url,
+        save_dir: Some(save_dir),
+        filename,
+        kind: IntakeKind::Dash(options.unwrap_or_default()),
+        ..IntakeItem::default()
+    };
+    submit_with_prompt(&app, &intake, vec![item], IntakeOptions::default())
+        .await
+        .pop()
+        .ok_or_else(|| "internal".to_string())
+        .and_then(|o| o.result.into_id())
+}
+
+/// 导入 Metalink（.meta4/.metalink）：source 为本地路径或下载地址，每个文件经任务入口创建一个多镜像任务
+#[tauri::command]
+async fn import_metalink(
+    source: String,
+    save_dir: String,
+    app: tauri::AppHandle,
+    intake: State<'_, Arc<Intake>>,
+) -> Result<Vec<String>, String> {
+    let source = source.trim();
+    let protocols = intake.scheduler().protocols();
+    let xml = if network::is_supported_url(source) {
+        let opts = intake.settings().network_options().await?;
+        let handler = protocols
+            .handler_for(source, &opts)
+            .map_err(|e| e.to_string())?;
+        let body = handler.fetch(source).await.map_err(|e| e.to_string())?;
+        String::from_utf8_lossy(&body).into_owned()
+    } else {
+        tokio::fs::read_to_string(source).await.map_err(|e| e.to_string())?
+    };
+    let items = intake::metalink_items(&xml, Some(save_dir), |u| protocols.supports(u))?;
+    let options = IntakeOptions {
+        source: TaskSource::Import,
+        ..IntakeOptions::default()
+    };
+    let mut ids = Vec::new();
+    let mut errors = Vec::new();
+    for outcome in submit_with_prompt(&app, &intake, items, options).await {
+        match outcome.result {
+            IntakeResult::Rejected { error } | IntakeResult::Failed { error } => {
+                errors.push(format!("{}: {}", outcome.url, error))
+            }
+            result => ids.extend(result.task_id().cloned()),
+        }
+    }
+    if ids.is_empty() && !errors.is_empty() {
+        return Err(errors.join("; "));
+    }
+    Ok(ids)
+}
+
+/// 浏览器扩展与启动参数共用的任务入口，由同一个后台任务逐条处理
+#[derive(Debug)]
+struct DownloadTask {
+    request: browser::DownloadRequest,
+    responder: oneshot::Sender<Result<TaskId, String>>,
+}
+
+#[derive(Debug)]
+struct OpenWindowTask {
+    url: String,
+    responder: oneshot::Sender<Result<(), String>>,
+}
+
+enum TaskMessage {
+    Download(DownloadTask),
+    OpenWindow(OpenWindowTask),
+}
+
+/// 启动参数队列，供 macOS 打开事件投递
+#[cfg(target_os = "macos")]
+struct LaunchQueue(tokio::sync::mpsc::UnboundedSender<instance::LaunchArgs>);
+
+/// 弹出确认框，用户选「添加」时返回 true
+async fn confirm_launch(app: &tauri::AppHandle, message: String) -> bool {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+    let (tx, rx) = oneshot::channel();
+    app.dialog()
+        .message(message)
+        .title("Multidown")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom("添加".to_string(), "取消".to_string()))
+        .show(move |ok| {
+            let _ = tx.send(ok);
+        });
+    rx.await.unwrap_or(false)
+}
+
+/// 启动参数处理失败：记日志并提示用户
+fn report_launch_error(app: &tauri::AppHandle, message: &str, error: &str) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+    error_log(app, message, error);
+    app.dialog()
+        .message(format!("{}\n{}", message, error))
+        .title("Multidown")
+        .kind(MessageDialogKind::Error)
+        .show(|_| {});
+}
+
+/// 处理启动参数：命令行给出的地址直接加入下载；协议链接来自网页、关联文件可能来自下载，
+/// 都先请用户确认。下载请求与浏览器扩展一样交给 TaskMessage 队列
+async fn handle_launch(
+    app: &tauri::AppHandle,
+    intake: &Intake,
+    tx: &tokio::sync::mpsc::UnboundedSender<TaskMessage>,
+    args: instance::LaunchArgs,
+) {
+    if args.show || !args.files.is_empty() {
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.show();
+            let _ = window.unminimize();
+            let _ = window.set_focus();
+        }
+    }
+    for url in args.urls {
+        let request = if instance::is_link(&url) {
+            let request = match instance::parse_link(&url) {
+                Ok(r) => r,
+                Err(e) => {
+                    report_launch_error(app, "无法打开链接", &e);
+                    continue;
+                }
+            };
+            let mut message = format!("网页请求用 Multidown 下载：\n{}", request.url);
+            if let Some(name) = &request.filename {
+                message.push_str(&format!("\n文件名：{}", name));
+            }
+            if let Some(referer) = &request.referer {
+                message.push_str(&format!("\n来源：{}", referer));
+            }
+            if !confirm_launch(app, message).await {
+                continue;
+            }
+            request
+        } else if network::is_supported_url(&url) {
+            browser::DownloadRequest::new(url)
+        } else {
+            report_launch_error(app, "不支持的下载地址", &url);
+            continue;
+        };
+        let (id_tx, id_rx) = oneshot::channel();
+        if tx.send(TaskMessage::Download(DownloadTask { request, responder: id_tx })).is_err() {
+            return;
+        }
+        if let Err(e) = id_rx.await.unwrap_or_else(|_| Err("timeout".to_string())) {
+            report_launch_error(app, "添加下载失败", &e);
+        }
+    }
+    for file in args.files {
+        if !confirm_launch(app, format!("打开文件并添加其中的下载任务？\n{}", file)).await {
+            continue;
+        }
+        if let Err(e) = launch_file(app, intake, &file).await {
+            report_launch_error(app, "无法打开文件", &format!("{}: {}", file, e));
+        }
+    }
+}
+
+/// 打开启动参数中的文件：Metalink 为每个文件创建多镜像任务，任务列表逐条建任务，都经任务入口开始下载
+async fn launch_file(app: &tauri::AppHandle, intake: &Intake, path: &str) -> Result<(), String> {
+    let lower = path.to_ascii_lowercase();
+    if lower.ends_with(".torrent") {
+        return Err("暂不支持 BitTorrent 种子".to_string());
+    }
+    let metalink = engine::metalink::is_metalink(&lower);
+    if !metalink && !instance::is_task_list(path) {
+        return Err("不支持的文件类型".to_string());
+    }
+    let text = tokio::fs::read_to_string(path).await.map_err(|e| e.to_string())?;
+    let items = if metalink {
+        // 清单中的文件名由任务入口校验，保存目录按设置
+        let protocols = intake.scheduler().protocols();
+        intake::metalink_items(&text, None, |u| protocols.supports(u))?
+    } else {
+        // 文件内容不可信：保存目录须在浏览器扩展同样允许的范围内，否则改用默认目录
+        let allowed = browser_allowed_dirs(app);
+        let mut items = import::preview(&text, None, None)?.items;
+        for item in &mut items {
+            item.save_dir = item.save_dir.take().filter(|d| browser::check_save_dir(d, &allowed).is_ok());
+            item.filename = item.filename.take().filter(|f| browser::check_filename(f).is_ok());
+        }
+        items
+    };
+    let options = IntakeOptions {
+        start: true,
+        source: TaskSource::Import,
+        ..IntakeOptions::default()
+    };
+    let errors: Vec<String> = submit_with_prompt(app, intake, items, options)
+        .await
+        .into_iter()
+        .filter_map(|o| match o.result {
+            IntakeResult::Rejected { error } | IntakeResult::Failed { error } => Some(format!("{}: {}", o.url, error)),
+            _ => None,
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+#[tauri::command]
+async fn stop_live_recording(task_id: String, state: State<'_, Arc<Scheduler>>) -> Result<(), String> {
+    state.stop_recording(&task_id).await
+}
+
+#[tauri::command]
+async fn start_download(
+    task_id: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<(), String> {
+    let scheduler = state.inner().clone();
+    let settings = current_settings(&app_handle);
+    let max_connections = Some(settings.max_connections_per_task as usize);
+    let net_opts = settings.network_options().await?;
+    state
+        .start_download(
+            &task_id,
+            Some(scheduler),
+            max_connections,
+            Some(net_opts),
+        )
+        .await
+}
+
+#[tauri::command]
+async fn pause_download(task_id: String, state: State<'_, Arc<Scheduler>>) -> Result<(), String> {
+    state.pause_task(&task_id).await
+}
+
+#[tauri::command]
+async fn resume_download(
+    task_id: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<(), String> {
+    let scheduler = state.inner().clone();
+    let settings = current_settings(&app_handle);
+    let max_connections = Some(settings.max_connections_per_task as usize);
+    let net_opts = settings.network_options().await?;
+    state
+        .resume_task(
+            &task_id,
+            Some(scheduler),
+            max_connections,
+            Some(net_opts),
+        )
+        .await
+}
+
+#[tauri::command]
+async fn cancel_download(task_id: String, state: State<'_, Arc<Scheduler>>) -> Result<(), String> {
+    state.cancel_task(&task_id).await
+}
+
+#[tauri::command]
+async fn remove_task(task_id: String, state: State<'_, Arc<Scheduler>>) -> Result<(), String> {
+    state.remove_task(&task_id).await
+}
+
+#[tauri::command]
+async fn list_downloads(state: State<'_, Arc<Scheduler>>) -> Result<Vec<engine::TaskInfo>, String> {
+    Ok(state.list_downloads().await)
+}
+
+/// 按条件检索任务：筛选、排序与分页在后端完成，任务很多时前端不必取回全部
+#[tauri::command]
+async fn query_downloads(query: Option<TaskQuery>, state: State<'_, Arc<Scheduler>>) -> Result<TaskPage, String> {
+    Ok(search::query(&state, &query.unwrap_or_default()).await)
+}
+
+#[tauri::command]
+async fn clear_completed_tasks(state: State<'_, Arc<Scheduler>>) -> Result<usize, String> {
+    state.clear_completed_tasks().await
+}
+
+#[tauri::command]
+async fn get_download_progress(
+    task_id: String,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<Option<engine::TaskInfo>, String> {
+    Ok(state.get_task(&task_id).await)
+}
+
+/// 使用默认程序打开文件
+#[tauri::command]
+fn open_file(path: String) -> Result<(), String> {
+    let path = std::path::Path::new(&path);
+    if !path.exists() {
+        return Err("文件不存在".to_string());
+    }
+    opener::open(path).map_err(|e| e.to_string())
+}
+
+/// 打开「打开方式」对话框
+#[tauri::command]
+fn open_with(path: String) -> Result<(), String> {
+    let path = std::path::Path::new(&path);
+    if !path.exists() {
+        return Err("文件不存在".to_string());
+    }
+    let path_str = path.canonicalize().map_err(|e| e.to_string())?.to_string_lossy().to_string();
+    #[cfg(target_os = "windows")]
+    std::process::Command::new("rundll32.exe")
+        .args(["shell32.dll,OpenAs_RunDLL", &path_str])
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    #[cfg(not(target_os = "windows"))]
+    opener::open(path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 在默认浏览器中打开 URL
+#[tauri::command]
+fn open_url(url: String) -> Result<(), String> {
+    let url = url.trim();
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err("仅支持 http/https 链接".to_string());
+    }
+    opener::open(url).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn open_folder(path: String) -> Result<(), String> {
+    let path = std::path::Path::new(&path);
+    let dir = if path.is_file() {
+        path.parent().ok_or("无法解析路径")?
+    } else {
+        path
+    };
+    #[cfg(target_os = "windows")]
+    std::process::Command::new("explorer")
+        .arg(dir.as_os_str())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    #[cfg(target_os = "macos")]
+    std::process::Command::new("open")
+        .arg(dir)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    #[cfg(target_os = "linux")]
+    std::process::Command::new("xdg-open")
+        .arg(dir)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_default_download_dir(app: tauri::AppHandle) -> Result<String, String> {
+    let settings = current_settings(&app);
+    if !settings.default_save_path.is_empty() {
+        return Ok(settings.default_save_path);
+    }
+    app.path()
+        .download_dir()
+        .map(|p| p.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn exit_app(app: tauri::AppHandle) {
+    if let Some(w) = app.get_webview_window("main") {
+        let _ = w.destroy();
+    }
+    
+    // 设置TCP服务器关闭标志
+    TCP_SHUTDOWN_FLAG.store(true, std::sync::atomic::Ordering::Relaxed);
+    
+    // 发送停止信号给TCP服务器
+    if let Some(tx) = TCP_SHUTDOWN_TX.lock().unwrap().take() {
+        let _ = tx.send(());
+    }
+    
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(350));
+        app.exit(0);
+    });
+}
+
+#[tauri::command]
+fn hide_app(app: tauri::AppHandle) {
+    if let Some(w) = app.get_webview_window("main") {
+        let _ = w.hide();
+    }
+}
+
+/// 从系统剪贴板读取文本（不依赖 WebView 权限，窗口获焦时可用）
+#[tauri::command]
+fn read_clipboard_text() -> Result<String, String> {
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    Ok(clipboard.get_text().unwrap_or_default())
+}
+
+/// 清空系统剪贴板文本，避免同一 URL 再次触发弹窗
+#[tauri::command]
+fn clear_clipboard_text() -> Result<(), String> {
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    clipboard.set_text("").map_err(|e| e.to_string())
+}
+
+/// 写入文本到系统剪贴板（用于导出等）
+#[tauri::command]
+fn write_clipboard_text(text: String) -> Result<(), String> {
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    clipboard.set_text(&text).map_err(|e| e.to_string())
+}
+
+/// 递归复制目录
+fn copy_dir_all(src: &std::path::Path, dst: &std::path::Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dst)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let ty = entry.file_type()?;
+        let dst_path = dst.join(entry.file_name());
+        if ty.is_dir() {
+            copy_dir_all(&entry.path(), &dst_path)?;
+        } else {
+            std::fs::copy(entry.path(), dst_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// 获取浏览器扩展所在路径（用于「加载已解压的扩展程序」）。
+/// 若安装包内带扩展，会复制到应用数据目录后返回；否则返回错误。
+#[tauri::command]
+fn get_browser_extension_path(app: tauri::AppHandle) -> Result<String, String> {
+    let app_data = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let crx_dest = app_data.join("extension").join("multidown-extension.crx");
+    if crx_dest.exists() {
+        return Ok(crx_dest.to_string_lossy().to_string());
+    }
+    if let Ok(res_dir) = app.path().resource_dir() {
+        let crx_src = res_dir.join("extension").join("multidown-extension.crx");
+        if crx_src.exists() {
+            let ext_dest_dir = app_data.join("extension");
+            std::fs::create_dir_all(&ext_dest_dir).map_err(|e| e.to_string())?;
+            std::fs::copy(&crx_src, &crx_dest).map_err(|e| e.to_string())?;
+            return Ok(crx_dest.to_string_lossy().to_string());
+        }
+    }
+    Err("扩展未随应用打包，请从项目 integration/extension 目录获取。".to_string())
+}
+
+#[tauri::command]
+async fn install_browser_extension(app: tauri::AppHandle) -> Result<(), String> {
+    // 尝试注册 Native Host
+    register_native_host(app.clone())?;
+    
+    // 首先尝试使用解压后的扩展目录安装（更可靠）
+    let ext_dir_result = get_extension_directory(app.clone());
+    if let Ok(ext_dir) = ext_dir_result {
+        // 尝试安装到 Chrome/Edge
+        let chrome_result = install_to_chrome(&ext_dir);
+        
+        // 尝试安装到 Firefox
+        let firefox_result = install_to_firefox(&ext_dir);
+        
+        // 如果至少有一个浏览器安装成功，则返回成功
+        if chrome_result.is_ok() || firefox_result.is_ok() {
+            return Ok(());
+        }
+    }
+    
+    // 所有安装方法都失败
+    Err("无法安装扩展到浏览器，请手动安装。\n提示：请在浏览器扩展管理页面启用开发者模式，然后加载解压后的扩展目录。".to_string())
+}
+
+/// 获取扩展目录（解压后的扩展文件）
+fn get_extension_directory(app: tauri::AppHandle) -> Result<String, String> {
+    let app_data = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let ext_dir = app_data.join("extension");
+    
+    // 检查扩展目录是否存在
+    if ext_dir.join("manifest.json").exists() {
+        return Ok(ext_dir.to_string_lossy().to_string());
+    }
+    
+    // 检查资源目录中的扩展文件
+    if let Ok(res_dir) = app.path().resource_dir() {
+        // 首先检查解压后的扩展目录
+        let unpacked_ext_dir = res_dir.join("extension").join("unpacked");
+        if unpacked_ext_dir.join("manifest.json").exists() {
+            // 复制到应用数据目录
+            std::fs::create_dir_all(&ext_dir).map_err(|e| e.to_string())?;
+            
+            // 复制所有文件
+            let files = std::fs::read_dir(&unpacked_ext_dir).map_err(|e| e.to_string())?;
+            for file in files {
+                let file = file.map_err(|e| e.to_string())?;
+                let src_path = file.path();
+                let dest_path = ext_dir.join(file.file_name());
+                if src_path.is_file() {
+                    std::fs::copy(&src_path, &dest_path).map_err(|e| e.to_string())?;
+                }
+            }
+            
+            return Ok(ext_dir.to_string_lossy().to_string());
+        }
+        
+        // 尝试使用ZIP文件
+        let zip_path = res_dir.join("extension").join("multidown-extension.zip");
+        if zip_path.exists() {
+            // 创建扩展目录
+            std::fs::create_dir_all(&ext_dir).map_err(|e| e.to_string())?;
+            
+            // 解压zip文件
+            let zip_content = std::fs::read(&zip_path).map_err(|e| e.to_string())?;
+            
+            // 使用zip库解压
+            let mut cursor = std::io::Cursor::new(zip_content);
+            let mut archive = zip::ZipArchive::new(&mut cursor).map_err(|e| e.to_string())?;
+            
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+                let outpath = ext_dir.join(file.name());
+                
+                if file.name().ends_with('/') {
+                    std::fs::create_dir_all(&outpath).map_err(|e| e.to_string())?;
+                } else {
+                    if let Some(p) = outpath.parent() {
+                        if !p.exists() {
+                            std::fs::create_dir_all(p).map_err(|e| e.to_string())?;
+                        }
+                    }
+                    let mut outfile = std::fs::File::create(&outpath).map_err(|e| e.to_string())?;
+                    std::io::copy(&mut file, &mut outfile).map_err(|e| e.to_string())?;
+                }
+            }
+            
+            return Ok(ext_dir.to_string_lossy().to_string());
+        }
+        
+        // 最后尝试CRX文件
+        let crx_path = res_dir.join("extension").join("multidown-extension.crx");
+        if crx_path.exists() {
+            // 创建扩展目录
+            std::fs::create_dir_all(&ext_dir).map_err(|e| e.to_string())?;
+            
+            // 解压crx文件（实际上是zip文件）
+            let crx_content = std::fs::read(&crx_path).map_err(|e| e.to_string())?;
+            
+            // 使用zip库解压
+            let mut cursor = std::io::Cursor::new(crx_content);
+            let mut archive = zip::ZipArchive::new(&mut cursor).map_err(|e| e.to_string())?;
+            
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+                let outpath = ext_dir.join(file.name());
+                
+                if file.name().ends_with('/') {
+                    std::fs::create_dir_all(&outpath).map_err(|e| e.to_string())?;
+                } else {
+                    if let Some(p) = outpath.parent() {
+                        if !p.exists() {
+                            std::fs::create_dir_all(p).map_err(|e| e.to_string())?;
+                        }
+                    }
+                    let mut outfile = std::fs::File::create(&outpath).map_err(|e| e.to_string())?;
+                    std::io::copy(&mut file, &mut outfile).map_err(|e| e.to_string())?;
+                }
+            }
+            
+            return Ok(ext_dir.to_string_lossy().to_string());
+        }
+    }
+    
+    Err("扩展文件不存在".to_string())
+}
+
+
+
+#[tauri::command]
+async fn package_browser_extension(app: tauri::AppHandle) -> Result<String, String> {
+    use std::fs::File;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    
+    // 获取扩展路径
+    let ext_path = get_browser_extension_path(app.clone())?;
+    let ext_dir = std::path::Path::new(&ext_path);
+    
+    // 创建输出目录
+    let app_data = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let output_dir = app_data.join("extension");
+    std::fs::create_dir_all(&output_dir).map_err(|e| e.to_string())?;
+    
+    // 生成zip文件路径
+    let zip_path = output_dir.join("multidown-extension.zip");
+    
+    // 创建zip文件
+    let file = File::create(&zip_path).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(file);
+    
+    // 遍历扩展目录中的所有文件
+    let walk_dir = walkdir::WalkDir::new(ext_dir).into_iter();
+    for entry in walk_dir.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_file() {
+            // 计算相对路径
+            let relative_path = path.strip_prefix(ext_dir).map_err(|e| e.to_string())?;
+            let relative_path_str = relative_path.to_string_lossy().to_string();
+            
+            // 写入文件到zip
+            zip.start_file(relative_path_str, FileOptions::default())
+                .map_err(|e| e.to_string())?;
+            let mut file = File::open(path).map_err(|e| e.to_string())?;
+            let mut buffer = Vec::new();
+            std::io::Read::read_to_end(&mut file, &mut buffer).map_err(|e| e.to_string())?;
+            zip.write_all(&buffer).map_err(|e| e.to_string())?;
+        }
+    }
+    
+    // 完成zip写入
+    zip.finish().map_err(|e| e.to_string())?;
+    
+    Ok(zip_path.to_string_lossy().to_string())
+}
+
+/// 注册 Native Host
+fn register_native_host(app: tauri::AppHandle) -> Result<(), String> {
+    #[cfg(target_os = "windows")]{
+        use winreg::enums::*;
+        use winreg::RegKey;
+        
+        // 获取资源目录
+        let res_dir = app.path().resource_dir().map_err(|e| e.to_string())?;
+        let native_host_src = res_dir.join("native-host");
+        
+        if !native_host_src.exists() {
+            return Err("Native host directory not found in resources".to_string());
+        }
+        
+        // 复制到应用数据目录以确保权限
+        let app_data = app.path().app_data_dir().map_err(|e| e.to_string())?;
+        let native_host_dir = app_data.join("native-host");
+        std::fs::create_dir_all(&native_host_dir).map_err(|e| e.to_string())?;
+        
+        // 复制 native host 文件
+        let files = std::fs::read_dir(&native_host_src).map_err(|e| e.to_string())?;
+        for file in files {
+            let file = file.map_err(|e| e.to_string())?;
+            let src_path = file.path();
+            let dest_path = native_host_dir.join(file.file_name());
+            if src_path.is_file() {
+                std::fs::copy(&src_path, &dest_path).map_err(|e| e.to_string())?;
+            }
+        }
+        
+        // 更新配置文件中的路径和扩展ID
+        let manifest_path = native_host_dir.join("com.multidown.app.json");
+        if manifest_path.exists() {
+            let mut manifest_content = std::fs::read_to_string(&manifest_path).map_err(|e| e.to_string())?;
+            let native_host_exe_path = native_host_dir.join("multidown-native-host.exe");
+            manifest_content = manifest_content.replace(
+                "MULTIDOWN_NATIVE_HOST_PATH",
+                &native_host_exe_path.to_string_lossy().to_string()
+            );
+            // 使用通配符支持所有扩展ID，更加灵活
+            manifest_content = manifest_content.replace(
+                "EXTENSION_ID_PLACEHOLDER",
+                "*"
+            );
+            std::fs::write(&manifest_path, manifest_content).map_err(|e| e.to_string())?;
+        }
+        
+        // 注册 Chrome Native Host
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let path = r"Software\Google\Chrome\NativeMessagingHosts\com.multidown.app";
+        let (key, _) = hkcu.create_subkey(path).map_err(|e| e.to_string())?;
+        key.set_value("", &manifest_path.to_string_lossy().to_string()).map_err(|e| e.to_string())?;
+        
+        // 注册 Edge Native Host
+        let path_edge = r"Software\Microsoft\Edge\NativeMessagingHosts\com.multidown.app";
+        let (key_edge, _) = hkcu.create_subkey(path_edge).map_err(|e| e.to_string())?;
+        key_edge.set_value("", &manifest_path.to_string_lossy().to_string()).map_err(|e| e.to_string())?;
+        
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]{
+        // macOS 实现
+        let home_dir = dirs::home_dir().ok_or("无法获取用户主目录".to_string())?;
+        let chrome_dir = home_dir.join("Library/Application Support/Google/Chrome/NativeMessagingHosts");
+        let edge_dir = home_dir.join("Library/Application Support/Microsoft Edge/NativeMessagingHosts");
+        
+        std::fs::create_dir_all(&chrome_dir).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&edge_dir).map_err(|e| e.to_string())?;
+        
+        // 获取资源目录
+        let res_dir = app.path().resource_dir().map_err(|e| e.to_string())?;
+        let native_host_path = res_dir.join("native-host").join("multidown-native-host");
+        
+        // 创建 manifest 文件
+        let manifest = serde_json::json!({
+            "name": "com.multidown.app",
+            "description": "Multidown Native Messaging Host",
+            "path": native_host_path.to_string_lossy().to_string(),
+            "type": "stdio",
+            "allowed_origins": ["chrome-extension://*", "moz-extension://*"]
+        });
+        
+        let manifest_str = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+        
+        std::fs::write(chrome_dir.join("com.multidown.app.json"), manifest_str.as_bytes()).map_err(|e| e.to_string())?;
+        std::fs::write(edge_dir.join("com.multidown.app.json"), manifest_str.as_bytes()).map_err(|e| e.to_string())?;
+        
+        Ok(())
+    }
+    #[cfg(target_os = "linux")]{
+        // Linux 实现
+        let home_dir = dirs::home_dir().ok_or("无法获取用户主目录".to_string())?;
+        let chrome_dir = home_dir.join(".config/google-chrome/NativeMessagingHosts");
+        let edge_dir = home_dir.join(".config/microsoft-edge/NativeMessagingHosts");
+        
+        std::fs::create_dir_all(&chrome_dir).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&edge_dir).map_err(|e| e.to_string())?;
+        
+        // 获取资源目录
+        let res_dir = app.path().resource_dir().map_err(|e| e.to_string())?;
+        let native_host_path = res_dir.join("native-host").join("multidown-native-host");
+        
+        // 创建 manifest 文件
+        let manifest = serde_json::json!({
+            "name": "com.multidown.app",
+            "description": "Multidown Native Messaging Host",
+            "path": native_host_path.to_string_lossy().to_string(),
+            "type": "stdio",
+            "allowed_origins": ["chrome-extension://*", "moz-extension://*"]
+        });
+        
+        let manifest_str = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+        
+        std::fs::write(chrome_dir.join("com.multidown.app.json"), manifest_str.as_bytes()).map_err(|e| e.to_string())?;
+        std::fs::write(edge_dir.join("com.multidown.app.json"), manifest_str.as_bytes()).map_err(|e| e.to_string())?;
+        
+        Ok(())
+    }
+}
+
+/// 安装扩展到 Chrome/Edge
+fn install_to_chrome(ext_path: &str) -> Result<(), String> {
+    #[cfg(target_os = "windows")]{
+        // 尝试查找 Chrome
+        let chrome_paths = [
+            "C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe",
+            "C:\\Program Files (x86)\\Google\\Chrome\\Application\\chrome.exe",
+            "C:\\Program Files\\Microsoft\\Edge\\Application\\msedge.exe",
+            "C:\\Program Files (x86)\\Microsoft\\Edge\\Application\\msedge.exe"
+        ];
+        
+        for chrome_path in &chrome_paths {
+            if std::path::Path::new(chrome_path).exists() {
+                // 检查扩展目录是否存在
+                if !std::path::Path::new(ext_path).exists() {
+                    return Err(format!("扩展目录不存在: {}", ext_path));
+                }
+                
+                // 检查扩展目录是否包含manifest.json
+                if !std::path::Path::new(ext_path).join("manifest.json").exists() {
+                    return Err(format!("扩展目录缺少manifest.json文件: {}", ext_path));
+                }
+                
+                // // 尝试关闭所有正在运行的浏览器实例
+                // let _ = std::process::Command::new("taskkill")
+                //     .arg("/F")
+                //     .arg("/IM")
+                //     .arg("chrome.exe")
+                //     .spawn();
+                // let _ = std::process::Command::new("taskkill")
+                //     .arg("/F")
+                //     .arg("/IM")
+                //     .arg("msedge.exe")
+                //     .spawn();
+                
+                // 等待浏览器关闭
+                // std::thread::sleep(std::time::Duration::from_millis(1000));
+                
+                // 启动 Chrome 并加载扩展，添加开发者模式相关参数
+                // 调整参数顺序，确保--load-extension在其他参数之前
+                let result = std::process::Command::new(chrome_path)
+                    .arg(format!("--load-extension={}", ext_path))
+                    .arg("--enable-extensions")
+                    .arg("--enable-dev-tools")
+                    .arg("--no-sandbox")
+                    .arg("--disable-background-timer-throttling")
+                    .arg("--disable-backgrounding-occluded-windows")
+                    .arg("--disable-renderer-backgrounding")
+                    .arg("chrome://extensions/")
+                    .spawn();
+                
+                if result.is_ok() {
+                    return Ok(());
+                } else {
+                    return Err(format!("无法启动浏览器: {}", result.unwrap_err()));
+                }
+            }
+        }
+        Err("未找到 Chrome 或 Edge 浏览器".to_string())
+    }
+    #[cfg(target_os = "macos")]{
+        // 尝试查找 Chrome
+        let chrome_paths = [
+            "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+            "/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge"
+        ];
+        
+        for chrome_path in &chrome_paths {
+            if std::path::Path::new(chrome_path).exists() {
+                // 检查扩展目录是否存在
+                if !std::path::Path::new(ext_path).exists() {
+                    return Err(format!("扩展目录不存在: {}", ext_path));
+                }
+                
+                // 检查扩展目录是否包含manifest.json
+                if !std::path::Path::new(ext_path).join("manifest.json").exists() {
+                    return Err(format!("扩展目录缺少manifest.json文件: {}", ext_path));
+                }
+                
+                // 尝试关闭所有正在运行的浏览器实例
+                let _ = std::process::Command::new("pkill")
+                    .arg("-f")
+                    .arg("Google Chrome")
+                    .spawn();
+                let _ = std::process::Command::new("pkill")
+                    .arg("-f")
+                    .arg("Microsoft Edge")
+                    .spawn();
+                
+                // 等待浏览器关闭
+                std::thread::sleep(std::time::Duration::from_millis(1000));
+                
+                // 启动 Chrome 并加载扩展
+                let result = std::process::Command::new(chrome_path)
+                    .arg(format!("--load-extension={}", ext_path))
+                    .arg("--enable-extensions")
+                    .arg("--enable-dev-tools")
+                    .arg("chrome://extensions/")
+                    .spawn();
+                
+                if result.is_ok() {
+                    return Ok(());
+                } else {
+                    return Err(format!("无法启动浏览器: {}", result.unwrap_err()));
+                }
+            }
+        }
+        Err("未找到 Chrome 或 Edge 浏览器".to_string())
+    }
+    #[cfg(target_os = "linux")]{
+        // 尝试查找 Chrome
+        let chrome_commands = ["google-chrome", "chromium", "microsoft-edge"];
+        
+        for cmd in &chrome_commands {
+            if let Ok(output) = std::process::Command::new("which").arg(cmd).output() {
+                if output.status.success() {
+                    // 检查扩展目录是否存在
+                    if !std::path::Path::new(ext_path).exists() {
+                        return Err(format!("扩展目录不存在: {}", ext_path));
+                    }
+                    
+                    // 检查扩展目录是否包含manifest.json
+                    if !std::path::Path::new(ext_path).join("manifest.json").exists() {
+                        return Err(format!("扩展目录缺少manifest.json文件: {}", ext_path));
+                    }
+                    
+                    // 尝试关闭所有正在运行的浏览器实例
+                    let _ = std::process::Command::new("pkill")
+                        .arg("-f")
+                        .arg(cmd)
+                        .spawn();
+                    
+                    // 等待浏览器关闭
+                    std::thread::sleep(std::time::Duration::from_millis(1000));
+                    
+                    // 启动 Chrome 并加载扩展
+                    let result = std::process::Command::new(cmd)
+                        .arg(format!("--load-extension={}", ext_path))
+                        .arg("--enable-extensions")
+                        .arg("--enable-dev-tools")
+                        .arg("chrome://extensions/")
+                        .spawn();
+                    
+                    if result.is_ok() {
+                        return Ok(());
+                    } else {
+                        return Err(format!("无法启动浏览器: {}", result.unwrap_err()));
+                    }
+                }
+            }
+        }
+        Err("未找到 Chrome 或 Edge 浏览器".to_string())
+    }
+}
+
+/// 安装扩展到 Firefox
+fn install_to_firefox(ext_path: &str) -> Result<(), String> {
+    #[cfg(target_os = "windows")]{
+        // 尝试查找 Firefox
+        let firefox_paths = [
+            "C:\\Program Files\\Mozilla Firefox\\firefox.exe",
+            "C:\\Program Files (x86)\\Mozilla Firefox\\firefox.exe"
+        ];
+        
+        for firefox_path in &firefox_paths {
+            if std::path::Path::new(firefox_path).exists() {
+                // 启动 Firefox 并打开调试页面
+                std::process::Command::new(firefox_path)
+                    .arg("about:debugging#/runtime/this-firefox")
+                    .spawn()
+                    .map_err(|e| e.to_string())?;
+                return Ok(());
+            }
+        }
+        Err("未找到 Firefox 浏览器".to_string())
+    }
+    #[cfg(target_os = "macos")]{
+        // 尝试查找 Firefox
+        let firefox_path = "/Applications/Firefox.app/Contents/MacOS/firefox";
+        
+        if std::path::Path::new(firefox_path).exists() {
+            // 启动 Firefox 并打开调试页面
+            std::process::Command::new(firefox_path)
+                .arg("about:debugging#/runtime/this-firefox")
+                .spawn()
+                .map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+        Err("未找到 Firefox 浏览器".to_string())
+    }
+    #[cfg(target_os = "linux")]{
+        // 尝试查找 Firefox
+        if let Ok(output) = std::process::Command::new("which").arg("firefox").output() {
+            if output.status.success() {
+                // 启动 Firefox 并打开调试页面
+                std::process::Command::new("firefox")
+                    .arg("about:debugging#/runtime/this-firefox")
+                    .spawn()
+                    .map_err(|e| e.to_string())?;
+                return Ok(());
+            }
+        }
+        Err("未找到 Firefox 浏览器".to_string())
+    }
+}
+
+/// 导出任务列表为 JSON 字符串（第 2 版，含完整下载状态）
+#[tauri::command]
+async fn export_tasks(state: State<'_, Arc<Scheduler>>) -> Result<String, String> {
+    let data = migrate::export(&state).await;
+    serde_json::to_string_pretty(&data).map_err(|e| e.to_string())
+}
+
+/// 导出为 zip：任务列表连同已下载的文件，用于迁移到另一台机器
+#[tauri::command]
+async fn export_task_archive(path: String, state: State<'_, Arc<Scheduler>>) -> Result<migrate::ArchiveSummary, String> {
+    let data = migrate::export(&state).await;
+    tokio::task::spawn_blocking(move || migrate::write_archive(&data, std::path::Path::new(&path)))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// 读出导出的 zip 中的任务列表，交给试导入
+#[tauri::command]
+async fn read_task_archive(path: String) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || migrate::read_archive_list(std::path::Path::new(&path)))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// 按完整状态恢复导出的任务（selected 为任务列表中的序号）；archive 为导出的 zip 时从中解出已下载的文件。
+/// 保存路径（映射后）须在浏览器扩展同样允许的目录之内
+#[tauri::command]
+async fn restore_tasks(
+    text: String,
+    archive: Option<String>,
+    selected: Option<Vec<usize>>,
+    mappings: Vec<migrate::DirMapping>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<migrate::RestoreReport, String> {
+    let data: migrate::ExportData =
+        serde_json::from_str(text.trim_start_matches('\u{feff}').trim()).map_err(|e| e.to_string())?;
+    let allowed = browser_allowed_dirs(&app);
+    migrate::restore(&state, data, selected, mappings, allowed, archive.map(std::path::PathBuf::from)).await
+}
+
+/// 检索下载历史，最近结束的在前
+#[tauri::command]
+async fn query_history(query: Option<HistoryQuery>, history: State<'_, Arc<History>>) -> Result<HistoryPage, String> {
+    Ok(history.query(&query.unwrap_or_default()).await)
+}
+
+/// 下载历史的汇总：成功率、每天与各站点的下载量（条件同检索，不分页）
+#[tauri::command]
+async fn history_stats(query: Option<HistoryQuery>, history: State<'_, Arc<History>>) -> Result<HistoryStats, String> {
+    Ok(history.stats(&query.unwrap_or_default()).await)
+}
+
+#[tauri::command]
+async fn delete_history(ids: Vec<u64>, history: State<'_, Arc<History>>) -> Result<usize, String> {
+    history.remove(&ids).await
+}
+
+#[tauri::command]
+async fn clear_history(history: State<'_, Arc<History>>) -> Result<(), String> {
+    history.clear().await
+}
+
+/// 试导入：解析文件内容（format 省略时按内容判断）并附加 Cookie 文件，不新建任务
+#[tauri::command]
+async fn preview_import(
+    text: String,
+    format: Option<ImportFormat>,
+    cookies: Option<String>,
+) -> Result<ImportPreview, String> {
+    import::preview(&text, format, cookies.as_deref())
+}
+
+/// 导入任务列表：本程序导出的 JSON、URL 列表、aria2 输入文件、HAR、IDM .ef2
+#[tauri::command]
+async fn import_tasks(
+    text: String,
+    format: Option<ImportFormat>,
+    cookies: Option<String>,
+    app: tauri::AppHandle,
+    intake: State<'_, Arc<Intake>>,
+) -> Result<usize, String> {
+    let items = import::preview(&text, format, cookies.as_deref())?.items;
+    let options = IntakeOptions {
+        source: TaskSource::Import,
+        ..IntakeOptions::default()
+    };
+    let outcomes = submit_with_prompt(&app, &intake, items, options).await;
+    Ok(outcomes.iter().filter(|o| o.result.task_id().is_some()).count())
+}
+
+#[tauri::command]
+async fn refresh_download_address(
+    task_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<(), String> {
+    let opts = network_options_from_app(&app).await?;
+    state.refresh_task_url(&task_id, &opts).await
+}
+
+/// 链接过期时换用新地址继续下载（保留已下载部分）
+#[tauri::command]
+async fn rebind_download_url(
+    task_id: String,
+    new_url: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<(), String> {
+    let opts = network_options_from_app(&app).await?;
+    state.rebind_task_url(&task_id, new_url, &opts).await
+}
+
+/// 开启后，浏览器扩展捕获到同名同大小的下载时自动更换该任务的地址
+#[tauri::command]
+async fn capture_download_link(
+    task_id: String,
+    enabled: bool,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<(), String> {
+    state.set_link_capture(&task_id, enabled).await
+}
+
+#[tauri::command]
+async fn update_task_save_path(
+    task_id: String,
+    new_save_path: String,
+    state: State<'_, Arc<Scheduler>>,
+) -> Result<(), String> {
+    state.update_task_save_path(&task_id, new_save_path).await
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_fs::init())
+        .manage(GrabJobs::default())
+        .setup(|app| {
+            // 单实例：已有实例在运行时把启动参数转交给它后退出
+            let launch_args = instance::LaunchArgs::parse(
+                std::env::args_os().skip(1).map(|a| a.to_string_lossy().into_owned()),
+                &std::env::current_dir().unwrap_or_default(),
+            );
+            let lock_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+            match instance::try_lock(&lock_dir) {
+                Ok(Some(lock)) => {
+                    app.manage(lock);
+                }
+                Ok(None) => {
+                    let code = match tauri::async_runtime::block_on(instance::forward(&lock_dir, &launch_args)) {
+                        Ok(()) => 0,
+                        Err(e) => {
+                            error_log(app.handle(), "转交启动参数失败", &e);
+                            eprintln!("{}", e);
+                            1
+                        }
+                    };
+                    std::process::exit(code);
+                }
+                Err(e) => debug_log(app.handle(), "获取单实例锁失败", Some(&e.to_string())),
+            }
+
+            let path = app
+                .path()
+                .app_data_dir()
+                .map_err(|e| e.to_string())?
+                .join("multidown_tasks.json");
+            // 界面与本地控制接口的事件流都需要调度器事件；下载历史不能丢事件，单独用不限长的通道
+            let control_events = Arc::new(BroadcastEvents::new(256));
+            let (history_events, history_rx) = ChannelEvents::channel();
+            let scheduler = Scheduler::load_from(&path)
+                .unwrap_or_else(|_| Scheduler::new(Some(path)))
+                .with_events(Arc::new(FanoutEvents(vec![
+                    Arc::new(TauriEvents(app.handle().clone())),
+                    control_events.clone(),
+                    history_events,
+                ])));
+            let scheduler = Arc::new(scheduler);
+            let sched_clone = scheduler.clone();
+            let app_handle = app.handle().clone();
+            app.manage(scheduler);
+            
+            // 检查是否首次运行，如果是则自动安装扩展
+            let app_data = app.path().app_data_dir().map_err(|e| e.to_string())?;
+
+            // 任务入口：各处新建任务共用；排队的任务在有空位时由 run_queue 开始
+            let fallback_save_dir = app
+                .path()
+                .download_dir()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|_| ".".to_string());
+            let intake = Arc::new(Intake::new(sched_clone.clone(), settings_path(&app_data), fallback_save_dir));
+            tauri::async_runtime::spawn(intake.clone().run_queue(control_events.subscribe()));
+            app.manage(intake.clone());
+            // 下载历史：任务结束时记录
+            let history = Arc::new(History::load(history::history_path(&app_data), &intake.settings()));
+            tauri::async_runtime::spawn(history.clone().run(sched_clone.clone(), history_rx));
+            app.manage(history);
+            let first_run_flag = app_data.join("first_run");
+            if !first_run_flag.exists() {
+                // 创建首次运行标志
+                std::fs::write(&first_run_flag, "").ok();
+                
+                // 自动安装扩展
+                let app_handle_clone = app_handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    let _ = install_browser_extension(app_handle_clone).await;
+                });
+            }
+
+            // 系统托盘：图标 + 菜单（显示主窗口 / 退出）
+            let show_i = MenuItem::with_id(app, "show", "显示主窗口", true, None::<&str>)?;
+            let quit_i = MenuItem::with_id(app, "quit", "退出", true, None::<&str>)?;
+            let menu = Menu::with_items(app, &[&show_i, &quit_i])?;
+            
+            // 尝试加载应用程序图标作为托盘图标
+            let mut tray_builder = TrayIconBuilder::new()
+                .tooltip("Multidown")
+                .menu(&menu)
+                .show_menu_on_left_click(false)
+                .on_menu_event(move |app, event| {
+                    match event.id.as_ref() {
+                        "show" => {
+                            if let Some(w) = app.get_webview_window("main") {
+                                let _ = w.show();
+                                let _ = w.unminimize();
+                                let _ = w.set_focus();
+                            }
+                        }
+                        "quit" => {
+                            if let Some(w) = app.get_webview_window("main") {
+                                let _ = w.destroy();
+                            }
+                            
+                            // 设置TCP服务器关闭标志
+                            TCP_SHUTDOWN_FLAG.store(true, std::sync::atomic::Ordering::Relaxed);
+                            
+                            // 发送停止信号给TCP服务器
+                            if let Some(tx) = TCP_SHUTDOWN_TX.lock().unwrap().take() {
+                                let _ = tx.send(());
+                            }
+                            
+                            let app = app.clone();
+                            std::thread::spawn(move || {
+                                std::thread::sleep(std::time::Duration::from_millis(200));
+                                app.exit(0);
+                            });
+                        }
+                        _ => {}
+                    }
+                });
+            
+            // 尝试使用应用程序图标作为托盘图标
+            if let Some(icon) = app.default_window_icon() {
+                // 直接使用默认窗口图标
+                tray_builder = tray_builder.icon(icon.to_owned());
+            }
+            
+            let _tray = tray_builder.build(app)?;
+
+            // 本地控制接口（HTTP/JSON-RPC），默认关闭
+            let mut control_settings = intake.settings();
+            if control_settings.control_api_enabled {
+                let app_handle_control = app_handle.clone();
+                let intake_control = intake.clone();
+                let sched_control = sched_clone.clone();
+                let events_control = control_events.clone();
+                tauri::async_runtime::spawn(async move {
+                    if control_settings.control_api_token.is_empty() {
+                        control_settings.control_api_token = control::generate_token();
+                        if let Err(e) = intake_control.save_settings(&control_settings).await {
+                            debug_log(&app_handle_control, "保存控制接口令牌失败", Some(&e.to_string()));
+                            return;
+                        }
+                    }
+                    let listener = match control::bind(control_settings.control_api_port).await {
+                        Ok(l) => l,
+                        Err(e) => {
+                            debug_log(&app_handle_control, "控制接口绑定端口失败", Some(&e.to_string()));
+                            return;
+                        }
+                    };
+                    debug_log(
+                        &app_handle_control,
+                        "控制接口已启动",
+                        Some(&format!("127.0.0.1:{}", control_settings.control_api_port)),
+                    );
+                    let ctx = control::ControlContext {
+                        scheduler: sched_control,
+                        intake: intake_control,
+                        events: events_control,
+                        token: control_settings.control_api_token,
+                        aria2_rpc: control_settings.aria2_rpc_enabled,
+                    };
+                    control::serve(listener, Arc::new(ctx)).await;
+                });
+            }
+
+            // 浏览器扩展 Native Host：本机 IPC（可选 TCP 回退），握手校验令牌后接收扩展发来的命令（协议见 browser 模块）
+            let (task_tx, mut task_rx) = tokio::sync::mpsc::unbounded_channel::<TaskMessage>();
+
+            // 启动参数：本进程的、再次启动时转交来的与 macOS 打开事件带来的，逐批处理
+            let (launch_tx, mut launch_rx) = tokio::sync::mpsc::unbounded_channel::<instance::LaunchArgs>();
+            if !launch_args.is_empty() {
+                let _ = launch_tx.send(launch_args);
+            }
+            #[cfg(target_os = "macos")]
+            app.manage(LaunchQueue(launch_tx.clone()));
+            let app_launch = app_handle.clone();
+            let intake_launch = intake.clone();
+            let tx_launch = task_tx.clone();
+            tauri::async_runtime::spawn(async move {
+                while let Some(args) = launch_rx.recv().await {
+                    handle_launch(&app_launch, &intake_launch, &tx_launch, args).await;
+                }
+            });
+            let app_data = match app.path().app_data_dir() {
+                Ok(d) => d,
+                Err(_) => std::path::PathBuf::new(),
+            };
+            let port_file = app_data.join(browser::PORT_FILENAME);
+            let token_file = app_data.join(browser::TOKEN_FILENAME);
+            let app_handle_clone = app_handle.clone();
+            let sched_browser = sched_clone.clone();
+            let browser_events = control_events.clone();
+            
+            debug_log(&app_handle, "启动浏览器集成服务", None);
+            
+            // 创建关闭信号通道
+            let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+            *TCP_SHUTDOWN_TX.lock().unwrap() = Some(shutdown_tx);
+            
+            tauri::async_runtime::spawn(async move {
+                if app_data.as_os_str().is_empty() {
+                    debug_log(&app_handle_clone, "无法获取应用数据目录，不启动浏览器集成服务", None);
+                    return;
+                }
+                // 另一实例正在提供服务时不能改写它的令牌
+                if browser::ipc::probe(&app_data).await {
+                    debug_log(&app_handle_clone, "已有实例在监听本机 IPC 端点，不启动浏览器集成服务", None);
+                    return;
+                }
+                // 先写令牌再创建端点：Native Host 能连上时令牌已就绪
+                let token = match browser::write_token_file(&app_data) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        debug_log(&app_handle_clone, "写入令牌文件失败", Some(&e.to_string()));
+                        return;
+                    }
+                };
+                let mut ipc = match browser::ipc::IpcListener::bind(&app_data).await {
+                    Ok(l) => {
+                        debug_log(&app_handle_clone, "本机 IPC 端点已创建", None);
+                        Some(l)
+                    }
+                    Err(e) => {
+                        debug_log(&app_handle_clone, "创建本机 IPC 端点失败", Some(&e.to_string()));
+                        None
+                    }
+                };
+                // 端口文件只在开启 TCP 回退时存在，先清除旧版本或崩溃遗留的文件
+                let _ = std::fs::remove_file(&port_file);
+                let tcp_fallback = current_settings(&app_handle_clone).browser_tcp_fallback;
+                let tcp = if tcp_fallback {
+                    match TcpListener::bind("127.0.0.1:0").await.and_then(|l| Ok((l.local_addr()?.port(), l))) {
+                        Ok((port, l)) => {
+                            debug_log(&app_handle_clone, "TCP回退已启动", Some(&format!("端口: {}", port)));
+                            if let Err(e) = std::fs::write(&port_file, port.to_string()) {
+                                debug_log(&app_handle_clone, "写入端口文件失败", Some(&e.to_string()));
+                            }
+                            Some(l)
+                        }
+                        Err(e) => {
+                            debug_log(&app_handle_clone, "绑定TCP端口失败", Some(&e.to_string()));
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                if ipc.is_none() && tcp.is_none() {
+                    let _ = std::fs::remove_file(&token_file);
+                    return;
+                }
+                let mut shutdown_rx = std::pin::pin!(shutdown_rx);
+                loop {
+                    let stream: Box<dyn browser::Stream> = tokio::select! {
+                        accepted = async {
+                            match ipc.as_mut() {
+                                Some(l) => l.accept().await,
+                                None => std::future::pending().await,
+                            }
+                        } => match accepted {
+                            Ok(s) => Box::new(s),
+                            Err(e) => {
+                                debug_log(&app_handle_clone, "接受连接失败", Some(&e.to_string()));
+                                continue;
+                            }
+                        },
+                        accepted = async {
+                            match tcp.as_ref() {
+                                Some(l) => l.accept().await,
+                                None => std::future::pending().await,
+                            }
+                        } => match accepted {
+                            Ok((s, addr)) => {
+                                debug_log(&app_handle_clone, "接受到TCP连接", Some(&addr.to_string()));
+                                Box::new(s)
+                            }
+                            Err(e) => {
+                                debug_log(&app_handle_clone, "接受连接失败", Some(&e.to_string()));
+                                continue;
+                            }
+                        },
+                        _ = &mut *shutdown_rx => {
+                            debug_log(&app_handle_clone, "接收到停止信号，关闭浏览器集成服务", None);
+                            if let Some(l) = &ipc {
+                                l.cleanup();
+                            }
+                            let _ = std::fs::remove_file(&port_file);
+                            let _ = std::fs::remove_file(&token_file);
+                            break;
+                        }
+                    };
+
+                    let app = app_handle_clone.clone();
+                    let tx = task_tx.clone();
+                    let launch_tx = launch_tx.clone();
+                    let token = token.clone();
+                    let sched = sched_browser.clone();
+                    let events = browser_events.clone();
+                    tauri::async_runtime::spawn(async move {
+                        let handler = |cmd: browser::Command| {
+                            let app = app.clone();
+                            let tx = tx.clone();
+                            let launch_tx = launch_tx.clone();
+                            let sched = sched.clone();
+                            async move {
+                                let (resp_tx, resp_rx) = oneshot::channel();
+                                let msg = match cmd {
+                                    browser::Command::Download(request) => {
+                                        debug_log(&app, "处理下载命令", Some(&request.url));
+                                        if let Err(e) = validate_browser_download(&app, &request) {
+                                            debug_log(&app, "下载请求被拒绝", Some(&e));
+                                            return browser::Response::error(e);
+                                        }
+                                        let (id_tx, id_rx) = oneshot::channel();
+                                        if tx.send(TaskMessage::Download(DownloadTask { request, responder: id_tx })).is_err() {
+                                            return browser::Response::error("internal");
+                                        }
+                                        let result = id_rx.await.unwrap_or_else(|_| Err("timeout".to_string()));
+                                        debug_log(&app, "命令处理结果", Some(&format!("{:?}", result)));
+                                        return browser::Response::from(result);
+                                    }
+                                    browser::Command::OpenWindow { url } => TaskMessage::OpenWindow(OpenWindowTask {
+                                        url: url.unwrap_or_default(),
+                                        responder: resp_tx,
+                                    }),
+                                    browser::Command::Pause { id } => return sched.pause_task(&id).await.into(),
+                                    browser::Command::Resume { id } => {
+                                        let settings = current_settings(&app);
+                                        let net_opts = match settings.network_options().await {
+                                            Ok(net_opts) => net_opts,
+                                            Err(e) => return Err::<(), String>(e).into(),
+                                        };
+                                        return sched
+                                            .resume_task(
+                                                &id,
+                                                Some(sched.clone()),
+                                                Some(settings.max_connections_per_task as usize),
+                                                Some(net_opts),
+                                            )
+                                            .await
+                                            .into();
+                                    }
+                                    browser::Command::Cancel { id } => return sched.cancel_task(&id).await.into(),
+                                    browser::Command::List { active, offset, limit } => {
+                                        return browser::list_page(sched.list_downloads().await, active, offset, limit);
+                                    }
+                                    browser::Command::Subscribe => return browser::Response::ok(),
+                                    // 需要用户确认的条目可能久等，收下即回复，转交的进程不必等待
+                                    browser::Command::Launch(args) => {
+                                        debug_log(&app, "收到转交的启动参数", Some(&format!("{:?}", args)));
+                                        return launch_tx.send(args).map_err(|_| "internal".to_string()).into();
+                                    }
+                                };
+                                if tx.send(msg).is_err() {
+                                    return browser::Response::error("internal");
+                                }
+                                let result = resp_rx.await.unwrap_or_else(|_| Err("timeout".to_string()));
+                                debug_log(&app, "命令处理结果", Some(&format!("{:?}", result)));
+                                browser::Response::from(result)
+                            }
+                        };
+                        if let Err(e) = browser::serve_connection(stream, &token, &sched, &events, handler).await {
+                            debug_log(&app, "连接处理失败", Some(&e.to_string()));
+                        }
+                    });
+                }
+            });
+            
+            let app_worker = app_handle.clone();
+            let intake_worker = intake.clone();
+            tauri::async_runtime::spawn(async move {
+                while let Some(task_msg) = task_rx.recv().await {
+                    match task_msg {
+                        TaskMessage::Download(task) => {
+                            let DownloadTask { request, responder } = task;
+                            let browser::DownloadRequest {
+                                url,
+                                filename,
+                                save_path,
+                                open_window,
+                                mirrors,
+                                ..
+                            } = request;
+                            
+                            // 链接过期后在浏览器中重新下载：同名同大小的等待任务优先认领新地址并续传
+                            // 网络设置无效时不认领，由下面新建任务报告错误
+                            let captured = match network_options_from_app(&app_worker).await {
+                                Ok(capture_opts) => {
+                                    intake_worker
+                                        .scheduler()
+                                        .capture_link(&url, filename.as_deref(), &capture_opts)
+                                        .await
+                                }
+                                Err(_) => None,
+                            };
+                            let result = match captured {
+                                Some(id) => intake_worker.start_or_queue(&id).await,
+                                None => {
+                                    let item = IntakeItem {
+                                        url,
+                                        mirrors,
+                                        save_dir: save_path,
+                                        filename,
+                                        probe: None,
+                                        headers: Vec::new(),
+                                        kind: IntakeKind::Auto,
+                                    };
+                                    let options = IntakeOptions {
+                                        start: true,
+                                        source: TaskSource::Browser,
+                                        ..IntakeOptions::default()
+                                    };
+                                    submit_with_prompt(&app_worker, &intake_worker, vec![item], options)
+                                        .await
+                                        .pop()
+                                        .map(|o| o.result)
+                                        .unwrap_or_else(|| IntakeResult::Failed { error: "internal".to_string() })
+                                }
+                            };
+                            // 如果需要打开窗口，显示主窗口
+                            if open_window && result.task_id().is_some() {
+                                if let Some(window) = app_worker.get_webview_window("main") {
+                                    let _ = window.show();
+                                    let _ = window.unminimize();
+                                    let _ = window.set_focus();
+                                }
+                            }
+                            let _ = responder.send(result.into_id());
+                        }
+                        
+                        TaskMessage::OpenWindow(task) => {
+                            let OpenWindowTask { url, responder } = task;
+                            
+                            // 显示主窗口
+                            if let Some(window) = app_worker.get_webview_window("main") {
+                                let _ = window.show();
+                                let _ = window.unminimize();
+                                let _ = window.set_focus();
+                            }
+                            
+                            // 如果提供了URL，自动添加到下载
+                            if !url.is_empty() && network::is_supported_url(&url) {
+                                let options = IntakeOptions {
+                                    start: true,
+                                    source: TaskSource::Browser,
+                                    ..IntakeOptions::default()
+                                };
+                                let result = submit_with_prompt(&app_worker, &intake_worker, vec![IntakeItem::new(url)], options)
+                                    .await
+                                    .pop()
+                                    .map_or_else(|| Err("internal".to_string()), |o| o.result.into_id().map(|_| ()));
+                                let _ = responder.send(result);
+                            } else {
+                                // 只是打开窗口，不添加下载
+                                let _ = responder.send(Ok(()));
+                            }
+                        }
+                    }
+                }
+            });
+
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    let interval = current_settings(&app_handle).save_progress_interval_secs;
+                    tokio::time::sleep(std::time::Duration::from_secs(interval.max(5))).await;
+                    if interval == 0 {
+                        continue;
+                    }
+                    let list = sched_clone.list_downloads().await;
+                    let has_downloading = list.iter().any(|t| t.status == TaskStatus::Downloading);
+                    if has_downloading {
+                        sched_clone.save_tasks().await;
+                    }
+                }
+            });
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                if window.label() == "main" {
+                    api.prevent_close();
+                    let _ = window.hide();
+                }
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            get_settings,
+            set_settings,
+            probe_download,
+            create_download,
+            create_download_with_probe,
+            probe_hls_variants,
+            create_hls_download,
+            probe_dash_representations,
+            create_dash_download,
+            import_metalink,
+            stop_live_recording,
+            start_download,
+            pause_download,
+            resume_download,
+            cancel_download,
+            remove_task,
+            list_downloads,
+            query_downloads,
+            clear_completed_tasks,
+            get_download_progress,
+            get_default_download_dir,
+            open_folder,
+            open_url,
+            open_file,
+            open_with,
+            refresh_download_address,
+            rebind_download_url,
+            capture_download_link,
+            update_task_save_path,
+            exit_app,
+            hide_app,
+            read_clipboard_text,
+            clear_clipboard_text,
+            write_clipboard_text,
+            get_browser_extension_path,
+            install_browser_extension,
+            package_browser_extension,
+            export_tasks,
+            export_task_archive,
+            read_task_archive,
+            restore_tasks,
+            import_tasks,
+            preview_import,
+            add_downloads,
+            expand_batch_urls,
+            grab_site,
+            cancel_grab,
+            query_history,
+            history_stats,
+            delete_history,
+            clear_history,
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // macOS 通过打开事件而不是命令行参数传递协议链接与双击的文件
+            #[cfg(target_os = "macos")]
+            if let tauri::RunEvent::Opened { urls } = event {
+                let mut args = instance::LaunchArgs::default();
+                for url in urls {
+                    match url.to_file_path() {
+                        Ok(path) if url.scheme() == "file" => args.files.push(path.to_string_lossy().into_owned()),
+                        _ => args.urls.push(url.to_string()),
+                    }
+                }
+                if let Some(queue) = app.try_state::<LaunchQueue>() {
+                    let _ = queue.0.send(args);
+                }
+            }
+            #[cfg(not(target_os = "macos"))]
+            let _ = (app, event);
+        });
+}
@@ -0,0 +1,127 @@
+//! 完整性校验：整文件哈希与分块（piece）哈希
+
+use digest::DynDigest;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum HashAlgo {
+    #[serde(rename = "md5")]
+    Md5,
+    #[serde(rename = "sha-1")]
+    Sha1,
+    #[serde(rename = "sha-256")]
+    Sha256,
+    #[serde(rename = "sha-512")]
+    Sha512,
+}
+
+impl HashAlgo {
+    /// 识别 "sha-256"、"sha256"、"SHA-1" 等写法
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "md5" => Some(Self::Md5),
+            "sha1" => Some(Self::Sha1),
+            "sha256" => Some(Self::Sha256),
+            "sha512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    fn hasher(self) -> Box<dyn DynDigest + Send> {
+        match self {
+            Self::Md5 => Box::new(md5::Md5::default()),
+            Self::Sha1 => Box::new(sha1::Sha1::default()),
+            Self::Sha256 => Box::new(sha2::Sha256::default()),
+            Self::Sha512 => Box::new(sha2::Sha512::default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHash {
+    pub algo: HashAlgo,
+    /// 小写十六进制
+    pub hex: String,
+}
+
+/// 按固定长度分块的哈希列表，最后一块可能较短
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PieceHashes {
+    pub algo: HashAlgo,
+    pub length: u64,
+    pub hashes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksums {
+    #[serde(default)]
+    pub file: Vec<FileHash>,
+    #[serde(default)]
+    pub pieces: Option<PieceHashes>,
+}
+
+impl Checksums {
+    pub fn is_empty(&self) -> bool {
+        self.file.is_empty() && self.pieces.is_none()
+    }
+
+    /// 最强的整文件哈希
+    pub fn strongest(&self) -> Option<&FileHash> {
+        self.file.iter().max_by_key(|h| h.algo)
+    }
+}
+
+fn hash_range(file: &mut std::fs::File, algo: HashAlgo, len: u64) -> std::io::Result<String> {
+    let mut hasher = algo.hasher();
+    let mut buf = vec![0u8; 256 * 1024];
+    let mut remain = len;
+    while remain > 0 {
+        let want = remain.min(buf.len() as u64) as usize;
+        let n = file.read(&mut buf[..want])?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        remain -= n as u64;
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// 计算整个文件的哈希
+pub async fn file_digest(path: &Path, algo: HashAlgo) -> Result<String, String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path)?;
+        hash_range(&mut file, algo, u64::MAX)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+/// 逐块校验，返回哈希不符的区间 (start, end) inclusive
+pub async fn verify_pieces(path: &Path, pieces: &PieceHashes, total: u64) -> Result<Vec<(u64, u64)>, String> {
+    let path = path.to_path_buf();
+    let pieces = pieces.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path)?;
+        let mut bad = Vec::new();
+        for (i, expected) in pieces.hashes.iter().enumerate() {
+            let start = i as u64 * pieces.length;
+            if start >= total {
+                break;
+            }
+            let end = (start + pieces.length).min(total) - 1;
+            let actual = hash_range(&mut file, pieces.algo, end - start + 1)?;
+            if !actual.eq_ignore_ascii_case(expected) {
+                bad.push((start, end));
+            }
+        }
+        Ok(bad)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e: std::io::Error| e.to_string())
+}
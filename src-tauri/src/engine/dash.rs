@@ -0,0 +1,784 @@
+//! MPEG-DASH（.mpd）下载：解析 SegmentTemplate/SegmentList/SegmentBase，按所选视频/音频轨分别输出（含初始化分片）
+
+use crate::engine::media::{
+    concat_pieces, existing_bytes, fetch_init, fetch_with_retry, needs_piece, piece_dir, piece_file, resolve,
+    save_piece, stream_piece, MediaError, MediaOutcome,
+};
+use crate::engine::task::Task;
+use crate::engine::types::TaskStatus;
+use crate::network::ProtocolHandler;
+use futures_util::stream::{self, TryStreamExt};
+use roxmltree::Node;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+/// SegmentBase 单文件按此大小切分为多个区间并发下载
+const SINGLE_FILE_CHUNK: u64 = 4 * 1024 * 1024;
+
+/// DASH 任务选项：指定表示 ID，未指定时选最高码率（视频受 max_height 限制）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DashOptions {
+    pub video_id: Option<String>,
+    pub audio_id: Option<String>,
+    pub max_height: Option<u32>,
+    /// 不下载音频轨
+    pub no_audio: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackType {
+    Video,
+    Audio,
+    Text,
+    Other,
+}
+
+/// 清单中的一个表示（Representation），供前端选择
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DashRepresentation {
+    pub id: String,
+    pub track: TrackType,
+    pub mime_type: Option<String>,
+    pub codecs: Option<String>,
+    pub bandwidth: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub lang: Option<String>,
+}
+
+/// 一个分片：整文件或其中一段
+#[derive(Debug, Clone, PartialEq)]
+pub struct Piece {
+    pub url: String,
+    pub byte_range: Option<(u64, u64)>,
+}
+
+/// 分片寻址方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum Addressing {
+    /// 初始化分片 + 媒体分片列表（SegmentTemplate / SegmentList）
+    Segments { init: Option<Piece>, segments: Vec<Piece> },
+    /// 单个文件（SegmentBase 或仅有 BaseURL），初始化数据位于文件头部
+    Single { url: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub representation: DashRepresentation,
+    /// 每个 Period 一段寻址；多 Period 时按相同表示 ID 拼接
+    pub periods: Vec<Addressing>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// type="dynamic" 的直播清单
+    pub dynamic: bool,
+    pub tracks: Vec<Track>,
+}
+
+/// 根据地址后缀或 Content-Type 判断是否为 DASH 清单
+pub fn is_dash(url: &str, content_type: Option<&str>) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.to_ascii_lowercase().ends_with(".mpd")
+        || content_type.is_some_and(|ct| ct.to_ascii_lowercase().contains("dash+xml"))
+}
+
+/// 输出文件名：把 .mpd 替换为 .mp4
+pub fn output_filename(name: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if ext.eq_ignore_ascii_case("mpd") => format!("{}.mp4", stem),
+        Some(_) => name.to_string(),
+        None => format!("{}.mp4", name),
+    }
+}
+
+/// ISO 8601 时长，如 "PT1H2M3.5S"、"P1DT2H"
+fn parse_duration(s: &str) -> Option<f64> {
+    let s = s.trim().strip_prefix('P')?;
+    let (date, time) = s.split_once('T').unwrap_or((s, ""));
+    const DATE_UNITS: &[(char, f64)] = &[('Y', 31_536_000.0), ('M', 2_592_000.0), ('W', 604_800.0), ('D', 86_400.0)];
+    const TIME_UNITS: &[(char, f64)] = &[('H', 3600.0), ('M', 60.0), ('S', 1.0)];
+    let mut total = 0.0;
+    for (part, units) in [(date, DATE_UNITS), (time, TIME_UNITS)] {
+        let mut num = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() || c == '.' {
+                num.push(c);
+            } else {
+                let (_, factor) = units.iter().find(|(u, _)| *u == c)?;
+                total += num.parse::<f64>().ok()? * factor;
+                num.clear();
+            }
+        }
+    }
+    Some(total)
+}
+
+/// "start-end" 字节区间
+fn parse_range(s: &str) -> Option<(u64, u64)> {
+    let (a, b) = s.trim().split_once('-')?;
+    Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+}
+
+/// 展开模板标识符：$RepresentationID$、$Number$、$Time$、$Bandwidth$（支持 %0Nd 宽度）与 $$
+fn expand_template(template: &str, id: &str, number: u64, time: u64, bandwidth: u64) -> String {
+    let mut out = String::new();
+    let mut parts = template.split('$');
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    let mut in_ident = true;
+    for part in parts {
+        if in_ident {
+            let (name, width) = match part.split_once('%') {
+                Some((n, fmt)) => (n, fmt.trim_start_matches('0').trim_end_matches('d').parse::<usize>().ok()),
+                None => (part, None),
+            };
+            let value = match name {
+                "" => "$".to_string(),
+                "RepresentationID" => id.to_string(),
+                "Number" => number.to_string(),
+                "Time" => time.to_string(),
+                "Bandwidth" => bandwidth.to_string(),
+                other => format!("${}$", other),
+            };
+            match width {
+                Some(w) if value.len() < w => out.push_str(&format!("{}{}", "0".repeat(w - value.len()), value)),
+                _ => out.push_str(&value),
+            }
+        } else {
+            out.push_str(part);
+        }
+        in_ident = !in_ident;
+    }
+    out
+}
+
+fn child<'a, 'i>(node: Node<'a, 'i>, name: &str) -> Option<Node<'a, 'i>> {
+    node.children().find(|n| n.tag_name().name() == name)
+}
+
+fn children<'a, 'i: 'a>(node: Node<'a, 'i>, name: &'a str) -> impl Iterator<Item = Node<'a, 'i>> + 'a {
+    node.children().filter(move |n| n.tag_name().name() == name)
+}
+
+/// 叠加 BaseURL：各层相对上一层解析
+fn base_url(node: Node, parent: &str) -> Result<String, String> {
+    match child(node, "BaseURL").and_then(|b| b.text()) {
+        Some(text) => resolve(parent, text.trim()),
+        None => Ok(parent.to_string()),
+    }
+}
+
+/// SegmentTemplate 属性，表示级覆盖自适应集级
+#[derive(Default, Clone)]
+struct Template {
+    media: Option<String>,
+    initialization: Option<String>,
+    start_number: Option<u64>,
+    timescale: Option<u64>,
+    duration: Option<u64>,
+    /// (t, d, r)
+    timeline: Option<Vec<(Option<u64>, u64, i64)>>,
+}
+
+impl Template {
+    fn from_node(node: Node) -> Self {
+        let num = |name: &str| node.attribute(name).and_then(|v| v.parse::<u64>().ok());
+        let timeline = child(node, "SegmentTimeline").map(|tl| {
+            children(tl, "S")
+                .map(|s| {
+                    (
+                        s.attribute("t").and_then(|v| v.parse().ok()),
+                        s.attribute("d").and_then(|v| v.parse().ok()).unwrap_or(0),
+                        s.attribute("r").and_then(|v| v.parse().ok()).unwrap_or(0),
+                    )
+                })
+                .collect()
+        });
+        Self {
+            media: node.attribute("media").map(String::from),
+            initialization: node.attribute("initialization").map(String::from),
+            start_number: num("startNumber"),
+            timescale: num("timescale"),
+            duration: num("duration"),
+            timeline,
+        }
+    }
+
+    fn merge(self, over: Template) -> Self {
+        Self {
+            media: over.media.or(self.media),
+            initialization: over.initialization.or(self.initialization),
+            start_number: over.start_number.or(self.start_number),
+            timescale: over.timescale.or(self.timescale),
+            duration: over.duration.or(self.duration),
+            timeline: over.timeline.or(self.timeline),
+        }
+    }
+
+    /// 展开为分片列表；period_secs 用于计算固定时长模板的分片数与 r=-1 的重复次数
+    fn expand(&self, base: &str, rep: &DashRepresentation, period_secs: Option<f64>) -> Result<Addressing, String> {
+        let media = self.media.as_deref().ok_or("SegmentTemplate 缺少 media")?;
+        let timescale = self.timescale.unwrap_or(1).max(1);
+        let start_number = self.start_number.unwrap_or(1);
+        let period_end = period_secs.map(|p| (p * timescale as f64).round() as u64);
+        let init = self
+            .initialization
+            .as_deref()
+            .map(|t| {
+                Ok::<_, String>(Piece {
+                    url: resolve(base, &expand_template(t, &rep.id, start_number, 0, rep.bandwidth))?,
+                    byte_range: None,
+                })
+            })
+            .transpose()?;
+
+        let mut segments = Vec::new();
+        let mut push = |number: u64, time: u64| -> Result<(), String> {
+            segments.push(Piece {
+                url: resolve(base, &expand_template(media, &rep.id, number, time, rep.bandwidth))?,
+                byte_range: None,
+            });
+            Ok(())
+        };
+        if let Some(timeline) = &self.timeline {
+            let mut time = 0u64;
+            let mut number = start_number;
+            for (i, &(t, d, r)) in timeline.iter().enumerate() {
+                if let Some(t) = t {
+                    time = t;
+                }
+                if d == 0 {
+                    continue;
+                }
+                let repeats = if r >= 0 {
+                    r as u64
+                } else {
+                    // r=-1：重复到下一个 S 的起点或 Period 结束
+                    let until = timeline
+                        .get(i + 1)
+                        .and_then(|next| next.0)
+                        .or(period_end)
+                        .ok_or("SegmentTimeline 无法确定 r=-1 的结束位置")?;
+                    ((until.saturating_sub(time) + d - 1) / d).saturating_sub(1)
+                };
+                for _ in 0..=repeats {
+                    push(number, time)?;
+                    number += 1;
+                    time += d;
+                }
+            }
+        } else {
+            let duration = self.duration.filter(|d| *d > 0).ok_or("SegmentTemplate 缺少 duration 或 SegmentTimeline")?;
+            let end = period_end.ok_or("清单缺少时长，无法计算分片数")?;
+            let count = (end + duration - 1) / duration;
+            for i in 0..count {
+                push(start_number + i, i * duration)?;
+            }
+        }
+        Ok(Addressing::Segments { init, segments })
+    }
+}
+
+fn segment_list(node: Node, base: &str) -> Result<Addressing, String> {
+    let init = child(node, "Initialization")
+        .map(|i| {
+            Ok::<_, String>(Piece {
+                url: match i.attribute("sourceURL") {
+                    Some(u) => resolve(base, u)?,
+                    None => base.to_string(),
+                },
+                byte_range: i.attribute("range").and_then(parse_range),
+            })
+        })
+        .transpose()?;
+    let segments = children(node, "SegmentURL")
+        .map(|s| {
+            Ok(Piece {
+                url: match s.attribute("media") {
+                    Some(u) => resolve(base, u)?,
+                    None => base.to_string(),
+                },
+                byte_range: s.attribute("mediaRange").and_then(parse_range),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(Addressing::Segments { init, segments })
+}
+
+fn track_type(set: Node, rep: Node, mime: Option<&str>, codecs: Option<&str>) -> TrackType {
+    let content = set.attribute("contentType").or_else(|| mime.and_then(|m| m.split('/').next()));
+    match content {
+        Some("video") => TrackType::Video,
+        Some("audio") => TrackType::Audio,
+        Some("text") => TrackType::Text,
+        _ if rep.attribute("height").is_some() || set.attribute("maxHeight").is_some() => TrackType::Video,
+        _ if codecs.is_some_and(|c| ["mp4a", "opus", "ac-3", "ec-3", "flac"].iter().any(|p| c.starts_with(p))) => {
+            TrackType::Audio
+        }
+        _ => TrackType::Other,
+    }
+}
+
+/// 解析 MPD；多个 Period 中 ID 相同的表示合并为同一轨
+pub fn parse_mpd(text: &str, manifest_url: &str) -> Result<Manifest, String> {
+    let doc = roxmltree::Document::parse(text).map_err(|e| format!("MPD 解析失败: {}", e))?;
+    let mpd = doc.root_element();
+    if mpd.tag_name().name() != "MPD" {
+        return Err("不是有效的 MPD 清单".to_string());
+    }
+    let dynamic = mpd.attribute("type") == Some("dynamic");
+    let total = mpd.attribute("mediaPresentationDuration").and_then(parse_duration);
+    let mpd_base = base_url(mpd, manifest_url)?;
+
+    let periods: Vec<Node> = children(mpd, "Period").collect();
+    let mut tracks: Vec<Track> = Vec::new();
+    for (pi, period) in periods.iter().enumerate() {
+        let start = period.attribute("start").and_then(parse_duration).unwrap_or(0.0);
+        let period_secs = period.attribute("duration").and_then(parse_duration).or_else(|| {
+            let next_start = periods.get(pi + 1).and_then(|p| p.attribute("start")).and_then(parse_duration);
+            next_start.or(total).map(|end| end - start)
+        });
+        let period_base = base_url(*period, &mpd_base)?;
+        for set in children(*period, "AdaptationSet") {
+            let set_base = base_url(set, &period_base)?;
+            let set_template = child(set, "SegmentTemplate").map(Template::from_node);
+            for rep in children(set, "Representation") {
+                let base = base_url(rep, &set_base)?;
+                let mime = rep.attribute("mimeType").or_else(|| set.attribute("mimeType"));
+                let codecs = rep.attribute("codecs").or_else(|| set.attribute("codecs"));
+                let num = |name: &str| rep.attribute(name).or_else(|| set.attribute(name)).and_then(|v| v.parse().ok());
+                let representation = DashRepresentation {
+                    id: rep.attribute("id").unwrap_or_default().to_string(),
+                    track: track_type(set, rep, mime, codecs),
+                    mime_type: mime.map(String::from),
+                    codecs: codecs.map(String::from),
+                    bandwidth: rep.attribute("bandwidth").and_then(|v| v.parse().ok()).unwrap_or(0),
+                    width: num("width"),
+                    height: num("height"),
+                    lang: set.attribute("lang").map(String::from),
+                };
+
+                let rep_template = child(rep, "SegmentTemplate").map(Template::from_node);
+                let addressing = match (set_template.clone(), rep_template) {
+                    (Some(a), Some(b)) => a.merge(b).expand(&base, &representation, period_secs)?,
+                    (Some(t), None) | (None, Some(t)) => t.expand(&base, &representation, period_secs)?,
+                    (None, None) => match child(rep, "SegmentList").or_else(|| child(set, "SegmentList")) {
+                        Some(list) => segment_list(list, &base)?,
+                        // SegmentBase 或仅 BaseURL：整个文件即一条轨
+                        None => Addressing::Single { url: base },
+                    },
+                };
+
+                match tracks.iter_mut().find(|t| pi > 0 && t.representation.id == representation.id) {
+                    Some(track) => track.periods.push(addressing),
+                    None if pi == 0 => tracks.push(Track {
+                        representation,
+                        periods: vec![addressing],
+                    }),
+                    None => {}
+                }
+            }
+        }
+    }
+    Ok(Manifest { dynamic, tracks })
+}
+
+/// 按选项挑选视频轨与音频轨
+pub fn select_tracks<'a>(tracks: &'a [Track], options: &DashOptions) -> Result<Vec<&'a Track>, String> {
+    let by_id = |id: &str| {
+        tracks
+            .iter()
+            .find(|t| t.representation.id == id)
+            .ok_or_else(|| format!("清单中不存在表示: {}", id))
+    };
+    let best = |kind: TrackType| {
+        let candidates = tracks.iter().filter(|t| t.representation.track == kind);
+        let fitting = candidates
+            .clone()
+            .filter(|t| options.max_height.map_or(true, |max| t.representation.height.map_or(true, |h| h <= max)))
+            .max_by_key(|t| t.representation.bandwidth);
+        fitting.or_else(|| candidates.min_by_key(|t| t.representation.bandwidth))
+    };
+
+    let mut selected = Vec::new();
+    match &options.video_id {
+        Some(id) => selected.push(by_id(id)?),
+        None => selected.extend(best(TrackType::Video)),
+    }
+    if !options.no_audio {
+        match &options.audio_id {
+            Some(id) => selected.push(by_id(id)?),
+            None => selected.extend(best(TrackType::Audio)),
+        }
+    }
+    if selected.is_empty() {
+        // 无法识别类型时退回码率最高的表示
+        selected.extend(tracks.iter().max_by_key(|t| t.representation.bandwidth));
+    }
+    if selected.is_empty() {
+        return Err("清单中没有可下载的表示".to_string());
+    }
+    Ok(selected)
+}
+
+/// 轨道输出路径：第一条轨写入任务保存路径，其余为 "<名称>.<类型>.<扩展名>"
+pub fn track_path(save_path: &str, track: &DashRepresentation, primary: bool) -> String {
+    if primary {
+        return save_path.to_string();
+    }
+    let stem = save_path.rsplit_once('.').map_or(save_path, |(s, _)| s);
+    let (label, ext) = match (track.track, track.mime_type.as_deref()) {
+        (TrackType::Audio, Some(m)) if m.contains("webm") => ("audio", "webm"),
+        (TrackType::Audio, _) => ("audio", "m4a"),
+        (TrackType::Text, Some(m)) if m.contains("vtt") => ("text", "vtt"),
+        (_, Some(m)) if m.contains("webm") => ("video", "webm"),
+        _ => ("video", "mp4"),
+    };
+    format!("{}.{}.{}", stem, label, ext)
+}
+
+/// 把轨道的所有 Period 展开为 (初始化分片, 媒体分片)；单文件按大小切分为区间
+async fn track_pieces(handler: &dyn ProtocolHandler, track: &Track) -> Result<(Option<Piece>, Vec<Piece>), MediaError> {
+    let mut init = None;
+    let mut pieces = Vec::new();
+    for addressing in &track.periods {
+        match addressing {
+            Addressing::Segments { init: i, segments } => {
+                if init.is_none() {
+                    init = i.clone();
+                }
+                pieces.extend(segments.iter().cloned());
+            }
+            Addressing::Single { url } => {
+                let probe = handler.probe(url).await?;
+                match probe.total_bytes.filter(|_| probe.supports_range) {
+                    Some(total) if total > 0 => {
+                        let mut start = 0;
+                        while start < total {
+                            let end = (start + SINGLE_FILE_CHUNK).min(total) - 1;
+                            pieces.push(Piece {
+                                url: url.clone(),
+                                byte_range: Some((start, end)),
+                            });
+                            start = end + 1;
+                        }
+                    }
+                    _ => pieces.push(Piece {
+                        url: url.clone(),
+                        byte_range: None,
+                    }),
+                }
+            }
+        }
+    }
+    Ok((init, pieces))
+}
+
+async fn fetch_manifest(handler: &dyn ProtocolHandler, url: &str) -> Result<Manifest, MediaError> {
+    let body = handler.fetch(url).await?;
+    Ok(parse_mpd(&String::from_utf8_lossy(&body), url)?)
+}
+
+/// 列出清单中的全部表示
+pub async fn list_representations(handler: &dyn ProtocolHandler, url: &str) -> Result<Vec<DashRepresentation>, String> {
+    Ok(fetch_manifest(handler, url)
+        .await
+        .map_err(|e| e.to_string())?
+        .tracks
+        .into_iter()
+        .map(|t| t.representation)
+        .collect())
+}
+
+async fn download_piece(
+    task: &Task,
+    handler: &dyn ProtocolHandler,
+    path: &Path,
+    piece: &Piece,
+    on_progress: &(dyn Fn() + Send + Sync),
+) -> Result<(), MediaError> {
+    if !needs_piece(task, path).await {
+        return Ok(());
+    }
+    let Some(range) = piece.byte_range else {
+        // 不支持 Range 的单文件可能很大，直接写入分片文件
+        return stream_piece(task, handler, &piece.url, path, on_progress).await;
+    };
+    let data = fetch_with_retry(handler, &piece.url, Some(range)).await?;
+    Ok(save_piece(task, path, &data, on_progress).await?)
+}
+
+/// 下载 DASH 任务：各轨分片并发下载到各自临时目录，全部完成后分别合并
+pub async fn download(
+    task: &Task,
+    handler: &dyn ProtocolHandler,
+    options: &DashOptions,
+    connections: usize,
+    on_progress: &(dyn Fn() + Send + Sync),
+) -> Result<MediaOutcome, MediaError> {
+    let manifest = fetch_manifest(handler, &task.url).await?;
+    if manifest.dynamic {
+        return Err("暂不支持直播（dynamic）DASH 清单".into());
+    }
+    let tracks = select_tracks(&manifest.tracks, options)?;
+    let outputs: Vec<String> = tracks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| track_path(&task.save_path, &t.representation, i == 0))
+        .collect();
+
+    let mut restored = 0;
+    for output in &outputs {
+        restored += existing_bytes(&piece_dir(output)).await;
+    }
+    task.downloaded.store(restored, Ordering::Relaxed);
+
+    for (track, output) in tracks.iter().zip(&outputs) {
+        let dir = piece_dir(output);
+        tokio::fs::create_dir_all(&dir).await.map_err(|e| e.to_string())?;
+        let (init, pieces) = track_pieces(handler, track).await?;
+        if let Some(init) = &init {
+            fetch_init(task, handler, &dir, &init.url, init.byte_range, on_progress).await?;
+        }
+        let dir_ref = &dir;
+        stream::iter(pieces.iter().enumerate().map(Ok))
+            .try_for_each_concurrent(connections.max(1), |(i, piece)| async move {
+                download_piece(task, handler, &piece_file(dir_ref, i as u64), piece, on_progress).await
+            })
+            .await?;
+        if *task.status.lock().await != TaskStatus::Downloading {
+            return Ok(MediaOutcome::Stopped);
+        }
+    }
+
+    for output in &outputs {
+        concat_pieces(&piece_dir(output), output).await?;
+    }
+    Ok(MediaOutcome::Finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = "https://cdn.example.com/m/manifest.mpd";
+
+    fn urls(addressing: &Addressing) -> (Option<&Piece>, Vec<&str>) {
+        match addressing {
+            Addressing::Segments { init, segments } => (init.as_ref(), segments.iter().map(|s| s.url.as_str()).collect()),
+            Addressing::Single { url } => panic!("unexpected single file {}", url),
+        }
+    }
+
+    #[test]
+    fn parses_durations_and_templates() {
+        assert_eq!(parse_duration("PT1H2M3.5S"), Some(3723.5));
+        assert_eq!(parse_duration("P1DT2H"), Some(93_600.0));
+        assert_eq!(parse_duration("PT0S"), Some(0.0));
+        assert_eq!(parse_duration("1H"), None);
+        assert_eq!(parse_range("100-199"), Some((100, 199)));
+        assert_eq!(
+            expand_template("$RepresentationID$/seg-$Number%05d$-$Time$-$Bandwidth$$$.m4s", "v1", 42, 9000, 800),
+            "v1/seg-00042-9000-800$.m4s"
+        );
+        assert_eq!(expand_template("a-$Unknown$.m4s", "v1", 1, 0, 0), "a-$Unknown$.m4s");
+    }
+
+    #[test]
+    fn segment_template_with_fixed_duration() {
+        let mpd = r#"<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT9S">
+  <BaseURL>media/</BaseURL>
+  <Period>
+    <AdaptationSet mimeType="video/mp4" codecs="avc1.640028">
+      <SegmentTemplate timescale="1000" duration="4000" startNumber="5" initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/$Number%03d$.m4s"/>
+      <Representation id="v720" bandwidth="2000000" width="1280" height="720"/>
+      <Representation id="v360" bandwidth="800000" width="640" height="360">
+        <SegmentTemplate startNumber="1"/>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+        let manifest = parse_mpd(mpd, MANIFEST).unwrap();
+        assert!(!manifest.dynamic);
+        assert_eq!(manifest.tracks.len(), 2);
+        let rep = &manifest.tracks[0].representation;
+        assert_eq!((rep.id.as_str(), rep.track, rep.bandwidth, rep.height), ("v720", TrackType::Video, 2_000_000, Some(720)));
+        assert_eq!(rep.mime_type.as_deref(), Some("video/mp4"));
+        // 9 秒按 4 秒一片为 3 片
+        let (init, segments) = urls(&manifest.tracks[0].periods[0]);
+        assert_eq!(init.unwrap().url, "https://cdn.example.com/m/media/v720/init.mp4");
+        assert_eq!(
+            segments,
+            [
+                "https://cdn.example.com/m/media/v720/005.m4s",
+                "https://cdn.example.com/m/media/v720/006.m4s",
+                "https://cdn.example.com/m/media/v720/007.m4s",
+            ]
+        );
+        // 表示级的 startNumber 覆盖自适应集级
+        let (_, segments) = urls(&manifest.tracks[1].periods[0]);
+        assert_eq!(segments[0], "https://cdn.example.com/m/media/v360/001.m4s");
+    }
+
+    #[test]
+    fn segment_template_with_timeline() {
+        let mpd = r#"<MPD type="static">
+  <Period duration="PT10S">
+    <AdaptationSet contentType="audio" lang="en">
+      <Representation id="a" bandwidth="128000" codecs="mp4a.40.2">
+        <SegmentTemplate timescale="10" media="a-$Time$.m4s">
+          <SegmentTimeline>
+            <S t="0" d="20" r="1"/>
+            <S d="10"/>
+            <S t="100" d="30" r="-1"/>
+          </SegmentTimeline>
+        </SegmentTemplate>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+        let manifest = parse_mpd(mpd, MANIFEST).unwrap();
+        let track = &manifest.tracks[0];
+        assert_eq!(track.representation.track, TrackType::Audio);
+        assert_eq!(track.representation.lang.as_deref(), Some("en"));
+        let (init, segments) = urls(&track.periods[0]);
+        assert!(init.is_none());
+        // r=-1 重复到 Period 结束（时间 100），从 100 起至少有一片
+        let names: Vec<&str> = segments.iter().map(|u| u.rsplit('/').next().unwrap()).collect();
+        assert_eq!(names, ["a-0.m4s", "a-20.m4s", "a-40.m4s", "a-100.m4s"]);
+
+        let open_ended = mpd.replace(" duration=\"PT10S\"", "");
+        assert!(parse_mpd(&open_ended, MANIFEST).is_err());
+    }
+
+    #[test]
+    fn segment_list_and_segment_base() {
+        let mpd = r#"<MPD type="static" mediaPresentationDuration="PT4S">
+  <Period>
+    <AdaptationSet mimeType="video/mp4">
+      <Representation id="list" bandwidth="500000" height="480">
+        <BaseURL>https://other.example.com/list/full.mp4</BaseURL>
+        <SegmentList>
+          <Initialization range="0-799"/>
+          <SegmentURL mediaRange="800-1799"/>
+          <SegmentURL media="part2.mp4" mediaRange="0-999"/>
+          <SegmentURL media="part3.mp4"/>
+        </SegmentList>
+      </Representation>
+      <Representation id="base" bandwidth="600000" height="480">
+        <BaseURL>single.mp4</BaseURL>
+        <SegmentBase indexRange="800-900"/>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+        let manifest = parse_mpd(mpd, MANIFEST).unwrap();
+        let Addressing::Segments { init, segments } = &manifest.tracks[0].periods[0] else {
+            panic!("expected segment list");
+        };
+        assert_eq!(
+            init,
+            &Some(Piece {
+                url: "https://other.example.com/list/full.mp4".to_string(),
+                byte_range: Some((0, 799)),
+            })
+        );
+        assert_eq!(
+            segments,
+            &[
+                Piece {
+                    url: "https://other.example.com/list/full.mp4".to_string(),
+                    byte_range: Some((800, 1799)),
+                },
+                Piece {
+                    url: "https://other.example.com/list/part2.mp4".to_string(),
+                    byte_range: Some((0, 999)),
+                },
+                Piece {
+                    url: "https://other.example.com/list/part3.mp4".to_string(),
+                    byte_range: None,
+                },
+            ]
+        );
+        assert_eq!(
+            manifest.tracks[1].periods[0],
+            Addressing::Single {
+                url: "https://cdn.example.com/m/single.mp4".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn periods_join_by_representation_id() {
+        let mpd = r#"<MPD type="dynamic" mediaPresentationDuration="PT6S">
+  <Period start="PT0S">
+    <AdaptationSet mimeType="video/mp4">
+      <SegmentTemplate duration="2" media="p1-$RepresentationID$-$Number$.m4s"/>
+      <Representation id="v" bandwidth="1"/>
+    </AdaptationSet>
+  </Period>
+  <Period start="PT4S">
+    <AdaptationSet mimeType="video/mp4">
+      <SegmentTemplate duration="2" media="p2-$RepresentationID$-$Number$.m4s"/>
+      <Representation id="v" bandwidth="1"/>
+      <Representation id="new" bandwidth="1"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+        let manifest = parse_mpd(mpd, MANIFEST).unwrap();
+        assert!(manifest.dynamic);
+        // 只在后一个 Period 出现的表示不成轨
+        assert_eq!(manifest.tracks.len(), 1);
+        let periods = &manifest.tracks[0].periods;
+        assert_eq!(urls(&periods[0]).1.len(), 2);
+        assert_eq!(urls(&periods[1]).1, ["https://cdn.example.com/m/p2-v-1.m4s"]);
+        assert!(parse_mpd("<Other/>", MANIFEST).is_err());
+        assert!(parse_mpd("<MPD", MANIFEST).is_err());
+    }
+
+    #[test]
+    fn selects_tracks_and_names_outputs() {
+        let track = |id: &str, kind: TrackType, bandwidth: u64, height: Option<u32>| Track {
+            representation: DashRepresentation {
+                id: id.to_string(),
+                track: kind,
+                mime_type: None,
+                codecs: None,
+                bandwidth,
+                width: None,
+                height,
+                lang: None,
+            },
+            periods: Vec::new(),
+        };
+        let tracks = [
+            track("v1080", TrackType::Video, 5_000_000, Some(1080)),
+            track("v480", TrackType::Video, 1_000_000, Some(480)),
+            track("a-hi", TrackType::Audio, 256_000, None),
+            track("a-lo", TrackType::Audio, 64_000, None),
+        ];
+        let ids = |options: DashOptions| -> Vec<String> {
+            select_tracks(&tracks, &options).unwrap().iter().map(|t| t.representation.id.clone()).collect()
+        };
+        assert_eq!(ids(DashOptions::default()), ["v1080", "a-hi"]);
+        assert_eq!(ids(DashOptions { max_height: Some(720), ..DashOptions::default() }), ["v480", "a-hi"]);
+        assert_eq!(ids(DashOptions { max_height: Some(240), no_audio: true, ..DashOptions::default() }), ["v480"]);
+        assert_eq!(
+            ids(DashOptions { video_id: Some("v480".into()), audio_id: Some("a-lo".into()), ..DashOptions::default() }),
+            ["v480", "a-lo"]
+        );
+        assert!(select_tracks(&tracks, &DashOptions { video_id: Some("x".into()), ..DashOptions::default() }).is_err());
+        assert!(select_tracks(&[], &DashOptions::default()).is_err());
+
+        assert_eq!(track_path("/d/movie.mp4", &tracks[0].representation, true), "/d/movie.mp4");
+        assert_eq!(track_path("/d/movie.mp4", &tracks[2].representation, false), "/d/movie.audio.m4a");
+        assert_eq!(output_filename("manifest.mpd"), "manifest.mp4");
+        assert!(is_dash("https://h/x/Manifest.MPD?t=1", None));
+        assert!(is_dash("https://h/x", Some("application/dash+xml")));
+    }
+}
@@ -0,0 +1,138 @@
+//! 引擎事件：调度器通过 EngineEvents 通知上层（桌面界面、命令行、守护进程），不依赖具体前端
+
+use crate::engine::types::{TaskId, TaskStatus};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+
+/// 事件接收方；在下载所在的异步任务中同步调用，实现应尽快返回。未实现的事件默认忽略
+pub trait EngineEvents: Send + Sync {
+    /// 下载量有更新
+    fn progress(&self, _id: &str) {}
+    /// 任务状态变化（开始、暂停、取消、完成、失败、链接过期）
+    fn status_changed(&self, _id: &str, _status: TaskStatus) {}
+    fn finished(&self, _id: &str, _filename: &str) {}
+    fn failed(&self, _id: &str, _filename: &str, _error: &str) {}
+    /// 签名链接过期，等待新地址
+    fn needs_refresh(&self, _id: &str, _filename: &str, _url: &str) {}
+}
+
+/// 忽略全部事件
+pub struct NoEvents;
+
+impl EngineEvents for NoEvents {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EngineEvent {
+    Progress { id: TaskId },
+    StatusChanged { id: TaskId, status: TaskStatus },
+    Finished { id: TaskId, filename: String },
+    Failed { id: TaskId, filename: String, error: String },
+    NeedsRefresh { id: TaskId, filename: String, url: String },
+}
+
+/// 把事件转发到通道，供无界面的使用方在自己的循环里消费
+pub struct ChannelEvents {
+    tx: mpsc::UnboundedSender<EngineEvent>,
+}
+
+impl ChannelEvents {
+    pub fn channel() -> (Arc<Self>, mpsc::UnboundedReceiver<EngineEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Arc::new(Self { tx }), rx)
+    }
+
+    fn send(&self, event: EngineEvent) {
+        let _ = self.tx.send(event);
+    }
+}
+
+/// 把事件广播给任意数量的订阅者（如控制接口的事件流）；没有订阅者时直接丢弃
+pub struct BroadcastEvents {
+    tx: broadcast::Sender<EngineEvent>,
+}
+
+impl BroadcastEvents {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self { tx }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<EngineEvent> {
+        self.tx.subscribe()
+    }
+
+    fn send(&self, event: EngineEvent) {
+        let _ = self.tx.send(event);
+    }
+}
+
+/// 依次通知多个接收方
+pub struct FanoutEvents(pub Vec<Arc<dyn EngineEvents>>);
+
+impl EngineEvents for FanoutEvents {
+    fn progress(&self, id: &str) {
+        self.0.iter().for_each(|e| e.progress(id));
+    }
+
+    fn status_changed(&self, id: &str, status: TaskStatus) {
+        self.0.iter().for_each(|e| e.status_changed(id, status));
+    }
+
+    fn finished(&self, id: &str, filename: &str) {
+        self.0.iter().for_each(|e| e.finished(id, filename));
+    }
+
+    fn failed(&self, id: &str, filename: &str, error: &str) {
+        self.0.iter().for_each(|e| e.failed(id, filename, error));
+    }
+
+    fn needs_refresh(&self, id: &str, filename: &str, url: &str) {
+        self.0.iter().for_each(|e| e.needs_refresh(id, filename, url));
+    }
+}
+
+/// 为只需把回调转成 EngineEvent 发出去的类型实现 EngineEvents（类型需提供 `fn send(&self, EngineEvent)`）
+macro_rules! forward_events {
+    ($ty:ty) => {
+        impl EngineEvents for $ty {
+            fn progress(&self, id: &str) {
+                self.send(EngineEvent::Progress { id: id.to_string() });
+            }
+
+            fn status_changed(&self, id: &str, status: TaskStatus) {
+                self.send(EngineEvent::StatusChanged {
+                    id: id.to_string(),
+                    status,
+                });
+            }
+
+            fn finished(&self, id: &str, filename: &str) {
+                self.send(EngineEvent::Finished {
+                    id: id.to_string(),
+                    filename: filename.to_string(),
+                });
+            }
+
+            fn failed(&self, id: &str, filename: &str, error: &str) {
+                self.send(EngineEvent::Failed {
+                    id: id.to_string(),
+                    filename: filename.to_string(),
+                    error: error.to_string(),
+                });
+            }
+
+            fn needs_refresh(&self, id: &str, filename: &str, url: &str) {
+                self.send(EngineEvent::NeedsRefresh {
+                    id: id.to_string(),
+                    filename: filename.to_string(),
+                    url: url.to_string(),
+                });
+            }
+        }
+    };
+}
+
+forward_events!(ChannelEvents);
+forward_events!(BroadcastEvents);
@@ -0,0 +1,562 @@
+//! HLS（m3u8）下载：解析主/媒体播放列表、选择码率、并发下载分片、AES-128 解密并合并为单个 .ts
+
+use crate::engine::media::{
+    concat_pieces, existing_bytes, fetch_init, fetch_with_retry, needs_piece, piece_dir, piece_file, resolve,
+    save_piece, MediaError, MediaOutcome,
+};
+use crate::engine::task::Task;
+use crate::engine::types::TaskStatus;
+use crate::network::ProtocolHandler;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use futures_util::stream::{self, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+/// HLS 任务选项：按带宽/分辨率上限选择变体，不设上限时取最高码率
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HlsOptions {
+    /// 最大带宽（bit/s）
+    pub max_bandwidth: Option<u64>,
+    /// 最大分辨率高度（如 720）
+    pub max_height: Option<u32>,
+}
+
+/// 主播放列表中的一个码率变体
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HlsVariant {
+    pub url: String,
+    pub bandwidth: u64,
+    /// (宽, 高)
+    pub resolution: Option<(u32, u32)>,
+    pub codecs: Option<String>,
+}
+
+/// AES-128 密钥引用
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentKey {
+    pub uri: String,
+    pub iv: [u8; 16],
+}
+
+/// 媒体分片
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaSegment {
+    pub url: String,
+    pub sequence: u64,
+    pub duration: f64,
+    /// 子区间 (start, end) inclusive
+    pub byte_range: Option<(u64, u64)>,
+    pub key: Option<SegmentKey>,
+}
+
+/// 初始化分片（fMP4 的 EXT-X-MAP）
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitSection {
+    pub url: String,
+    pub byte_range: Option<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaPlaylist {
+    pub target_duration: f64,
+    pub media_sequence: u64,
+    pub segments: Vec<MediaSegment>,
+    pub init: Option<InitSection>,
+    /// 含 EXT-X-ENDLIST（或 VOD 类型）：列表不会再增长
+    pub ended: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Playlist {
+    Master(Vec<HlsVariant>),
+    Media(MediaPlaylist),
+}
+
+/// 根据地址后缀或 Content-Type 判断是否为 HLS 播放列表
+pub fn is_hls(url: &str, content_type: Option<&str>) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    if path.to_ascii_lowercase().ends_with(".m3u8") {
+        return true;
+    }
+    content_type.is_some_and(|ct| {
+        let ct = ct.to_ascii_lowercase();
+        ct.contains("vnd.apple.mpegurl") || ct.contains("x-mpegurl") || ct.contains("audio/mpegurl")
+    })
+}
+
+/// 输出文件名：把 .m3u8 替换为 .ts
+pub fn output_filename(name: &str) -> String {
+    let stem = match name.rsplit_once('.') {
+        Some((stem, ext)) if ext.eq_ignore_ascii_case("m3u8") || ext.eq_ignore_ascii_case("m3u") => stem,
+        _ => name,
+    };
+    if stem.to_ascii_lowercase().ends_with(".ts") {
+        stem.to_string()
+    } else {
+        format!("{}.ts", stem)
+    }
+}
+
+/// 解析属性列表：KEY=VALUE,KEY="VALUE,含逗号"
+fn parse_attributes(s: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let (value, next) = if let Some(quoted) = after.strip_prefix('"') {
+            match quoted.split_once('"') {
+                Some((v, n)) => (v, n),
+                None => (quoted, ""),
+            }
+        } else {
+            match after.split_once(',') {
+                Some((v, n)) => (v, n),
+                None => (after, ""),
+            }
+        };
+        attrs.insert(key.trim().to_ascii_uppercase(), value.trim().to_string());
+        rest = next.trim_start_matches(',').trim_start();
+    }
+    attrs
+}
+
+/// "n[@o]"；缺省偏移时紧接上一分片
+fn parse_byte_range(s: &str, next_offset: u64) -> Option<(u64, u64)> {
+    let (len, offset) = match s.trim().split_once('@') {
+        Some((l, o)) => (l.parse::<u64>().ok()?, o.parse::<u64>().ok()?),
+        None => (s.trim().parse::<u64>().ok()?, next_offset),
+    };
+    if len == 0 {
+        return None;
+    }
+    Some((offset, offset + len - 1))
+}
+
+/// "0x..." 十六进制 IV
+fn parse_iv(s: &str) -> Option<[u8; 16]> {
+    let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+    u128::from_str_radix(hex, 16).ok().map(|v| v.to_be_bytes())
+}
+
+/// 解析 m3u8 文本；相对地址按 base_url 解析
+pub fn parse_playlist(text: &str, base_url: &str) -> Result<Playlist, String> {
+    let mut lines = text.trim_start_matches('\u{feff}').lines().map(str::trim);
+    if lines.next() != Some("#EXTM3U") {
+        return Err("不是有效的 m3u8 播放列表".to_string());
+    }
+
+    let mut variants = Vec::new();
+    let mut pending_variant: Option<HashMap<String, String>> = None;
+
+    let mut target_duration = 0.0;
+    let mut media_sequence = 0u64;
+    let mut segments = Vec::new();
+    let mut init = None;
+    let mut ended = false;
+    let mut duration = 0.0;
+    let mut byte_range: Option<(u64, u64)> = None;
+    let mut range_cursor: HashMap<String, u64> = HashMap::new();
+    let mut pending_range: Option<String> = None;
+    // (uri, 显式 IV)
+    let mut key: Option<(String, Option<[u8; 16]>)> = None;
+
+    for line in lines {
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(tag) = line.strip_prefix('#') {
+            let (name, value) = tag.split_once(':').unwrap_or((tag, ""));
+            match name {
+                "EXT-X-STREAM-INF" => pending_variant = Some(parse_attributes(value)),
+                "EXT-X-TARGETDURATION" => target_duration = value.trim().parse().unwrap_or(0.0),
+                "EXT-X-MEDIA-SEQUENCE" => media_sequence = value.trim().parse().unwrap_or(0),
+                "EXTINF" => {
+                    duration = value.split(',').next().unwrap_or("").trim().parse().unwrap_or(0.0);
+                }
+                "EXT-X-BYTERANGE" => pending_range = Some(value.to_string()),
+                "EXT-X-KEY" => {
+                    let attrs = parse_attributes(value);
+                    match attrs.get("METHOD").map(String::as_str) {
+                        Some("NONE") => key = None,
+                        Some("AES-128") => {
+                            let uri = attrs.get("URI").ok_or("EXT-X-KEY 缺少 URI")?;
+                            let iv = attrs.get("IV").map(|v| parse_iv(v).ok_or("EXT-X-KEY 的 IV 无效")).transpose()?;
+                            key = Some((resolve(base_url, uri)?, iv));
+                        }
+                        Some(other) => return Err(format!("不支持的加密方式: {}", other)),
+                        None => return Err("EXT-X-KEY 缺少 METHOD".to_string()),
+                    }
+                }
+                "EXT-X-MAP" => {
+                    let attrs = parse_attributes(value);
+                    if init.is_none() {
+                        if let Some(uri) = attrs.get("URI") {
+                            init = Some(InitSection {
+                                url: resolve(base_url, uri)?,
+                                byte_range: attrs.get("BYTERANGE").and_then(|r| parse_byte_range(r, 0)),
+                            });
+                        }
+                    }
+                }
+                "EXT-X-ENDLIST" => ended = true,
+                "EXT-X-PLAYLIST-TYPE" if value.trim() == "VOD" => ended = true,
+                _ => {}
+            }
+            continue;
+        }
+
+        let url = resolve(base_url, line)?;
+        if let Some(attrs) = pending_variant.take() {
+            variants.push(HlsVariant {
+                url,
+                bandwidth: attrs.get("BANDWIDTH").and_then(|b| b.parse().ok()).unwrap_or(0),
+                resolution: attrs.get("RESOLUTION").and_then(|r| {
+                    let (w, h) = r.split_once(['x', 'X'])?;
+                    Some((w.parse().ok()?, h.parse().ok()?))
+                }),
+                codecs: attrs.get("CODECS").cloned(),
+            });
+            continue;
+        }
+
+        let sequence = media_sequence + segments.len() as u64;
+        if let Some(r) = pending_range.take() {
+            let cursor = range_cursor.get(&url).copied().unwrap_or(0);
+            byte_range = parse_byte_range(&r, cursor);
+            if let Some((_, end)) = byte_range {
+                range_cursor.insert(url.clone(), end + 1);
+            }
+        }
+        segments.push(MediaSegment {
+            url,
+            sequence,
+            duration,
+            byte_range: byte_range.take(),
+            // 未给出 IV 时以媒体序号作为 IV
+            key: key.as_ref().map(|(uri, iv)| SegmentKey {
+                uri: uri.clone(),
+                iv: iv.unwrap_or_else(|| (sequence as u128).to_be_bytes()),
+            }),
+        });
+        duration = 0.0;
+    }
+
+    if !variants.is_empty() {
+        return Ok(Playlist::Master(variants));
+    }
+    Ok(Playlist::Media(MediaPlaylist {
+        target_duration,
+        media_sequence,
+        segments,
+        init,
+        ended,
+    }))
+}
+
+/// 选择满足上限的最高带宽变体；都不满足时退回最低带宽
+pub fn select_variant<'a>(variants: &'a [HlsVariant], options: &HlsOptions) -> Option<&'a HlsVariant> {
+    let fits = |v: &&HlsVariant| {
+        options.max_bandwidth.map_or(true, |max| v.bandwidth <= max)
+            && options
+                .max_height
+                .map_or(true, |max| v.resolution.map_or(true, |(_, h)| h <= max))
+    };
+    variants
+        .iter()
+        .filter(fits)
+        .max_by_key(|v| v.bandwidth)
+        .or_else(|| variants.iter().min_by_key(|v| v.bandwidth))
+}
+
+/// AES-128-CBC 解密（PKCS7 填充）
+pub fn decrypt_segment(data: &[u8], key: &[u8; 16], iv: &[u8; 16]) -> Result<Vec<u8>, String> {
+    Aes128CbcDec::new(key.into(), iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(data)
+        .map_err(|_| "分片解密失败：密钥或 IV 不正确".to_string())
+}
+
+async fn fetch_playlist(handler: &dyn ProtocolHandler, url: &str) -> Result<Playlist, MediaError> {
+    let body = handler.fetch(url).await?;
+    Ok(parse_playlist(&String::from_utf8_lossy(&body), url)?)
+}
+
+/// 列出主播放列表中的变体；媒体播放列表返回空
+pub async fn list_variants(handler: &dyn ProtocolHandler, url: &str) -> Result<Vec<HlsVariant>, String> {
+    match fetch_playlist(handler, url).await.map_err(|e| e.to_string())? {
+        Playlist::Master(variants) => Ok(variants),
+        Playlist::Media(_) => Ok(Vec::new()),
+    }
+}
+
+/// 解析到媒体播放列表：主列表先按选项选择变体
+async fn load_media_playlist(
+    handler: &dyn ProtocolHandler,
+    url: &str,
+    options: &HlsOptions,
+) -> Result<(String, MediaPlaylist), MediaError> {
+    match fetch_playlist(handler, url).await? {
+        Playlist::Media(media) => Ok((url.to_string(), media)),
+        Playlist::Master(variants) => {
+            let variant = select_variant(&variants, options).ok_or("主播放列表中没有可用变体")?;
+            match fetch_playlist(handler, &variant.url).await? {
+                Playlist::Media(media) => Ok((variant.url.clone(), media)),
+                Playlist::Master(_) => Err("变体地址仍是主播放列表".into()),
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct KeyCache {
+    keys: Mutex<HashMap<String, [u8; 16]>>,
+}
+
+impl KeyCache {
+    async fn get(&self, handler: &dyn ProtocolHandler, uri: &str) -> Result<[u8; 16], MediaError> {
+        if let Some(k) = self.keys.lock().await.get(uri) {
+            return Ok(*k);
+        }
+        let data = fetch_with_retry(handler, uri, None).await?;
+        let key: [u8; 16] = data
+            .as_ref()
+            .try_into()
+            .map_err(|_| format!("密钥长度应为 16 字节，实际 {}", data.len()))?;
+        self.keys.lock().await.insert(uri.to_string(), key);
+        Ok(key)
+    }
+}
+
+async fn download_segment(
+    task: &Task,
+    handler: &dyn ProtocolHandler,
+    segment: &MediaSegment,
+    dir: &Path,
+    keys: &KeyCache,
+    on_progress: &(dyn Fn() + Send + Sync),
+) -> Result<(), MediaError> {
+    let path = piece_file(dir, segment.sequence);
+    if !needs_piece(task, &path).await {
+        return Ok(());
+    }
+    let data = fetch_with_retry(handler, &segment.url, segment.byte_range).await?;
+    match &segment.key {
+        Some(k) => {
+            let plain = decrypt_segment(&data, &keys.get(handler, &k.uri).await?, &k.iv)?;
+            Ok(save_piece(task, &path, &plain, on_progress).await?)
+        }
+        None => Ok(save_piece(task, &path, &data, on_progress).await?),
+    }
+}
+
+/// 等待下一次刷新直播列表；期间被暂停或请求停止则提前返回
+async fn wait_for_refresh(task: &Task, target_duration: f64) {
+    let wait = Duration::from_secs_f64((target_duration / 2.0).clamp(1.0, 10.0));
+    let deadline = tokio::time::Instant::now() + wait;
+    while tokio::time::Instant::now() < deadline {
+        if task.stop_requested.load(Ordering::Relaxed) || *task.status.lock().await != TaskStatus::Downloading {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+/// 下载 HLS 任务：点播列表下载全部分片；直播列表持续刷新直到结束或收到停止信号，然后合并输出
+pub async fn download(
+    task: &Task,
+    handler: &dyn ProtocolHandler,
+    options: &HlsOptions,
+    connections: usize,
+    on_progress: &(dyn Fn() + Send + Sync),
+) -> Result<MediaOutcome, MediaError> {
+    let (media_url, mut playlist) = load_media_playlist(handler, &task.url, options).await?;
+    let dir = piece_dir(&task.save_path);
+    tokio::fs::create_dir_all(&dir).await.map_err(|e| e.to_string())?;
+    task.downloaded.store(existing_bytes(&dir).await, Ordering::Relaxed);
+
+    let keys = KeyCache::default();
+    let mut last_sequence: Option<u64> = None;
+    loop {
+        if let Some(init) = &playlist.init {
+            fetch_init(task, handler, &dir, &init.url, init.byte_range, on_progress).await?;
+        }
+
+        let fresh: Vec<&MediaSegment> = playlist
+            .segments
+            .iter()
+            .filter(|s| last_sequence.map_or(true, |last| s.sequence > last))
+            .collect();
+        stream::iter(fresh.into_iter().map(Ok))
+            .try_for_each_concurrent(connections.max(1), |segment| {
+                download_segment(task, handler, segment, &dir, &keys, on_progress)
+            })
+            .await?;
+        if *task.status.lock().await != TaskStatus::Downloading {
+            return Ok(MediaOutcome::Stopped);
+        }
+        if let Some(last) = playlist.segments.last() {
+            last_sequence = Some(last.sequence);
+        }
+
+        if playlist.ended || task.stop_requested.load(Ordering::Relaxed) {
+            break;
+        }
+        wait_for_refresh(task, playlist.target_duration).await;
+        if *task.status.lock().await != TaskStatus::Downloading {
+            return Ok(MediaOutcome::Stopped);
+        }
+        if task.stop_requested.load(Ordering::Relaxed) {
+            break;
+        }
+        playlist = match fetch_playlist(handler, &media_url).await? {
+            Playlist::Media(media) => media,
+            Playlist::Master(_) => return Err("直播列表刷新后变为主播放列表".into()),
+        };
+    }
+
+    concat_pieces(&dir, &task.save_path).await?;
+    Ok(MediaOutcome::Finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media(text: &str) -> MediaPlaylist {
+        match parse_playlist(text, "https://cdn.example.com/v/index.m3u8").unwrap() {
+            Playlist::Media(m) => m,
+            Playlist::Master(_) => panic!("expected media playlist"),
+        }
+    }
+
+    fn variant(bandwidth: u64, height: Option<u32>) -> HlsVariant {
+        HlsVariant {
+            url: format!("https://h/{}.m3u8", bandwidth),
+            bandwidth,
+            resolution: height.map(|h| (h * 16 / 9, h)),
+            codecs: None,
+        }
+    }
+
+    #[test]
+    fn parses_master_playlist() {
+        let text = "\u{feff}#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360,CODECS=\"avc1.4d401e,mp4a.40.2\"\nlow/index.m3u8\n\n#EXT-X-STREAM-INF:RESOLUTION=1920X1080,BANDWIDTH=5000000\nhttps://other.example.com/hi.m3u8\n";
+        let Playlist::Master(variants) = parse_playlist(text, "https://cdn.example.com/v/master.m3u8?t=1").unwrap() else {
+            panic!("expected master playlist");
+        };
+        assert_eq!(
+            variants,
+            [
+                HlsVariant {
+                    url: "https://cdn.example.com/v/low/index.m3u8".to_string(),
+                    bandwidth: 800_000,
+                    resolution: Some((640, 360)),
+                    codecs: Some("avc1.4d401e,mp4a.40.2".to_string()),
+                },
+                HlsVariant {
+                    url: "https://other.example.com/hi.m3u8".to_string(),
+                    bandwidth: 5_000_000,
+                    resolution: Some((1920, 1080)),
+                    codecs: None,
+                },
+            ]
+        );
+        assert!(parse_playlist("#EXTINF:1,\na.ts\n", "https://h/").is_err());
+    }
+
+    #[test]
+    fn parses_media_playlist_with_byte_ranges_and_map() {
+        let m = media(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:100\n#EXT-X-MAP:URI=\"init.mp4\",BYTERANGE=\"720@0\"\n#EXTINF:5.5,\nseg.mp4\n#EXT-X-BYTERANGE:1000@720\nall.mp4\n#EXTINF:6.0,title\n#EXT-X-BYTERANGE:500\nall.mp4\n#EXTINF:4\n/abs/last.ts\n#EXT-X-ENDLIST\n",
+        );
+        assert_eq!(m.target_duration, 6.0);
+        assert_eq!(m.media_sequence, 100);
+        assert!(m.ended);
+        assert_eq!(
+            m.init,
+            Some(InitSection {
+                url: "https://cdn.example.com/v/init.mp4".to_string(),
+                byte_range: Some((0, 719)),
+            })
+        );
+        let seqs: Vec<u64> = m.segments.iter().map(|s| s.sequence).collect();
+        assert_eq!(seqs, [100, 101, 102, 103]);
+        assert_eq!(m.segments[0].duration, 5.5);
+        assert_eq!(m.segments[0].byte_range, None);
+        // 未给偏移的 BYTERANGE 紧接同一文件的上一区间
+        assert_eq!(m.segments[1].byte_range, Some((720, 1719)));
+        assert_eq!(m.segments[2].byte_range, Some((1720, 2219)));
+        assert_eq!(m.segments[2].duration, 6.0);
+        assert_eq!(m.segments[3].url, "https://cdn.example.com/abs/last.ts");
+        assert!(m.segments.iter().all(|s| s.key.is_none()));
+
+        let live = media("#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXTINF:2,\na.ts\n");
+        assert!(!live.ended);
+        assert!(media("#EXTM3U\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXTINF:2,\na.ts\n").ended);
+    }
+
+    #[test]
+    fn aes_keys_use_explicit_iv_or_sequence() {
+        let m = media(
+            "#EXTM3U\n#EXT-X-MEDIA-SEQUENCE:7\n#EXT-X-KEY:METHOD=AES-128,URI=\"keys/k1.bin\"\n#EXTINF:2,\na.ts\n#EXT-X-KEY:METHOD=AES-128,URI=\"https://keys.example.com/k2\",IV=0x000102030405060708090A0B0C0D0E0F\n#EXTINF:2,\nb.ts\n#EXT-X-KEY:METHOD=NONE\n#EXTINF:2,\nc.ts\n",
+        );
+        let a = m.segments[0].key.as_ref().unwrap();
+        assert_eq!(a.uri, "https://cdn.example.com/v/keys/k1.bin");
+        assert_eq!(a.iv, 7u128.to_be_bytes());
+        let b = m.segments[1].key.as_ref().unwrap();
+        assert_eq!(b.uri, "https://keys.example.com/k2");
+        assert_eq!(b.iv, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+        assert_eq!(m.segments[2].key, None);
+
+        let parse = |key: &str| parse_playlist(&format!("#EXTM3U\n#EXT-X-KEY:{}\n#EXTINF:2,\na.ts\n", key), "https://h/");
+        assert!(parse("METHOD=SAMPLE-AES,URI=\"k\"").is_err());
+        assert!(parse("METHOD=AES-128").is_err());
+        assert!(parse("METHOD=AES-128,URI=\"k\",IV=xyz").is_err());
+        assert!(parse("URI=\"k\"").is_err());
+    }
+
+    #[test]
+    fn decrypts_aes_128_cbc() {
+        use aes::cipher::BlockEncryptMut;
+        let key = [0x11u8; 16];
+        let iv = 3u128.to_be_bytes();
+        let plain = b"segment payload that spans more than one block".to_vec();
+        let encrypted = cbc::Encryptor::<aes::Aes128>::new(&key.into(), &iv.into()).encrypt_padded_vec_mut::<Pkcs7>(&plain);
+        assert_eq!(decrypt_segment(&encrypted, &key, &iv).unwrap(), plain);
+        assert!(decrypt_segment(&encrypted, &[0x22u8; 16], &iv).is_err());
+    }
+
+    #[test]
+    fn selects_variant_within_limits() {
+        let variants = [variant(800_000, Some(360)), variant(5_000_000, Some(1080)), variant(2_500_000, Some(720)), variant(1_000_000, None)];
+        let pick = |max_bandwidth, max_height| {
+            select_variant(&variants, &HlsOptions { max_bandwidth, max_height }).map(|v| v.bandwidth)
+        };
+        assert_eq!(pick(None, None), Some(5_000_000));
+        assert_eq!(pick(Some(3_000_000), None), Some(2_500_000));
+        assert_eq!(pick(None, Some(720)), Some(2_500_000));
+        // 没有分辨率的变体不受高度上限限制
+        assert_eq!(pick(Some(2_000_000), Some(300)), Some(1_000_000));
+        // 都不满足时退回最低带宽
+        assert_eq!(pick(Some(100), None), Some(800_000));
+        assert_eq!(select_variant(&[], &HlsOptions::default()), None);
+    }
+
+    #[test]
+    fn detects_hls_and_names_output() {
+        assert!(is_hls("https://h/a/index.M3U8?token=1", None));
+        assert!(is_hls("https://h/play", Some("application/vnd.apple.mpegurl; charset=utf-8")));
+        assert!(!is_hls("https://h/a.m3u8.mp4", Some("video/mp4")));
+        assert_eq!(output_filename("index.m3u8"), "index.ts");
+        assert_eq!(output_filename("movie.ts.m3u8"), "movie.ts");
+        assert_eq!(output_filename("video"), "video.ts");
+    }
+}
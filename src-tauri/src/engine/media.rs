@@ -0,0 +1,240 @@
+//! 流媒体分片下载的公共部分：分片临时目录、重试、落盘与合并（HLS / DASH 共用）
+
+use crate::engine::task::Task;
+use crate::engine::types::TaskStatus;
+use crate::network::{NetworkError, ProtocolHandler};
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// 单个分片的最大尝试次数
+const PIECE_ATTEMPTS: u32 = 3;
+
+/// 初始化分片的文件名，合并时放在最前
+pub(crate) const INIT_FILE: &str = "init.bin";
+
+/// 下载结束方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaOutcome {
+    /// 已合并为输出文件
+    Finished,
+    /// 暂停/取消，已下载分片保留在临时目录
+    Stopped,
+}
+
+/// 流媒体下载错误：签名链接过期单独区分，由调度器转为等待新地址
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MediaError {
+    LinkExpired(String),
+    Other(String),
+}
+
+impl MediaError {
+    pub fn is_link_expired(&self) -> bool {
+        matches!(self, Self::LinkExpired(_))
+    }
+}
+
+impl fmt::Display for MediaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LinkExpired(msg) | Self::Other(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl From<NetworkError> for MediaError {
+    fn from(e: NetworkError) -> Self {
+        if e.is_link_expired() {
+            Self::LinkExpired(e.to_string())
+        } else {
+            Self::Other(e.to_string())
+        }
+    }
+}
+
+impl From<String> for MediaError {
+    fn from(msg: String) -> Self {
+        Self::Other(msg)
+    }
+}
+
+impl From<&str> for MediaError {
+    fn from(msg: &str) -> Self {
+        Self::Other(msg.to_string())
+    }
+}
+
+/// 分片临时目录：输出路径加 .parts 后缀
+pub fn piece_dir(output: &str) -> PathBuf {
+    PathBuf::from(format!("{}.parts", output))
+}
+
+pub(crate) fn piece_file(dir: &Path, index: u64) -> PathBuf {
+    dir.join(format!("{:012}.seg", index))
+}
+
+/// 相对地址按清单地址解析
+pub(crate) fn resolve(base: &str, uri: &str) -> Result<String, String> {
+    let base = reqwest::Url::parse(base).map_err(|e| format!("无效地址 {}: {}", base, e))?;
+    base.join(uri)
+        .map(|u| u.to_string())
+        .map_err(|e| format!("无效地址 {}: {}", uri, e))
+}
+
+/// 请求整个资源或其中一段，失败时退避重试；链接过期时不再重试
+pub(crate) async fn fetch_with_retry(
+    handler: &dyn ProtocolHandler,
+    url: &str,
+    byte_range: Option<(u64, u64)>,
+) -> Result<Bytes, MediaError> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let result = match byte_range {
+            Some((start, end)) => handler.fetch_range(url, start, end).await,
+            None => handler.fetch(url).await,
+        };
+        match result {
+            Ok(data) => return Ok(data),
+            Err(e) if attempt >= PIECE_ATTEMPTS || e.is_link_expired() => return Err(e.into()),
+            Err(_) => tokio::time::sleep(Duration::from_secs(attempt as u64)).await,
+        }
+    }
+}
+
+/// 分片文件不存在且任务仍在下载时才需要请求
+pub(crate) async fn needs_piece(task: &Task, path: &Path) -> bool {
+    !path.exists() && *task.status.lock().await == TaskStatus::Downloading
+}
+
+/// 先写临时文件再改名，目录中存在的分片即为已完成；随后累计进度
+pub(crate) async fn save_piece(
+    task: &Task,
+    path: &Path,
+    data: &[u8],
+    on_progress: &(dyn Fn() + Send + Sync),
+) -> Result<(), String> {
+    let tmp = path.with_extension("part");
+    tokio::fs::write(&tmp, data).await.map_err(|e| e.to_string())?;
+    tokio::fs::rename(&tmp, path).await.map_err(|e| e.to_string())?;
+    task.add_downloaded(data.len() as u64);
+    task.set_speed_sample(task.downloaded_bytes());
+    on_progress();
+    Ok(())
+}
+
+/// 整个资源边下边写入分片文件并累计进度，不在内存中缓存整个文件（不支持 Range 的单文件轨）；
+/// 失败时从头重试，暂停或取消时丢弃未完成的临时文件
+pub(crate) async fn stream_piece(
+    task: &Task,
+    handler: &dyn ProtocolHandler,
+    url: &str,
+    path: &Path,
+    on_progress: &(dyn Fn() + Send + Sync),
+) -> Result<(), MediaError> {
+    let tmp = path.with_extension("part");
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let mut written = 0;
+        let result = stream_to_file(task, handler, url, &tmp, &mut written, on_progress).await;
+        if !matches!(result, Ok(true)) {
+            task.downloaded.fetch_sub(written, Ordering::Relaxed);
+            let _ = tokio::fs::remove_file(&tmp).await;
+        }
+        match result {
+            Ok(true) => return Ok(tokio::fs::rename(&tmp, path).await.map_err(|e| e.to_string())?),
+            Ok(false) => return Ok(()),
+            Err(e) if attempt >= PIECE_ATTEMPTS || e.is_link_expired() => return Err(e),
+            Err(_) => tokio::time::sleep(Duration::from_secs(attempt as u64)).await,
+        }
+    }
+}
+
+/// 写入 tmp，written 记录本次已计入进度的字节数；任务不再是下载中时停止并返回 false
+async fn stream_to_file(
+    task: &Task,
+    handler: &dyn ProtocolHandler,
+    url: &str,
+    tmp: &Path,
+    written: &mut u64,
+    on_progress: &(dyn Fn() + Send + Sync),
+) -> Result<bool, MediaError> {
+    let mut stream = handler.open(url).await?;
+    let mut file = tokio::fs::File::create(tmp).await.map_err(|e| e.to_string())?;
+    while let Some(chunk) = stream.try_next().await? {
+        if *task.status.lock().await != TaskStatus::Downloading {
+            return Ok(false);
+        }
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        *written += chunk.len() as u64;
+        task.add_downloaded(chunk.len() as u64);
+        task.set_speed_sample(task.downloaded_bytes());
+        on_progress();
+    }
+    file.flush().await.map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// 下载初始化分片（已存在则跳过）
+pub(crate) async fn fetch_init(
+    task: &Task,
+    handler: &dyn ProtocolHandler,
+    dir: &Path,
+    url: &str,
+    byte_range: Option<(u64, u64)>,
+    on_progress: &(dyn Fn() + Send + Sync),
+) -> Result<(), MediaError> {
+    let path = dir.join(INIT_FILE);
+    if !needs_piece(task, &path).await {
+        return Ok(());
+    }
+    let data = fetch_with_retry(handler, url, byte_range).await?;
+    Ok(save_piece(task, &path, &data, on_progress).await?)
+}
+
+/// 已下载分片的总大小，用于续传时恢复进度
+pub(crate) async fn existing_bytes(dir: &Path) -> u64 {
+    let mut total = 0;
+    if let Ok(mut entries) = tokio::fs::read_dir(dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let done = entry.path().extension().is_some_and(|e| e == "seg" || e == "bin");
+            if let (true, Ok(meta)) = (done, entry.metadata().await) {
+                total += meta.len();
+            }
+        }
+    }
+    total
+}
+
+/// 按序号合并分片到输出文件（初始化分片在最前），成功后删除临时目录
+pub(crate) async fn concat_pieces(dir: &Path, output: &str) -> Result<(), String> {
+    let mut pieces = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await.map_err(|e| e.to_string())?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().is_some_and(|e| e == "seg") {
+            pieces.push(path);
+        }
+    }
+    pieces.sort();
+    let init = dir.join(INIT_FILE);
+    if init.exists() {
+        pieces.insert(0, init);
+    }
+
+    let mut out = tokio::fs::File::create(output).await.map_err(|e| e.to_string())?;
+    for piece in pieces {
+        let mut f = tokio::fs::File::open(&piece).await.map_err(|e| e.to_string())?;
+        tokio::io::copy(&mut f, &mut out).await.map_err(|e| e.to_string())?;
+    }
+    out.flush().await.map_err(|e| e.to_string())?;
+    out.sync_all().await.map_err(|e| e.to_string())?;
+    let _ = tokio::fs::remove_dir_all(dir).await;
+    Ok(())
+}
@@ -0,0 +1,206 @@
+//! Metalink 解析：RFC 5854（.meta4）与 Metalink 3.0（.metalink）
+
+use crate::engine::checksum::{Checksums, FileHash, HashAlgo, PieceHashes};
+use roxmltree::Node;
+
+/// 清单中的一个文件：镜像地址按优先级排列（数字越小越优先）
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetalinkFile {
+    pub name: String,
+    pub size: Option<u64>,
+    pub urls: Vec<(String, u32)>,
+    pub checksums: Checksums,
+}
+
+/// 根据文件名判断是否为 Metalink 清单
+pub fn is_metalink(name: &str) -> bool {
+    let path = name.split(['?', '#']).next().unwrap_or(name).to_ascii_lowercase();
+    path.ends_with(".meta4") || path.ends_with(".metalink")
+}
+
+fn children<'a, 'i: 'a>(node: Node<'a, 'i>, name: &'a str) -> impl Iterator<Item = Node<'a, 'i>> + 'a {
+    node.children().filter(move |n| n.tag_name().name() == name)
+}
+
+fn text(node: Node) -> Option<String> {
+    node.text().map(|t| t.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+/// 去掉路径部分，避免清单中的文件名跳出保存目录
+fn safe_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next()?.trim();
+    (!name.is_empty() && name != "." && name != "..").then(|| name.to_string())
+}
+
+fn parse_file(file: Node) -> Option<MetalinkFile> {
+    let name = safe_name(file.attribute("name")?)?;
+    let size = children(file, "size").next().and_then(text).and_then(|s| s.parse().ok());
+
+    // 3.0 的 hash/pieces 位于 verification 下，4.0 直接在 file 下
+    let hash_parent = children(file, "verification").next().unwrap_or(file);
+    let mut checksums = Checksums::default();
+    for hash in children(hash_parent, "hash") {
+        if let (Some(algo), Some(hex)) = (hash.attribute("type").and_then(HashAlgo::parse), text(hash)) {
+            checksums.file.push(FileHash {
+                algo,
+                hex: hex.to_ascii_lowercase(),
+            });
+        }
+    }
+    checksums.pieces = children(hash_parent, "pieces")
+        .filter_map(|p| {
+            Some(PieceHashes {
+                algo: p.attribute("type").and_then(HashAlgo::parse)?,
+                length: p.attribute("length")?.parse().ok().filter(|l| *l > 0)?,
+                hashes: children(p, "hash").filter_map(text).map(|h| h.to_ascii_lowercase()).collect(),
+            })
+        })
+        .filter(|p| !p.hashes.is_empty())
+        .max_by_key(|p| p.algo);
+
+    let url_parent = children(file, "resources").next().unwrap_or(file);
+    let mut urls: Vec<(String, u32)> = children(url_parent, "url")
+        .filter_map(|u| {
+            let url = text(u)?;
+            // 4.0: priority 1..999999 越小越优先；3.0: preference 0..100 越大越优先
+            let priority = match (u.attribute("priority"), u.attribute("preference")) {
+                (Some(p), _) => p.parse().unwrap_or(999_999),
+                (None, Some(p)) => 100u32.saturating_sub(p.parse().unwrap_or(0)),
+                (None, None) => 999_999,
+            };
+            Some((url, priority))
+        })
+        .collect();
+    urls.sort_by_key(|(_, p)| *p);
+    if urls.is_empty() {
+        return None;
+    }
+    Some(MetalinkFile {
+        name,
+        size,
+        urls,
+        checksums,
+    })
+}
+
+/// 解析清单，返回含至少一个下载地址的文件
+pub fn parse_metalink(text: &str) -> Result<Vec<MetalinkFile>, String> {
+    let doc = roxmltree::Document::parse(text).map_err(|e| format!("Metalink 解析失败: {}", e))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "metalink" {
+        return Err("不是有效的 Metalink 文件".to_string());
+    }
+    let files_parent = children(root, "files").next().unwrap_or(root);
+    let files: Vec<MetalinkFile> = children(files_parent, "file").filter_map(parse_file).collect();
+    if files.is_empty() {
+        return Err("Metalink 中没有可下载的文件".to_string());
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_metalink_4_with_hashes_and_pieces() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <file name="../../etc/example.iso">
+    <size>1048576</size>
+    <hash type="sha-256">ABCDEF</hash>
+    <hash type="md5">0123</hash>
+    <hash type="crc32">ignored</hash>
+    <pieces length="262144" type="sha-1">
+      <hash>AA</hash><hash>bb</hash><hash>cc</hash><hash>dd</hash>
+    </pieces>
+    <pieces length="524288" type="sha-256">
+      <hash>11</hash><hash>22</hash>
+    </pieces>
+    <pieces length="0" type="sha-512"><hash>33</hash></pieces>
+    <url priority="2">https://mirror2.example.com/example.iso</url>
+    <url>ftp://fallback.example.com/example.iso</url>
+    <url priority="1">https://mirror1.example.com/example.iso</url>
+  </file>
+  <file name="no-urls.bin"><size>1</size></file>
+</metalink>"#;
+        let files = parse_metalink(text).unwrap();
+        assert_eq!(files.len(), 1);
+        let f = &files[0];
+        assert_eq!(f.name, "example.iso");
+        assert_eq!(f.size, Some(1_048_576));
+        assert_eq!(
+            f.urls,
+            [
+                ("https://mirror1.example.com/example.iso".to_string(), 1),
+                ("https://mirror2.example.com/example.iso".to_string(), 2),
+                ("ftp://fallback.example.com/example.iso".to_string(), 999_999),
+            ]
+        );
+        assert_eq!(
+            f.checksums.file,
+            [
+                FileHash {
+                    algo: HashAlgo::Sha256,
+                    hex: "abcdef".to_string(),
+                },
+                FileHash {
+                    algo: HashAlgo::Md5,
+                    hex: "0123".to_string(),
+                },
+            ]
+        );
+        // 多组分块哈希取最强的算法；长度为 0 的忽略
+        assert_eq!(
+            f.checksums.pieces,
+            Some(PieceHashes {
+                algo: HashAlgo::Sha256,
+                length: 524_288,
+                hashes: vec!["11".to_string(), "22".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn parses_metalink_3_verification_and_preference() {
+        let text = r#"<?xml version="1.0"?>
+<metalink version="3.0" xmlns="http://www.metalinker.org/">
+  <files>
+    <file name="C:\dl\tool.zip">
+      <size>300</size>
+      <verification>
+        <hash type="sha1">FFEE</hash>
+        <pieces length="100" type="sha1"><hash piece="0">a1</hash><hash piece="1">a2</hash><hash piece="2">a3</hash></pieces>
+      </verification>
+      <resources>
+        <url type="http" preference="10">http://slow.example.com/tool.zip</url>
+        <url type="http" preference="90">http://fast.example.com/tool.zip</url>
+      </resources>
+    </file>
+    <file name="..">
+      <resources><url>http://h/x</url></resources>
+    </file>
+  </files>
+</metalink>"#;
+        let files = parse_metalink(text).unwrap();
+        assert_eq!(files.len(), 1);
+        let f = &files[0];
+        assert_eq!(f.name, "tool.zip");
+        assert_eq!(f.urls[0], ("http://fast.example.com/tool.zip".to_string(), 10));
+        assert_eq!(f.urls[1], ("http://slow.example.com/tool.zip".to_string(), 90));
+        assert_eq!(f.checksums.file[0].algo, HashAlgo::Sha1);
+        assert_eq!(f.checksums.file[0].hex, "ffee");
+        let pieces = f.checksums.pieces.as_ref().unwrap();
+        assert_eq!((pieces.algo, pieces.length, pieces.hashes.len()), (HashAlgo::Sha1, 100, 3));
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert!(parse_metalink("<metalink").is_err());
+        assert!(parse_metalink("<feed/>").is_err());
+        assert!(parse_metalink("<metalink><file name=\"a\"/></metalink>").is_err());
+        assert!(is_metalink("https://h/a.META4?x=1"));
+        assert!(is_metalink("list.metalink"));
+        assert!(!is_metalink("a.meta4.zip"));
+    }
+}
@@ -3,7 +3,14 @@
 mod types;
 mod task;
 pub mod scheduler;
+pub mod events;
 mod writer;
+pub mod hls;
+pub mod dash;
+mod media;
+pub mod checksum;
+pub mod metalink;
+mod sources;
 mod persistence;
 
 pub use persistence::{load_tasks_from_file, save_tasks_to_file, PersistedTask};
@@ -11,3 +18,8 @@ pub use types::*;
 pub use task::*;
 pub use scheduler::*;
 pub use writer::*;
+pub use hls::{HlsOptions, HlsVariant};
+pub use dash::{DashOptions, DashRepresentation};
+pub use media::{piece_dir, MediaError, MediaOutcome};
+pub use sources::{SourceInfo, SourcePool};
+pub use events::{BroadcastEvents, ChannelEvents, EngineEvent, EngineEvents, FanoutEvents, NoEvents};
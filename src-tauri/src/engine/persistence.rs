@@ -1,7 +1,9 @@
 //! 任务进度持久化：保存/加载未完成区间与元数据
 
+use crate::engine::checksum::Checksums;
+use crate::engine::sources::SourcePool;
 use crate::engine::task::Task;
-use crate::engine::types::{TaskId, TaskStatus};
+use crate::engine::types::{TaskId, TaskKind, TaskSource, TaskStatus, Validators};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::path::Path;
@@ -19,6 +21,24 @@ pub struct PersistedTask {
     pub pending_segments: Vec<(u64, u64)>,
     pub supports_range: bool,
     pub created_at: i64,
+    #[serde(default)]
+    pub kind: TaskKind,
+    /// 全部下载源（含主地址），按优先级排列；为空时只用 url
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// 新建时未采用的镜像及原因，重启后仍不使用
+    #[serde(default)]
+    pub rejected_sources: Vec<(String, String)>,
+    #[serde(default)]
+    pub checksums: Checksums,
+    #[serde(default)]
+    pub validators: Validators,
+    #[serde(default)]
+    pub deferred_probe: bool,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub source: TaskSource,
 }
 
 pub fn tasks_to_json(tasks: &[PersistedTask]) -> Result<String, serde_json::Error> {
@@ -48,6 +68,14 @@ impl Task {
         use std::sync::atomic::AtomicU64;
         use std::sync::Arc;
         use tokio::sync::Mutex;
+        let sources = if p.sources.is_empty() {
+            SourcePool::single(&p.url)
+        } else {
+            SourcePool::new(p.sources.into_iter().enumerate().map(|(i, u)| (u, i as u32)))
+        };
+        for (url, reason) in &p.rejected_sources {
+            sources.reject(url, reason);
+        }
         Self {
             id: p.id,
             url: p.url,
@@ -62,6 +90,15 @@ impl Task {
             created_at: p.created_at,
             last_downloaded: Arc::new(AtomicU64::new(0)),
             last_speed_time: Arc::new(Mutex::new(None)),
+            kind: p.kind,
+            sources: Arc::new(sources),
+            checksums: p.checksums,
+            stop_requested: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            validators: p.validators,
+            deferred_probe: p.deferred_probe,
+            headers: p.headers,
+            source: p.source,
+            running: Arc::new(Mutex::new(())),
         }
     }
 }
@@ -88,6 +125,14 @@ impl PersistedTask {
             pending_segments: pending,
             supports_range: task.supports_range,
             created_at: task.created_at,
+            kind: task.kind.clone(),
+            sources: if task.sources.len() > 1 { task.sources.urls() } else { Vec::new() },
+            rejected_sources: task.sources.rejected(),
+            checksums: task.checksums.clone(),
+            validators: task.validators.clone(),
+            deferred_probe: task.deferred_probe,
+            headers: task.headers.clone(),
+            source: task.source,
         }
     }
 }
@@ -1,20 +1,31 @@
 //! 任务调度：创建/暂停/恢复/取消，启动多连接下载
 
+use crate::engine::dash::{self, DashOptions, DashRepresentation};
+use crate::engine::hls::{self, HlsOptions, HlsVariant};
+use crate::engine::media::{MediaError, MediaOutcome};
 use crate::engine::persistence::{save_tasks_to_file, PersistedTask};
-use crate::engine::task::Task;
-use crate::engine::types::{TaskId, TaskInfo, TaskStatus};
+use crate::engine::checksum;
+use crate::engine::events::{EngineEvents, NoEvents};
+use crate::engine::metalink::MetalinkFile;
+use crate::engine::sources::SourcePool;
+use crate::engine::task::{initial_segments, Task};
+use crate::engine::types::{TaskId, TaskInfo, TaskKind, TaskSource, TaskStatus, Validators};
 use crate::engine::writer::{run_file_writer, WriterMessage};
-use crate::network::{build_client_from_options, fetch_range_with_client, probe, probe_with_options, NetworkOptions, ProbeResult};
-use reqwest::Client;
-use std::collections::HashMap;
+use crate::network::{NetworkOptions, ProbeResult, ProtocolHandler, ProtocolRegistry};
+use futures_util::StreamExt;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tauri::Emitter;
 use tokio::sync::{mpsc, Mutex};
 
 pub struct Scheduler {
     tasks: Arc<Mutex<HashMap<TaskId, Arc<Task>>>>,
     save_path: Option<PathBuf>,
+    protocols: Arc<ProtocolRegistry>,
+    /// 等待从浏览器捕获新地址的任务（按文件名与大小匹配）
+    link_capture: Arc<Mutex<HashSet<TaskId>>>,
+    events: Arc<dyn EngineEvents>,
 }
 
 impl Scheduler {
@@ -22,9 +33,28 @@ impl Scheduler {
         Self {
             tasks: Arc::new(Mutex::new(HashMap::new())),
             save_path,
+            protocols: Arc::new(ProtocolRegistry::default()),
+            link_capture: Arc::new(Mutex::new(HashSet::new())),
+            events: Arc::new(NoEvents),
         }
     }
 
+    /// 替换协议注册表（如注册内存协议以脱离网络运行）
+    pub fn with_protocols(mut self, protocols: ProtocolRegistry) -> Self {
+        self.protocols = Arc::new(protocols);
+        self
+    }
+
+    /// 设置事件接收方（界面、命令行等），默认忽略全部事件
+    pub fn with_events(mut self, events: Arc<dyn EngineEvents>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn protocols(&self) -> &ProtocolRegistry {
+        &self.protocols
+    }
+
     /// 从持久化文件加载任务（启动时调用）
     pub fn load_from(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let persisted = crate::engine::load_tasks_from_file(path)?;
@@ -35,6 +65,9 @@ impl Scheduler {
         Ok(Self {
             tasks: Arc::new(Mutex::new(tasks)),
             save_path: Some(path.to_path_buf()),
+            protocols: Arc::new(ProtocolRegistry::default()),
+            link_capture: Arc::new(Mutex::new(HashSet::new())),
+            events: Arc::new(NoEvents),
         })
     }
 
@@ -52,16 +85,47 @@ impl Scheduler {
         let _ = save_tasks_to_file(&path, &snapshots).await;
     }
 
+    /// 全部任务的完整状态，按创建时间排列（导出迁移用）
+    pub async fn snapshot_tasks(&self) -> Vec<PersistedTask> {
+        let tasks = self.tasks.lock().await;
+        let mut snapshots: Vec<PersistedTask> = Vec::new();
+        for t in tasks.values() {
+            snapshots.push(PersistedTask::from_task(t).await);
+        }
+        snapshots.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        snapshots
+    }
+
+    /// 按完整状态加入任务（迁移恢复用）；id 已存在的跳过，返回加入的任务
+    pub async fn restore_tasks(&self, persisted: Vec<PersistedTask>) -> Vec<TaskId> {
+        let mut added = Vec::new();
+        {
+            let mut tasks = self.tasks.lock().await;
+            for p in persisted {
+                if tasks.contains_key(&p.id) {
+                    continue;
+                }
+                added.push(p.id.clone());
+                tasks.insert(p.id.clone(), Arc::new(Task::from_persisted(p)));
+            }
+        }
+        if !added.is_empty() {
+            self.save_tasks().await;
+        }
+        added
+    }
+
     pub async fn probe(&self, url: &str) -> Result<ProbeResult, crate::network::NetworkError> {
-        probe(url).await
+        self.probe_with_options(url, &NetworkOptions::default()).await
     }
 
+    /// 按 URL scheme 选择协议实现进行探测
     pub async fn probe_with_options(
         &self,
         url: &str,
         options: &NetworkOptions,
     ) -> Result<ProbeResult, crate::network::NetworkError> {
-        probe_with_options(url, options).await
+        self.protocols.handler_for(url, options)?.probe(url).await
     }
 
     pub async fn create_task(
@@ -71,126 +135,316 @@ impl Scheduler {
         filename: Option<String>,
         probe_result: Option<ProbeResult>,
     ) -> Result<TaskId, String> {
-        let (supports_range, total_bytes, suggested_filename) = match probe_result {
-            Some(p) => (p.supports_range, p.total_bytes, p.suggested_filename),
-            None => {
-                let p = probe(&url).await.map_err(|e| e.to_string())?;
-                (p.supports_range, p.total_bytes, p.suggested_filename)
-            }
+        self.create_task_with_mirrors(url, Vec::new(), save_dir, filename, probe_result, &NetworkOptions::default())
+            .await
+    }
+
+    /// 新建任务并附带等价镜像地址：按 options（代理、请求头）逐个探测，无法访问、大小或校验器
+    /// （ETag/Last-Modified）与主地址不一致的镜像不采用，原因记在下载源列表中；
+    /// 下载时各段分散到可用镜像，出错的镜像自动切换
+    pub async fn create_task_with_mirrors(
+        &self,
+        url: String,
+        mirrors: Vec<String>,
+        save_dir: String,
+        filename: Option<String>,
+        probe_result: Option<ProbeResult>,
+        options: &NetworkOptions,
+    ) -> Result<TaskId, String> {
+        let p = match probe_result {
+            Some(p) => p,
+            None => self.probe_with_options(&url, options).await.map_err(|e| e.to_string())?,
         };
-        let filename = filename.or(Some(suggested_filename));
+        // 播放列表/清单按 HLS、DASH 处理，默认选最高码率
+        if hls::is_hls(&url, p.content_type.as_deref()) || hls::is_hls(&p.final_url, None) {
+            let filename = filename.or(Some(p.suggested_filename));
+            return self.create_hls_task(url, save_dir, filename, HlsOptions::default()).await;
+        }
+        if dash::is_dash(&url, p.content_type.as_deref()) || dash::is_dash(&p.final_url, None) {
+            let filename = filename.or(Some(p.suggested_filename));
+            return self.create_dash_task(url, save_dir, filename, DashOptions::default()).await;
+        }
+        let filename = filename.or(Some(p.suggested_filename.clone()));
         let input = crate::engine::types::CreateTaskInput {
             url: url.clone(),
             save_dir,
             filename,
         };
-        let task = Task::new(input, supports_range, total_bytes);
+        let mut sources = vec![(url.clone(), 0)];
+        let mut rejected: Vec<(String, String)> = Vec::new();
+        for mirror in mirrors {
+            let mirror = mirror.trim().to_string();
+            if mirror.is_empty()
+                || sources.iter().any(|(u, _)| *u == mirror)
+                || rejected.iter().any(|(u, _)| *u == mirror)
+                || !self.protocols.supports(&mirror)
+            {
+                continue;
+            }
+            let reason = match self.probe_with_options(&mirror, options).await {
+                Err(e) => Some(format!("无法访问：{}", e)),
+                Ok(mp) => match check_mirror(&p, &mp) {
+                    Err(e) => Some(format!("与主地址不一致：{}", e)),
+                    // 主地址可分段时，不支持 Range 的镜像无法承担分段
+                    Ok(()) if p.supports_range && !mp.supports_range => Some("不支持断点续传".to_string()),
+                    Ok(()) => None,
+                },
+            };
+            match reason {
+                Some(reason) => rejected.push((mirror, reason)),
+                None => {
+                    let priority = sources.len() as u32;
+                    sources.push((mirror, priority));
+                }
+            }
+        }
+        let validators = Validators {
+            etag: p.etag.clone(),
+            last_modified: p.last_modified.clone(),
+        };
+        let pool = SourcePool::new(sources);
+        for (mirror, reason) in &rejected {
+            pool.reject(mirror, reason);
+        }
+        let task = Task::new(input, p.supports_range, p.total_bytes)
+            .with_sources(pool)
+            .with_validators(validators);
+        let id = task.id.clone();
+        self.tasks.lock().await.insert(id.clone(), Arc::new(task));
+        self.save_tasks().await;
+        Ok(id)
+    }
+
+    /// 新建不探测的任务（批量添加大量地址时使用）：首次开始下载时再探测大小与是否支持分段
+    pub async fn create_deferred_task(&self, url: String, save_dir: String, filename: String) -> Result<TaskId, String> {
+        if !self.protocols.supports(&url) {
+            return Err("不支持的下载地址".to_string());
+        }
+        let input = crate::engine::types::CreateTaskInput {
+            url,
+            save_dir,
+            filename: Some(filename),
+        };
+        let task = Task::new(input, false, None).with_deferred_probe();
+        let id = task.id.clone();
+        self.tasks.lock().await.insert(id.clone(), Arc::new(task));
+        self.save_tasks().await;
+        Ok(id)
+    }
+
+    /// 新建 HLS 任务：输出为单个 .ts，大小在下载完成前未知
+    pub async fn create_hls_task(
+        &self,
+        url: String,
+        save_dir: String,
+        filename: Option<String>,
+        options: HlsOptions,
+    ) -> Result<TaskId, String> {
+        let filename = filename.unwrap_or_else(|| {
+            let path = url.split(['?', '#']).next().unwrap_or(&url).trim_end_matches('/');
+            path.rsplit('/').next().unwrap_or("video").to_string()
+        });
+        let input = crate::engine::types::CreateTaskInput {
+            url,
+            save_dir,
+            filename: Some(hls::output_filename(&filename)),
+        };
+        let task = Task::new(input, false, None).with_kind(TaskKind::Hls(options));
         let id = task.id.clone();
         self.tasks.lock().await.insert(id.clone(), Arc::new(task));
         self.save_tasks().await;
         Ok(id)
     }
 
+    /// 新建 DASH 任务：首条轨写入保存路径，其余轨输出为同名的 .audio 等文件
+    pub async fn create_dash_task(
+        &self,
+        url: String,
+        save_dir: String,
+        filename: Option<String>,
+        options: DashOptions,
+    ) -> Result<TaskId, String> {
+        let filename = filename.unwrap_or_else(|| {
+            let path = url.split(['?', '#']).next().unwrap_or(&url).trim_end_matches('/');
+            path.rsplit('/').next().unwrap_or("video").to_string()
+        });
+        let input = crate::engine::types::CreateTaskInput {
+            url,
+            save_dir,
+            filename: Some(dash::output_filename(&filename)),
+        };
+        let task = Task::new(input, false, None).with_kind(TaskKind::Dash(options));
+        let id = task.id.clone();
+        self.tasks.lock().await.insert(id.clone(), Arc::new(task));
+        self.save_tasks().await;
+        Ok(id)
+    }
+
+    /// 列出 HLS 主播放列表中的码率变体（媒体播放列表返回空）
+    pub async fn hls_variants(&self, url: &str, options: &NetworkOptions) -> Result<Vec<HlsVariant>, String> {
+        let handler = self.protocols.handler_for(url, options).map_err(|e| e.to_string())?;
+        hls::list_variants(handler.as_ref(), url).await
+    }
+
+    /// 列出 DASH 清单中的视频/音频表示
+    pub async fn dash_representations(
+        &self,
+        url: &str,
+        options: &NetworkOptions,
+    ) -> Result<Vec<DashRepresentation>, String> {
+        let handler = self.protocols.handler_for(url, options).map_err(|e| e.to_string())?;
+        dash::list_representations(handler.as_ref(), url).await
+    }
+
     pub async fn start_download(
         &self,
         task_id: &str,
-        app_handle: Option<tauri::AppHandle>,
         scheduler_for_save: Option<Arc<Scheduler>>,
         max_connections: Option<usize>,
         network_options: Option<NetworkOptions>,
     ) -> Result<(), String> {
-        let tasks = self.tasks.clone();
-        let task = tasks
-            .lock()
-            .await
-            .get(task_id)
-            .cloned()
-            .ok_or_else(|| "任务不存在".to_string())?;
-        {
+        // 持有任务表锁时改状态，与重建任务（rebuild_task）互斥
+        let task = {
+            let tasks = self.tasks.lock().await;
+            let task = tasks.get(task_id).cloned().ok_or_else(|| "任务不存在".to_string())?;
             let mut st = task.status.lock().await;
-            if *st != TaskStatus::Pending && *st != TaskStatus::Paused {
+            if !matches!(*st, TaskStatus::Pending | TaskStatus::Paused | TaskStatus::NeedsRefresh) {
                 return Err("任务状态不允许开始".to_string());
             }
             *st = TaskStatus::Downloading;
-        }
+            drop(st);
+            task
+        };
+        let events = self.events.clone();
+        events.status_changed(&task.id, TaskStatus::Downloading);
+
+        let net_opts = network_options.unwrap_or_default().with_headers(&task.headers);
+        let task = if task.deferred_probe {
+            match self.resolve_deferred(&task, &net_opts).await {
+                Ok(Some(resolved)) => resolved,
+                // 探测期间已被暂停或取消
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    mark_failed(&task, e, events.as_ref()).await;
+                    if let Some(s) = scheduler_for_save {
+                        s.save_tasks().await;
+                    }
+                    return Ok(());
+                }
+            }
+        } else {
+            task
+        };
 
         if let Some(parent) = std::path::Path::new(&task.save_path).parent() {
             let _ = tokio::fs::create_dir_all(parent).await;
         }
-        let (tx, rx) = mpsc::channel::<WriterMessage>(32);
-        let path = task.save_path.clone();
-        let total_bytes = task.total_bytes;
-        let writer_handle = tokio::spawn(async move {
-            let _ = run_file_writer(path, total_bytes, rx).await;
-        });
+        task.stop_requested.store(false, Ordering::Relaxed);
 
-        let n_workers = if task.supports_range {
-            max_connections.unwrap_or(8).max(1).min(32)
-        } else {
-            1
-        };
         let task_clone = task.clone();
-        let task_id_s = task_id.to_string();
-        let url = task.url.clone();
-        let net_opts = network_options.unwrap_or_default();
-        let client = match build_client_from_options(&net_opts) {
-            Ok(c) => std::sync::Arc::new(c),
+        let handlers = match self.source_handlers(&task, &net_opts) {
+            Ok(h) => Arc::new(h),
             Err(e) => {
-                let _ = task_clone.error_message.lock().await.insert(e.to_string());
-                let mut st = task_clone.status.lock().await;
-                *st = TaskStatus::Failed;
-                if let Some(app) = &app_handle {
-                    let _ = app.emit("download-finished", (
-                        task_id_s.clone(),
-                        "failed".to_string(),
-                        task_clone.filename.clone(),
-                    ));
-                }
-                drop(tx);
-                let _ = writer_handle.await;
+                mark_failed(&task_clone, e.to_string(), events.as_ref()).await;
                 if let Some(s) = scheduler_for_save {
                     s.save_tasks().await;
                 }
                 return Ok(());
             }
         };
+        let handler = handlers
+            .get(&task.url)
+            .or_else(|| handlers.values().next())
+            .cloned()
+            .ok_or_else(|| "没有可用的下载源".to_string())?;
+
+        let caps = handler.capabilities();
+        let connections = {
+            let n = max_connections.unwrap_or(8).clamp(1, 32);
+            caps.max_connections.map_or(n, |m| n.min(m.max(1)))
+        };
+
+        if task.kind != TaskKind::File {
+            tokio::spawn(async move {
+                let running = task_clone.running.clone().lock_owned().await;
+                let progress_events = events.clone();
+                let progress_id = task_clone.id.clone();
+                let on_progress = move || progress_events.progress(&progress_id);
+                let result = match &task_clone.kind {
+                    TaskKind::Hls(options) => {
+                        hls::download(&task_clone, handler.as_ref(), options, connections, &on_progress).await
+                    }
+                    TaskKind::Dash(options) => {
+                        dash::download(&task_clone, handler.as_ref(), options, connections, &on_progress).await
+                    }
+                    TaskKind::File => Ok(MediaOutcome::Stopped),
+                };
+                match result {
+                    Ok(MediaOutcome::Finished) => mark_completed(&task_clone, events.as_ref()).await,
+                    Ok(MediaOutcome::Stopped) => {}
+                    Err(MediaError::LinkExpired(_)) => mark_needs_refresh(&task_clone, events.as_ref()).await,
+                    Err(e) => mark_failed(&task_clone, e.to_string(), events.as_ref()).await,
+                }
+                drop(running);
+                events.progress(&task_clone.id);
+                if let Some(s) = scheduler_for_save {
+                    s.save_tasks().await;
+                }
+            });
+            return Ok(());
+        }
+
+        let ranged = task.supports_range && caps.supports_range;
+        let n_workers = if ranged { connections } else { 1 };
 
         tokio::spawn(async move {
-            let mut handles = Vec::new();
-            for _ in 0..n_workers {
-                let task_ref = task_clone.clone();
-                let url_ref = url.clone();
-                let tx_w = tx.clone();
-                let ah = app_handle.clone();
-                let tid = task_id_s.clone();
-                let client_ref = client.clone();
-                handles.push(tokio::spawn(async move {
-                    run_worker(task_ref, &url_ref, tx_w, ah, &tid, &client_ref).await;
-                }));
-            }
-            for h in handles {
-                let _ = h.await;
-            }
-            drop(tx);
-            let _ = writer_handle.await;
-
-            let mut st = task_clone.status.lock().await;
-            if *st == TaskStatus::Downloading {
-                let pending = task_clone.pending_segments.lock().await;
-                if pending.is_empty() {
-                    *st = TaskStatus::Completed;
-                    if let Some(app) = &app_handle {
-                        let _ = app.emit("download-finished", (
-                            task_id_s.clone(),
-                            "completed".to_string(),
-                            task_clone.filename.clone(),
-                        ));
+            // 上一轮（如刚暂停）的 worker 交回分段后再开始
+            let running = task_clone.running.clone().lock_owned().await;
+            if !ranged {
+                // 不支持 Range 时服务器总是从头返回，中断过的任务只能整体重下
+                if let Some(total) = task_clone.total_bytes.filter(|t| *t > 0) {
+                    let mut pending = task_clone.pending_segments.lock().await;
+                    if pending.front().map_or(true, |&(start, _)| start != 0) || pending.len() > 1 {
+                        *pending = std::collections::VecDeque::from([(0, total - 1)]);
+                        task_clone.downloaded.store(0, Ordering::Relaxed);
                     }
                 }
             }
-            if let Some(app) = app_handle {
-                let _ = app.emit("download-progress", ());
+            let mut repairs = 0;
+            loop {
+                run_workers(&task_clone, n_workers, &handlers, &events).await;
+                {
+                    let st = task_clone.status.lock().await;
+                    if *st != TaskStatus::Downloading || !task_clone.pending_segments.lock().await.is_empty() {
+                        break;
+                    }
+                }
+                match verify_download(&task_clone).await {
+                    Ok(bad) if bad.is_empty() => {
+                        mark_completed(&task_clone, events.as_ref()).await;
+                        break;
+                    }
+                    // 分块校验失败：重新下载这些区间（镜像由源池重新选择）
+                    Ok(bad) if repairs < MAX_REPAIR_ROUNDS => {
+                        repairs += 1;
+                        let mut pending = task_clone.pending_segments.lock().await;
+                        for (start, end) in bad {
+                            task_clone.downloaded.fetch_sub(end - start + 1, Ordering::Relaxed);
+                            pending.push_back((start, end));
+                        }
+                    }
+                    Ok(_) => {
+                        mark_failed(&task_clone, "分块校验多次失败".to_string(), events.as_ref()).await;
+                        break;
+                    }
+                    Err(e) => {
+                        mark_failed(&task_clone, e, events.as_ref()).await;
+                        break;
+                    }
+                }
             }
+            drop(running);
+            events.progress(&task_clone.id);
             if let Some(s) = scheduler_for_save {
                 s.save_tasks().await;
             }
@@ -198,6 +452,79 @@ impl Scheduler {
         Ok(())
     }
 
+    /// 为任务的每个下载源创建协议处理器，同一协议共用一个实例；无法处理的源被跳过
+    fn source_handlers(
+        &self,
+        task: &Task,
+        options: &NetworkOptions,
+    ) -> Result<HashMap<String, Arc<dyn ProtocolHandler>>, crate::network::NetworkError> {
+        let mut by_scheme: HashMap<String, Arc<dyn ProtocolHandler>> = HashMap::new();
+        let mut handlers = HashMap::new();
+        let mut first_err = None;
+        for url in task.sources.urls() {
+            let scheme = url.split("://").next().unwrap_or_default().to_ascii_lowercase();
+            let handler = match by_scheme.get(&scheme) {
+                Some(h) => h.clone(),
+                None => match self.protocols.handler_for(&url, options) {
+                    Ok(h) => {
+                        by_scheme.insert(scheme, h.clone());
+                        h
+                    }
+                    Err(e) => {
+                        first_err.get_or_insert(e);
+                        continue;
+                    }
+                },
+            };
+            handlers.insert(url, handler);
+        }
+        if handlers.is_empty() {
+            return Err(first_err.unwrap_or_else(|| crate::network::NetworkError::Url("没有可用的下载源".to_string())));
+        }
+        Ok(handlers)
+    }
+
+    /// 新建 Metalink 中一个文件的任务：清单中的受支持地址按优先级作为下载源，附带清单中的校验值。
+    /// 各镜像由校验值保证一致，不按 ETag 比较；按 options 探测首个可用地址以确认 Range 支持，清单中的大小优先
+    pub async fn create_metalink_task(
+        &self,
+        file: MetalinkFile,
+        save_dir: String,
+        filename: String,
+        options: &NetworkOptions,
+    ) -> Result<TaskId, String> {
+        let urls: Vec<(String, u32)> = file
+            .urls
+            .into_iter()
+            .filter(|(u, _)| self.protocols.supports(u))
+            .collect();
+        let Some((primary, _)) = urls.first().cloned() else {
+            return Err("Metalink 中没有受支持协议的下载地址".to_string());
+        };
+        let mut probed = None;
+        for (url, _) in &urls {
+            if let Ok(p) = self.probe_with_options(url, options).await {
+                probed = Some(p);
+                break;
+            }
+        }
+        let supports_range = probed.as_ref().is_some_and(|p| p.supports_range);
+        let total_bytes = file.size.or_else(|| probed.and_then(|p| p.total_bytes));
+        let input = crate::engine::types::CreateTaskInput {
+            url: primary,
+            save_dir,
+            filename: Some(filename),
+        };
+        let task = Task::new(input, supports_range, total_bytes)
+            .with_sources(SourcePool::new(urls))
+            .with_checksums(file.checksums);
+        let id = task.id.clone();
+        self.tasks.lock().await.insert(id.clone(), Arc::new(task));
+        self.save_tasks().await;
+        Ok(id)
+    }
+
+    /// 暂停下载中的任务；尚未开始的任务也标记为暂停
     pub async fn pause_task(&self, task_id: &str) -> Result<(), String> {
         let task = {
             let tasks = self.tasks.lock().await;
@@ -205,8 +532,9 @@ impl Scheduler {
         };
         {
             let mut st = task.status.lock().await;
-            if *st == TaskStatus::Downloading {
+            if *st == TaskStatus::Downloading || *st == TaskStatus::Pending {
                 *st = TaskStatus::Paused;
+                self.events.status_changed(task_id, TaskStatus::Paused);
             }
         }
         self.save_tasks().await;
@@ -216,14 +544,12 @@ impl Scheduler {
     pub async fn resume_task(
         &self,
         task_id: &str,
-        app_handle: Option<tauri::AppHandle>,
         scheduler_for_save: Option<Arc<Scheduler>>,
         max_connections: Option<usize>,
         network_options: Option<NetworkOptions>,
     ) -> Result<(), String> {
         self.start_download(
             task_id,
-            app_handle,
             scheduler_for_save,
             max_connections,
             network_options,
@@ -231,21 +557,37 @@ impl Scheduler {
         .await
     }
 
+    /// 停止直播录制：不再刷新播放列表，合并已下载分片后完成任务
+    pub async fn stop_recording(&self, task_id: &str) -> Result<(), String> {
+        let tasks = self.tasks.lock().await;
+        let task = tasks.get(task_id).ok_or_else(|| "任务不存在".to_string())?;
+        if !matches!(task.kind, TaskKind::Hls(_)) {
+            return Err("仅 HLS 任务可停止录制".to_string());
+        }
+        task.stop_requested.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
     pub async fn cancel_task(&self, task_id: &str) -> Result<(), String> {
         let tasks = self.tasks.lock().await;
         let task = tasks.get(task_id).ok_or_else(|| "任务不存在".to_string())?;
         let mut st = task.status.lock().await;
         *st = TaskStatus::Cancelled;
+        self.events.status_changed(task_id, TaskStatus::Cancelled);
         Ok(())
     }
 
-    /// 删除任务：先取消再从列表移除并持久化，任务记录从文件中删除
+    /// 删除任务：先取消（未结束的任务发出取消事件）再从列表移除并持久化，任务记录从文件中删除
     pub async fn remove_task(&self, task_id: &str) -> Result<(), String> {
         {
             let tasks = self.tasks.lock().await;
             let task = tasks.get(task_id).ok_or_else(|| "任务不存在".to_string())?;
             let mut st = task.status.lock().await;
+            let finished = matches!(*st, TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled);
             *st = TaskStatus::Cancelled;
+            if !finished {
+                self.events.status_changed(task_id, TaskStatus::Cancelled);
+            }
         }
         {
             let mut tasks = self.tasks.lock().await;
@@ -255,12 +597,14 @@ impl Scheduler {
         Ok(())
     }
 
+    /// 全部任务，按创建时间升序（相同时按 id），顺序稳定
     pub async fn list_downloads(&self) -> Vec<TaskInfo> {
         let tasks = self.tasks.lock().await;
         let mut infos = Vec::new();
         for t in tasks.values() {
             infos.push(task_to_info(t).await);
         }
+        infos.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
         infos
     }
 
@@ -297,50 +641,238 @@ impl Scheduler {
         }
     }
 
-    /// 刷新下载地址：重新探测 URL，更新为最终重定向地址
+    /// 刷新下载地址：重新探测 URL，更新为最终重定向地址；下载中的任务须先暂停
     pub async fn refresh_task_url(
         &self,
         task_id: &str,
         options: &NetworkOptions,
     ) -> Result<(), String> {
-        let tasks = self.tasks.lock().await;
-        let task = tasks.get(task_id).ok_or("任务不存在")?;
-        let mut pt = PersistedTask::from_task(task).await;
-        let id = task_id.to_string();
-        drop(tasks);
-        let probe_result = probe_with_options(&pt.url, options).await.map_err(|e| e.to_string())?;
-        pt.url = probe_result.final_url;
+        let (url, headers) = self
+            .tasks
+            .lock()
+            .await
+            .get(task_id)
+            .map(|t| (t.url.clone(), t.headers.clone()))
+            .ok_or("任务不存在")?;
+        let probe_result = self
+            .probe_with_options(&url, &options.with_headers(&headers))
+            .await
+            .map_err(|e| e.to_string())?;
+        self.rebuild_task(task_id, |_, pt| {
+            replace_primary_url(pt, probe_result.final_url);
+            Ok(())
+        })
+        .await?;
+        self.save_tasks().await;
+        Ok(())
+    }
+
+    /// 更换下载地址（如签名链接过期）：仅限暂停、失败或链接过期的任务；新地址的大小与校验器须与原文件一致，
+    /// 保留已下载区间，之后继续下载即从断点续传
+    pub async fn rebind_task_url(
+        &self,
+        task_id: &str,
+        new_url: String,
+        options: &NetworkOptions,
+    ) -> Result<(), String> {
+        let new_url = new_url.trim().to_string();
+        if !self.protocols.supports(&new_url) {
+            return Err("不支持的下载地址".to_string());
+        }
+        let headers = self
+            .tasks
+            .lock()
+            .await
+            .get(task_id)
+            .map(|t| t.headers.clone())
+            .ok_or("任务不存在")?;
+        let probe_result = self
+            .probe_with_options(&new_url, &options.with_headers(&headers))
+            .await
+            .map_err(|e| e.to_string())?;
+        self.rebind_with_probe(task_id, new_url, &probe_result).await
+    }
+
+    /// 延迟探测的任务首次开始时探测：补全大小、分段与校验器后替换任务；探测期间不再是下载中时返回 None
+    async fn resolve_deferred(&self, task: &Arc<Task>, options: &NetworkOptions) -> Result<Option<Arc<Task>>, String> {
+        let p = self.probe_with_options(&task.url, options).await.map_err(|e| e.to_string())?;
+        let playlist = hls::is_hls(&task.url, p.content_type.as_deref())
+            || hls::is_hls(&p.final_url, None)
+            || dash::is_dash(&task.url, p.content_type.as_deref())
+            || dash::is_dash(&p.final_url, None);
+        if playlist {
+            return Err("该地址是流媒体清单，请关闭「开始下载时再探测」后重新添加".to_string());
+        }
+        // 延迟探测的任务还没有运行过 worker，这里只为与其他重建保持同一加锁顺序
+        let _idle = task.running.lock().await;
         let mut tasks = self.tasks.lock().await;
-        tasks.insert(id, Arc::new(Task::from_persisted(pt)));
+        if *task.status.lock().await != TaskStatus::Downloading {
+            return Ok(None);
+        }
+        let mut pt = PersistedTask::from_task(task).await;
+        pt.total_bytes = p.total_bytes;
+        pt.supports_range = p.supports_range;
+        pt.pending_segments = initial_segments(p.supports_range, p.total_bytes).into();
+        pt.validators = Validators {
+            etag: p.etag,
+            last_modified: p.last_modified,
+        };
+        pt.deferred_probe = false;
+        let resolved = Arc::new(Task::from_persisted(pt));
+        tasks.insert(resolved.id.clone(), resolved.clone());
+        Ok(Some(resolved))
+    }
+
+    async fn rebind_with_probe(&self, task_id: &str, new_url: String, probe: &ProbeResult) -> Result<(), String> {
+        self.rebuild_task(task_id, |task, pt| {
+            if !matches!(pt.status, TaskStatus::Paused | TaskStatus::Failed | TaskStatus::NeedsRefresh) {
+                return Err("仅暂停、失败或链接过期的任务可更换下载地址".to_string());
+            }
+            // 流媒体清单地址不对应单个文件，已下载分片按序号沿用
+            if task.kind == TaskKind::File {
+                check_same_file(task, probe)?;
+            }
+            replace_primary_url(pt, new_url);
+            pt.status = TaskStatus::Paused;
+            if pt.validators == Validators::default() {
+                pt.validators = Validators {
+                    etag: probe.etag.clone(),
+                    last_modified: probe.last_modified.clone(),
+                };
+            }
+            Ok(())
+        })
+        .await?;
+        self.link_capture.lock().await.remove(task_id);
+        self.events.status_changed(task_id, TaskStatus::Paused);
+        self.save_tasks().await;
+        Ok(())
+    }
+
+    /// 开启/关闭自动捕获：开启后浏览器发来的同名同大小下载用于更换该任务的地址
+    pub async fn set_link_capture(&self, task_id: &str, enabled: bool) -> Result<(), String> {
+        if !self.tasks.lock().await.contains_key(task_id) {
+            return Err("任务不存在".to_string());
+        }
+        let mut capture = self.link_capture.lock().await;
+        if enabled {
+            capture.insert(task_id.to_string());
+        } else {
+            capture.remove(task_id);
+        }
+        Ok(())
+    }
+
+    /// 尝试把浏览器发来的地址交给等待捕获或链接已过期的任务：文件名与大小都匹配且校验通过时更换地址并返回任务 id
+    pub async fn capture_link(
+        &self,
+        url: &str,
+        filename: Option<&str>,
+        options: &NetworkOptions,
+    ) -> Option<TaskId> {
+        let mut waiting: Vec<TaskId> = self.link_capture.lock().await.iter().cloned().collect();
+        for (id, t) in self.tasks.lock().await.iter() {
+            if *t.status.lock().await == TaskStatus::NeedsRefresh && !waiting.contains(id) {
+                waiting.push(id.clone());
+            }
+        }
+        if waiting.is_empty() {
+            return None;
+        }
+        let probe = self.probe_with_options(url, options).await.ok()?;
+        let name = filename.filter(|f| !f.is_empty()).unwrap_or(&probe.suggested_filename);
+        for id in waiting {
+            let matched = {
+                let tasks = self.tasks.lock().await;
+                tasks.get(&id).is_some_and(|t| {
+                    t.filename == name && t.total_bytes.is_some() && t.total_bytes == probe.total_bytes
+                })
+            };
+            if matched && self.rebind_with_probe(&id, url.to_string(), &probe).await.is_ok() {
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    /// 设置任务附带的请求头（替换原有的），下次开始下载时生效
+    pub async fn set_task_headers(&self, task_id: &str, headers: Vec<(String, String)>) -> Result<(), String> {
+        self.update_persisted(task_id, |pt| pt.headers = headers).await
+    }
+
+    /// 记录任务来源
+    pub async fn set_task_source(&self, task_id: &str, source: TaskSource) -> Result<(), String> {
+        self.update_persisted(task_id, |pt| pt.source = source).await
+    }
+
+    /// 按持久化数据修改任务后替换并保存；下载中的任务须先暂停
+    async fn update_persisted(&self, task_id: &str, f: impl FnOnce(&mut PersistedTask)) -> Result<(), String> {
+        self.rebuild_task(task_id, |_, pt| {
+            f(pt);
+            Ok(())
+        })
+        .await?;
         self.save_tasks().await;
         Ok(())
     }
 
-    /// 移动/重命名：更新任务保存路径，若文件已存在则移动
+    /// 等待任务当前这一轮下载结束：暂停后 worker 交回未完成的分段、写入端落盘
+    pub async fn wait_idle(&self, task_id: &str) {
+        let task = self.tasks.lock().await.get(task_id).cloned();
+        if let Some(task) = task {
+            let _idle = task.running.lock().await;
+        }
+    }
+
+    /// 按持久化数据重建任务（Task 的字段不可变）：先等本轮下载结束，让 worker 交回手上的分段，
+    /// 再持有任务表锁快照、修改并替换。下载中的任务不能重建；f 返回错误时不替换
+    async fn rebuild_task(
+        &self,
+        task_id: &str,
+        f: impl FnOnce(&Task, &mut PersistedTask) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let busy = || "下载中的任务请先暂停".to_string();
+        loop {
+            let task = self.tasks.lock().await.get(task_id).cloned().ok_or("任务不存在")?;
+            if *task.status.lock().await == TaskStatus::Downloading {
+                return Err(busy());
+            }
+            let _idle = task.running.lock().await;
+            let mut tasks = self.tasks.lock().await;
+            // 等待期间已被替换：按新的任务重来
+            if !tasks.get(task_id).is_some_and(|t| Arc::ptr_eq(t, &task)) {
+                continue;
+            }
+            let mut pt = PersistedTask::from_task(&task).await;
+            if pt.status == TaskStatus::Downloading {
+                return Err(busy());
+            }
+            f(&task, &mut pt)?;
+            tasks.insert(task_id.to_string(), Arc::new(Task::from_persisted(pt)));
+            return Ok(());
+        }
+    }
+
+    /// 移动/重命名：更新任务保存路径，若文件已存在则移动；下载中的任务须先暂停
     pub async fn update_task_save_path(&self, task_id: &str, new_save_path: String) -> Result<(), String> {
-        let tasks = self.tasks.lock().await;
-        let task = tasks.get(task_id).ok_or("任务不存在")?;
-        let old_path = task.save_path.clone();
-        let mut pt = PersistedTask::from_task(task).await;
-        let id = task_id.to_string();
-        drop(tasks);
         let new_path = std::path::Path::new(&new_save_path);
-        if std::path::Path::new(&old_path).exists() {
-            if let Some(parent) = new_path.parent() {
-                let _ = tokio::fs::create_dir_all(parent).await;
+        if let Some(parent) = new_path.parent() {
+            let _ = tokio::fs::create_dir_all(parent).await;
+        }
+        self.rebuild_task(task_id, |task, pt| {
+            // 已等到写入结束，文件不再变化
+            if std::path::Path::new(&task.save_path).exists() {
+                std::fs::rename(&task.save_path, &new_save_path).map_err(|e| e.to_string())?;
             }
-            tokio::fs::rename(&old_path, &new_save_path)
-                .await
-                .map_err(|e| e.to_string())?;
-        }
-        pt.save_path = new_save_path.clone();
-        pt.filename = new_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("download")
-            .to_string();
-        let mut tasks = self.tasks.lock().await;
-        tasks.insert(id, Arc::new(Task::from_persisted(pt)));
+            pt.save_path = new_save_path.clone();
+            pt.filename = new_path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("download")
+                .to_string();
+            Ok(())
+        })
+        .await?;
         self.save_tasks().await;
         Ok(())
     }
@@ -360,16 +892,174 @@ async fn task_to_info(t: &Arc<Task>) -> TaskInfo {
         error_message: err,
         speed_bps: t.speed_bps(),
         created_at: t.created_at,
+        kind: t.kind.clone(),
+        sources: if t.sources.len() > 1 { t.sources.infos() } else { Vec::new() },
+        source: t.source,
+    }
+}
+
+/// 记录错误并标记失败，发出事件
+async fn mark_failed(task: &Task, message: String, events: &dyn EngineEvents) {
+    let _ = task.error_message.lock().await.insert(message.clone());
+    *task.status.lock().await = TaskStatus::Failed;
+    events.status_changed(&task.id, TaskStatus::Failed);
+    events.failed(&task.id, &task.filename, &message);
+}
+
+/// 链接过期：保留全部进度，发出事件请求新地址（仅当仍处于下载中）
+async fn mark_needs_refresh(task: &Task, events: &dyn EngineEvents) {
+    let mut st = task.status.lock().await;
+    if *st != TaskStatus::Downloading {
+        return;
+    }
+    *st = TaskStatus::NeedsRefresh;
+    let _ = task.error_message.lock().await.insert("下载链接已过期，请提供新链接".to_string());
+    events.status_changed(&task.id, TaskStatus::NeedsRefresh);
+    events.needs_refresh(&task.id, &task.filename, &task.url);
+}
+
+/// 比较镜像与主地址的探测结果：双方都给出的大小与校验器必须一致
+fn check_mirror(primary: &ProbeResult, mirror: &ProbeResult) -> Result<(), String> {
+    if let (Some(a), Some(b)) = (primary.total_bytes, mirror.total_bytes) {
+        if a != b {
+            return Err(format!("大小 {} ≠ {}", a, b));
+        }
+    }
+    check_validators(
+        &primary.etag,
+        &primary.last_modified,
+        &mirror.etag,
+        &mirror.last_modified,
+    )
+}
+
+fn check_validators(
+    etag_a: &Option<String>,
+    modified_a: &Option<String>,
+    etag_b: &Option<String>,
+    modified_b: &Option<String>,
+) -> Result<(), String> {
+    // 弱校验器前缀 W/ 不影响比较
+    let tag = |t: &Option<String>| t.as_deref().map(|t| t.trim_start_matches("W/").to_string());
+    if let (Some(a), Some(b)) = (tag(etag_a), tag(etag_b)) {
+        if a != b {
+            return Err(format!("ETag {} ≠ {}", a, b));
+        }
+    }
+    if let (Some(a), Some(b)) = (modified_a, modified_b) {
+        if a != b {
+            return Err(format!("Last-Modified {} ≠ {}", a, b));
+        }
+    }
+    Ok(())
+}
+
+/// 新地址须指向任务原来的文件：大小一致、校验器一致，已有进度时还须支持 Range
+fn check_same_file(task: &Task, probe: &ProbeResult) -> Result<(), String> {
+    if let Some(total) = task.total_bytes {
+        match probe.total_bytes {
+            Some(n) if n == total => {}
+            Some(n) => return Err(format!("新地址文件大小不一致：{} ≠ {}", n, total)),
+            None => return Err("新地址未返回文件大小，无法确认是同一文件".to_string()),
+        }
+    }
+    check_validators(
+        &task.validators.etag,
+        &task.validators.last_modified,
+        &probe.etag,
+        &probe.last_modified,
+    )
+    .map_err(|e| format!("新地址与原文件不一致：{}", e))?;
+    if task.supports_range && !probe.supports_range && task.downloaded_bytes() > 0 {
+        return Err("新地址不支持断点续传，无法沿用已下载部分".to_string());
+    }
+    Ok(())
+}
+
+/// 替换主地址，镜像列表中的旧地址同步替换
+fn replace_primary_url(pt: &mut PersistedTask, new_url: String) {
+    if let Some(u) = pt.sources.iter_mut().find(|u| **u == pt.url) {
+        *u = new_url.clone();
+    }
+    pt.url = new_url;
+}
+
+/// 标记完成并发出事件（仅当仍处于下载中）
+async fn mark_completed(task: &Task, events: &dyn EngineEvents) {
+    let mut st = task.status.lock().await;
+    if *st != TaskStatus::Downloading {
+        return;
     }
+    *st = TaskStatus::Completed;
+    events.status_changed(&task.id, TaskStatus::Completed);
+    events.finished(&task.id, &task.filename);
+}
+
+/// 校验修复的最大轮数
+const MAX_REPAIR_ROUNDS: u32 = 3;
+
+/// 按任务附带的校验信息检查文件：返回需重新下载的区间；整文件哈希不符时返回错误
+async fn verify_download(task: &Task) -> Result<Vec<(u64, u64)>, String> {
+    let path = std::path::Path::new(&task.save_path);
+    if let (Some(pieces), Some(total)) = (&task.checksums.pieces, task.total_bytes) {
+        let bad = checksum::verify_pieces(path, pieces, total).await?;
+        if !bad.is_empty() {
+            // 不支持区间请求时只能整体重下
+            return Ok(if task.supports_range { bad } else { vec![(0, total - 1)] });
+        }
+    }
+    if let Some(expected) = task.checksums.strongest() {
+        let actual = checksum::file_digest(path, expected.algo).await?;
+        if !actual.eq_ignore_ascii_case(&expected.hex) {
+            return Err(format!("文件校验失败：{:?} 不匹配", expected.algo));
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// 启动写入任务与多个 worker，等待本轮全部结束
+async fn run_workers(
+    task: &Arc<Task>,
+    n_workers: usize,
+    handlers: &Arc<HashMap<String, Arc<dyn ProtocolHandler>>>,
+    events: &Arc<dyn EngineEvents>,
+) {
+    let (tx, rx) = mpsc::channel::<WriterMessage>(32);
+    let path = task.save_path.clone();
+    let total_bytes = task.total_bytes;
+    let writer_handle = tokio::spawn(async move {
+        let _ = run_file_writer(path, total_bytes, rx).await;
+    });
+    let mut handles = Vec::new();
+    for _ in 0..n_workers {
+        let task_ref = task.clone();
+        let tx_w = tx.clone();
+        let events_w = events.clone();
+        let handlers_ref = handlers.clone();
+        handles.push(tokio::spawn(async move {
+            run_worker(task_ref, tx_w, events_w, handlers_ref).await;
+        }));
+    }
+    for h in handles {
+        let _ = h.await;
+    }
+    drop(tx);
+    let _ = writer_handle.await;
+}
+
+/// 单段下载的结束方式
+enum SegmentEnd {
+    /// 整段写完（本段实际写入的字节数）
+    Done(u64),
+    /// 暂停/取消或写入端关闭，未完成部分已放回队列
+    Stopped,
 }
 
 async fn run_worker(
     task: Arc<Task>,
-    url: &str,
     tx: mpsc::Sender<WriterMessage>,
-    app_handle: Option<tauri::AppHandle>,
-    _task_id: &str,
-    client: &Client,
+    events: Arc<dyn EngineEvents>,
+    handlers: Arc<HashMap<String, Arc<dyn ProtocolHandler>>>,
 ) {
     loop {
         let status = *task.status.lock().await;
@@ -379,38 +1069,88 @@ async fn run_worker(
         let Some((start, end)) = task.take_next_segment() else {
             break;
         };
-        let len = end - start + 1;
-        match fetch_range_with_client(client, url, start, end).await {
-            Ok(data) => {
-                if data.len() as u64 != len {
-                    // 可能服务器返回不完整，仍写入
-                }
-                task.add_downloaded(data.len() as u64);
+        // 每段重新选择下载源，出错的段放回队列由其他镜像接手
+        let source = task.sources.acquire();
+        let Some((url, handler)) = source.and_then(|u| handlers.get(&u).cloned().map(|h| (u, h))) else {
+            task.pending_segments.lock().await.push_back((start, end));
+            mark_failed(&task, "没有可用的下载源".to_string(), events.as_ref()).await;
+            break;
+        };
+        let began = std::time::Instant::now();
+        match download_segment(&task, &url, start, end, &tx, handler.as_ref()).await {
+            Ok(SegmentEnd::Done(bytes)) => {
+                task.sources.release_ok(&url, bytes, began.elapsed());
                 task.set_speed_sample(task.downloaded_bytes());
-                if tx.send((start, data)).await.is_err() {
-                    break;
-                }
-                if let Some(app) = &app_handle {
-                    let _ = app.emit("download-progress", ());
-                }
+                events.progress(&task.id);
+            }
+            Ok(SegmentEnd::Stopped) => {
+                task.sources.release(&url);
+                break;
             }
             Err(e) => {
-                let _ = task
-                    .error_message
-                    .lock()
-                    .await
-                    .insert(e.to_string());
-                let mut st = task.status.lock().await;
-                *st = TaskStatus::Failed;
-                if let Some(app) = &app_handle {
-                    let _ = app.emit("download-finished", (
-                        _task_id.to_string(),
-                        "failed".to_string(),
-                        task.filename.clone(),
-                    ));
+                if task.sources.release_err(&url, &e.to_string()) {
+                    continue;
+                }
+                // 其他 worker 已经结束任务（如已标记为链接过期）时不再覆盖状态
+                if *task.status.lock().await != TaskStatus::Downloading {
+                    break;
+                }
+                if e.is_link_expired() {
+                    mark_needs_refresh(&task, events.as_ref()).await;
+                } else {
+                    mark_failed(&task, e.to_string(), events.as_ref()).await;
                 }
                 break;
             }
         }
     }
 }
+
+/// 流式下载一段并按偏移写入；中途停止或出错时把剩余区间放回待下载队列
+async fn download_segment(
+    task: &Task,
+    url: &str,
+    start: u64,
+    end: u64,
+    tx: &mpsc::Sender<WriterMessage>,
+    handler: &dyn ProtocolHandler,
+) -> Result<SegmentEnd, crate::network::NetworkError> {
+    let mut offset = start;
+    let result = async {
+        let mut stream = handler.open_range(url, start, end).await?;
+        while let Some(chunk) = stream.next().await {
+            let mut chunk = chunk?;
+            // 服务器多给的数据截断到段末
+            let remain = end + 1 - offset;
+            if chunk.len() as u64 > remain {
+                chunk = chunk.slice(..remain as usize);
+            }
+            let n = chunk.len() as u64;
+            if tx.send((offset, chunk)).await.is_err() {
+                return Ok(SegmentEnd::Stopped);
+            }
+            offset += n;
+            task.add_downloaded(n);
+            if offset > end {
+                return Ok(SegmentEnd::Done(offset - start));
+            }
+            let status = *task.status.lock().await;
+            if status != TaskStatus::Downloading {
+                return Ok(SegmentEnd::Stopped);
+            }
+        }
+        if offset == start {
+            return Err(crate::network::NetworkError::Url(format!(
+                "区间 {}-{} 未返回数据",
+                start, end
+            )));
+        }
+        // 数据提前结束：剩余部分放回队列，由后续请求补齐
+        Ok(SegmentEnd::Done(offset - start))
+    }
+    .await;
+    if offset <= end {
+        task.pending_segments.lock().await.push_back((offset, end));
+    }
+    result
+}
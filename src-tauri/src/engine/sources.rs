@@ -0,0 +1,222 @@
+//! 多下载源：按段选择镜像，统计速度与失败次数，剔除出错或过慢的镜像
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 连续失败达到此次数的镜像被剔除（至少保留一个可用源）
+const MAX_FAILURES: u32 = 2;
+
+/// 速度低于最快镜像的 1/SLOW_FACTOR 时视为过慢
+const SLOW_FACTOR: u64 = 5;
+
+/// 判断过慢前至少完成的段数
+const MIN_SAMPLES: u32 = 2;
+
+#[derive(Debug, Clone)]
+struct SourceState {
+    url: String,
+    /// 越小越优先
+    priority: u32,
+    in_use: usize,
+    failures: u32,
+    segments: u32,
+    bytes: u64,
+    elapsed: Duration,
+    disabled: Option<String>,
+    /// 新建时未采用的镜像：只用于展示原因，不参与下载
+    rejected: bool,
+}
+
+impl SourceState {
+    fn speed(&self) -> Option<u64> {
+        let ms = self.elapsed.as_millis() as u64;
+        (self.segments >= MIN_SAMPLES && ms > 0).then(|| self.bytes * 1000 / ms)
+    }
+}
+
+/// 前端展示用的镜像状态
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SourceInfo {
+    pub url: String,
+    pub priority: u32,
+    pub speed_bps: Option<u64>,
+    pub failures: u32,
+    /// 被剔除的原因；None 表示仍在使用
+    pub disabled: Option<String>,
+}
+
+/// 任务的下载源集合；单源任务也经由此选择地址
+#[derive(Debug, Default)]
+pub struct SourcePool {
+    sources: Mutex<Vec<SourceState>>,
+}
+
+impl SourcePool {
+    /// (地址, 优先级)，重复地址只保留第一个
+    pub fn new(urls: impl IntoIterator<Item = (String, u32)>) -> Self {
+        let mut sources: Vec<SourceState> = Vec::new();
+        for (url, priority) in urls {
+            if sources.iter().any(|s| s.url == url) {
+                continue;
+            }
+            sources.push(SourceState {
+                url,
+                priority,
+                in_use: 0,
+                failures: 0,
+                segments: 0,
+                bytes: 0,
+                elapsed: Duration::ZERO,
+                disabled: None,
+                rejected: false,
+            });
+        }
+        Self {
+            sources: Mutex::new(sources),
+        }
+    }
+
+    pub fn single(url: &str) -> Self {
+        Self::new([(url.to_string(), 0)])
+    }
+
+    /// 记录新建时未采用的镜像及原因（不可访问、与主地址不一致、不支持 Range）；已有的地址忽略
+    pub fn reject(&self, url: &str, reason: &str) {
+        let Ok(mut sources) = self.sources.lock() else {
+            return;
+        };
+        if sources.iter().any(|s| s.url == url) {
+            return;
+        }
+        let priority = sources.iter().map(|s| s.priority + 1).max().unwrap_or(0);
+        sources.push(SourceState {
+            url: url.to_string(),
+            priority,
+            in_use: 0,
+            failures: 0,
+            segments: 0,
+            bytes: 0,
+            elapsed: Duration::ZERO,
+            disabled: Some(reason.to_string()),
+            rejected: true,
+        });
+    }
+
+    /// 未采用的镜像（地址, 原因）
+    pub fn rejected(&self) -> Vec<(String, String)> {
+        self.sources
+            .lock()
+            .map(|s| {
+                s.iter()
+                    .filter(|s| s.rejected)
+                    .map(|s| (s.url.clone(), s.disabled.clone().unwrap_or_default()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 参与下载的全部地址（含运行中剔除的，不含未采用的），按优先级排序
+    pub fn urls(&self) -> Vec<String> {
+        let mut sources = self.sources.lock().map(|s| s.clone()).unwrap_or_default();
+        sources.retain(|s| !s.rejected);
+        sources.sort_by_key(|s| s.priority);
+        sources.into_iter().map(|s| s.url).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.lock().map(|s| s.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.sources
+            .lock()
+            .map(|s| s.iter().filter(|s| s.disabled.is_none()).count())
+            .unwrap_or(0)
+    }
+
+    pub fn infos(&self) -> Vec<SourceInfo> {
+        self.sources
+            .lock()
+            .map(|s| {
+                s.iter()
+                    .map(|s| SourceInfo {
+                        url: s.url.clone(),
+                        priority: s.priority,
+                        speed_bps: s.speed(),
+                        failures: s.failures,
+                        disabled: s.disabled.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 为下一段选择镜像：占用连接最少者优先，其次优先级、速度；调用方用完后须 release
+    pub fn acquire(&self) -> Option<String> {
+        let mut sources = self.sources.lock().ok()?;
+        let best = sources
+            .iter_mut()
+            .filter(|s| s.disabled.is_none())
+            .min_by_key(|s| (s.in_use, s.priority, std::cmp::Reverse(s.speed().unwrap_or(0))))?;
+        best.in_use += 1;
+        Some(best.url.clone())
+    }
+
+    /// 一段成功结束：记录速度，并剔除明显慢于最快镜像的源
+    pub fn release_ok(&self, url: &str, bytes: u64, elapsed: Duration) {
+        let Ok(mut sources) = self.sources.lock() else {
+            return;
+        };
+        if let Some(s) = sources.iter_mut().find(|s| s.url == url) {
+            s.in_use = s.in_use.saturating_sub(1);
+            s.failures = 0;
+            s.segments += 1;
+            s.bytes += bytes;
+            s.elapsed += elapsed;
+        }
+        let fastest = sources
+            .iter()
+            .filter(|s| s.disabled.is_none())
+            .filter_map(SourceState::speed)
+            .max()
+            .unwrap_or(0);
+        let active = sources.iter().filter(|s| s.disabled.is_none()).count();
+        if let Some(s) = sources.iter_mut().find(|s| s.url == url && s.disabled.is_none()) {
+            if active > 1 && s.speed().is_some_and(|v| v * SLOW_FACTOR < fastest) {
+                s.disabled = Some("速度过慢".to_string());
+            }
+        }
+    }
+
+    /// 一段出错：累计失败次数，达到上限时剔除。返回是否仍有其他可用源（为 false 时任务应失败）
+    pub fn release_err(&self, url: &str, error: &str) -> bool {
+        let Ok(mut sources) = self.sources.lock() else {
+            return false;
+        };
+        let others = sources
+            .iter()
+            .filter(|s| s.disabled.is_none() && s.url != url)
+            .count();
+        if let Some(s) = sources.iter_mut().find(|s| s.url == url) {
+            s.in_use = s.in_use.saturating_sub(1);
+            s.failures += 1;
+            if others > 0 && s.failures >= MAX_FAILURES {
+                s.disabled = Some(error.to_string());
+            }
+        }
+        others > 0
+    }
+
+    /// 段被暂停/取消而未完成：仅归还连接
+    pub fn release(&self, url: &str) {
+        if let Ok(mut sources) = self.sources.lock() {
+            if let Some(s) = sources.iter_mut().find(|s| s.url == url) {
+                s.in_use = s.in_use.saturating_sub(1);
+            }
+        }
+    }
+}
@@ -1,11 +1,32 @@
-use crate::engine::types::{static_segments, CreateTaskInput, TaskId, TaskStatus, MIN_SEGMENT_SIZE};
+use crate::engine::checksum::Checksums;
+use crate::engine::sources::SourcePool;
+use crate::engine::types::{
+    static_segments, CreateTaskInput, TaskId, TaskKind, TaskSource, TaskStatus, Validators, MIN_SEGMENT_SIZE,
+};
 use std::collections::VecDeque;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 const DEFAULT_CONNECTIONS: usize = 8;
 
+/// 新任务的待下载段：支持 Range 时按默认连接数静态切分，否则整个文件一段；大小未知时为空
+pub(crate) fn initial_segments(supports_range: bool, total_bytes: Option<u64>) -> VecDeque<(u64, u64)> {
+    if supports_range {
+        let total = total_bytes.unwrap_or(0);
+        if total > 0 {
+            VecDeque::from_iter(static_segments(total, DEFAULT_CONNECTIONS))
+        } else {
+            VecDeque::new()
+        }
+    } else {
+        total_bytes
+            .filter(|&t| t > 0)
+            .map(|t| VecDeque::from_iter(std::iter::once((0, t.saturating_sub(1)))))
+            .unwrap_or_default()
+    }
+}
+
 /// 单个下载任务状态（引擎内部）
 pub struct Task {
     pub id: TaskId,
@@ -23,6 +44,21 @@ pub struct Task {
     /// 用于估算速度：最近一次更新的下载量
     pub last_downloaded: Arc<AtomicU64>,
     pub last_speed_time: Arc<Mutex<Option<(u64, std::time::Instant)>>>,
+    pub kind: TaskKind,
+    /// 下载源（主地址 + 镜像），worker 每段从中选择
+    pub sources: Arc<SourcePool>,
+    /// 完成后需要通过的校验
+    pub checksums: Checksums,
+    /// 直播录制的停止信号：置位后不再刷新列表，合并已下载分片
+    pub stop_requested: Arc<AtomicBool>,
+    pub validators: Validators,
+    /// 新建时未探测（批量添加时延后到开始下载时）：大小与分段未知
+    pub deferred_probe: bool,
+    /// 该任务每个请求附带的请求头（导入时带来的 Cookie、Referer、User-Agent 等）
+    pub headers: Vec<(String, String)>,
+    pub source: TaskSource,
+    /// 一轮下载（worker 与写入）运行期间持有；快照或替换任务前先获取，等 worker 交回手上的分段
+    pub running: Arc<Mutex<()>>,
 }
 
 impl Task {
@@ -34,20 +70,9 @@ impl Task {
         let save_path = std::path::Path::new(&input.save_dir).join(&filename);
         let save_path = save_path.to_string_lossy().to_string();
 
-        let pending_segments = if supports_range {
-            let total = total_bytes.unwrap_or(0);
-            if total > 0 {
-                VecDeque::from_iter(static_segments(total, DEFAULT_CONNECTIONS))
-            } else {
-                VecDeque::new()
-            }
-        } else {
-            total_bytes
-                .filter(|&t| t > 0)
-                .map(|t| VecDeque::from_iter(std::iter::once((0, t.saturating_sub(1)))))
-                .unwrap_or_default()
-        };
+        let pending_segments = initial_segments(supports_range, total_bytes);
 
+        let sources = Arc::new(SourcePool::single(&input.url));
         Self {
             id: crate::engine::types::new_task_id(),
             url: input.url,
@@ -65,14 +90,54 @@ impl Task {
                 .as_secs() as i64,
             last_downloaded: Arc::new(AtomicU64::new(0)),
             last_speed_time: Arc::new(Mutex::new(None)),
+            kind: TaskKind::File,
+            sources,
+            checksums: Checksums::default(),
+            stop_requested: Arc::new(AtomicBool::new(false)),
+            validators: Validators::default(),
+            deferred_probe: false,
+            headers: Vec::new(),
+            source: TaskSource::Manual,
+            running: Arc::new(Mutex::new(())),
         }
     }
 
+    pub fn with_deferred_probe(mut self) -> Self {
+        self.deferred_probe = true;
+        self
+    }
+
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_kind(mut self, kind: TaskKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_sources(mut self, sources: SourcePool) -> Self {
+        self.sources = Arc::new(sources);
+        self
+    }
+
+    pub fn with_checksums(mut self, checksums: Checksums) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    pub fn with_validators(mut self, validators: Validators) -> Self {
+        self.validators = validators;
+        self
+    }
+
     pub fn downloaded_bytes(&self) -> u64 {
         self.downloaded.load(Ordering::Relaxed)
     }
 
-    /// 动态分段：取当前最大未完成段，若可对半切则切分并返回后半段，否则返回整段
+    /// 动态分段：取当前最大未完成段，若可对半切则切分并返回后半段，否则返回整段；
+    /// 不支持 Range 的任务不切分
     pub fn take_next_segment(&self) -> Option<(u64, u64)> {
         let mut segs = self.pending_segments.try_lock().ok()?;
         if segs.is_empty() {
@@ -89,7 +154,7 @@ impl Task {
             }
         }
         let (start, end) = segs.remove(max_idx).unwrap();
-        if max_len > MIN_SEGMENT_SIZE {
+        if self.supports_range && max_len > MIN_SEGMENT_SIZE {
             let mid = start + (max_len / 2) - 1;
             segs.push_back((start, mid));
             Some((mid + 1, end))
@@ -1,3 +1,6 @@
+use crate::engine::dash::DashOptions;
+use crate::engine::hls::HlsOptions;
+use crate::engine::sources::SourceInfo;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -12,6 +15,42 @@ pub enum TaskStatus {
     Completed,
     Failed,
     Cancelled,
+    /// 签名链接已过期：进度保留，等待新地址
+    NeedsRefresh,
+}
+
+/// 任务从哪里添加，记入下载历史
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskSource {
+    /// 界面新建、批量添加、站点抓取与命令行
+    #[default]
+    Manual,
+    /// 浏览器扩展与网页中的 multidown:// 链接
+    Browser,
+    /// 导入的任务列表
+    Import,
+    /// 本地控制接口
+    Control,
+}
+
+/// 任务类型：普通文件按字节区间分段；HLS / DASH 按清单分片下载后合并
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskKind {
+    #[default]
+    File,
+    Hls(HlsOptions),
+    Dash(DashOptions),
+}
+
+/// 创建任务时服务器返回的校验器，更换地址时用于确认仍是同一文件
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
 }
 
 /// 前端展示用的任务信息
@@ -27,6 +66,10 @@ pub struct TaskInfo {
     pub error_message: Option<String>,
     pub speed_bps: Option<u64>,
     pub created_at: i64,
+    pub kind: TaskKind,
+    /// 下载源状态；仅单一地址时为空
+    pub sources: Vec<SourceInfo>,
+    pub source: TaskSource,
 }
 
 /// 新建任务参数
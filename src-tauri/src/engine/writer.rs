@@ -2,7 +2,7 @@
 
 use bytes::Bytes;
 use std::path::Path;
-use tokio::fs::File;
+use tokio::fs::OpenOptions;
 use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 use tokio::sync::mpsc;
 
@@ -15,9 +15,17 @@ pub async fn run_file_writer(
     mut rx: mpsc::Receiver<WriterMessage>,
 ) -> Result<(), std::io::Error> {
     let path = path.as_ref();
-    let mut file = File::create(path).await?;
+    // 续传/校验修复时保留已下载内容，只有大小未知（整体重下）时才清空
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(total_bytes.is_none())
+        .open(path)
+        .await?;
     if let Some(total) = total_bytes {
-        file.set_len(total).await?;
+        if file.metadata().await?.len() != total {
+            file.set_len(total).await?;
+        }
     }
     while let Some((offset, data)) = rx.recv().await {
         file.seek(std::io::SeekFrom::Start(offset)).await?;
@@ -0,0 +1,158 @@
+//! 从 HTML 中提取链接：只做标签与属性的词法扫描，跳过注释、脚本与样式内容
+
+/// 链接来源：`<a>` 可能指向下一层页面，其余只可能是文件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Anchor,
+    Media,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// 已按页面地址（或 `<base href>`）解析为绝对地址，去掉了 `#` 片段
+    pub url: String,
+    pub kind: LinkKind,
+}
+
+/// 提取 `a[href]`、`img[src|srcset]`、`video[src|poster]`、`audio[src]`、`source[src|srcset]`；按出现顺序，已去重
+pub fn extract_links(html: &str, page_url: &str) -> Vec<Link> {
+    let mut base = page_url.to_string();
+    let mut links: Vec<Link> = Vec::new();
+    let mut rest = html;
+    while let Some(pos) = rest.find('<') {
+        rest = &rest[pos + 1..];
+        if let Some(after) = rest.strip_prefix("!--") {
+            rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+            continue;
+        }
+        let name_len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        if name_len == 0 {
+            continue;
+        }
+        let name = rest[..name_len].to_ascii_lowercase();
+        let (attrs, after) = parse_attrs(&rest[name_len..]);
+        rest = after;
+        // 脚本与样式中的 `<` 不是标签
+        if name == "script" || name == "style" {
+            let close = format!("</{}", name);
+            let lower = rest.to_ascii_lowercase();
+            rest = lower.find(&close).map_or("", |end| &rest[end..]);
+            continue;
+        }
+        let wanted: &[&str] = match name.as_str() {
+            "base" => {
+                if let Some(href) = attr(&attrs, "href") {
+                    if let Some(url) = resolve(&base, href) {
+                        base = url;
+                    }
+                }
+                continue;
+            }
+            "a" => &["href"],
+            "img" => &["src", "srcset", "data-src"],
+            "video" => &["src", "poster"],
+            "audio" => &["src"],
+            "source" => &["src", "srcset"],
+            _ => continue,
+        };
+        let kind = if name == "a" { LinkKind::Anchor } else { LinkKind::Media };
+        for &key in wanted {
+            let Some(value) = attr(&attrs, key) else {
+                continue;
+            };
+            let candidates: Vec<&str> = if key == "srcset" {
+                // 「地址 宽度描述, 地址 宽度描述」
+                value.split(',').filter_map(|c| c.split_whitespace().next()).collect()
+            } else {
+                vec![value]
+            };
+            for candidate in candidates {
+                if let Some(url) = resolve(&base, candidate) {
+                    if !links.iter().any(|l| l.url == url) {
+                        links.push(Link { url, kind });
+                    }
+                }
+            }
+        }
+    }
+    links
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+        .filter(|v| !v.is_empty())
+}
+
+/// 解析到标签结束的 `>` 为止，返回 (小写属性名, 解码后的值) 与标签之后的内容
+fn parse_attrs(mut s: &str) -> (Vec<(String, String)>, &str) {
+    let mut attrs = Vec::new();
+    loop {
+        s = s.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if s.is_empty() {
+            return (attrs, s);
+        }
+        if let Some(after) = s.strip_prefix('>') {
+            return (attrs, after);
+        }
+        let key_len = s
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(s.len())
+            .max(1);
+        let key = s[..key_len].to_ascii_lowercase();
+        s = s[key_len..].trim_start();
+        let mut value = String::new();
+        if let Some(after) = s.strip_prefix('=') {
+            let after = after.trim_start();
+            let (raw, rest) = match after.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let body = &after[1..];
+                    match body.find(q) {
+                        Some(end) => (&body[..end], &body[end + 1..]),
+                        None => (body, ""),
+                    }
+                }
+                _ => {
+                    let end = after
+                        .find(|c: char| c.is_whitespace() || c == '>')
+                        .unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = decode_entities(raw.trim());
+            s = rest;
+        }
+        attrs.push((key, value));
+    }
+}
+
+/// 属性值中常见的字符实体
+fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    s.replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+}
+
+/// 相对地址按页面地址解析；javascript:、mailto:、data: 等不是下载地址的返回 None
+fn resolve(base: &str, href: &str) -> Option<String> {
+    let href = href.trim();
+    if href.is_empty() || href.starts_with('#') {
+        return None;
+    }
+    let mut url = reqwest::Url::parse(base).ok()?.join(href).ok()?;
+    if matches!(url.scheme(), "javascript" | "mailto" | "data" | "tel" | "about" | "blob") {
+        return None;
+    }
+    url.set_fragment(None);
+    Some(url.to_string())
+}
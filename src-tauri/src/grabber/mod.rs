@@ -0,0 +1,467 @@
+//! 站点抓取：从起始页面出发按层抓取 HTML，提取其中的链接，按扩展名、MIME、正则与大小筛选出要下载的文件，
+//! 由用户勾选后经任务入口批量新建。页面与文件都先探测，并发数有上限，可随时取消（返回已得到的结果）
+
+pub mod html;
+
+use crate::network::{NetworkError, NetworkOptions, ProbeResult, ProtocolHandler, ProtocolRegistry};
+use futures_util::stream::{self, StreamExt};
+use html::{Link, LinkKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+/// 单个页面的大小上限，超过的不解析
+const MAX_PAGE_BYTES: u64 = 8 * 1024 * 1024;
+/// robots.txt 只读前 512 KB
+const MAX_ROBOTS_BYTES: u64 = 512 * 1024;
+/// 检查取消标记的间隔
+const CANCEL_POLL: Duration = Duration::from_millis(50);
+const MAX_CONCURRENCY: usize = 16;
+/// 按扩展名视为页面（服务器未给出 Content-Type 时）
+const PAGE_EXTENSIONS: &[&str] = &["html", "htm", "xhtml", "shtml", "php", "asp", "aspx", "jsp"];
+const USER_AGENT_TOKEN: &str = "multidown";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GrabOptions {
+    pub url: String,
+    /// 跟随链接的层数：1 只看起始页面上的链接，2 再进入其中链接到的页面，以此类推
+    pub depth: u32,
+    /// 只进入与起始页面同一主机的页面（文件可以在其他主机，如 CDN）
+    pub same_host: bool,
+    /// 遵守 robots.txt（User-agent 为 multidown 或 *）
+    pub respect_robots: bool,
+    /// 文件扩展名（不含点，不区分大小写）；为空时不限
+    pub extensions: Vec<String>,
+    /// MIME 类型或前缀（如 `image/`）；为空时不限，服务器未给出类型的文件不按此项排除
+    pub mime_types: Vec<String>,
+    /// 地址须匹配的正则表达式
+    pub pattern: Option<String>,
+    /// 大小范围（字节）；大小未知的文件不按此项排除
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub max_pages: usize,
+    pub max_files: usize,
+    pub concurrency: usize,
+}
+
+impl Default for GrabOptions {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            depth: 1,
+            same_host: true,
+            respect_robots: true,
+            extensions: Vec::new(),
+            mime_types: Vec::new(),
+            pattern: None,
+            min_size: None,
+            max_size: None,
+            max_pages: 50,
+            max_files: 1000,
+            concurrency: 4,
+        }
+    }
+}
+
+/// 一个候选文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GrabbedFile {
+    pub url: String,
+    /// 链接所在页面
+    pub referer: String,
+    pub filename: String,
+    pub size: Option<u64>,
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GrabResult {
+    pub files: Vec<GrabbedFile>,
+    /// 已解析的页面数
+    pub pages: usize,
+    /// 被筛选条件、robots.txt 或数量上限排除的链接数
+    pub skipped: usize,
+    /// 无法访问的地址及原因
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+}
+
+/// 抓取进度，每处理完一个地址回调一次
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct GrabProgress {
+    pub pages: usize,
+    pub files: usize,
+    /// 当前层尚未处理的地址数
+    pub pending: usize,
+}
+
+/// 处理一个地址的结果
+enum Visit {
+    Page(Vec<Link>),
+    File(GrabbedFile),
+    Skipped,
+    Error(String),
+}
+
+struct Filter {
+    extensions: Vec<String>,
+    mime_types: Vec<String>,
+    pattern: Option<regex::Regex>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl Filter {
+    fn new(options: &GrabOptions) -> Result<Self, String> {
+        let pattern = match options.pattern.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+            Some(p) => Some(regex::Regex::new(p).map_err(|e| format!("过滤表达式无效: {}", e))?),
+            None => None,
+        };
+        let list = |v: &[String]| -> Vec<String> {
+            v.iter()
+                .map(|s| s.trim().trim_start_matches('.').to_ascii_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect()
+        };
+        Ok(Self {
+            extensions: list(&options.extensions),
+            mime_types: list(&options.mime_types),
+            pattern,
+            min_size: options.min_size,
+            max_size: options.max_size,
+        })
+    }
+
+    /// 探测前即可判断的条件
+    fn accepts_url(&self, url: &str) -> bool {
+        let ext_ok = self.extensions.is_empty() || self.extensions.iter().any(|e| *e == extension(url));
+        ext_ok && self.pattern.as_ref().map_or(true, |p| p.is_match(url))
+    }
+
+    fn accepts(&self, file: &GrabbedFile) -> bool {
+        let mime_ok = match &file.content_type {
+            Some(ct) if !self.mime_types.is_empty() => self.mime_types.iter().any(|m| ct.starts_with(m.as_str())),
+            _ => true,
+        };
+        let size_ok = file.size.map_or(true, |s| {
+            self.min_size.map_or(true, |min| s >= min) && self.max_size.map_or(true, |max| s <= max)
+        });
+        mime_ok && size_ok
+    }
+}
+
+/// 抓取站点；`cancel` 置位后不再发起新的请求并放弃进行中的请求，返回已得到的结果
+pub async fn grab(
+    protocols: &ProtocolRegistry,
+    net: &NetworkOptions,
+    options: &GrabOptions,
+    cancel: &AtomicBool,
+    on_progress: &(dyn Fn(GrabProgress) + Sync),
+) -> Result<GrabResult, String> {
+    let start = options.url.trim().to_string();
+    let start_host = host(&start).ok_or_else(|| format!("无效地址: {}", start))?;
+    if !protocols.supports(&start) {
+        return Err(format!("不支持的地址: {}", start));
+    }
+    let filter = Filter::new(options)?;
+    let depth = options.depth.max(1);
+    let concurrency = options.concurrency.clamp(1, MAX_CONCURRENCY);
+    let robots = Robots::default();
+    let crawler = Crawler {
+        protocols,
+        net,
+        options,
+        filter: &filter,
+        robots: &robots,
+        cancel,
+    };
+
+    let mut result = GrabResult::default();
+    let mut seen: HashSet<String> = HashSet::from([start.clone()]);
+    // 本层要处理的 (地址, 来源页面, 是否可能是页面)
+    let mut level = vec![(start.clone(), String::new(), true)];
+    for current in 0..depth + 1 {
+        if level.is_empty() {
+            break;
+        }
+        // 最后一层只收集文件，不再进入页面
+        let follow = current < depth;
+        let mut next = Vec::new();
+        let mut next_pages = 0;
+        let mut pending = level.len();
+        let mut visits = stream::iter(level.into_iter().map(|(url, referer, maybe_page)| {
+            let crawler = &crawler;
+            async move {
+                let visit = crawler.visit(&url, &referer, maybe_page && follow).await;
+                (url, visit)
+            }
+        }))
+        .buffer_unordered(concurrency);
+        while let Some((url, visit)) = visits.next().await {
+            pending -= 1;
+            match visit {
+                Visit::Page(links) => {
+                    result.pages += 1;
+                    for link in links {
+                        if !seen.insert(link.url.clone()) {
+                            continue;
+                        }
+                        let maybe_page = link.kind == LinkKind::Anchor
+                            && (!options.same_host || host(&link.url).as_deref() == Some(start_host.as_str()))
+                            && result.pages + next_pages < options.max_pages.max(1);
+                        if !maybe_page && !filter.accepts_url(&link.url) {
+                            result.skipped += 1;
+                            continue;
+                        }
+                        next_pages += usize::from(maybe_page);
+                        next.push((link.url, url.clone(), maybe_page));
+                    }
+                }
+                Visit::File(file) if result.files.len() < options.max_files.max(1) => result.files.push(file),
+                Visit::File(_) | Visit::Skipped => result.skipped += 1,
+                Visit::Error(e) => result.errors.push(format!("{}: {}", url, e)),
+            }
+            on_progress(GrabProgress {
+                pages: result.pages,
+                files: result.files.len(),
+                pending,
+            });
+        }
+        if cancel.load(Ordering::Relaxed) {
+            result.cancelled = true;
+            break;
+        }
+        level = next;
+    }
+    Ok(result)
+}
+
+struct Crawler<'a> {
+    protocols: &'a ProtocolRegistry,
+    net: &'a NetworkOptions,
+    options: &'a GrabOptions,
+    filter: &'a Filter,
+    robots: &'a Robots,
+    cancel: &'a AtomicBool,
+}
+
+impl Crawler<'_> {
+    /// 处理一个地址；取消时放弃进行中的探测与下载
+    async fn visit(&self, url: &str, referer: &str, follow: bool) -> Visit {
+        if self.cancel.load(Ordering::Relaxed) {
+            return Visit::Skipped;
+        }
+        tokio::select! {
+            visit = self.probe_and_fetch(url, referer, follow) => visit,
+            _ = cancelled(self.cancel) => Visit::Skipped,
+        }
+    }
+
+    /// 探测一个地址：是页面且允许进入时解析其中的链接，否则按筛选条件作为候选文件
+    async fn probe_and_fetch(&self, url: &str, referer: &str, follow: bool) -> Visit {
+        let handler = match self.protocols.handler_for(url, self.net) {
+            Ok(h) => h,
+            Err(_) => return Visit::Skipped,
+        };
+        if self.options.respect_robots && !self.robots.allows(handler.as_ref(), url).await {
+            return Visit::Skipped;
+        }
+        let probe = match handler.probe(url).await {
+            Ok(p) => p,
+            Err(e) => return Visit::Error(e.to_string()),
+        };
+        let content_type = probe
+            .content_type
+            .as_deref()
+            .and_then(|ct| ct.split(';').next())
+            .map(|ct| ct.trim().to_ascii_lowercase())
+            .filter(|ct| !ct.is_empty());
+        if is_page(url, &probe, content_type.as_deref()) {
+            if !follow || probe.total_bytes.is_some_and(|n| n > MAX_PAGE_BYTES) {
+                return Visit::Skipped;
+            }
+            if probe.total_bytes == Some(0) {
+                return Visit::Page(Vec::new());
+            }
+            // 服务器未给出大小时边读边检查，超过上限的不解析
+            return match fetch_capped(handler.as_ref(), url, MAX_PAGE_BYTES).await {
+                Ok(Some(body)) => Visit::Page(html::extract_links(&String::from_utf8_lossy(&body), &probe.final_url)),
+                Ok(None) => Visit::Skipped,
+                Err(e) => Visit::Error(e.to_string()),
+            };
+        }
+        let file = GrabbedFile {
+            url: url.to_string(),
+            referer: referer.to_string(),
+            filename: probe.suggested_filename,
+            size: probe.total_bytes,
+            content_type,
+        };
+        if self.filter.accepts_url(url) && self.filter.accepts(&file) {
+            Visit::File(file)
+        } else {
+            Visit::Skipped
+        }
+    }
+}
+
+/// 取消标记置位时完成
+async fn cancelled(cancel: &AtomicBool) {
+    while !cancel.load(Ordering::Relaxed) {
+        tokio::time::sleep(CANCEL_POLL).await;
+    }
+}
+
+/// 读取整个资源，最多 `limit` 字节；超过时返回 None。服务器可能忽略 Range，因此读取时也计数
+async fn fetch_capped(handler: &dyn ProtocolHandler, url: &str, limit: u64) -> Result<Option<Vec<u8>>, NetworkError> {
+    let mut stream = handler.open_range(url, 0, limit).await?;
+    let mut body = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        body.extend_from_slice(&chunk?);
+        if body.len() as u64 > limit {
+            return Ok(None);
+        }
+    }
+    Ok(Some(body))
+}
+
+fn is_page(url: &str, probe: &ProbeResult, content_type: Option<&str>) -> bool {
+    match content_type {
+        Some(ct) => ct == "text/html" || ct == "application/xhtml+xml",
+        None => {
+            let ext = extension(&probe.final_url);
+            let ext = if ext.is_empty() { extension(url) } else { ext };
+            ext.is_empty() || PAGE_EXTENSIONS.contains(&ext.as_str())
+        }
+    }
+}
+
+/// 地址路径最后一段的扩展名（小写）；没有时为空
+fn extension(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    let name = path.split_once('/').map_or("", |(_, p)| p).rsplit('/').next().unwrap_or("");
+    name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default()
+}
+
+/// 主机名（含端口），小写
+fn host(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host,
+    })
+}
+
+/// robots.txt 规则：(路径前缀, 是否允许)
+type RobotsRules = Vec<(String, bool)>;
+
+/// 按站点缓存的 robots.txt 规则
+#[derive(Default)]
+struct Robots {
+    /// 站点根地址 → 规则；取不到 robots.txt 时为空（全部允许）
+    sites: Mutex<HashMap<String, Arc<OnceCell<RobotsRules>>>>,
+}
+
+impl Robots {
+    async fn allows(&self, handler: &dyn ProtocolHandler, url: &str) -> bool {
+        let Ok(parsed) = reqwest::Url::parse(url) else {
+            return true;
+        };
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return true;
+        }
+        let origin = parsed.origin().ascii_serialization();
+        let mut path = parsed.path().to_string();
+        if let Some(q) = parsed.query() {
+            path.push('?');
+            path.push_str(q);
+        }
+        // 同一站点只取一次：同站点的其他地址等待这次请求，不同站点互不影响
+        let cell = self.sites.lock().unwrap().entry(origin.clone()).or_default().clone();
+        let rules = cell
+            .get_or_init(|| async {
+                match fetch_capped(handler, &format!("{}/robots.txt", origin), MAX_ROBOTS_BYTES).await {
+                    Ok(Some(body)) => parse_robots(&String::from_utf8_lossy(&body)),
+                    _ => Vec::new(),
+                }
+            })
+            .await;
+        robots_allows(rules, &path)
+    }
+}
+
+/// 取 User-agent 为 multidown 的分组，没有时取 `*`
+fn parse_robots(text: &str) -> RobotsRules {
+    let mut groups: Vec<(Vec<String>, RobotsRules)> = Vec::new();
+    let mut in_agents = false;
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "user-agent" => {
+                if !in_agents {
+                    groups.push((Vec::new(), Vec::new()));
+                }
+                in_agents = true;
+                if let Some(group) = groups.last_mut() {
+                    group.0.push(value.to_ascii_lowercase());
+                }
+            }
+            key @ ("allow" | "disallow") => {
+                in_agents = false;
+                // 空的 Disallow 表示不限制
+                if let Some(group) = groups.last_mut().filter(|_| !value.is_empty()) {
+                    group.1.push((value.to_string(), key == "allow"));
+                }
+            }
+            _ => in_agents = false,
+        }
+    }
+    let find = |agent: &str| groups.iter().find(|(agents, _)| agents.iter().any(|a| a == agent));
+    find(USER_AGENT_TOKEN)
+        .or_else(|| find("*"))
+        .map(|(_, rules)| rules.clone())
+        .unwrap_or_default()
+}
+
+/// 最长匹配的规则生效，长度相同时 Allow 优先；规则支持 `*` 通配与结尾 `$`
+fn robots_allows(rules: &[(String, bool)], path: &str) -> bool {
+    rules
+        .iter()
+        .filter(|(pattern, _)| robots_match(pattern, path))
+        .max_by_key(|(pattern, allow)| (pattern.len(), *allow))
+        .map_or(true, |(_, allow)| *allow)
+}
+
+fn robots_match(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let mut pieces = pattern.split('*');
+    let first = pieces.next().unwrap_or("");
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let pieces: Vec<&str> = pieces.collect();
+    for (i, piece) in pieces.iter().enumerate() {
+        // 结尾锚定时最后一段须与路径结尾对齐
+        if anchored && i == pieces.len() - 1 {
+            return rest.ends_with(piece);
+        }
+        match rest.find(piece) {
+            Some(pos) => rest = &rest[pos + piece.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
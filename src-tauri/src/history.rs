@@ -0,0 +1,415 @@
+//! 下载历史：记录每个任务的结局（完成、失败、取消）与地址、大小、耗时、平均速度、哈希和来源，
+//! 支持按日期、站点、分类检索与汇总统计，并按设置的保留期限与条数清理
+
+use crate::engine::checksum::{file_digest, HashAlgo};
+use crate::engine::scheduler::Scheduler;
+use crate::engine::{EngineEvent, TaskId, TaskInfo, TaskSource, TaskStatus};
+use crate::intake::{category, host_matches, url_host};
+use crate::settings::AppSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{mpsc, Mutex};
+
+const HISTORY_FILENAME: &str = "multidown_history.jsonl";
+/// 查询未指定条数时每页返回的条数
+const DEFAULT_PAGE: usize = 100;
+
+pub fn history_path(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(HISTORY_FILENAME)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// 记录自身的编号，删除单条时使用（同一任务重新下载会有多条记录）
+    pub id: u64,
+    pub task_id: TaskId,
+    pub url: String,
+    #[serde(default)]
+    pub host: String,
+    pub filename: String,
+    pub save_path: String,
+    /// 文件分类标识，与按类型保存的分类相同（program、document、video……）
+    pub category: String,
+    /// 文件大小；未完成时为已下载量
+    pub size: u64,
+    /// Unix 秒
+    pub started_at: i64,
+    pub finished_at: i64,
+    /// 实际下载的秒数（不含暂停）
+    pub duration_secs: u64,
+    /// 字节/秒；按本次会话传输的字节与下载时长计算
+    pub average_speed: u64,
+    /// 完成的文件的 SHA-256（小写十六进制）
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub source: TaskSource,
+    pub outcome: Outcome,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// 查询条件；均可省略
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryQuery {
+    /// 文件名或地址包含（不区分大小写）
+    pub text: Option<String>,
+    /// 主机名；`*.example.com` 匹配 example.com 及其子域名
+    pub host: Option<String>,
+    pub category: Option<String>,
+    pub outcome: Option<Outcome>,
+    pub source: Option<TaskSource>,
+    /// 结束时间范围（Unix 秒，含两端）
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub offset: usize,
+    /// 省略时 100 条
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    fn matches(&self, e: &HistoryEntry) -> bool {
+        if let Some(text) = self.text.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+            let text = text.to_lowercase();
+            if !e.filename.to_lowercase().contains(&text) && !e.url.to_lowercase().contains(&text) {
+                return false;
+            }
+        }
+        if let Some(pattern) = self.host.as_deref().filter(|h| !h.trim().is_empty()) {
+            if !host_matches(pattern, &e.host) {
+                return false;
+            }
+        }
+        self.category.as_ref().map_or(true, |c| c.is_empty() || *c == e.category)
+            && self.outcome.map_or(true, |o| o == e.outcome)
+            && self.source.map_or(true, |s| s == e.source)
+            && self.from.map_or(true, |t| e.finished_at >= t)
+            && self.to.map_or(true, |t| e.finished_at <= t)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryPage {
+    /// 最近结束的在前
+    pub entries: Vec<HistoryEntry>,
+    /// 符合条件的总条数（分页前）
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Bucket {
+    pub count: usize,
+    /// 完成的下载的字节数
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HistoryStats {
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    /// 完成数 / (完成数 + 失败数)；取消的不计入；没有记录时为 0
+    pub success_rate: f64,
+    pub bytes: u64,
+    /// 按本地日期（YYYY-MM-DD）
+    pub per_day: BTreeMap<String, Bucket>,
+    pub per_host: BTreeMap<String, Bucket>,
+}
+
+/// 下载历史，保存为每行一条的 JSON；新增时追加，清理与删除时整体重写
+pub struct History {
+    path: PathBuf,
+    /// 保留天数与条数（0 为不限），取自设置；设置保存后经 apply_retention 更新
+    retention: std::sync::Mutex<(u32, u32)>,
+    entries: Mutex<Vec<HistoryEntry>>,
+}
+
+impl History {
+    /// 读出已有记录；文件不存在或某行损坏时跳过
+    pub fn load(path: PathBuf, settings: &AppSettings) -> Self {
+        let mut entries: Vec<HistoryEntry> = std::fs::read_to_string(&path)
+            .map(|text| {
+                text.lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        // 旧版本可能写出了乱序的记录
+        entries.sort_by_key(|e| e.finished_at);
+        Self {
+            path,
+            retention: std::sync::Mutex::new((settings.history_retention_days, settings.history_max_entries)),
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// 按结束时间插入一条记录，随后按保留设置清理。完成的记录要等哈希算完才写入，
+    /// 可能晚于之后结束的任务，此时不能只追加到文件末尾
+    pub async fn record(&self, mut entry: HistoryEntry) -> Result<(), String> {
+        let mut entries = self.entries.lock().await;
+        entry.id = entries.iter().map(|e| e.id + 1).max().unwrap_or(1);
+        let mut line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+        let pos = entries.partition_point(|e| e.finished_at <= entry.finished_at);
+        let appended = pos == entries.len();
+        entries.insert(pos, entry);
+        let now = chrono::Utc::now().timestamp();
+        if self.prune(&mut entries, now) || !appended {
+            return self.write_all(&entries).await;
+        }
+        use tokio::io::AsyncWriteExt;
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| e.to_string())?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| e.to_string())?;
+        file.write_all(line.as_bytes()).await.map_err(|e| e.to_string())
+    }
+
+    pub async fn query(&self, query: &HistoryQuery) -> HistoryPage {
+        let entries = self.entries.lock().await;
+        let matched: Vec<&HistoryEntry> = entries.iter().rev().filter(|e| query.matches(e)).collect();
+        HistoryPage {
+            total: matched.len(),
+            entries: matched
+                .into_iter()
+                .skip(query.offset)
+                .take(query.limit.unwrap_or(DEFAULT_PAGE))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// 符合条件的记录的汇总（不分页）
+    pub async fn stats(&self, query: &HistoryQuery) -> HistoryStats {
+        let entries = self.entries.lock().await;
+        summarize(entries.iter().filter(|e| query.matches(e)))
+    }
+
+    /// 删除指定编号的记录，返回删除的条数
+    pub async fn remove(&self, ids: &[u64]) -> Result<usize, String> {
+        let mut entries = self.entries.lock().await;
+        let before = entries.len();
+        entries.retain(|e| !ids.contains(&e.id));
+        let removed = before - entries.len();
+        if removed > 0 {
+            self.write_all(&entries).await?;
+        }
+        Ok(removed)
+    }
+
+    pub async fn clear(&self) -> Result<(), String> {
+        let mut entries = self.entries.lock().await;
+        entries.clear();
+        self.write_all(&entries).await
+    }
+
+    /// 换用新的保留设置并立即清理（保留期限或条数改小后立即生效）
+    pub async fn apply_retention(&self, settings: &AppSettings) -> Result<usize, String> {
+        if let Ok(mut retention) = self.retention.lock() {
+            *retention = (settings.history_retention_days, settings.history_max_entries);
+        }
+        let mut entries = self.entries.lock().await;
+        let before = entries.len();
+        if self.prune(&mut entries, chrono::Utc::now().timestamp()) {
+            self.write_all(&entries).await?;
+        }
+        Ok(before - entries.len())
+    }
+
+    /// 去掉超过保留天数的记录，再只保留最近的若干条；有删除时返回 true
+    fn prune(&self, entries: &mut Vec<HistoryEntry>, now: i64) -> bool {
+        let (days, max_entries) = self.retention.lock().map(|r| *r).unwrap_or_default();
+        prune(entries, days, max_entries, now)
+    }
+
+    async fn write_all(&self, entries: &[HistoryEntry]) -> Result<(), String> {
+        let mut text = String::new();
+        for e in entries {
+            text.push_str(&serde_json::to_string(e).map_err(|e| e.to_string())?);
+            text.push('\n');
+        }
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| e.to_string())?;
+        }
+        tokio::fs::write(&self.path, text).await.map_err(|e| e.to_string())
+    }
+
+    /// 跟踪调度器事件，在任务完成、失败或取消（含删除未结束的任务）时记录；完成的文件在后台计算哈希后再写入。
+    /// 事件须来自不丢弃的通道（ChannelEvents），否则会漏记
+    pub async fn run(self: Arc<Self>, scheduler: Arc<Scheduler>, mut events: mpsc::UnboundedReceiver<EngineEvent>) {
+        let mut sessions: HashMap<TaskId, Session> = HashMap::new();
+        while let Some(event) = events.recv().await {
+            let EngineEvent::StatusChanged { id, status } = event else {
+                continue;
+            };
+            let info = scheduler.get_task(&id).await;
+            match status {
+                TaskStatus::Downloading => {
+                    let Some(info) = info else { continue };
+                    let session = sessions.entry(id).or_insert_with(|| Session {
+                        started_at: chrono::Utc::now().timestamp(),
+                        start_bytes: info.downloaded_bytes,
+                        active_secs: 0.0,
+                        active_since: None,
+                        info: info.clone(),
+                    });
+                    session.active_since.get_or_insert_with(Instant::now);
+                    session.info = info;
+                }
+                TaskStatus::Paused | TaskStatus::Pending | TaskStatus::NeedsRefresh => {
+                    if let Some(session) = sessions.get_mut(&id) {
+                        session.pause();
+                        if let Some(info) = info {
+                            session.info = info;
+                        }
+                    }
+                }
+                TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled => {
+                    let session = sessions.remove(&id);
+                    // 删除的任务已不在列表中，用开始下载时的信息；本次运行中没开始过的任务只在仍可查到时记录
+                    let Some(entry) = finish(session, info, status) else { continue };
+                    let history = self.clone();
+                    tokio::spawn(async move {
+                        let mut entry = entry;
+                        if entry.outcome == Outcome::Completed {
+                            entry.sha256 = file_digest(Path::new(&entry.save_path), HashAlgo::Sha256).await.ok();
+                        }
+                        let _ = history.record(entry).await;
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// 本次运行中一个任务的下载过程
+struct Session {
+    started_at: i64,
+    /// 开始时已下载的字节（断点续传的部分不计入速度）
+    start_bytes: u64,
+    active_secs: f64,
+    active_since: Option<Instant>,
+    /// 最近一次取到的任务信息
+    info: TaskInfo,
+}
+
+impl Session {
+    fn pause(&mut self) {
+        if let Some(since) = self.active_since.take() {
+            self.active_secs += since.elapsed().as_secs_f64();
+        }
+    }
+}
+
+fn finish(session: Option<Session>, info: Option<TaskInfo>, status: TaskStatus) -> Option<HistoryEntry> {
+    let outcome = match status {
+        TaskStatus::Completed => Outcome::Completed,
+        TaskStatus::Failed => Outcome::Failed,
+        _ => Outcome::Cancelled,
+    };
+    let now = chrono::Utc::now().timestamp();
+    let (info, started_at, duration, transferred) = match session {
+        Some(mut s) => {
+            s.pause();
+            let info = info.unwrap_or(s.info);
+            let transferred = info.downloaded_bytes.saturating_sub(s.start_bytes);
+            (info, s.started_at, s.active_secs, transferred)
+        }
+        None => (info?, now, 0.0, 0),
+    };
+    let size = match outcome {
+        Outcome::Completed => info.total_bytes.unwrap_or(info.downloaded_bytes),
+        _ => info.downloaded_bytes,
+    };
+    Some(entry_from(&info, outcome, started_at, now, duration, transferred, size))
+}
+
+fn entry_from(
+    info: &TaskInfo,
+    outcome: Outcome,
+    started_at: i64,
+    finished_at: i64,
+    duration: f64,
+    transferred: u64,
+    size: u64,
+) -> HistoryEntry {
+    HistoryEntry {
+        id: 0,
+        task_id: info.id.clone(),
+        url: info.url.clone(),
+        host: url_host(&info.url).unwrap_or_default(),
+        filename: info.filename.clone(),
+        save_path: info.save_path.clone(),
+        category: category(&info.filename).0.to_string(),
+        size,
+        started_at,
+        finished_at,
+        duration_secs: duration.round() as u64,
+        average_speed: if duration > 0.0 { (transferred as f64 / duration) as u64 } else { 0 },
+        sha256: None,
+        source: info.source,
+        outcome,
+        error: info.error_message.clone().filter(|_| outcome == Outcome::Failed),
+    }
+}
+
+/// 保留天数与条数为 0 表示不限；有删除时返回 true
+pub fn prune(entries: &mut Vec<HistoryEntry>, retention_days: u32, max_entries: u32, now: i64) -> bool {
+    let before = entries.len();
+    // 按结束时间去掉最旧的，不依赖记录的先后
+    entries.sort_by_key(|e| e.finished_at);
+    if retention_days > 0 {
+        let cutoff = now - i64::from(retention_days) * 86_400;
+        entries.retain(|e| e.finished_at >= cutoff);
+    }
+    if max_entries > 0 && entries.len() > max_entries as usize {
+        let excess = entries.len() - max_entries as usize;
+        entries.drain(..excess);
+    }
+    entries.len() != before
+}
+
+/// 汇总：各结局的条数、成功率，完成的下载按日期与站点累计字节
+pub fn summarize<'a>(entries: impl Iterator<Item = &'a HistoryEntry>) -> HistoryStats {
+    let mut stats = HistoryStats::default();
+    for e in entries {
+        stats.total += 1;
+        match e.outcome {
+            Outcome::Completed => stats.completed += 1,
+            Outcome::Failed => stats.failed += 1,
+            Outcome::Cancelled => stats.cancelled += 1,
+        }
+        let bytes = if e.outcome == Outcome::Completed { e.size } else { 0 };
+        stats.bytes += bytes;
+        let day = chrono::DateTime::from_timestamp(e.finished_at, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        for bucket in [stats.per_day.entry(day).or_default(), stats.per_host.entry(e.host.clone()).or_default()] {
+            bucket.count += 1;
+            bucket.bytes += bytes;
+        }
+    }
+    let decided = stats.completed + stats.failed;
+    if decided > 0 {
+        stats.success_rate = stats.completed as f64 / decided as f64;
+    }
+    stats
+}
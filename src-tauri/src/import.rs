@@ -0,0 +1,612 @@
+//! 导入任务列表：本程序导出的 JSON、URL 列表（wget -i）、aria2 输入文件、HAR、IDM 导出的 .ef2，
+//! 可另附 Netscape 格式的 Cookie 文件（cookies.txt）。这里只解析不新建，新建统一交给任务入口
+
+use crate::intake::{IntakeItem, IntakeKind};
+use crate::migrate::{self, ExportData};
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    /// 本程序导出的任务列表
+    Multidown,
+    /// 每行一个地址，`#` 开头为注释
+    UrlList,
+    /// aria2 `--input-file`：地址行（Tab 分隔的多个地址为同一文件的镜像）后跟缩进的 `选项=值`
+    Aria2,
+    Har,
+    /// IDM 导出：`<` 与 `>` 之间一条，首行地址，其后为 `referer: ` 等字段
+    Ef2,
+}
+
+/// 试导入的结果：尚未新建任何任务
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportPreview {
+    pub format: ImportFormat,
+    pub items: Vec<IntakeItem>,
+    /// 跳过的条目与忽略的选项
+    pub warnings: Vec<String>,
+    /// 从 Cookie 文件带上了 Cookie 的条目数
+    pub cookies_attached: usize,
+    /// 带完整下载状态（第 2 版任务列表）、可以迁移恢复的条目数
+    pub restorable: usize,
+    /// 可迁移任务所在的目录，恢复时可逐个映射到新目录
+    pub directories: Vec<String>,
+}
+
+/// 解析并附加 Cookie；format 省略时按内容判断
+pub fn preview(text: &str, format: Option<ImportFormat>, cookies: Option<&str>) -> Result<ImportPreview, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let format = format.unwrap_or_else(|| detect(text));
+    let mut restorable = 0;
+    let mut directories = Vec::new();
+    let (mut items, warnings) = match format {
+        ImportFormat::Multidown => {
+            let data: ExportData = serde_json::from_str(text.trim()).map_err(|e| e.to_string())?;
+            restorable = data.tasks.iter().filter(|t| t.state.is_some()).count();
+            directories = migrate::directories(&data);
+            (multidown_items(data), Vec::new())
+        }
+        ImportFormat::UrlList => parse_url_list(text),
+        ImportFormat::Aria2 => parse_aria2(text),
+        ImportFormat::Har => parse_har(text)?,
+        ImportFormat::Ef2 => parse_ef2(text),
+    };
+    let cookies_attached = match cookies.map(|c| c.trim_start_matches('\u{feff}')).filter(|c| !c.trim().is_empty()) {
+        Some(c) => {
+            let jar = parse_cookies(c)?;
+            attach_cookies(&mut items, &jar, chrono::Utc::now().timestamp())
+        }
+        None => 0,
+    };
+    Ok(ImportPreview {
+        format,
+        items,
+        warnings,
+        cookies_attached,
+        restorable,
+        directories,
+    })
+}
+
+/// 按内容判断格式：JSON 中有 `log` 为 HAR，否则为任务列表；首行为 `<` 为 .ef2；有缩进的 `选项=值` 行为 aria2
+pub fn detect(text: &str) -> ImportFormat {
+    let trim = text.trim_start();
+    if trim.starts_with('{') {
+        let har = serde_json::from_str::<serde_json::Value>(trim).is_ok_and(|v| v.get("log").is_some());
+        return if har { ImportFormat::Har } else { ImportFormat::Multidown };
+    }
+    if trim.lines().next().map(str::trim) == Some("<") {
+        return ImportFormat::Ef2;
+    }
+    let aria2 = text
+        .lines()
+        .any(|l| l.starts_with([' ', '\t']) && l.trim().split_once('=').is_some_and(|(k, _)| !k.contains("://")));
+    if aria2 {
+        ImportFormat::Aria2
+    } else {
+        ImportFormat::UrlList
+    }
+}
+
+/// 导入的请求头中可以沿用的：排除由下载过程自行决定的（Host、Range、压缩、连接与条件请求）
+pub fn is_forwardable_header(name: &str) -> bool {
+    const RESERVED: &[&str] = &[
+        "host",
+        "range",
+        "if-range",
+        "if-match",
+        "if-none-match",
+        "if-modified-since",
+        "if-unmodified-since",
+        "accept-encoding",
+        "content-length",
+        "content-type",
+        "connection",
+        "keep-alive",
+        "transfer-encoding",
+        "te",
+        "upgrade",
+        "proxy-authorization",
+        "proxy-connection",
+    ];
+    let name = name.trim().to_ascii_lowercase();
+    !name.is_empty() && !name.starts_with(':') && !RESERVED.contains(&name.as_str())
+}
+
+/// `名称: 值` 形式的一行请求头
+fn split_header(line: &str) -> Option<(String, String)> {
+    let (name, value) = line.split_once(':')?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| (name.to_string(), value.trim().to_string()))
+}
+
+fn is_url(s: &str) -> bool {
+    s.split_once("://").is_some_and(|(scheme, rest)| {
+        !scheme.is_empty() && scheme.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'+') && !rest.is_empty()
+    })
+}
+
+/// 条目与任务列表一一对应（恢复时按序号选择）；带完整状态的沿用其中的请求头
+fn multidown_items(data: ExportData) -> Vec<IntakeItem> {
+    data.tasks
+        .into_iter()
+        .map(|t| IntakeItem {
+            url: t.url.trim().to_string(),
+            mirrors: t.mirrors,
+            // 导出的是完整保存路径，取其所在目录
+            save_dir: std::path::Path::new(&t.save_path)
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .filter(|d| !d.is_empty()),
+            filename: Some(t.filename).filter(|f| !f.is_empty()),
+            probe: None,
+            headers: t.state.map(|s| s.headers).unwrap_or_default(),
+            kind: IntakeKind::Auto,
+        })
+        .collect()
+}
+
+fn parse_url_list(text: &str) -> (Vec<IntakeItem>, Vec<String>) {
+    let mut items = Vec::new();
+    let mut warnings = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if is_url(line) {
+            items.push(IntakeItem::new(line));
+        } else {
+            warnings.push(format!("第 {} 行不是下载地址，已跳过", i + 1));
+        }
+    }
+    (items, warnings)
+}
+
+fn parse_aria2(text: &str) -> (Vec<IntakeItem>, Vec<String>) {
+    let mut items: Vec<IntakeItem> = Vec::new();
+    let mut warnings = Vec::new();
+    // 当前条目的 dir 与 out，条目结束时合成保存目录与文件名（out 可以带子目录）
+    let mut dir: Option<String> = None;
+    let mut out: Option<String> = None;
+    let finish = |items: &mut Vec<IntakeItem>, dir: &mut Option<String>, out: &mut Option<String>| {
+        let Some(item) = items.last_mut() else {
+            return;
+        };
+        let out = out.take();
+        let (sub, name) = match out.as_deref().map(|o| o.rsplit_once(['/', '\\'])) {
+            Some(Some((sub, name))) => (Some(sub.to_string()), Some(name.to_string())),
+            Some(None) => (None, out),
+            None => (None, None),
+        };
+        item.save_dir = match (dir.take(), sub) {
+            (Some(d), Some(s)) => Some(std::path::Path::new(&d).join(s).to_string_lossy().into_owned()),
+            (d, s) => d.or(s),
+        };
+        item.filename = name.filter(|n| !n.is_empty());
+    };
+    let mut current = false;
+    for (i, line) in text.lines().enumerate() {
+        let n = i + 1;
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !line.starts_with([' ', '\t']) {
+            if current {
+                finish(&mut items, &mut dir, &mut out);
+            }
+            let mut uris = line.split('\t').map(str::trim).filter(|u| !u.is_empty());
+            current = false;
+            match uris.next() {
+                Some(url) if is_url(url) => {
+                    let mut item = IntakeItem::new(url);
+                    item.mirrors = uris.filter(|u| is_url(u)).map(String::from).collect();
+                    items.push(item);
+                    current = true;
+                }
+                _ => warnings.push(format!("第 {} 行不是下载地址，已跳过", n)),
+            }
+            continue;
+        }
+        if !current {
+            continue;
+        }
+        let Some((key, value)) = line.trim().split_once('=') else {
+            warnings.push(format!("第 {} 行无法识别，已忽略", n));
+            continue;
+        };
+        let value = value.trim();
+        let item = items.last_mut().expect("current item");
+        match key.trim() {
+            "dir" => dir = Some(value.to_string()),
+            "out" => out = Some(value.to_string()),
+            "header" => match split_header(value) {
+                Some(h) if is_forwardable_header(&h.0) => item.headers.push(h),
+                _ => warnings.push(format!("第 {} 行：请求头 {} 已忽略", n, value)),
+            },
+            "referer" => item.headers.push(("Referer".to_string(), value.to_string())),
+            "user-agent" => item.headers.push(("User-Agent".to_string(), value.to_string())),
+            other => warnings.push(format!("第 {} 行：不支持的选项 {}，已忽略", n, other)),
+        }
+    }
+    if current {
+        finish(&mut items, &mut dir, &mut out);
+    }
+    (items, warnings)
+}
+
+#[derive(Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Deserialize)]
+struct HarLog {
+    #[serde(default)]
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Deserialize)]
+struct HarEntry {
+    request: HarRequest,
+    #[serde(default)]
+    response: Option<HarResponse>,
+}
+
+#[derive(Deserialize)]
+struct HarRequest {
+    #[serde(default)]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<HarPair>,
+    #[serde(default)]
+    cookies: Vec<HarPair>,
+}
+
+#[derive(Deserialize)]
+struct HarResponse {
+    #[serde(default)]
+    status: i64,
+}
+
+#[derive(Deserialize)]
+struct HarPair {
+    name: String,
+    #[serde(default)]
+    value: String,
+}
+
+/// 取 GET 且未失败的请求，带上其请求头与 Cookie；同一地址只取第一次
+fn parse_har(text: &str) -> Result<(Vec<IntakeItem>, Vec<String>), String> {
+    let har: Har = serde_json::from_str(text.trim()).map_err(|e| format!("HAR 文件格式错误: {}", e))?;
+    let mut items = Vec::new();
+    let mut seen = HashSet::new();
+    let (mut not_get, mut failed) = (0, 0);
+    for entry in har.log.entries {
+        let request = entry.request;
+        if !(request.method.is_empty() || request.method.eq_ignore_ascii_case("GET")) {
+            not_get += 1;
+            continue;
+        }
+        // 状态 0 表示未收到响应（被取消或由浏览器缓存提供），仍可重新请求
+        let status = entry.response.map_or(0, |r| r.status);
+        if status >= 300 {
+            failed += 1;
+            continue;
+        }
+        let lower = request.url.to_ascii_lowercase();
+        if !(lower.starts_with("http://") || lower.starts_with("https://")) || !seen.insert(request.url.clone()) {
+            continue;
+        }
+        let mut headers: Vec<(String, String)> = request
+            .headers
+            .into_iter()
+            .filter(|h| is_forwardable_header(&h.name))
+            .map(|h| (h.name, h.value))
+            .collect();
+        if !request.cookies.is_empty() && !headers.iter().any(|(k, _)| k.eq_ignore_ascii_case("cookie")) {
+            let cookie = request
+                .cookies
+                .iter()
+                .map(|c| format!("{}={}", c.name, c.value))
+                .collect::<Vec<_>>()
+                .join("; ");
+            headers.push(("Cookie".to_string(), cookie));
+        }
+        let mut item = IntakeItem::new(request.url);
+        item.headers = headers;
+        items.push(item);
+    }
+    let mut warnings = Vec::new();
+    if not_get > 0 {
+        warnings.push(format!("{} 个非 GET 请求已跳过", not_get));
+    }
+    if failed > 0 {
+        warnings.push(format!("{} 个重定向或失败的请求已跳过", failed));
+    }
+    Ok((items, warnings))
+}
+
+fn parse_ef2(text: &str) -> (Vec<IntakeItem>, Vec<String>) {
+    let mut items = Vec::new();
+    let mut warnings = Vec::new();
+    let mut block: Option<Vec<&str>> = None;
+    for line in text.lines().map(str::trim) {
+        match (line, block.as_mut()) {
+            ("<", _) => block = Some(Vec::new()),
+            (">", Some(_)) => {
+                if let Some(lines) = block.take() {
+                    ef2_item(&lines, &mut items, &mut warnings);
+                }
+            }
+            (_, Some(lines)) if !line.is_empty() => lines.push(line),
+            _ => {}
+        }
+    }
+    if let Some(lines) = block {
+        ef2_item(&lines, &mut items, &mut warnings);
+    }
+    (items, warnings)
+}
+
+fn ef2_item(lines: &[&str], items: &mut Vec<IntakeItem>, warnings: &mut Vec<String>) {
+    let Some((&url, fields)) = lines.split_first() else {
+        return;
+    };
+    if !is_url(url) {
+        warnings.push(format!("{} 不是下载地址，已跳过", url));
+        return;
+    }
+    let mut item = IntakeItem::new(url);
+    let (mut username, mut password) = (None, None);
+    for field in fields {
+        let Some((key, value)) = split_header(field) else {
+            continue;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "referer" => item.headers.push(("Referer".to_string(), value)),
+            "user-agent" => item.headers.push(("User-Agent".to_string(), value)),
+            "cookie" => item.headers.push(("Cookie".to_string(), value)),
+            "filename" => item.filename = Some(value).filter(|f| !f.is_empty()),
+            "username" => username = Some(value),
+            "password" => password = Some(value),
+            "postdata" => {
+                warnings.push(format!("{} 需要 POST 请求，已跳过", url));
+                return;
+            }
+            other => warnings.push(format!("{}：不支持的字段 {}，已忽略", url, other)),
+        }
+    }
+    if let Some(user) = username.filter(|u| !u.is_empty()) {
+        let credentials = format!("{}:{}", user, password.unwrap_or_default());
+        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+        item.headers.push(("Authorization".to_string(), format!("Basic {}", encoded)));
+    }
+    items.push(item);
+}
+
+/// Netscape 格式 Cookie 文件中的一条
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    /// 不含开头的 `.`，小写
+    pub domain: String,
+    /// 是否也发给子域名
+    pub include_subdomains: bool,
+    pub path: String,
+    pub secure: bool,
+    /// Unix 秒；0 为会话 Cookie
+    pub expires: i64,
+    pub name: String,
+    pub value: String,
+}
+
+/// 每行 7 个 Tab 分隔的字段：域名、是否含子域名、路径、是否仅 HTTPS、过期时间、名称、值；
+/// `#HttpOnly_` 开头的行是 HttpOnly Cookie，其余 `#` 开头的是注释
+pub fn parse_cookies(text: &str) -> Result<Vec<Cookie>, String> {
+    let mut cookies = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 6 {
+            return Err(format!("Cookie 文件第 {} 行格式错误", i + 1));
+        }
+        let flag = |s: &str| s.trim().eq_ignore_ascii_case("TRUE");
+        let domain = fields[0].trim().to_ascii_lowercase();
+        cookies.push(Cookie {
+            include_subdomains: flag(fields[1]) || domain.starts_with('.'),
+            domain: domain.trim_start_matches('.').to_string(),
+            path: fields[2].trim().to_string(),
+            secure: flag(fields[3]),
+            expires: fields[4].trim().parse().unwrap_or(0),
+            name: fields[5].trim().to_string(),
+            value: fields.get(6).map_or("", |v| v.trim()).to_string(),
+        });
+    }
+    if cookies.is_empty() {
+        return Err("Cookie 文件中没有 Cookie".to_string());
+    }
+    Ok(cookies)
+}
+
+/// 请求路径与 Cookie 路径相同，或以其为前缀且在 `/` 处分隔（`/a` 匹配 `/a/b`，不匹配 `/ab`）
+fn path_matches(path: &str, cookie_path: &str) -> bool {
+    match path.strip_prefix(cookie_path) {
+        Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// 按主地址的域名、路径与协议把未过期的 Cookie 合入条目的 Cookie 请求头（已有的同名 Cookie 不覆盖），返回带上了 Cookie 的条目数
+pub fn attach_cookies(items: &mut [IntakeItem], cookies: &[Cookie], now: i64) -> usize {
+    let mut attached = 0;
+    for item in items {
+        let Ok(url) = reqwest::Url::parse(item.url.trim()) else {
+            continue;
+        };
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let matching: Vec<&Cookie> = cookies
+            .iter()
+            .filter(|c| host == c.domain || (c.include_subdomains && host.ends_with(&format!(".{}", c.domain))))
+            .filter(|c| path_matches(url.path(), &c.path))
+            .filter(|c| !c.secure || url.scheme() == "https")
+            .filter(|c| c.expires == 0 || c.expires > now)
+            .collect();
+        if matching.is_empty() {
+            continue;
+        }
+        let index = match item.headers.iter().position(|(k, _)| k.eq_ignore_ascii_case("cookie")) {
+            Some(i) => i,
+            None => {
+                item.headers.push(("Cookie".to_string(), String::new()));
+                item.headers.len() - 1
+            }
+        };
+        let header = &mut item.headers[index].1;
+        let mut names: HashSet<String> = header
+            .split(';')
+            .filter_map(|pair| pair.split_once('=').map(|(n, _)| n.trim().to_string()))
+            .collect();
+        for cookie in matching {
+            if names.insert(cookie.name.clone()) {
+                if !header.trim().is_empty() {
+                    header.push_str("; ");
+                }
+                header.push_str(&format!("{}={}", cookie.name, cookie.value));
+            }
+        }
+        attached += 1;
+    }
+    attached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header<'a>(item: &'a IntakeItem, name: &str) -> Option<&'a str> {
+        item.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(detect(r#"{"log":{"entries":[]}}"#), ImportFormat::Har);
+        assert_eq!(detect(r#"{"version":2,"tasks":[]}"#), ImportFormat::Multidown);
+        assert_eq!(detect("<\r\nhttp://h/a\r\n>\r\n"), ImportFormat::Ef2);
+        assert_eq!(detect("http://h/a\n  out=a.bin\n"), ImportFormat::Aria2);
+        assert_eq!(detect("http://h/a\n# c\nhttp://h/b\n"), ImportFormat::UrlList);
+        // 缩进的地址行里的 `=` 不算 aria2 选项
+        assert_eq!(detect("  http://h/a?x=1\n"), ImportFormat::UrlList);
+    }
+
+    #[test]
+    fn aria2_mirrors_options_and_out_subdirectory() {
+        let text = "http://h/a.bin\thttp://m/a.bin\tnot-a-url\n  dir=/data\n  out=sub/dir/a.bin\n  header=Accept: */*\n  header=Range: bytes=0-\n  referer=http://h/\n  max-tries=3\n\nhttp://h/b.bin\n\tout=b.bin\nftp://h/c.bin\n  out=x/c.bin\nnope\n  out=ignored\n";
+        let (items, warnings) = parse_aria2(text);
+        assert_eq!(items.len(), 3);
+        let a = &items[0];
+        assert_eq!(a.mirrors, ["http://m/a.bin"]);
+        let expected = std::path::Path::new("/data").join("sub/dir").to_string_lossy().into_owned();
+        assert_eq!(a.save_dir.as_deref(), Some(expected.as_str()));
+        assert_eq!(a.filename.as_deref(), Some("a.bin"));
+        assert_eq!(header(a, "accept"), Some("*/*"));
+        assert_eq!(header(a, "range"), None);
+        assert_eq!(header(a, "referer"), Some("http://h/"));
+        assert_eq!((items[1].save_dir.as_deref(), items[1].filename.as_deref()), (None, Some("b.bin")));
+        // 只有 out 带子目录时子目录即保存目录
+        assert_eq!((items[2].save_dir.as_deref(), items[2].filename.as_deref()), (Some("x"), Some("c.bin")));
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert!(warnings.iter().any(|w| w.contains("max-tries")));
+        assert!(warnings.iter().any(|w| w.starts_with("第 13 行不是下载地址")));
+    }
+
+    #[test]
+    fn har_keeps_successful_gets_with_headers_and_cookies() {
+        let text = r#"{"log":{"entries":[
+            {"request":{"method":"GET","url":"https://h/a.bin","headers":[{"name":":authority","value":"h"},{"name":"Host","value":"h"},{"name":"Referer","value":"https://h/"}],"cookies":[{"name":"sid","value":"1"},{"name":"t","value":"2"}]},"response":{"status":200}},
+            {"request":{"method":"GET","url":"https://h/a.bin","headers":[]},"response":{"status":200}},
+            {"request":{"method":"POST","url":"https://h/api","headers":[]},"response":{"status":200}},
+            {"request":{"method":"GET","url":"https://h/moved","headers":[]},"response":{"status":302}},
+            {"request":{"method":"GET","url":"https://h/c.bin","headers":[{"name":"Cookie","value":"own=1"}],"cookies":[{"name":"sid","value":"1"}]},"response":{"status":0}},
+            {"request":{"method":"GET","url":"data:text/plain,x","headers":[]}}
+        ]}}"#;
+        let (items, warnings) = parse_har(text).unwrap();
+        assert_eq!(items.iter().map(|i| i.url.as_str()).collect::<Vec<_>>(), ["https://h/a.bin", "https://h/c.bin"]);
+        assert_eq!(items[0].headers, [("Referer".to_string(), "https://h/".to_string()), ("Cookie".to_string(), "sid=1; t=2".to_string())]);
+        assert_eq!(header(&items[1], "cookie"), Some("own=1"));
+        assert_eq!(warnings, ["1 个非 GET 请求已跳过", "1 个重定向或失败的请求已跳过"]);
+        assert!(parse_har("{\"log\":").is_err());
+    }
+
+    #[test]
+    fn ef2_blocks() {
+        let text = "<\r\nhttp://h/a.bin\r\nreferer: http://h/\r\nUser-Agent: UA\r\ncookie: a=1\r\nfilename: a.bin\r\nusername: u\r\npassword: p\r\nflags: 1\r\n>\r\n<\r\nhttp://h/form\r\npostdata: x=1\r\n>\r\n<\r\nnot a url\r\n>\r\n<\r\nhttp://h/last.bin\r\n";
+        let (items, warnings) = parse_ef2(text);
+        assert_eq!(items.len(), 2);
+        let a = &items[0];
+        assert_eq!(a.url, "http://h/a.bin");
+        assert_eq!(a.filename.as_deref(), Some("a.bin"));
+        assert_eq!(header(a, "referer"), Some("http://h/"));
+        assert_eq!(header(a, "user-agent"), Some("UA"));
+        assert_eq!(header(a, "cookie"), Some("a=1"));
+        assert_eq!(header(a, "authorization"), Some("Basic dTpw"));
+        // 没有结尾 `>` 的最后一条也导入
+        assert_eq!(items[1].url, "http://h/last.bin");
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert!(warnings.iter().any(|w| w.contains("POST")));
+    }
+
+    #[test]
+    fn parses_netscape_cookies() {
+        let text = "# Netscape HTTP Cookie File\n.example.com\tTRUE\t/\tFALSE\t0\tsid\tabc\n#HttpOnly_dl.example.com\tFALSE\t/files\tTRUE\t2000000000\ttok\tx y\r\nexample.org\tFALSE\t/\tFALSE\t0\tempty\n";
+        let jar = parse_cookies(text).unwrap();
+        assert_eq!(jar.len(), 3);
+        assert_eq!(jar[0].domain, "example.com");
+        assert!(jar[0].include_subdomains);
+        assert_eq!(jar[1].domain, "dl.example.com");
+        assert!(!jar[1].include_subdomains && jar[1].secure);
+        assert_eq!((jar[1].path.as_str(), jar[1].expires, jar[1].value.as_str()), ("/files", 2_000_000_000, "x y"));
+        assert_eq!((jar[2].name.as_str(), jar[2].value.as_str()), ("empty", ""));
+        assert!(parse_cookies("# only comments\n").is_err());
+        assert!(parse_cookies("example.com\tTRUE\t/\n").is_err());
+    }
+
+    #[test]
+    fn attaches_cookies_by_domain_path_scheme_and_expiry() {
+        let cookie = |domain: &str, sub: bool, path: &str, secure: bool, expires: i64, name: &str| Cookie {
+            domain: domain.to_string(),
+            include_subdomains: sub,
+            path: path.to_string(),
+            secure,
+            expires,
+            name: name.to_string(),
+            value: "v".to_string(),
+        };
+        let jar = [
+            cookie("example.com", true, "/", false, 0, "all"),
+            cookie("example.com", false, "/", false, 0, "exact"),
+            cookie("example.com", true, "/files", false, 0, "files"),
+            cookie("example.com", true, "/", true, 0, "secure"),
+            cookie("example.com", true, "/", false, 100, "expired"),
+            cookie("example.com", true, "/", false, 0, "mine"),
+        ];
+        let mut items = vec![
+            IntakeItem::new("https://example.com/files/a.bin"),
+            IntakeItem::new("http://dl.example.com/filesystem/b.bin"),
+            IntakeItem::new("http://notexample.com/c.bin"),
+        ];
+        items[0].headers.push(("cookie".to_string(), "mine=old".to_string()));
+        assert_eq!(attach_cookies(&mut items, &jar, 1000), 2);
+        assert_eq!(header(&items[0], "cookie"), Some("mine=old; all=v; exact=v; files=v; secure=v"));
+        assert_eq!(items[0].headers.len(), 1);
+        assert_eq!(header(&items[1], "cookie"), Some("all=v; mine=v"));
+        assert!(items[2].headers.is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+//! 单实例：应用数据目录下的锁文件保证同一时间只有一个进程读写任务列表；
+//! 后启动的进程经本机 IPC（见 `browser::ipc`）把启动参数转交给已运行的实例后退出。
+//! 启动参数也承载 `multidown://` 协议链接与双击关联文件打开的路径。
+
+use crate::browser::{self, ipc};
+use crate::network;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+pub const LOCK_FILENAME: &str = "multidown.lock";
+
+/// 协议链接：`multidown://add?url=<编码后的地址>&referer=…&filename=…&mirror=…`
+pub const LINK_SCHEME: &str = "multidown";
+/// 导出任务列表的扩展名（内容为 JSON），与 .metalink/.meta4 一起关联到本程序
+pub const TASK_LIST_EXTENSION: &str = "mdtasks";
+
+/// 转交参数时等待已运行实例就绪的次数与间隔（实例可能刚启动，端点尚未创建）
+const FORWARD_ATTEMPTS: u32 = 20;
+const FORWARD_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// 持有期间其他进程无法取得锁；进程退出（包括崩溃）时由系统释放
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// 尝试取得单实例锁；已被其他进程持有时返回 Ok(None)
+pub fn try_lock(app_data: &Path) -> std::io::Result<Option<InstanceLock>> {
+    std::fs::create_dir_all(app_data)?;
+    let path = app_data.join(LOCK_FILENAME);
+    let mut opts = OpenOptions::new();
+    opts.create(true).write(true).truncate(false);
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        let file = opts.open(&path)?;
+        // SAFETY: fd 在 file 存活期间有效；flock 不涉及内存
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = std::io::Error::last_os_error();
+            return match err.kind() {
+                std::io::ErrorKind::WouldBlock => Ok(None),
+                _ => Err(err),
+            };
+        }
+        Ok(Some(InstanceLock { _file: file }))
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        // 不共享打开，其他进程再打开即失败（32 = ERROR_SHARING_VIOLATION）
+        match opts.share_mode(0).open(&path) {
+            Ok(file) => Ok(Some(InstanceLock { _file: file })),
+            Err(e) if e.raw_os_error() == Some(32) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// 启动参数：要下载的地址、要打开的文件（.metalink 等）与是否显示主窗口
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchArgs {
+    #[serde(default)]
+    pub urls: Vec<String>,
+    /// 绝对路径
+    #[serde(default)]
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub show: bool,
+}
+
+impl LaunchArgs {
+    /// 解析命令行（不含程序名）：`--show`、`--add <地址或文件>`，其余不以 `-` 开头的参数按地址或文件处理；
+    /// 未知选项忽略（部分平台启动时会附加自己的参数）。相对路径按 cwd 补全
+    pub fn parse<I: IntoIterator<Item = String>>(args: I, cwd: &Path) -> Self {
+        let mut out = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--show" => out.show = true,
+                "--add" => {
+                    if let Some(value) = args.next() {
+                        out.push(&value, cwd);
+                    }
+                }
+                _ => {
+                    if let Some(value) = arg.strip_prefix("--add=") {
+                        out.push(value, cwd);
+                    } else if !arg.starts_with('-') {
+                        out.push(&arg, cwd);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn push(&mut self, value: &str, cwd: &Path) {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+        if value.contains("://") || is_link(value) {
+            self.urls.push(value.to_string());
+        } else {
+            let path = PathBuf::from(value);
+            let path = if path.is_absolute() { path } else { cwd.join(path) };
+            self.files.push(path.to_string_lossy().into_owned());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.urls.is_empty() && self.files.is_empty() && !self.show
+    }
+}
+
+/// 把启动参数交给已运行的实例；没有参数时请它显示主窗口
+pub async fn forward(app_data: &Path, args: &LaunchArgs) -> Result<(), String> {
+    let mut args = args.clone();
+    if args.is_empty() {
+        args.show = true;
+    }
+    let mut last_err = String::new();
+    for attempt in 0..FORWARD_ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(FORWARD_RETRY_DELAY).await;
+        }
+        match try_forward(app_data, &args).await {
+            Ok(result) => return result,
+            Err(e) => last_err = e,
+        }
+    }
+    Err(format!("无法连接正在运行的 Multidown: {}", last_err))
+}
+
+/// 外层错误表示连接或握手失败（可重试），内层为实例处理参数的结果
+async fn try_forward(app_data: &Path, args: &LaunchArgs) -> Result<Result<(), String>, String> {
+    let token = std::fs::read_to_string(app_data.join(browser::TOKEN_FILENAME))
+        .map(|t| t.trim().to_string())
+        .map_err(|e| e.to_string())?;
+    let mut stream = BufReader::new(ipc::connect(app_data).await.map_err(|e| e.to_string())?);
+
+    let hello = serde_json::json!({ "action": "hello", "token": token, "versions": [browser::PROTOCOL_VERSION] });
+    // 令牌可能正被新启动的实例改写，握手失败按可重试处理
+    request(&mut stream, &hello).await?;
+    let mut launch = serde_json::to_value(args).map_err(|e| e.to_string())?;
+    launch["action"] = "launch".into();
+    Ok(request(&mut stream, &launch).await)
+}
+
+/// 发送一行命令并读取一行应答，应答 ok 为 false 时返回其中的错误
+async fn request(stream: &mut BufReader<ipc::ClientStream>, body: &serde_json::Value) -> Result<(), String> {
+    let line = format!("{}\n", body);
+    stream.get_mut().write_all(line.as_bytes()).await.map_err(|e| e.to_string())?;
+    let mut reply = String::new();
+    if stream.read_line(&mut reply).await.map_err(|e| e.to_string())? == 0 {
+        return Err("连接已关闭".into());
+    }
+    let reply: serde_json::Value = serde_json::from_str(&reply).map_err(|e| e.to_string())?;
+    if reply["ok"].as_bool() == Some(true) {
+        Ok(())
+    } else {
+        Err(reply["error"].as_str().unwrap_or("未知错误").to_string())
+    }
+}
+
+/// 是否为 `multidown:` 协议链接
+pub fn is_link(value: &str) -> bool {
+    value
+        .split_once(':')
+        .is_some_and(|(scheme, _)| scheme.eq_ignore_ascii_case(LINK_SCHEME))
+}
+
+/// 解析协议链接为下载请求。链接来自网页，只接受地址、来源页、文件名与镜像，
+/// 保存目录、Cookie 等字段一律忽略
+pub fn parse_link(link: &str) -> Result<browser::DownloadRequest, String> {
+    let rest = match link.trim().split_once(':') {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case(LINK_SCHEME) => rest.trim_start_matches('/'),
+        _ => return Err("不是 multidown 链接".to_string()),
+    };
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let action = action.trim_end_matches('/');
+    if !action.eq_ignore_ascii_case("add") {
+        return Err(format!("不支持的链接操作: {}", action));
+    }
+    let mut url = None;
+    let mut referer = None;
+    let mut filename = None;
+    let mut mirrors = Vec::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = urlencoding::decode(&value.replace('+', " "))
+            .map_err(|_| "链接参数编码无效".to_string())?
+            .trim()
+            .to_string();
+        if value.is_empty() {
+            continue;
+        }
+        match key {
+            "url" => url = Some(value),
+            "referer" => referer = Some(value),
+            "filename" => filename = Some(value),
+            "mirror" => mirrors.push(value),
+            _ => {}
+        }
+    }
+    let url = url.ok_or_else(|| "链接缺少 url 参数".to_string())?;
+    if !network::is_supported_url(&url) {
+        return Err(format!("不支持的下载地址: {}", url));
+    }
+    if let Some(r) = &referer {
+        let lower = r.to_ascii_lowercase();
+        if !lower.starts_with("http://") && !lower.starts_with("https://") {
+            return Err("来源页地址无效".to_string());
+        }
+    }
+    if let Some(name) = &filename {
+        browser::check_filename(name)?;
+    }
+    let mut request = browser::DownloadRequest::new(url);
+    request.referer = referer;
+    request.filename = filename;
+    request.mirrors = mirrors.into_iter().filter(|m| network::is_supported_url(m)).collect();
+    Ok(request)
+}
+
+/// 是否为导出的任务列表文件
+pub fn is_task_list(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(TASK_LIST_EXTENSION))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> LaunchArgs {
+        LaunchArgs::parse(list.iter().map(|s| s.to_string()), Path::new("/work"))
+    }
+
+    #[test]
+    fn parses_launch_args() {
+        let parsed = args(&[
+            "--show",
+            "--add",
+            "https://h/a.bin",
+            "--add=multidown://add?url=https%3A%2F%2Fh%2Fb.bin",
+            "lists/mirrors.meta4",
+            "/abs/tasks.mdtasks",
+            "-psn_0_12345",
+            "--unknown",
+            "--add",
+            "  ",
+        ]);
+        assert!(parsed.show);
+        assert_eq!(parsed.urls, ["https://h/a.bin", "multidown://add?url=https%3A%2F%2Fh%2Fb.bin"]);
+        let expected = Path::new("/work").join("lists/mirrors.meta4").to_string_lossy().into_owned();
+        assert_eq!(parsed.files, [expected, "/abs/tasks.mdtasks".to_string()]);
+        assert!(args(&["--add"]).is_empty());
+        assert!(!args(&["--show"]).is_empty());
+        assert!(is_task_list("/abs/tasks.MDTASKS"));
+        assert!(!is_task_list("/abs/tasks.json"));
+    }
+
+    #[test]
+    fn parses_add_link() {
+        let request = parse_link(
+            "MultiDown://add/?url=https%3A%2F%2Fexample.com%2Ffile%20name.zip&referer=https://example.com/page&filename=my+file.zip&mirror=ftp%3A%2F%2Fm%2Ff.zip&mirror=javascript%3Aalert(1)&save_path=%2Fetc&cookie=a%3D1",
+        )
+        .unwrap();
+        assert_eq!(request.url, "https://example.com/file name.zip");
+        assert_eq!(request.referer.as_deref(), Some("https://example.com/page"));
+        assert_eq!(request.filename.as_deref(), Some("my file.zip"));
+        assert_eq!(request.mirrors, ["ftp://m/f.zip"]);
+        // 网页传来的保存目录与 Cookie 一律忽略
+        assert_eq!(request.save_path, None);
+        assert_eq!(request.cookie, None);
+        assert!(is_link("multidown:add?url=x"));
+        assert!(!is_link("https://h/a"));
+    }
+
+    #[test]
+    fn rejects_invalid_links() {
+        let err = |link: &str| parse_link(link).unwrap_err();
+        assert!(err("https://example.com/a.zip").contains("不是 multidown 链接"));
+        assert!(err("multidown://remove?url=https%3A%2F%2Fh%2Fa").contains("不支持的链接操作"));
+        assert!(err("multidown://add?referer=https%3A%2F%2Fh%2F").contains("缺少 url"));
+        assert!(err("multidown://add?url=file%3A%2F%2F%2Fetc%2Fpasswd").contains("不支持的下载地址"));
+        assert!(err("multidown://add?url=https%3A%2F%2Fh%2Fa&referer=javascript%3Aalert(1)").contains("来源页"));
+        assert!(err("multidown://add?url=https%3A%2F%2Fh%2Fa&filename=..%2F..%2F.bashrc").contains("路径"));
+        assert!(err("multidown://add?url=https%3A%2F%2Fh%2Fa&filename=a%5Cb").contains("路径"));
+        assert!(err("multidown://add?url=%FF").contains("编码"));
+    }
+}
@@ -0,0 +1,577 @@
+//! 任务入口：界面添加、批量添加、导入、浏览器扩展、启动参数与控制接口新建任务都经过这里，
+//! 按同一套规则校验地址、决定保存目录（站点规则、分类）、处理重复链接，并按设置开始下载或排队
+
+use crate::engine::metalink::{self, MetalinkFile};
+use crate::engine::scheduler::Scheduler;
+use crate::engine::{dash, hls, DashOptions, EngineEvent, HlsOptions, TaskId, TaskInfo, TaskSource, TaskStatus};
+use crate::network::{NetworkOptions, ProbeResult};
+use crate::settings::{load_settings, save_settings, AppSettings, SiteRule};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::Mutex;
+
+/// 分类：(标识, 目录名, 扩展名)，与界面「下载文件信息」中的分类一致
+const CATEGORIES: &[(&str, &str, &[&str])] = &[
+    ("program", "程序", &["exe", "msi", "dmg", "pkg", "deb", "rpm", "apk", "appimage"]),
+    ("document", "文档", &["pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "txt", "epub"]),
+    ("video", "视频", &["mp4", "mkv", "avi", "mov", "wmv", "flv", "webm", "ts", "m4v"]),
+    ("archive", "压缩包", &["zip", "rar", "7z", "tar", "gz", "bz2", "xz", "tgz", "iso"]),
+];
+const OTHER_CATEGORY: (&str, &str) = ("other", "其他");
+
+/// 按扩展名取分类的 (标识, 目录名)
+pub fn category(filename: &str) -> (&'static str, &'static str) {
+    let ext = Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    CATEGORIES
+        .iter()
+        .find(|(_, _, exts)| exts.contains(&ext.as_str()))
+        .map(|(id, label, _)| (*id, *label))
+        .unwrap_or(OTHER_CATEGORY)
+}
+
+/// 地址的主机名（小写，去掉用户信息、端口与末尾的点）
+pub fn url_host(url: &str) -> Option<String> {
+    url.split_once("://")
+        .map(|(_, rest)| rest)
+        .and_then(|rest| rest.split(['/', '?', '#']).next())
+        .map(|authority| authority.rsplit('@').next().unwrap_or(authority))
+        .map(|h| h.rsplit_once(':').filter(|(_, port)| port.parse::<u16>().is_ok()).map_or(h, |(h, _)| h))
+        .map(|h| h.trim_end_matches('.').to_ascii_lowercase())
+}
+
+/// 地址对应的站点规则：主机名完全相同，或规则为 `*.example.com` 时匹配 example.com 及其子域名
+pub fn site_rule<'a>(rules: &'a [SiteRule], url: &str) -> Option<&'a SiteRule> {
+    let host = url_host(url)?;
+    rules.iter().find(|r| !r.host.trim().is_empty() && host_matches(&r.host, &host))
+}
+
+/// 主机名是否符合模式：完全相同，或模式为 `*.example.com` 时为 example.com 及其子域名
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host == domain || host.ends_with(&format!(".{}", domain)),
+        None => host == pattern,
+    }
+}
+
+/// 与已有任务重复（地址相同或保存路径相同）时的处理方式，对应设置项 duplicate_action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// 不新建，交由调用方询问用户
+    Ask,
+    Skip,
+    /// 删除已有任务后新建
+    Overwrite,
+    /// 新建，文件名加序号
+    Rename,
+}
+
+impl DuplicatePolicy {
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "skip" => Self::Skip,
+            "overwrite" => Self::Overwrite,
+            "rename" => Self::Rename,
+            _ => Self::Ask,
+        }
+    }
+
+    /// 无人可询问的来源（控制接口等）把 ask 按 rename 处理，与 aria2 默认的自动改名一致
+    pub fn unattended(self) -> Self {
+        match self {
+            Self::Ask => Self::Rename,
+            other => other,
+        }
+    }
+}
+
+/// 一条新建请求；除地址外均可省略
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntakeItem {
+    pub url: String,
+    pub mirrors: Vec<String>,
+    /// 保存目录；省略时依次取站点规则、默认保存路径（开启分类时再加分类子目录）
+    pub save_dir: Option<String>,
+    pub filename: Option<String>,
+    /// 调用方已探测过时传入，避免重复探测
+    pub probe: Option<ProbeResult>,
+    /// 探测与下载时附带的请求头（Cookie、Referer 等），随任务保存
+    pub headers: Vec<(String, String)>,
+    /// 任务类型；只由程序内部指定
+    #[serde(skip)]
+    pub kind: IntakeKind,
+}
+
+/// 新建的任务类型
+#[derive(Debug, Clone, Default)]
+pub enum IntakeKind {
+    /// 按探测结果：播放列表、清单按 HLS、DASH 处理，其余为普通文件
+    #[default]
+    Auto,
+    Hls(HlsOptions),
+    Dash(DashOptions),
+    /// Metalink 中的一个文件：地址、镜像与校验值取自清单
+    Metalink(MetalinkFile),
+}
+
+impl IntakeItem {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct IntakeOptions {
+    /// 省略时按设置
+    pub duplicate: Option<DuplicatePolicy>,
+    /// 新建后开始下载；同时下载数已达上限时排队，有空位后自动开始
+    pub start: bool,
+    /// 新建时不探测，开始下载时再探测（批量添加大量地址时使用）；文件名取自地址，带镜像的请求仍立即探测
+    pub lazy_probe: bool,
+    /// 记入下载历史的来源
+    pub source: TaskSource,
+}
+
+/// 单条请求的结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum IntakeResult {
+    /// 已新建，未要求开始
+    Added { id: TaskId },
+    Started { id: TaskId },
+    /// 已新建，等待空位自动开始
+    Queued { id: TaskId },
+    /// 重复，按设置跳过
+    Skipped { existing: TaskId },
+    /// 重复，需要用户选择处理方式后以明确的 duplicate 重新提交
+    Duplicate { existing: TaskId },
+    /// 地址或参数无效
+    Rejected { error: String },
+    /// 探测、新建或开始失败
+    Failed { error: String },
+}
+
+impl IntakeResult {
+    /// 新建出的任务
+    pub fn task_id(&self) -> Option<&TaskId> {
+        match self {
+            Self::Added { id } | Self::Started { id } | Self::Queued { id } => Some(id),
+            _ => None,
+        }
+    }
+
+    /// 转为单个任务 ID；未新建时返回说明
+    pub fn into_id(self) -> Result<TaskId, String> {
+        match self {
+            Self::Added { id } | Self::Started { id } | Self::Queued { id } => Ok(id),
+            Self::Skipped { existing } => Err(format!("已有相同的任务，已跳过（{}）", existing)),
+            Self::Duplicate { existing } => Err(format!("已有相同的任务（{}）", existing)),
+            Self::Rejected { error } | Self::Failed { error } => Err(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IntakeOutcome {
+    pub url: String,
+    #[serde(flatten)]
+    pub result: IntakeResult,
+}
+
+pub struct Intake {
+    scheduler: Arc<Scheduler>,
+    settings_path: PathBuf,
+    /// 设置缓存；经 save_settings 修改
+    settings: std::sync::RwLock<AppSettings>,
+    /// 设置中未指定默认保存目录时使用
+    fallback_save_dir: String,
+    /// 等待空位自动开始的任务，先进先出
+    queue: Mutex<VecDeque<TaskId>>,
+}
+
+impl Intake {
+    pub fn new(scheduler: Arc<Scheduler>, settings_path: PathBuf, fallback_save_dir: String) -> Self {
+        let settings = load_settings(&settings_path).unwrap_or_default();
+        Self {
+            scheduler,
+            settings_path,
+            settings: std::sync::RwLock::new(settings),
+            fallback_save_dir,
+            queue: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn scheduler(&self) -> &Arc<Scheduler> {
+        &self.scheduler
+    }
+
+    pub fn settings(&self) -> AppSettings {
+        self.settings.read().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// 保存设置并更新缓存；各处修改设置都经过这里
+    pub async fn save_settings(&self, settings: &AppSettings) -> Result<(), String> {
+        save_settings(&self.settings_path, settings).await.map_err(|e| e.to_string())?;
+        if let Ok(mut cached) = self.settings.write() {
+            *cached = settings.clone();
+        }
+        Ok(())
+    }
+
+    pub fn default_save_dir(&self, settings: &AppSettings) -> String {
+        if settings.default_save_path.is_empty() {
+            self.fallback_save_dir.clone()
+        } else {
+            settings.default_save_path.clone()
+        }
+    }
+
+    /// 逐条处理，结果与请求一一对应；设置与网络选项整批只读取一次
+    pub async fn submit(&self, items: Vec<IntakeItem>, options: IntakeOptions) -> Vec<IntakeOutcome> {
+        let settings = self.settings();
+        let net = match settings.network_options().await {
+            Ok(net) => net,
+            Err(error) => {
+                return items
+                    .into_iter()
+                    .map(|item| IntakeOutcome {
+                        url: item.url.trim().to_string(),
+                        result: IntakeResult::Failed { error: error.clone() },
+                    })
+                    .collect();
+            }
+        };
+        let policy = options
+            .duplicate
+            .unwrap_or_else(|| DuplicatePolicy::from_setting(&settings.duplicate_action));
+        let mut outcomes = Vec::with_capacity(items.len());
+        for item in items {
+            let url = item.url.trim().to_string();
+            let result = self.add(item, &settings, &net, policy, options).await;
+            outcomes.push(IntakeOutcome { url, result });
+        }
+        outcomes
+    }
+
+    /// 只提交一条
+    pub async fn submit_one(&self, item: IntakeItem, options: IntakeOptions) -> IntakeResult {
+        self.submit(vec![item], options)
+            .await
+            .pop()
+            .map(|o| o.result)
+            .unwrap_or_else(|| IntakeResult::Failed { error: "internal".to_string() })
+    }
+
+    async fn add(
+        &self,
+        item: IntakeItem,
+        settings: &AppSettings,
+        net: &NetworkOptions,
+        policy: DuplicatePolicy,
+        options: IntakeOptions,
+    ) -> IntakeResult {
+        let url = item.url.trim().to_string();
+        if !self.scheduler.protocols().supports(&url) {
+            return IntakeResult::Rejected {
+                error: format!("不支持的下载地址: {}", url),
+            };
+        }
+        let filename = item.filename.map(|f| f.trim().to_string()).filter(|f| !f.is_empty());
+        if let Some(name) = &filename {
+            if let Err(error) = crate::browser::check_filename(name) {
+                return IntakeResult::Rejected { error };
+            }
+        }
+        if let Err(error) = check_headers(&item.headers) {
+            return IntakeResult::Rejected { error };
+        }
+        let headers = item.headers;
+        let mirrors: Vec<String> = item
+            .mirrors
+            .into_iter()
+            .map(|m| m.trim().to_string())
+            .filter(|m| self.scheduler.protocols().supports(m))
+            .collect();
+
+        let kind = item.kind;
+        // 指定了类型的任务不在这里探测：HLS、DASH 开始下载时读取清单，Metalink 新建时按清单探测镜像
+        let probe = match item.probe {
+            Some(p) => Some(p),
+            None if !matches!(kind, IntakeKind::Auto) => None,
+            None if options.lazy_probe && mirrors.is_empty() => None,
+            None => match self.probe(&url, &net.with_headers(&headers)).await {
+                Ok(p) => Some(p),
+                Err(error) => return IntakeResult::Failed { error },
+            },
+        };
+        let filename = filename.unwrap_or_else(|| match &probe {
+            Some(p) => p.suggested_filename.clone(),
+            None => filename_from_url(&url),
+        });
+        // 按输出文件判断重复
+        let mut filename = match &kind {
+            IntakeKind::Hls(_) => hls::output_filename(&filename),
+            IntakeKind::Dash(_) => dash::output_filename(&filename),
+            _ => filename,
+        };
+        let rule = site_rule(&settings.site_rules, &url);
+        let save_dir = match item.save_dir.map(|d| d.trim().to_string()).filter(|d| !d.is_empty()) {
+            Some(dir) => dir,
+            None => {
+                let base = rule
+                    .map(|r| r.save_dir.trim())
+                    .filter(|d| !d.is_empty())
+                    .map(String::from)
+                    .unwrap_or_else(|| self.default_save_dir(settings));
+                if settings.categorize_downloads {
+                    Path::new(&base).join(category(&filename).1).to_string_lossy().into_owned()
+                } else {
+                    base
+                }
+            }
+        };
+
+        let tasks = self.scheduler.list_downloads().await;
+        let target = Path::new(&save_dir).join(&filename);
+        if let Some(existing) = find_duplicate(&tasks, &url, &target) {
+            match policy {
+                DuplicatePolicy::Ask => return IntakeResult::Duplicate { existing },
+                DuplicatePolicy::Skip => return IntakeResult::Skipped { existing },
+                DuplicatePolicy::Overwrite => {
+                    self.queue.lock().await.retain(|id| *id != existing);
+                    if let Err(error) = self.scheduler.remove_task(&existing).await {
+                        return IntakeResult::Failed { error };
+                    }
+                }
+                DuplicatePolicy::Rename => filename = unique_filename(&save_dir, &filename, &tasks),
+            }
+        }
+
+        let created = match (kind, probe) {
+            (IntakeKind::Hls(hls), _) => self.scheduler.create_hls_task(url, save_dir, Some(filename), hls).await,
+            (IntakeKind::Dash(dash), _) => self.scheduler.create_dash_task(url, save_dir, Some(filename), dash).await,
+            (IntakeKind::Metalink(file), _) => {
+                self.scheduler
+                    .create_metalink_task(file, save_dir, filename, &net.with_headers(&headers))
+                    .await
+            }
+            (IntakeKind::Auto, Some(probe)) => {
+                self.scheduler
+                    .create_task_with_mirrors(url, mirrors, save_dir, Some(filename), Some(probe), &net.with_headers(&headers))
+                    .await
+            }
+            (IntakeKind::Auto, None) => self.scheduler.create_deferred_task(url, save_dir, filename).await,
+        };
+        let id = match created {
+            Ok(id) => id,
+            Err(error) => return IntakeResult::Failed { error },
+        };
+        let attached = async {
+            if !headers.is_empty() {
+                self.scheduler.set_task_headers(&id, headers).await?;
+            }
+            if options.source != TaskSource::Manual {
+                self.scheduler.set_task_source(&id, options.source).await?;
+            }
+            Ok::<(), String>(())
+        }
+        .await;
+        if let Err(error) = attached {
+            // 请求头或来源没记上：删掉刚建的任务，不留下与结果不符的半成品
+            let _ = self.scheduler.remove_task(&id).await;
+            return IntakeResult::Failed { error };
+        }
+        if !options.start {
+            return IntakeResult::Added { id };
+        }
+        self.start_or_queue_with(id, settings, net).await
+    }
+
+    /// 开始已有的任务（如认领了新地址的等待任务）；同时下载数已达上限时排队
+    pub async fn start_or_queue(&self, id: &str) -> IntakeResult {
+        let settings = self.settings();
+        let net = match settings.network_options().await {
+            Ok(net) => net,
+            Err(error) => return IntakeResult::Failed { error },
+        };
+        self.start_or_queue_with(id.to_string(), &settings, &net).await
+    }
+
+    async fn start_or_queue_with(&self, id: TaskId, settings: &AppSettings, net: &NetworkOptions) -> IntakeResult {
+        let mut queue = self.queue.lock().await;
+        if has_free_slot(&self.scheduler, settings).await {
+            match self.start(&id, settings, net).await {
+                Ok(()) => IntakeResult::Started { id },
+                Err(error) => IntakeResult::Failed { error },
+            }
+        } else {
+            if !queue.contains(&id) {
+                queue.push_back(id.clone());
+            }
+            IntakeResult::Queued { id }
+        }
+    }
+
+    async fn probe(&self, url: &str, net: &NetworkOptions) -> Result<ProbeResult, String> {
+        let handler = self.scheduler.protocols().handler_for(url, net).map_err(|e| e.to_string())?;
+        handler.probe(url).await.map_err(|e| e.to_string())
+    }
+
+    /// 按设置与站点规则的连接数开始下载
+    async fn start(&self, id: &str, settings: &AppSettings, net: &NetworkOptions) -> Result<(), String> {
+        let url = self.scheduler.get_task(id).await.map(|t| t.url).unwrap_or_default();
+        let connections = site_rule(&settings.site_rules, &url)
+            .map(|r| r.max_connections)
+            .filter(|&n| n > 0)
+            .unwrap_or(settings.max_connections_per_task);
+        self.scheduler
+            .start_download(id, Some(self.scheduler.clone()), Some(connections as usize), Some(net.clone()))
+            .await
+    }
+
+    /// 有空位时按先后开始排队的任务；已被手动开始或删除的任务直接移出队列
+    pub async fn fill_slots(&self) {
+        let mut queue = self.queue.lock().await;
+        if queue.is_empty() {
+            return;
+        }
+        let settings = self.settings();
+        // 网络设置无效时保留队列，改好设置后的下一次补位再开始
+        let Ok(net) = settings.network_options().await else {
+            return;
+        };
+        while !queue.is_empty() && has_free_slot(&self.scheduler, &settings).await {
+            let Some(id) = queue.pop_front() else {
+                break;
+            };
+            // 认领新地址的任务为暂停状态，其余为等待状态
+            let waiting = self
+                .scheduler
+                .get_task(&id)
+                .await
+                .is_some_and(|t| matches!(t.status, TaskStatus::Pending | TaskStatus::Paused));
+            if waiting {
+                let _ = self.start(&id, &settings, &net).await;
+            }
+        }
+    }
+
+    /// 任务结束、暂停或失败后补位；随应用一直运行
+    pub async fn run_queue(self: Arc<Self>, mut events: broadcast::Receiver<EngineEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(EngineEvent::Progress { .. }) => continue,
+                // 排队中被暂停的任务移出队列，等用户继续时再开始；暂停下载中的任务则空出位置
+                Ok(EngineEvent::StatusChanged {
+                    id,
+                    status: TaskStatus::Paused,
+                }) => {
+                    self.queue.lock().await.retain(|queued| *queued != id);
+                    self.fill_slots().await;
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => self.fill_slots().await,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+/// 未探测时的文件名：地址路径的最后一段（去掉查询参数并解码），取不到时为 download
+fn filename_from_url(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    path.split_once('/')
+        .map(|(_, p)| p.trim_end_matches('/'))
+        .and_then(|p| p.rsplit('/').next())
+        .map(|name| urlencoding::decode(name).map(|n| n.into_owned()).unwrap_or_else(|_| name.to_string()))
+        .filter(|name| !name.is_empty() && crate::browser::check_filename(name).is_ok())
+        .unwrap_or_else(|| "download".to_string())
+}
+
+/// Metalink 清单中的文件逐个转为新建请求：主地址为优先级最高的受支持地址；没有受支持地址的文件跳过
+pub fn metalink_items(xml: &str, save_dir: Option<String>, supports: impl Fn(&str) -> bool) -> Result<Vec<IntakeItem>, String> {
+    let items: Vec<IntakeItem> = metalink::parse_metalink(xml)?
+        .into_iter()
+        .filter_map(|mut file| {
+            file.urls.retain(|(u, _)| supports(u));
+            let url = file.urls.first()?.0.clone();
+            Some(IntakeItem {
+                url,
+                save_dir: save_dir.clone(),
+                filename: Some(file.name.clone()),
+                kind: IntakeKind::Metalink(file),
+                ..IntakeItem::default()
+            })
+        })
+        .collect();
+    if items.is_empty() {
+        return Err("Metalink 中没有受支持协议的下载地址".to_string());
+    }
+    Ok(items)
+}
+
+/// 请求头须是合法的名称与值，且不能是由下载过程自行决定的（Host、Range 等）
+fn check_headers(headers: &[(String, String)]) -> Result<(), String> {
+    for (name, value) in headers {
+        let name = name.trim();
+        if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+            return Err(format!("无效的请求头名称: {}", name));
+        }
+        if reqwest::header::HeaderValue::from_str(value.trim()).is_err() {
+            return Err(format!("请求头 {} 的值无效", name));
+        }
+        if !crate::import::is_forwardable_header(name) {
+            return Err(format!("不能指定请求头 {}", name));
+        }
+    }
+    Ok(())
+}
+
+/// 同时下载数未达上限（0 表示不限）
+async fn has_free_slot(scheduler: &Scheduler, settings: &AppSettings) -> bool {
+    if settings.max_concurrent_tasks == 0 {
+        return true;
+    }
+    let active = scheduler
+        .list_downloads()
+        .await
+        .iter()
+        .filter(|t| t.status == TaskStatus::Downloading)
+        .count();
+    active < settings.max_concurrent_tasks as usize
+}
+
+/// 地址（含镜像）相同或保存路径相同的未取消任务
+fn find_duplicate(tasks: &[TaskInfo], url: &str, target: &Path) -> Option<TaskId> {
+    tasks
+        .iter()
+        .filter(|t| t.status != TaskStatus::Cancelled)
+        .find(|t| t.url == url || t.sources.iter().any(|s| s.url == url) || Path::new(&t.save_path) == target)
+        .map(|t| t.id.clone())
+}
+
+/// 在文件名后加 (1)、(2)…，直到既不与已有任务冲突也不与磁盘上的文件冲突
+fn unique_filename(save_dir: &str, filename: &str, tasks: &[TaskInfo]) -> String {
+    let path = Path::new(filename);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| format!("{} ({}){}", stem, n, ext))
+        .find(|name| {
+            let target = Path::new(save_dir).join(name);
+            !target.exists() && !tasks.iter().any(|t| Path::new(&t.save_path) == target)
+        })
+        .unwrap_or_else(|| filename.to_string())
+}
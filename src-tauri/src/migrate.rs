@@ -0,0 +1,307 @@
+//! 任务迁移：导出完整任务状态（第 2 版任务列表，可连同已下载的文件打包为 zip），
+//! 在另一台机器上按目录映射恢复，文件不在或大小对不上的任务从头下载
+
+use crate::engine::scheduler::Scheduler;
+use crate::engine::{initial_segments, PersistedTask, TaskId, TaskInfo, TaskKind, TaskStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+pub const EXPORT_VERSION: u32 = 2;
+/// 打包中的任务列表
+pub const ARCHIVE_LIST: &str = "tasks.mdtasks";
+/// 打包中已下载的文件按任务 id 存放在此目录下
+const ARCHIVE_FILES: &str = "files/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportTask {
+    pub url: String,
+    #[serde(default)]
+    pub save_path: String,
+    #[serde(default)]
+    pub filename: String,
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    /// 第 2 版起：完整任务状态（进度、请求头、校验、状态与创建时间）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<PersistedTask>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportData {
+    #[serde(default = "first_version")]
+    pub version: u32,
+    /// Unix 秒
+    #[serde(default)]
+    pub exported_at: i64,
+    #[serde(default)]
+    pub tasks: Vec<ExportTask>,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+/// 当前全部任务，按创建时间排列
+pub async fn export(scheduler: &Scheduler) -> ExportData {
+    let tasks = scheduler
+        .snapshot_tasks()
+        .await
+        .into_iter()
+        .map(|p| ExportTask {
+            url: p.url.clone(),
+            save_path: p.save_path.clone(),
+            filename: p.filename.clone(),
+            mirrors: p.sources.iter().filter(|s| **s != p.url).cloned().collect(),
+            state: Some(p),
+        })
+        .collect();
+    ExportData {
+        version: EXPORT_VERSION,
+        exported_at: chrono::Utc::now().timestamp(),
+        tasks,
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ArchiveSummary {
+    pub tasks: usize,
+    /// 一并打包了文件的任务数
+    pub files: usize,
+    /// 正在下载、未打包文件的任务（文件名）
+    pub skipped: Vec<String>,
+}
+
+/// 写出 zip：任务列表与普通任务已下载的文件（流媒体任务的分片不打包）；正在下载的任务文件还在变化，不打包
+pub fn write_archive(data: &ExportData, path: &Path) -> Result<ArchiveSummary, String> {
+    use std::io::Write;
+    use zip::write::FileOptions;
+
+    let json = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    zip.start_file(ARCHIVE_LIST, FileOptions::default())
+        .map_err(|e| e.to_string())?;
+    zip.write_all(json.as_bytes()).map_err(|e| e.to_string())?;
+    // 已下载的内容多为压缩过的格式，直接存储
+    let stored = FileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .large_file(true);
+    let mut summary = ArchiveSummary {
+        tasks: data.tasks.len(),
+        ..ArchiveSummary::default()
+    };
+    for state in data.tasks.iter().filter_map(|t| t.state.as_ref()) {
+        if state.kind != TaskKind::File || (state.downloaded_bytes == 0 && state.status != TaskStatus::Completed) {
+            continue;
+        }
+        if state.status == TaskStatus::Downloading {
+            summary.skipped.push(state.filename.clone());
+            continue;
+        }
+        let Ok(mut file) = File::open(&state.save_path) else {
+            continue;
+        };
+        zip.start_file(format!("{}{}", ARCHIVE_FILES, state.id), stored)
+            .map_err(|e| e.to_string())?;
+        std::io::copy(&mut file, &mut zip).map_err(|e| e.to_string())?;
+        summary.files += 1;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
+/// 读出 zip 中的任务列表
+pub fn read_archive_list(path: &Path) -> Result<String, String> {
+    use std::io::Read;
+    let mut archive = zip::ZipArchive::new(File::open(path).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+    let mut entry = archive
+        .by_name(ARCHIVE_LIST)
+        .map_err(|_| "不是 Multidown 导出的任务包".to_string())?;
+    let mut text = String::new();
+    entry.read_to_string(&mut text).map_err(|e| e.to_string())?;
+    Ok(text)
+}
+
+/// 目录映射：保存路径在 from 之下的任务改到 to 之下
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirMapping {
+    pub from: String,
+    pub to: String,
+}
+
+/// 带完整状态的任务所在的目录（去重排序），供界面逐个填写新目录
+pub fn directories(data: &ExportData) -> Vec<String> {
+    data.tasks
+        .iter()
+        .filter_map(|t| t.state.as_ref())
+        .filter_map(|s| s.save_path.rsplit_once(['/', '\\']).map(|(dir, _)| dir.to_string()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// 按最长匹配的映射改写保存路径；`/` 与 `\` 视为相同，从 Windows 迁到其他系统也能匹配
+pub fn remap(save_path: &str, mappings: &[DirMapping]) -> String {
+    let normalize = |s: &str| s.replace('\\', "/").trim_end_matches('/').to_string();
+    let path = normalize(save_path);
+    mappings
+        .iter()
+        .filter(|m| !m.from.trim().is_empty())
+        .filter_map(|m| {
+            let from = normalize(m.from.trim());
+            let rest = path.strip_prefix(&from)?.strip_prefix('/')?;
+            Some((from.len(), m, rest.to_string()))
+        })
+        .max_by_key(|(len, _, _)| *len)
+        .map(|(_, m, rest)| {
+            rest.split('/')
+                .fold(PathBuf::from(m.to.trim()), |p, part| p.join(part))
+                .to_string_lossy()
+                .into_owned()
+        })
+        .unwrap_or_else(|| save_path.to_string())
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RestoreReport {
+    pub restored: Vec<TaskId>,
+    /// 文件不在或大小不符、将从头下载的任务数
+    pub reset: usize,
+    /// 目标位置已有其他文件、改名恢复的任务
+    pub renamed: Vec<String>,
+    /// 未恢复的任务及原因
+    pub skipped: Vec<String>,
+}
+
+/// 准备要恢复的任务（会从 zip 中解出文件，须在阻塞线程中调用）：selected 为任务列表中的序号，省略时全部；
+/// 映射后的保存路径不在 allowed 目录之内的、与已有任务 id、地址或保存路径相同的跳过；
+/// 从归档恢复时目标位置已有文件（不是本归档解出的）则改名，不覆盖也不把它当作下载进度；
+/// 下载中的改为暂停，进度与磁盘上的文件对不上时从头开始
+pub fn prepare_restore(
+    data: ExportData,
+    selected: Option<&[usize]>,
+    mappings: &[DirMapping],
+    allowed: &[PathBuf],
+    archive: Option<&Path>,
+    existing: &[TaskInfo],
+) -> Result<(Vec<PersistedTask>, RestoreReport), String> {
+    let mut zip = match archive {
+        Some(path) => Some(zip::ZipArchive::new(File::open(path).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?),
+        None => None,
+    };
+    let mut report = RestoreReport::default();
+    let mut restored: Vec<PersistedTask> = Vec::new();
+    for (i, task) in data.tasks.into_iter().enumerate() {
+        if selected.is_some_and(|s| !s.contains(&i)) {
+            continue;
+        }
+        let Some(mut state) = task.state else {
+            report.skipped.push(format!("{}：没有下载进度，请用普通导入", task.url));
+            continue;
+        };
+        state.save_path = remap(&state.save_path, mappings);
+        // 导出文件不可信：与浏览器扩展指定的目录同样校验，避免把文件解到任意位置
+        if let Err(e) = crate::browser::check_save_dir(&state.save_path, allowed) {
+            report.skipped.push(format!("{}：{}（{}）", state.filename, e, state.save_path));
+            continue;
+        }
+        let duplicate = existing
+            .iter()
+            .any(|t| t.id == state.id || t.url == state.url || Path::new(&t.save_path) == Path::new(&state.save_path))
+            || restored
+                .iter()
+                .any(|t| t.id == state.id || Path::new(&t.save_path) == Path::new(&state.save_path));
+        if duplicate {
+            report.skipped.push(format!("{}：已有相同的任务", state.filename));
+            continue;
+        }
+        if let Some(zip) = zip.as_mut() {
+            if Path::new(&state.save_path).exists() {
+                let taken = |p: &Path| {
+                    p.exists()
+                        || existing.iter().any(|t| Path::new(&t.save_path) == p)
+                        || restored.iter().any(|t| Path::new(&t.save_path) == p)
+                };
+                let Some(free) = free_path(Path::new(&state.save_path), taken) else {
+                    report.skipped.push(format!("{}：目标位置已有同名文件", state.filename));
+                    continue;
+                };
+                let filename = free.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                report.renamed.push(format!("{}：目标位置已有同名文件，改存为 {}", state.filename, filename));
+                state.filename = filename;
+                state.save_path = free.to_string_lossy().into_owned();
+            }
+            if let Ok(mut entry) = zip.by_name(&format!("{}{}", ARCHIVE_FILES, state.id)) {
+                let target = Path::new(&state.save_path);
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                let mut out = File::options()
+                    .write(true)
+                    .create_new(true)
+                    .open(target)
+                    .map_err(|e| e.to_string())?;
+                std::io::copy(&mut entry, &mut out).map_err(|e| e.to_string())?;
+            }
+        }
+        if !file_matches(&state, Path::new(&state.save_path)) {
+            if state.downloaded_bytes > 0 || state.status == TaskStatus::Completed {
+                report.reset += 1;
+            }
+            state.downloaded_bytes = 0;
+            state.pending_segments = initial_segments(state.supports_range, state.total_bytes).into();
+            if state.status == TaskStatus::Completed {
+                state.status = TaskStatus::Pending;
+            }
+        }
+        if matches!(state.status, TaskStatus::Downloading | TaskStatus::NeedsRefresh) {
+            state.status = TaskStatus::Paused;
+        }
+        restored.push(state);
+    }
+    Ok((restored, report))
+}
+
+/// 在文件名后加 (1)、(2)…，找一个未被占用的路径
+fn free_path(target: &Path, taken: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+    let stem = target.file_stem()?.to_string_lossy().into_owned();
+    let ext = target.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..1000)
+        .map(|n| target.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|p| !taken(p))
+}
+
+/// 磁盘上的文件能否接着用：普通任务大小已知时写入前已预分配到总大小，未知时与已下载量相同；
+/// 未完成的流媒体任务的分片另存、没有随任务迁移，一律从头下载
+fn file_matches(state: &PersistedTask, path: &Path) -> bool {
+    if state.downloaded_bytes == 0 && state.status != TaskStatus::Completed {
+        return true;
+    }
+    let Ok(meta) = std::fs::metadata(path) else {
+        return false;
+    };
+    match state.kind {
+        TaskKind::File => meta.len() == state.total_bytes.unwrap_or(state.downloaded_bytes),
+        _ => state.status == TaskStatus::Completed,
+    }
+}
+
+/// 恢复选中的任务；恢复后均不自动开始
+pub async fn restore(
+    scheduler: &Scheduler,
+    data: ExportData,
+    selected: Option<Vec<usize>>,
+    mappings: Vec<DirMapping>,
+    allowed: Vec<PathBuf>,
+    archive: Option<PathBuf>,
+) -> Result<RestoreReport, String> {
+    let existing = scheduler.list_downloads().await;
+    let (tasks, mut report) = tokio::task::spawn_blocking(move || {
+        prepare_restore(data, selected.as_deref(), &mappings, &allowed, archive.as_deref(), &existing)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    report.restored = scheduler.restore_tasks(tasks).await;
+    Ok(report)
+}
@@ -1,5 +1,7 @@
+use crate::network::proxy::{ProxyConfig, ProxyMode};
 use reqwest::Client;
 use serde::Serialize;
+use std::future::Future;
 use std::time::Duration;
 use thiserror::Error;
 
@@ -9,13 +11,82 @@ pub enum Error {
     Request(#[from] reqwest::Error),
     #[error("Invalid URL: {0}")]
     Url(String),
+    #[error("Proxy error: {0}")]
+    Proxy(String),
+    #[error("FTP error: {0}")]
+    Ftp(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Link expired (HTTP {0})")]
+    LinkExpired(u16),
 }
 
-/// 可选网络选项：代理、超时
+impl Error {
+    /// 签名链接已过期，需要换用新地址
+    pub fn is_link_expired(&self) -> bool {
+        matches!(self, Self::LinkExpired(_))
+    }
+}
+
+/// 403 响应体中表示签名失效的标记（S3 / GCS / Azure / CloudFront），按小写比较
+const EXPIRED_MARKERS: &[&str] = &[
+    "request has expired",
+    "signaturedoesnotmatch",
+    "expiredtoken",
+    "signature expired",
+    "signature has expired",
+    "signed expiry time",
+    "url has expired",
+];
+
+/// 地址带有签名有效期参数（预签名链接）
+pub fn is_signed_url(url: &str) -> bool {
+    let Some((_, query)) = url.split_once('?') else {
+        return false;
+    };
+    let keys: Vec<String> = query
+        .split('&')
+        .map(|kv| kv.split('=').next().unwrap_or("").to_ascii_lowercase())
+        .collect();
+    let has = |k: &str| keys.iter().any(|x| x == k);
+    has("x-amz-expires") || has("x-goog-expires") || (has("expires") && has("signature")) || (has("se") && has("sig"))
+}
+
+/// 检查响应状态：410 及签名失效的 403 归为 LinkExpired，其余错误状态按请求错误返回
+pub(crate) async fn check_status(resp: reqwest::Response) -> Result<reqwest::Response, Error> {
+    let status = resp.status();
+    if status == reqwest::StatusCode::GONE {
+        return Err(Error::LinkExpired(status.as_u16()));
+    }
+    let Err(err) = resp.error_for_status_ref() else {
+        return Ok(resp);
+    };
+    if status == reqwest::StatusCode::FORBIDDEN {
+        let signed = is_signed_url(resp.url().as_str());
+        let body = resp.text().await.unwrap_or_default().to_ascii_lowercase();
+        if signed || EXPIRED_MARKERS.iter().any(|m| body.contains(m)) {
+            return Err(Error::LinkExpired(status.as_u16()));
+        }
+    }
+    Err(Error::Request(err))
+}
+
+/// 可选网络选项：代理、超时、附加请求头
 #[derive(Clone, Default)]
 pub struct NetworkOptions {
-    pub proxy_url: Option<String>,
+    pub proxy: ProxyConfig,
     pub timeout_secs: u64,
+    /// 每个请求都带上的请求头（如导入任务时附带的 Cookie、Referer）
+    pub headers: Vec<(String, String)>,
+}
+
+impl NetworkOptions {
+    /// 追加请求头后的副本；没有要追加的时原样复制
+    pub fn with_headers(&self, headers: &[(String, String)]) -> Self {
+        let mut options = self.clone();
+        options.headers.extend(headers.iter().cloned());
+        options
+    }
 }
 
 fn default_timeout() -> Duration {
@@ -24,37 +95,66 @@ fn default_timeout() -> Duration {
 
 /// 根据 NetworkOptions 构建可复用的 HTTP Client
 pub fn build_client_from_options(options: &NetworkOptions) -> Result<Client, Error> {
-    build_client(options.proxy_url.as_deref(), options.timeout_secs)
+    let builder = client_builder(&options.proxy, options.timeout_secs)?;
+    if options.headers.is_empty() {
+        return builder.build().map_err(Error::Request);
+    }
+    let mut headers = reqwest::header::HeaderMap::new();
+    for (name, value) in &options.headers {
+        let name = reqwest::header::HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| Error::Url(format!("无效的请求头名称: {}", name)))?;
+        let value = reqwest::header::HeaderValue::from_str(value.trim())
+            .map_err(|_| Error::Url(format!("请求头 {} 的值无效", name)))?;
+        headers.append(name, value);
+    }
+    builder.default_headers(headers).build().map_err(Error::Request)
+}
+
+fn build_client(proxy: &ProxyConfig, timeout_secs: u64) -> Result<Client, Error> {
+    client_builder(proxy, timeout_secs)?.build().map_err(Error::Request)
 }
 
-fn build_client(proxy_url: Option<&str>, timeout_secs: u64) -> Result<Client, Error> {
+fn client_builder(proxy: &ProxyConfig, timeout_secs: u64) -> Result<reqwest::ClientBuilder, Error> {
     let timeout = if timeout_secs > 0 {
         Duration::from_secs(timeout_secs)
     } else {
         default_timeout()
     };
-    let mut builder = Client::builder()
+    let builder = Client::builder()
         .redirect(reqwest::redirect::Policy::limited(10))
         .timeout(timeout);
-    if let Some(url) = proxy_url {
-        if !url.is_empty() {
-            builder = builder.proxy(reqwest::Proxy::all(url).map_err(|e| Error::Url(e.to_string()))?);
+    let builder = match &proxy.mode {
+        ProxyMode::Direct => builder.no_proxy(),
+        ProxyMode::Pac { location, script: None } => {
+            return Err(Error::Proxy(format!("PAC 脚本未加载: {}", location)));
         }
-    }
-    builder.build().map_err(Error::Request)
+        _ => {
+            // 按目标 URL 逐个决定代理，绕过列表与 PAC 结果都在 resolve 中处理；
+            // PAC 须先经 with_failover 中的 prepare 求值，这里只查缓存
+            let config = proxy.clone();
+            builder.proxy(reqwest::Proxy::custom(move |url| config.resolve(url)))
+        }
+    };
+    Ok(builder)
 }
 
-/// 协议探测结果：是否支持 Range、总大小、建议文件名、最终 URL
+/// 协议探测结果：是否支持 Range、总大小、建议文件名、最终 URL、校验器（Last-Modified/ETag）、内容类型
 #[derive(Debug, Clone, Serialize, serde::Deserialize)]
 pub struct ProbeResult {
     pub supports_range: bool,
     pub total_bytes: Option<u64>,
     pub suggested_filename: String,
     pub final_url: String,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub etag: Option<String>,
 }
 
 fn default_client() -> Client {
-    build_client(None, 30).expect("build http client")
+    build_client(&ProxyConfig::default(), 30).expect("build http client")
 }
 
 /// 探测 URL：HEAD 或 GET 判断 Range 支持并获取大小与文件名
@@ -65,8 +165,26 @@ pub async fn probe(url: &str) -> Result<ProbeResult, Error> {
 
 /// 使用可选代理与超时进行探测
 pub async fn probe_with_options(url: &str, options: &NetworkOptions) -> Result<ProbeResult, Error> {
-    let client = build_client(options.proxy_url.as_deref(), options.timeout_secs)?;
-    probe_with_client(&client, url).await
+    let client = build_client_from_options(options)?;
+    with_failover(&options.proxy, url, || probe_with_client(&client, url)).await
+}
+
+/// 发请求前为目标主机准备代理选择；连不上代理时换 PAC 的下一个候选重试
+pub(crate) async fn with_failover<T, F, Fut>(proxy: &ProxyConfig, url: &str, request: F) -> Result<T, Error>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let target = reqwest::Url::parse(url).ok();
+    if let Some(target) = &target {
+        proxy.prepare(target).await;
+    }
+    loop {
+        match request().await {
+            Err(Error::Request(e)) if e.is_connect() && target.as_ref().is_some_and(|t| proxy.fail_over(t)) => continue,
+            result => return result,
+        }
+    }
 }
 
 pub async fn probe_with_client(client: &Client, url: &str) -> Result<ProbeResult, Error> {
@@ -121,6 +239,21 @@ pub async fn probe_with_client(client: &Client, url: &str) -> Result<ProbeResult
             .and_then(|s| s.parse::<u64>().ok());
     }
 
+    let last_modified = headers
+        .get("last-modified")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+
+    let content_type = headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+
+    let etag = headers
+        .get("etag")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+
     let suggested_filename = headers
         .get("content-disposition")
         .and_then(|v| v.to_str().ok())
@@ -132,6 +265,9 @@ pub async fn probe_with_client(client: &Client, url: &str) -> Result<ProbeResult
         total_bytes,
         suggested_filename,
         final_url,
+        last_modified,
+        content_type,
+        etag,
     })
 }
 
@@ -176,8 +312,8 @@ pub async fn fetch_range_with_options(
     end: u64,
     options: &NetworkOptions,
 ) -> Result<bytes::Bytes, Error> {
-    let client = build_client(options.proxy_url.as_deref(), options.timeout_secs)?;
-    fetch_range_with_client(&client, url, start, end).await
+    let client = build_client_from_options(options)?;
+    with_failover(&options.proxy, url, || fetch_range_with_client(&client, url, start, end)).await
 }
 
 /// 使用已有 Client 请求一段，实现连接复用
@@ -189,13 +325,7 @@ pub async fn fetch_range_with_client(
 ) -> Result<bytes::Bytes, Error> {
     let url = url.parse::<reqwest::Url>().map_err(|e| Error::Url(e.to_string()))?;
     let range_header = format!("bytes={}-{}", start, end);
-    let body = client
-        .get(url)
-        .header("Range", range_header)
-        .send()
-        .await?
-        .error_for_status()?
-        .bytes()
-        .await?;
+    let resp = client.get(url).header("Range", range_header).send().await?;
+    let body = check_status(resp).await?.bytes().await?;
     Ok(body)
 }
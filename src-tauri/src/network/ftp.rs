@@ -0,0 +1,558 @@
+//! FTP / FTPS 客户端：SIZE/MDTM 探测、被动模式、REST 断点与多连接分段
+//!
+//! `ftps://` 默认使用显式 TLS（AUTH TLS，端口 21）；端口为 990 时按隐式 TLS 连接。
+//! 未提供账号时使用匿名登录。FTP 不经代理：按代理设置应走代理的地址直接报错，不会绕过代理直连。
+
+use crate::network::client::{Error, NetworkOptions, ProbeResult};
+use crate::network::protocol::ByteStream;
+use crate::network::proxy::ProxyConfig;
+use bytes::Bytes;
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use reqwest::Url;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio_native_tls::{native_tls, TlsConnector, TlsStream};
+
+/// 每个处理器最多缓存的空闲控制连接数
+const MAX_IDLE_SESSIONS: usize = 8;
+
+/// 数据连接每次读取的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const CONTROL_CLOSED: &str = "控制连接已关闭";
+
+/// 控制/数据连接：明文或 TLS
+enum FtpStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl AsyncRead for FtpStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            FtpStream::Plain(s) => Pin::new(s).poll_read(cx, buf),
+            FtpStream::Tls(s) => Pin::new(s.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for FtpStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            FtpStream::Plain(s) => Pin::new(s).poll_write(cx, buf),
+            FtpStream::Tls(s) => Pin::new(s.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            FtpStream::Plain(s) => Pin::new(s).poll_flush(cx),
+            FtpStream::Tls(s) => Pin::new(s.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            FtpStream::Plain(s) => Pin::new(s).poll_shutdown(cx),
+            FtpStream::Tls(s) => Pin::new(s.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+/// 服务器回复：三位状态码 + 文本（多行时合并）
+#[derive(Debug)]
+struct Reply {
+    code: u16,
+    text: String,
+}
+
+/// 从 URL 解析出的连接参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FtpTarget {
+    host: String,
+    port: u16,
+    user: String,
+    password: String,
+    path: String,
+    tls: bool,
+    implicit_tls: bool,
+}
+
+impl FtpTarget {
+    fn parse(url: &str) -> Result<Self, Error> {
+        let u = Url::parse(url).map_err(|e| Error::Url(e.to_string()))?;
+        let tls = match u.scheme() {
+            "ftp" => false,
+            "ftps" => true,
+            other => return Err(Error::Url(format!("不是 FTP 地址: {}", other))),
+        };
+        let host = u.host_str().ok_or_else(|| Error::Url("缺少主机名".to_string()))?.to_string();
+        let port = u.port().unwrap_or(21);
+        let user = if u.username().is_empty() {
+            "anonymous".to_string()
+        } else {
+            decode(u.username())
+        };
+        let password = match u.password() {
+            Some(p) => decode(p),
+            None if user == "anonymous" => "anonymous@".to_string(),
+            None => String::new(),
+        };
+        let path = decode(u.path());
+        if path.is_empty() || path.ends_with('/') {
+            return Err(Error::Url("FTP 地址需指向文件".to_string()));
+        }
+        Ok(Self {
+            host,
+            port,
+            user,
+            password,
+            path,
+            tls,
+            implicit_tls: tls && port == 990,
+        })
+    }
+
+    /// 会话复用键：同主机、端口、账号与加密方式可共用控制连接
+    fn session_key(&self) -> (String, u16, String, bool) {
+        (self.host.clone(), self.port, self.user.clone(), self.tls)
+    }
+}
+
+fn decode(s: &str) -> String {
+    urlencoding::decode(s)
+        .map(|c| c.into_owned())
+        .unwrap_or_else(|_| s.to_string())
+}
+
+/// 已登录、处于二进制模式的控制连接
+struct FtpSession {
+    key: (String, u16, String, bool),
+    control: BufReader<FtpStream>,
+    peer: SocketAddr,
+    tls: Option<TlsConnector>,
+    host: String,
+}
+
+impl FtpSession {
+    async fn connect(target: &FtpTarget, timeout: Duration) -> Result<Self, Error> {
+        let tcp = with_timeout(timeout, TcpStream::connect((target.host.as_str(), target.port))).await??;
+        let peer = tcp.peer_addr()?;
+        let connector = if target.tls {
+            let c = native_tls::TlsConnector::new().map_err(|e| Error::Ftp(e.to_string()))?;
+            Some(TlsConnector::from(c))
+        } else {
+            None
+        };
+        let stream = match (&connector, target.implicit_tls) {
+            (Some(c), true) => FtpStream::Tls(Box::new(
+                c.connect(&target.host, tcp).await.map_err(|e| Error::Ftp(e.to_string()))?,
+            )),
+            _ => FtpStream::Plain(tcp),
+        };
+        let mut session = Self {
+            key: target.session_key(),
+            control: BufReader::new(stream),
+            peer,
+            tls: connector,
+            host: target.host.clone(),
+        };
+        session.expect(timeout, &[220]).await?;
+
+        if target.tls && !target.implicit_tls {
+            session.command("AUTH TLS", timeout, &[234]).await?;
+            let plain = match session.control.into_inner() {
+                FtpStream::Plain(s) => s,
+                FtpStream::Tls(_) => return Err(Error::Ftp("控制连接状态异常".to_string())),
+            };
+            let connector = session.tls.clone().expect("tls connector");
+            let tls = connector
+                .connect(&target.host, plain)
+                .await
+                .map_err(|e| Error::Ftp(e.to_string()))?;
+            session.control = BufReader::new(FtpStream::Tls(Box::new(tls)));
+        }
+
+        let user = session.send(&format!("USER {}", target.user), timeout).await?;
+        match user.code {
+            230 => {}
+            331 | 332 => {
+                session
+                    .command(&format!("PASS {}", target.password), timeout, &[230, 202])
+                    .await?;
+            }
+            _ => return Err(Error::Ftp(format!("登录失败: {} {}", user.code, user.text))),
+        }
+        if target.tls {
+            session.command("PBSZ 0", timeout, &[200]).await?;
+            session.command("PROT P", timeout, &[200]).await?;
+        }
+        session.command("TYPE I", timeout, &[200]).await?;
+        Ok(session)
+    }
+
+    async fn read_reply(&mut self) -> Result<Reply, Error> {
+        let mut line = String::new();
+        if self.control.read_line(&mut line).await? == 0 {
+            return Err(Error::Ftp(CONTROL_CLOSED.to_string()));
+        }
+        let code: u16 = line
+            .get(..3)
+            .and_then(|c| c.parse().ok())
+            .ok_or_else(|| Error::Ftp(format!("无效回复: {}", line.trim_end())))?;
+        let mut text = line.get(4..).unwrap_or("").trim_end().to_string();
+        // 多行回复：以 "xyz-" 开始，以 "xyz " 结束
+        if line.as_bytes().get(3) == Some(&b'-') {
+            let end = format!("{} ", &line[..3]);
+            loop {
+                let mut next = String::new();
+                if self.control.read_line(&mut next).await? == 0 {
+                    return Err(Error::Ftp(CONTROL_CLOSED.to_string()));
+                }
+                text.push('\n');
+                text.push_str(next.trim_end());
+                if next.starts_with(&end) {
+                    break;
+                }
+            }
+        }
+        Ok(Reply { code, text })
+    }
+
+    async fn send(&mut self, cmd: &str, timeout: Duration) -> Result<Reply, Error> {
+        let stream = self.control.get_mut();
+        stream.write_all(format!("{}\r\n", cmd).as_bytes()).await?;
+        stream.flush().await?;
+        with_timeout(timeout, self.read_reply()).await?
+    }
+
+    async fn expect(&mut self, timeout: Duration, codes: &[u16]) -> Result<Reply, Error> {
+        let reply = with_timeout(timeout, self.read_reply()).await??;
+        check(reply, codes)
+    }
+
+    async fn command(&mut self, cmd: &str, timeout: Duration, codes: &[u16]) -> Result<Reply, Error> {
+        let reply = self.send(cmd, timeout).await?;
+        check(reply, codes).map_err(|e| match e {
+            // 回复中不回显密码
+            Error::Ftp(msg) if cmd.starts_with("PASS") => Error::Ftp(msg.replace(cmd, "PASS ***")),
+            other => other,
+        })
+    }
+
+    /// 进入被动模式并建立数据连接；优先 EPSV，失败时回退 PASV
+    async fn open_data(&mut self, timeout: Duration) -> Result<TcpStream, Error> {
+        let epsv = self.send("EPSV", timeout).await?;
+        let addr = if epsv.code == 229 {
+            let port = parse_epsv(&epsv.text).ok_or_else(|| Error::Ftp(format!("无效 EPSV 回复: {}", epsv.text)))?;
+            SocketAddr::new(self.peer.ip(), port)
+        } else {
+            let pasv = self.command("PASV", timeout, &[227]).await?;
+            let port = parse_pasv(&pasv.text).ok_or_else(|| Error::Ftp(format!("无效 PASV 回复: {}", pasv.text)))?;
+            // 忽略服务器报告的 IP（NAT 后常为内网地址），沿用控制连接的对端地址
+            SocketAddr::new(self.peer.ip(), port)
+        };
+        Ok(with_timeout(timeout, TcpStream::connect(addr)).await??)
+    }
+
+    async fn wrap_data(&self, tcp: TcpStream) -> Result<FtpStream, Error> {
+        match &self.tls {
+            Some(c) => Ok(FtpStream::Tls(Box::new(
+                c.connect(&self.host, tcp).await.map_err(|e| Error::Ftp(e.to_string()))?,
+            ))),
+            None => Ok(FtpStream::Plain(tcp)),
+        }
+    }
+
+    /// 从 offset 开始 RETR，返回已建立的数据连接
+    async fn start_retrieve(&mut self, path: &str, offset: u64, timeout: Duration) -> Result<FtpStream, Error> {
+        let data = self.open_data(timeout).await?;
+        if offset > 0 {
+            self.command(&format!("REST {}", offset), timeout, &[350]).await?;
+        }
+        self.command(&format!("RETR {}", path), timeout, &[125, 150]).await?;
+        self.wrap_data(data).await
+    }
+
+    /// SIZE/MDTM/REST 0：返回大小、修改时间与是否支持断点
+    async fn stat(&mut self, path: &str, timeout: Duration) -> Result<(Option<u64>, Option<String>, bool), Error> {
+        let size = self.send(&format!("SIZE {}", path), timeout).await?;
+        let total_bytes = if size.code == 213 {
+            size.text.split_whitespace().last().and_then(|s| s.parse::<u64>().ok())
+        } else if size.code == 550 {
+            return Err(Error::Ftp(format!("文件不存在: {}", path)));
+        } else {
+            None
+        };
+        let mdtm = self.send(&format!("MDTM {}", path), timeout).await?;
+        let last_modified = (mdtm.code == 213).then(|| mdtm_to_http_date(&mdtm.text)).flatten();
+        // REST 0 返回 350 即支持断点续传
+        let rest = self.send("REST 0", timeout).await?;
+        Ok((total_bytes, last_modified, rest.code == 350 && total_bytes.is_some()))
+    }
+}
+
+/// 进行中的 RETR：按块读取数据连接
+///
+/// 流被提前丢弃（暂停、出错）时数据与控制连接一并关闭，服务器随之中止传输；
+/// 正常读完后读取结束回复，控制连接放回空闲池。
+struct Transfer {
+    session: FtpSession,
+    data: FtpStream,
+    remaining: Option<u64>,
+    buf: Vec<u8>,
+    eof: bool,
+    timeout: Duration,
+    idle: Arc<Mutex<Vec<FtpSession>>>,
+}
+
+impl Transfer {
+    async fn read(&mut self) -> Result<Option<Bytes>, Error> {
+        let want = self.remaining.map_or(CHUNK_SIZE, |r| r.min(CHUNK_SIZE as u64) as usize);
+        if want == 0 {
+            return Ok(None);
+        }
+        let n = with_timeout(self.timeout, self.data.read(&mut self.buf[..want])).await??;
+        if n == 0 {
+            self.eof = true;
+            return Ok(None);
+        }
+        if let Some(r) = &mut self.remaining {
+            *r -= n as u64;
+        }
+        Ok(Some(Bytes::copy_from_slice(&self.buf[..n])))
+    }
+
+    /// 关闭数据连接并读取结束回复
+    async fn finish(self) -> Result<(), Error> {
+        let Transfer {
+            mut session,
+            data,
+            eof,
+            timeout,
+            idle,
+            ..
+        } = self;
+        drop(data);
+        let reply = with_timeout(timeout, session.read_reply()).await.and_then(|r| r);
+        match reply {
+            Ok(Reply { code: 226 | 250, .. }) => release(&idle, session).await,
+            // 读满请求长度后主动关闭了数据连接，服务器回 426/451，控制连接仍可用
+            Ok(Reply { code: 426 | 451, .. }) if !eof => release(&idle, session).await,
+            Ok(r) if eof => return Err(Error::Ftp(format!("传输中断: {} {}", r.code, r.text))),
+            Err(e) if eof => return Err(e),
+            // 请求的数据已经读满，只是回复异常：不再复用这条控制连接
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn into_stream(self) -> ByteStream {
+        stream::try_unfold(Some(self), |state| async move {
+            let Some(mut t) = state else { return Ok(None) };
+            match t.read().await? {
+                // 读满后立即收尾，调用方拿到最后一块就停止读取时连接也能回到空闲池
+                Some(chunk) if t.remaining == Some(0) => {
+                    t.finish().await?;
+                    Ok(Some((chunk, None)))
+                }
+                Some(chunk) => Ok(Some((chunk, Some(t)))),
+                None => {
+                    t.finish().await?;
+                    Ok(None)
+                }
+            }
+        })
+        .boxed()
+    }
+}
+
+async fn release(idle: &Mutex<Vec<FtpSession>>, session: FtpSession) {
+    let mut idle = idle.lock().await;
+    if idle.len() < MAX_IDLE_SESSIONS {
+        idle.push(session);
+    }
+}
+
+/// 控制连接已失效（被服务器关闭或重置），换新连接重试即可
+fn is_dead(e: &Error) -> bool {
+    match e {
+        Error::Io(_) => true,
+        Error::Ftp(msg) => msg == CONTROL_CLOSED,
+        _ => false,
+    }
+}
+
+fn check(reply: Reply, codes: &[u16]) -> Result<Reply, Error> {
+    if codes.contains(&reply.code) {
+        Ok(reply)
+    } else {
+        Err(Error::Ftp(format!("{} {}", reply.code, reply.text)))
+    }
+}
+
+async fn with_timeout<F: std::future::Future>(timeout: Duration, fut: F) -> Result<F::Output, Error> {
+    tokio::time::timeout(timeout, fut)
+        .await
+        .map_err(|_| Error::Ftp("操作超时".to_string()))
+}
+
+/// 解析 "Entering Extended Passive Mode (|||6446|)"
+fn parse_epsv(text: &str) -> Option<u16> {
+    let inner = text.split('(').nth(1)?.split(')').next()?;
+    let delim = inner.chars().next()?;
+    inner.split(delim).nth(3)?.parse().ok()
+}
+
+/// 解析 "Entering Passive Mode (h1,h2,h3,h4,p1,p2)"
+fn parse_pasv(text: &str) -> Option<u16> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let nums: Vec<u16> = text[start..]
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .take(6)
+        .filter_map(|s| s.parse().ok())
+        .collect();
+    if nums.len() < 6 {
+        return None;
+    }
+    Some(nums[4] * 256 + nums[5])
+}
+
+/// 把 MDTM 的 "YYYYMMDDhhmmss" 转为 HTTP 日期格式，便于与 Last-Modified 统一比较
+fn mdtm_to_http_date(text: &str) -> Option<String> {
+    let ts = text.split_whitespace().last()?;
+    let ts = ts.split('.').next()?;
+    let dt = chrono::NaiveDateTime::parse_from_str(ts, "%Y%m%d%H%M%S").ok()?;
+    Some(dt.and_utc().format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+}
+
+/// FTP/FTPS 协议处理器：缓存空闲控制连接，避免每段都重新登录
+pub struct FtpHandler {
+    timeout: Duration,
+    proxy: ProxyConfig,
+    idle: Arc<Mutex<Vec<FtpSession>>>,
+}
+
+impl FtpHandler {
+    pub fn new(options: &NetworkOptions) -> Self {
+        let secs = if options.timeout_secs > 0 { options.timeout_secs } else { 30 };
+        Self {
+            timeout: Duration::from_secs(secs),
+            proxy: options.proxy.clone(),
+            idle: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// 解析地址；按代理设置该走代理时报错（FTP 不支持代理），在绕过列表中的主机直连
+    async fn target(&self, url: &str) -> Result<FtpTarget, Error> {
+        let target = FtpTarget::parse(url)?;
+        let parsed = Url::parse(url).ok();
+        if let Some(parsed) = &parsed {
+            self.proxy.prepare(parsed).await;
+        }
+        if let Some(proxy) = parsed.and_then(|u| self.proxy.resolve(&u)) {
+            return Err(Error::Proxy(format!(
+                "FTP 下载不支持代理（{}:{}），如需直连请把 {} 加入不使用代理的地址",
+                proxy.host_str().unwrap_or_default(),
+                proxy.port_or_known_default().unwrap_or_default(),
+                target.host
+            )));
+        }
+        Ok(target)
+    }
+
+    /// 取一条可用的控制连接；第二项表示是否来自空闲池
+    async fn session(&self, target: &FtpTarget) -> Result<(FtpSession, bool), Error> {
+        let key = target.session_key();
+        {
+            let mut idle = self.idle.lock().await;
+            if let Some(pos) = idle.iter().position(|s| s.key == key) {
+                return Ok((idle.swap_remove(pos), true));
+            }
+        }
+        Ok((FtpSession::connect(target, self.timeout).await?, false))
+    }
+
+    pub async fn probe(&self, url: &str) -> Result<ProbeResult, Error> {
+        let target = self.target(url).await?;
+        let (mut session, pooled) = self.session(&target).await?;
+        let (total_bytes, last_modified, supports_range) = match session.stat(&target.path, self.timeout).await {
+            // 空闲连接可能已被服务器关闭：换新连接重试一次
+            Err(e) if pooled && is_dead(&e) => {
+                session = FtpSession::connect(&target, self.timeout).await?;
+                session.stat(&target.path, self.timeout).await?
+            }
+            other => other?,
+        };
+        release(&self.idle, session).await;
+
+        let suggested_filename = target
+            .path
+            .rsplit('/')
+            .next()
+            .filter(|s| !s.is_empty())
+            .unwrap_or("download")
+            .to_string();
+        Ok(ProbeResult {
+            supports_range,
+            total_bytes,
+            suggested_filename,
+            final_url: url.to_string(),
+            last_modified,
+            content_type: None,
+            etag: None,
+        })
+    }
+
+    /// 打开 [start, end]（inclusive）的数据流，通过 REST 定位
+    pub async fn open_range(&self, url: &str, start: u64, end: u64) -> Result<ByteStream, Error> {
+        self.retrieve(url, start, Some(end.saturating_sub(start) + 1)).await
+    }
+
+    /// 请求 [start, end]（inclusive）并收集为完整数据
+    pub async fn fetch_range(&self, url: &str, start: u64, end: u64) -> Result<Bytes, Error> {
+        let chunks: Vec<Bytes> = self.open_range(url, start, end).await?.try_collect().await?;
+        Ok(Bytes::from(chunks.concat()))
+    }
+
+    /// 打开整个文件的数据流
+    pub async fn open(&self, url: &str) -> Result<ByteStream, Error> {
+        self.retrieve(url, 0, None).await
+    }
+
+    /// 下载整个文件
+    pub async fn fetch(&self, url: &str) -> Result<Bytes, Error> {
+        let chunks: Vec<Bytes> = self.open(url).await?.try_collect().await?;
+        Ok(Bytes::from(chunks.concat()))
+    }
+
+    /// 从 offset 开始读取 len 字节（len 为 None 时读到结束）
+    async fn retrieve(&self, url: &str, offset: u64, len: Option<u64>) -> Result<ByteStream, Error> {
+        let target = self.target(url).await?;
+        let (mut session, pooled) = self.session(&target).await?;
+        let data = match session.start_retrieve(&target.path, offset, self.timeout).await {
+            Err(e) if pooled && is_dead(&e) => {
+                session = FtpSession::connect(&target, self.timeout).await?;
+                session.start_retrieve(&target.path, offset, self.timeout).await?
+            }
+            other => other?,
+        };
+        let transfer = Transfer {
+            session,
+            data,
+            remaining: len,
+            buf: vec![0u8; CHUNK_SIZE],
+            eof: false,
+            timeout: self.timeout,
+            idle: self.idle.clone(),
+        };
+        Ok(transfer.into_stream())
+    }
+}
@@ -0,0 +1,143 @@
+//! 内存协议：数据来自预先放入的字节，不建立任何连接，供无网络环境驱动调度器
+
+use crate::network::client::{Error, ProbeResult};
+use crate::network::protocol::{ByteStream, ProtocolCapabilities, ProtocolHandler};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+/// 以 URL 为键的内存文件表；按 chunk_size 分块产出以模拟流式到达
+pub struct MemoryHandler {
+    files: RwLock<HashMap<String, Bytes>>,
+    /// 模拟已过期的签名链接：请求返回 LinkExpired
+    expired: RwLock<HashSet<String>>,
+    supports_range: bool,
+    chunk_size: usize,
+    /// 每块数据到达前的等待，模拟慢速连接
+    delay: Duration,
+    /// 实际返回的区间（地址、起点、终点），按请求顺序
+    ranges: Mutex<Vec<(String, u64, u64)>>,
+}
+
+impl Default for MemoryHandler {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl MemoryHandler {
+    pub fn new(supports_range: bool) -> Self {
+        Self {
+            files: RwLock::new(HashMap::new()),
+            expired: RwLock::new(HashSet::new()),
+            supports_range,
+            chunk_size: 16 * 1024,
+            delay: Duration::ZERO,
+            ranges: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// 某地址实际返回过的区间 [start, end]，按请求顺序
+    pub fn requested_ranges(&self, url: &str) -> Vec<(u64, u64)> {
+        self.ranges
+            .lock()
+            .map(|r| r.iter().filter(|(u, _, _)| u == url).map(|&(_, s, e)| (s, e)).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn insert(&self, url: impl Into<String>, data: impl Into<Bytes>) {
+        if let Ok(mut files) = self.files.write() {
+            files.insert(url.into(), data.into());
+        }
+    }
+
+    pub fn remove(&self, url: &str) {
+        if let Ok(mut files) = self.files.write() {
+            files.remove(url);
+        }
+    }
+
+    /// 标记地址已过期，数据保留
+    pub fn expire(&self, url: &str) {
+        if let Ok(mut expired) = self.expired.write() {
+            expired.insert(url.to_string());
+        }
+    }
+
+    fn get(&self, url: &str) -> Result<Bytes, Error> {
+        if self.expired.read().is_ok_and(|e| e.contains(url)) {
+            return Err(Error::LinkExpired(403));
+        }
+        self.files
+            .read()
+            .ok()
+            .and_then(|f| f.get(url).cloned())
+            .ok_or_else(|| Error::Url(format!("内存中不存在: {}", url)))
+    }
+}
+
+#[async_trait]
+impl ProtocolHandler for MemoryHandler {
+    fn capabilities(&self) -> ProtocolCapabilities {
+        ProtocolCapabilities {
+            supports_range: self.supports_range,
+            max_connections: None,
+        }
+    }
+
+    async fn probe(&self, url: &str) -> Result<ProbeResult, Error> {
+        let data = self.get(url)?;
+        let name = url.trim_end_matches('/').rsplit('/').next().unwrap_or("download");
+        Ok(ProbeResult {
+            supports_range: self.supports_range,
+            total_bytes: Some(data.len() as u64),
+            suggested_filename: name.to_string(),
+            final_url: url.to_string(),
+            last_modified: None,
+            content_type: None,
+            etag: None,
+        })
+    }
+
+    async fn open_range(&self, url: &str, start: u64, end: u64) -> Result<ByteStream, Error> {
+        let data = self.get(url)?;
+        let len = data.len() as u64;
+        if start >= len || end < start {
+            return Err(Error::Url(format!("区间越界: {}-{} / {}", start, end, len)));
+        }
+        // 不支持 Range 时与真实服务器一样忽略区间，总是从头返回整个文件
+        let (start, end) = if self.supports_range { (start, end.min(len - 1)) } else { (0, len - 1) };
+        if let Ok(mut ranges) = self.ranges.lock() {
+            ranges.push((url.to_string(), start, end));
+        }
+        let slice = data.slice(start as usize..=end as usize);
+        let chunk = self.chunk_size;
+        let chunks: Vec<Result<Bytes, Error>> = (0..slice.len())
+            .step_by(chunk)
+            .map(|i| Ok(slice.slice(i..(i + chunk).min(slice.len()))))
+            .collect();
+        let delay = self.delay;
+        if delay.is_zero() {
+            return Ok(stream::iter(chunks).boxed());
+        }
+        Ok(stream::iter(chunks)
+            .then(move |c| async move {
+                tokio::time::sleep(delay).await;
+                c
+            })
+            .boxed())
+    }
+}
@@ -1,6 +1,15 @@
-//! HTTP 客户端：协议探测与 Range 请求
+//! 网络层：协议注册表与 HTTP(S)/FTP(S)/内存实现
 
 mod client;
+mod ftp;
+mod memory;
+mod os_proxy;
+pub mod protocol;
+pub mod proxy;
 
-pub use client::{build_client_from_options, fetch_range, fetch_range_with_client, fetch_range_with_options, probe, probe_with_options, NetworkOptions, ProbeResult};
+pub use client::{build_client_from_options, fetch_range, fetch_range_with_client, fetch_range_with_options, is_signed_url, probe, probe_with_options, NetworkOptions, ProbeResult};
 pub use client::Error as NetworkError;
+pub use ftp::FtpHandler;
+pub use memory::MemoryHandler;
+pub use protocol::{is_supported_url, ByteStream, HttpHandler, ProtocolCapabilities, ProtocolHandler, ProtocolRegistry};
+pub use proxy::{BypassList, ProxyConfig, ProxyMode, SystemProxy};
@@ -0,0 +1,341 @@
+//! 读取操作系统的代理设置（"使用系统设置"）
+//!
+//! Windows 读 WinINET 注册表（`reg query`），macOS 读 `scutil --proxy`，
+//! Linux 下 KDE 读 kioslaverc、其他桌面读 GNOME 的 gsettings。读不到时返回 None，由调用方退回环境变量。
+
+use crate::network::proxy::{parse_proxy_url, BypassList, ProxyMode, SystemProxy};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// 系统设置的缓存时间，避免每个任务都启动外部命令
+const OS_PROXY_TTL: Duration = Duration::from_secs(60);
+
+type OsProxyCache = Mutex<Option<(Instant, Option<ProxyMode>)>>;
+
+fn cache() -> &'static OsProxyCache {
+    static CACHE: OnceLock<OsProxyCache> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(None))
+}
+
+/// 当前系统代理：Direct、按协议的代理（System）或 PAC 地址（脚本尚未加载）
+pub async fn read() -> Option<ProxyMode> {
+    if let Some((at, mode)) = cache().lock().ok().and_then(|c| c.clone()) {
+        if at.elapsed() < OS_PROXY_TTL {
+            return mode;
+        }
+    }
+    let mode = tokio::task::spawn_blocking(read_blocking).await.ok().flatten();
+    if let Ok(mut c) = cache().lock() {
+        *c = Some((Instant::now(), mode.clone()));
+    }
+    mode
+}
+
+/// 运行命令并取标准输出；命令不存在或失败时返回 None
+#[cfg_attr(not(any(windows, target_os = "macos", target_os = "linux")), allow(dead_code))]
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let out = std::process::Command::new(program).args(args).output().ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+#[cfg(windows)]
+fn read_blocking() -> Option<ProxyMode> {
+    let text = output(
+        "reg",
+        &["query", r"HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings"],
+    )?;
+    Some(parse_wininet(&text))
+}
+
+#[cfg(target_os = "macos")]
+fn read_blocking() -> Option<ProxyMode> {
+    Some(parse_scutil(&output("scutil", &["--proxy"])?))
+}
+
+#[cfg(target_os = "linux")]
+fn read_blocking() -> Option<ProxyMode> {
+    let kde = std::env::var("XDG_CURRENT_DESKTOP").is_ok_and(|d| d.to_ascii_uppercase().contains("KDE"));
+    if kde {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(std::path::PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|h| h.join(".config")))?;
+        if let Ok(text) = std::fs::read_to_string(config.join("kioslaverc")) {
+            return parse_kioslaverc(&text);
+        }
+    }
+    parse_gsettings(&output("gsettings", &["list-recursively", "org.gnome.system.proxy"])?)
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn read_blocking() -> Option<ProxyMode> {
+    None
+}
+
+/// 同一代理用于 HTTP 与 HTTPS；全部为空时为直连
+fn manual(http: Option<reqwest::Url>, https: Option<reqwest::Url>, all: Option<reqwest::Url>, no_proxy: BypassList) -> ProxyMode {
+    if http.is_none() && https.is_none() && all.is_none() {
+        return ProxyMode::Direct;
+    }
+    ProxyMode::System(SystemProxy {
+        http,
+        https,
+        all,
+        no_proxy,
+    })
+}
+
+fn pac(location: &str) -> ProxyMode {
+    ProxyMode::Pac {
+        location: location.trim().to_string(),
+        script: None,
+    }
+}
+
+/// `reg query` 的输出："    名称    REG_SZ    值"
+#[cfg_attr(not(any(windows, test)), allow(dead_code))]
+fn parse_wininet(text: &str) -> ProxyMode {
+    let values: HashMap<&str, &str> = text
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (name, rest) = line.split_once(char::is_whitespace)?;
+            let rest = rest.trim_start();
+            let (kind, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            kind.starts_with("REG_").then(|| (name, value.trim()))
+        })
+        .collect();
+    if let Some(url) = values.get("AutoConfigURL").filter(|v| !v.is_empty()) {
+        return pac(url);
+    }
+    let enabled = values
+        .get("ProxyEnable")
+        .is_some_and(|v| u32::from_str_radix(v.trim_start_matches("0x"), 16).unwrap_or(0) != 0);
+    let server = values.get("ProxyServer").copied().unwrap_or_default();
+    if !enabled || server.is_empty() {
+        return ProxyMode::Direct;
+    }
+    let no_proxy = BypassList::parse(values.get("ProxyOverride").copied().unwrap_or_default());
+    if !server.contains('=') {
+        let proxy = parse_proxy_url(server, "http");
+        return manual(proxy.clone(), proxy, None, no_proxy);
+    }
+    // 按协议分别设置："http=host:port;https=host:port;socks=host:port"
+    let mut http = None;
+    let mut https = None;
+    let mut all = None;
+    for entry in server.split(';') {
+        match entry.trim().split_once('=') {
+            Some(("http", v)) => http = parse_proxy_url(v, "http"),
+            Some(("https", v)) => https = parse_proxy_url(v, "http"),
+            Some(("socks", v)) => all = parse_proxy_url(v, "socks4"),
+            _ => {}
+        }
+    }
+    manual(http, https, all, no_proxy)
+}
+
+/// `scutil --proxy` 的输出："  HTTPProxy : host"，例外列表为 "ExceptionsList : <array> { 0 : *.local }"
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+fn parse_scutil(text: &str) -> ProxyMode {
+    let mut values = HashMap::new();
+    let mut exceptions = Vec::new();
+    let mut in_exceptions = false;
+    for line in text.lines() {
+        let line = line.trim();
+        if line == "}" {
+            in_exceptions = false;
+            continue;
+        }
+        let Some((key, value)) = line.split_once(" : ") else {
+            continue;
+        };
+        if key == "ExceptionsList" {
+            in_exceptions = true;
+        } else if in_exceptions {
+            exceptions.push(value.trim().to_string());
+        } else {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    let enabled = |key: &str| values.get(key).is_some_and(|v| v == "1");
+    if enabled("ProxyAutoConfigEnable") {
+        if let Some(url) = values.get("ProxyAutoConfigURLString") {
+            return pac(url);
+        }
+    }
+    let proxy = |prefix: &str, scheme: &str| {
+        if !enabled(&format!("{}Enable", prefix)) {
+            return None;
+        }
+        let host = values.get(&format!("{}Proxy", prefix))?;
+        let port = values.get(&format!("{}Port", prefix))?;
+        parse_proxy_url(&format!("{}:{}", host, port), scheme)
+    };
+    let mut no_proxy = BypassList::from_entries(exceptions.iter().map(String::as_str));
+    if enabled("ExcludeSimpleHostnames") {
+        no_proxy.extend(BypassList::parse("<local>"));
+    }
+    manual(proxy("HTTP", "http"), proxy("HTTPS", "http"), proxy("SOCKS", "socks5"), no_proxy)
+}
+
+/// `gsettings list-recursively org.gnome.system.proxy` 的输出："org.gnome.system.proxy.http host 'proxy'"
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_gsettings(text: &str) -> Option<ProxyMode> {
+    let values: HashMap<(&str, &str), &str> = text
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            Some(((parts.next()?, parts.next()?), parts.next()?.trim()))
+        })
+        .collect();
+    let get = |schema: &str, key: &str| {
+        values
+            .get(&(schema, key))
+            .map(|v| v.trim_matches('\'').to_string())
+            .unwrap_or_default()
+    };
+    const BASE: &str = "org.gnome.system.proxy";
+    match get(BASE, "mode").as_str() {
+        "auto" => {
+            let url = get(BASE, "autoconfig-url");
+            // 未填 PAC 地址时 GNOME 走 WPAD 自动发现，这里不支持
+            (!url.is_empty()).then(|| pac(&url))
+        }
+        "manual" => {
+            let proxy = |schema: &str, scheme: &str| {
+                let schema = format!("{}.{}", BASE, schema);
+                let host = get(&schema, "host");
+                let port = get(&schema, "port");
+                (!host.is_empty() && port != "0")
+                    .then(|| parse_proxy_url(&format!("{}:{}", host, port), scheme))
+                    .flatten()
+            };
+            // ignore-hosts 形如 ['localhost', '127.0.0.0/8']
+            let ignore = get(BASE, "ignore-hosts");
+            let no_proxy = BypassList::from_entries(
+                ignore
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .split(',')
+                    .map(|h| h.trim().trim_matches('\'')),
+            );
+            Some(manual(proxy("http", "http"), proxy("https", "http"), proxy("socks", "socks5"), no_proxy))
+        }
+        "none" => Some(ProxyMode::Direct),
+        _ => None,
+    }
+}
+
+/// KDE 的 kioslaverc：[Proxy Settings] 中 ProxyType 0 直连、1 手动、2 PAC、4 环境变量
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_kioslaverc(text: &str) -> Option<ProxyMode> {
+    let mut section = "";
+    let mut values = HashMap::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name;
+        } else if section == "Proxy Settings" {
+            if let Some((key, value)) = line.split_once('=') {
+                values.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+    }
+    let get = |key: &str| values.get(key).map(String::as_str).unwrap_or_default();
+    match get("ProxyType") {
+        "" | "0" => Some(ProxyMode::Direct),
+        "1" => {
+            // 旧版本写作 "http://host 8080"，新版本为 "http://host:8080"
+            let proxy = |key: &str, scheme: &str| {
+                let value = get(key).replacen(' ', ":", 1);
+                parse_proxy_url(&value, scheme)
+            };
+            let no_proxy = BypassList::parse(get("NoProxyFor"));
+            Some(manual(proxy("httpProxy", "http"), proxy("httpsProxy", "http"), proxy("socksProxy", "socks5"), no_proxy))
+        }
+        "2" => {
+            let url = get("Proxy Config Script");
+            (!url.is_empty()).then(|| pac(url))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Url;
+
+    fn resolve(mode: &ProxyMode, target: &str) -> Option<String> {
+        let ProxyMode::System(sys) = mode else {
+            panic!("不是手动代理: {:?}", mode);
+        };
+        sys.proxy_for(&Url::parse(target).unwrap()).map(|u| u.to_string())
+    }
+
+    #[test]
+    fn wininet_single_and_per_protocol_servers() {
+        let single = "\r\nHKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings\r\n    ProxyEnable    REG_DWORD    0x1\r\n    ProxyServer    REG_SZ    proxy.corp:8080\r\n    ProxyOverride    REG_SZ    *.corp;<local>\r\n";
+        let mode = parse_wininet(single);
+        assert_eq!(resolve(&mode, "https://example.com/").as_deref(), Some("http://proxy.corp:8080/"));
+        assert_eq!(resolve(&mode, "http://intranet/"), None);
+        assert_eq!(resolve(&mode, "http://files.corp/"), None);
+
+        let split = "    ProxyEnable    REG_DWORD    0x1\r\n    ProxyServer    REG_SZ    http=h1:8080;https=h2:8443;socks=s:1080\r\n";
+        let mode = parse_wininet(split);
+        assert_eq!(resolve(&mode, "http://a.com/").as_deref(), Some("http://h1:8080/"));
+        assert_eq!(resolve(&mode, "https://a.com/").as_deref(), Some("http://h2:8443/"));
+
+        let disabled = "    ProxyEnable    REG_DWORD    0x0\r\n    ProxyServer    REG_SZ    proxy.corp:8080\r\n";
+        assert!(matches!(parse_wininet(disabled), ProxyMode::Direct));
+
+        let auto = "    ProxyEnable    REG_DWORD    0x0\r\n    AutoConfigURL    REG_SZ    http://wpad/proxy.pac\r\n";
+        assert!(matches!(parse_wininet(auto), ProxyMode::Pac { location, .. } if location == "http://wpad/proxy.pac"));
+    }
+
+    #[test]
+    fn scutil_proxies_and_exceptions() {
+        let text = "<dictionary> {\n  ExceptionsList : <array> {\n    0 : *.local\n    1 : 169.254/16\n  }\n  ExcludeSimpleHostnames : 1\n  HTTPEnable : 1\n  HTTPPort : 3128\n  HTTPProxy : proxy.lan\n  HTTPSEnable : 0\n  HTTPSPort : 3129\n  HTTPSProxy : other.lan\n}\n";
+        let mode = parse_scutil(text);
+        assert_eq!(resolve(&mode, "http://example.com/").as_deref(), Some("http://proxy.lan:3128/"));
+        assert_eq!(resolve(&mode, "https://example.com/"), None);
+        assert_eq!(resolve(&mode, "http://printer.local/"), None);
+        assert_eq!(resolve(&mode, "http://nas/"), None);
+
+        let pac_text = "<dictionary> {\n  ProxyAutoConfigEnable : 1\n  ProxyAutoConfigURLString : http://wpad/wpad.dat\n}\n";
+        assert!(matches!(parse_scutil(pac_text), ProxyMode::Pac { location, .. } if location == "http://wpad/wpad.dat"));
+        assert!(matches!(parse_scutil("<dictionary> {\n}\n"), ProxyMode::Direct));
+    }
+
+    #[test]
+    fn gsettings_modes() {
+        let text = "org.gnome.system.proxy autoconfig-url ''\norg.gnome.system.proxy ignore-hosts ['localhost', '127.0.0.0/8', '*.lan']\norg.gnome.system.proxy mode 'manual'\norg.gnome.system.proxy.http host 'proxy.lan'\norg.gnome.system.proxy.http port 8080\norg.gnome.system.proxy.https host ''\norg.gnome.system.proxy.https port 0\norg.gnome.system.proxy.socks host 'socks.lan'\norg.gnome.system.proxy.socks port 1080\n";
+        let mode = parse_gsettings(text).unwrap();
+        assert_eq!(resolve(&mode, "http://example.com/").as_deref(), Some("http://proxy.lan:8080/"));
+        assert_eq!(resolve(&mode, "https://example.com/").as_deref(), Some("socks5://socks.lan:1080"));
+        assert_eq!(resolve(&mode, "http://nas.lan/"), None);
+        assert_eq!(resolve(&mode, "http://127.0.0.1/"), None);
+
+        let auto = "org.gnome.system.proxy mode 'auto'\norg.gnome.system.proxy autoconfig-url 'http://wpad/wpad.dat'\n";
+        assert!(matches!(parse_gsettings(auto), Some(ProxyMode::Pac { location, .. }) if location == "http://wpad/wpad.dat"));
+        assert!(matches!(parse_gsettings("org.gnome.system.proxy mode 'none'\n"), Some(ProxyMode::Direct)));
+        assert!(parse_gsettings("").is_none());
+    }
+
+    #[test]
+    fn kioslaverc_modes() {
+        let text = "[Cookie Policy]\nCookieGlobalAdvice=Accept\n\n[Proxy Settings]\nNoProxyFor=localhost,.lan\nProxyType=1\nhttpProxy=http://proxy.lan 3128\nhttpsProxy=http://proxy.lan:3129\n";
+        let mode = parse_kioslaverc(text).unwrap();
+        assert_eq!(resolve(&mode, "http://example.com/").as_deref(), Some("http://proxy.lan:3128/"));
+        assert_eq!(resolve(&mode, "https://example.com/").as_deref(), Some("http://proxy.lan:3129/"));
+        assert_eq!(resolve(&mode, "http://nas.lan/"), None);
+
+        let pac_text = "[Proxy Settings]\nProxy Config Script=http://wpad/wpad.dat\nProxyType=2\n";
+        assert!(matches!(parse_kioslaverc(pac_text), Some(ProxyMode::Pac { location, .. }) if location == "http://wpad/wpad.dat"));
+        assert!(matches!(parse_kioslaverc("[Proxy Settings]\nProxyType=0\n"), Some(ProxyMode::Direct)));
+        assert!(parse_kioslaverc("[Proxy Settings]\nProxyType=4\n").is_none());
+    }
+}
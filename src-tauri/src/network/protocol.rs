@@ -0,0 +1,204 @@
+//! 协议抽象：调度器通过 ProtocolHandler 探测与分段下载，不直接依赖具体协议
+
+use crate::network::client::{build_client_from_options, check_status, probe_with_client, with_failover, Error, NetworkOptions, ProbeResult};
+use crate::network::proxy::ProxyConfig;
+use crate::network::ftp::FtpHandler;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::stream::{BoxStream, StreamExt, TryStreamExt};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 区间数据流：按到达顺序产出数据块
+pub type ByteStream = BoxStream<'static, Result<Bytes, Error>>;
+
+/// 协议能力：调度器据此决定连接数与是否分段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolCapabilities {
+    /// 是否可能支持区间请求（最终以探测结果为准）
+    pub supports_range: bool,
+    /// 单任务最大连接数上限；None 表示不限制
+    pub max_connections: Option<usize>,
+}
+
+/// 下载协议实现
+#[async_trait]
+pub trait ProtocolHandler: Send + Sync {
+    fn capabilities(&self) -> ProtocolCapabilities;
+
+    /// 探测大小、Range 支持与建议文件名
+    async fn probe(&self, url: &str) -> Result<ProbeResult, Error>;
+
+    /// 打开一段 [start, end]（inclusive）的数据流
+    async fn open_range(&self, url: &str, start: u64, end: u64) -> Result<ByteStream, Error>;
+
+    /// 请求一段并收集为完整数据
+    async fn fetch_range(&self, url: &str, start: u64, end: u64) -> Result<Bytes, Error> {
+        let chunks: Vec<Bytes> = self.open_range(url, start, end).await?.try_collect().await?;
+        Ok(Bytes::from(chunks.concat()))
+    }
+
+    /// 请求整个资源（播放列表、密钥等小文件）
+    async fn fetch(&self, url: &str) -> Result<Bytes, Error> {
+        self.fetch_range(url, 0, u64::MAX - 1).await
+    }
+
+    /// 打开整个资源的数据流（不支持 Range 的大文件）
+    async fn open(&self, url: &str) -> Result<ByteStream, Error> {
+        self.open_range(url, 0, u64::MAX - 1).await
+    }
+}
+
+/// HTTP(S)：复用同一个 reqwest Client
+pub struct HttpHandler {
+    client: Client,
+    proxy: ProxyConfig,
+}
+
+impl HttpHandler {
+    pub fn new(options: &NetworkOptions) -> Result<Self, Error> {
+        Ok(Self {
+            client: build_client_from_options(options)?,
+            proxy: options.proxy.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl ProtocolHandler for HttpHandler {
+    fn capabilities(&self) -> ProtocolCapabilities {
+        ProtocolCapabilities {
+            supports_range: true,
+            max_connections: None,
+        }
+    }
+
+    async fn probe(&self, url: &str) -> Result<ProbeResult, Error> {
+        with_failover(&self.proxy, url, || probe_with_client(&self.client, url)).await
+    }
+
+    async fn open_range(&self, url: &str, start: u64, end: u64) -> Result<ByteStream, Error> {
+        let parsed = url.parse::<reqwest::Url>().map_err(|e| Error::Url(e.to_string()))?;
+        let resp = with_failover(&self.proxy, url, || async {
+            let range = format!("bytes={}-{}", start, end);
+            Ok(self.client.get(parsed.clone()).header("Range", range).send().await?)
+        })
+        .await?;
+        let resp = check_status(resp).await?;
+        Ok(resp.bytes_stream().map_err(Error::Request).boxed())
+    }
+
+    async fn fetch(&self, url: &str) -> Result<Bytes, Error> {
+        let parsed = url.parse::<reqwest::Url>().map_err(|e| Error::Url(e.to_string()))?;
+        let resp = with_failover(&self.proxy, url, || async { Ok(self.client.get(parsed.clone()).send().await?) }).await?;
+        Ok(check_status(resp).await?.bytes().await?)
+    }
+
+    async fn open(&self, url: &str) -> Result<ByteStream, Error> {
+        let parsed = url.parse::<reqwest::Url>().map_err(|e| Error::Url(e.to_string()))?;
+        let resp = with_failover(&self.proxy, url, || async { Ok(self.client.get(parsed.clone()).send().await?) }).await?;
+        Ok(check_status(resp).await?.bytes_stream().map_err(Error::Request).boxed())
+    }
+}
+
+#[async_trait]
+impl ProtocolHandler for FtpHandler {
+    fn capabilities(&self) -> ProtocolCapabilities {
+        // FTP 服务器普遍限制单 IP 连接数
+        ProtocolCapabilities {
+            supports_range: true,
+            max_connections: Some(4),
+        }
+    }
+
+    async fn probe(&self, url: &str) -> Result<ProbeResult, Error> {
+        FtpHandler::probe(self, url).await
+    }
+
+    async fn open_range(&self, url: &str, start: u64, end: u64) -> Result<ByteStream, Error> {
+        FtpHandler::open_range(self, url, start, end).await
+    }
+
+    async fn fetch_range(&self, url: &str, start: u64, end: u64) -> Result<Bytes, Error> {
+        FtpHandler::fetch_range(self, url, start, end).await
+    }
+
+    async fn fetch(&self, url: &str) -> Result<Bytes, Error> {
+        FtpHandler::fetch(self, url).await
+    }
+
+    async fn open(&self, url: &str) -> Result<ByteStream, Error> {
+        FtpHandler::open(self, url).await
+    }
+}
+
+fn url_scheme(url: &str) -> Option<String> {
+    let (scheme, _) = url.trim().split_once("://")?;
+    Some(scheme.to_ascii_lowercase())
+}
+
+/// 默认注册的协议
+const DEFAULT_SCHEMES: &[&str] = &["http", "https", "ftp", "ftps"];
+
+/// 是否为默认协议可下载的地址（http/https/ftp/ftps）
+pub fn is_supported_url(url: &str) -> bool {
+    url_scheme(url).is_some_and(|s| DEFAULT_SCHEMES.contains(&s.as_str()))
+}
+
+type HandlerFactory = Arc<dyn Fn(&NetworkOptions) -> Result<Arc<dyn ProtocolHandler>, Error> + Send + Sync>;
+
+/// 协议注册表：按 URL scheme 创建对应的处理器
+#[derive(Clone)]
+pub struct ProtocolRegistry {
+    factories: HashMap<String, HandlerFactory>,
+}
+
+impl Default for ProtocolRegistry {
+    /// 内置 HTTP(S) 与 FTP(S)
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        for scheme in ["http", "https"] {
+            registry.register(scheme, |opts| Ok(Arc::new(HttpHandler::new(opts)?) as Arc<dyn ProtocolHandler>));
+        }
+        for scheme in ["ftp", "ftps"] {
+            registry.register(scheme, |opts| Ok(Arc::new(FtpHandler::new(opts)) as Arc<dyn ProtocolHandler>));
+        }
+        registry
+    }
+}
+
+impl ProtocolRegistry {
+    /// 不含任何协议的注册表
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// 注册（或替换）某个 scheme 的处理器工厂；每次开始下载时按网络选项创建
+    pub fn register<F>(&mut self, scheme: &str, factory: F)
+    where
+        F: Fn(&NetworkOptions) -> Result<Arc<dyn ProtocolHandler>, Error> + Send + Sync + 'static,
+    {
+        self.factories.insert(scheme.to_ascii_lowercase(), Arc::new(factory));
+    }
+
+    /// 注册一个共享实例，忽略网络选项（用于内存实现等）
+    pub fn register_handler(&mut self, scheme: &str, handler: Arc<dyn ProtocolHandler>) {
+        self.register(scheme, move |_| Ok(handler.clone()));
+    }
+
+    pub fn supports(&self, url: &str) -> bool {
+        url_scheme(url).is_some_and(|s| self.factories.contains_key(&s))
+    }
+
+    pub fn handler_for(&self, url: &str, options: &NetworkOptions) -> Result<Arc<dyn ProtocolHandler>, Error> {
+        let scheme = url_scheme(url).ok_or_else(|| Error::Url(format!("无效地址: {}", url)))?;
+        let factory = self
+            .factories
+            .get(&scheme)
+            .ok_or_else(|| Error::Url(format!("不支持的协议: {}", scheme)))?;
+        factory(options)
+    }
+}
@@ -0,0 +1,252 @@
+//! 任务检索：按状态、分类、站点、创建时间与文件名/地址筛选，排序后分页；
+//! 排序键相同时按创建时间与 id，顺序稳定，分页不会重复或漏掉任务
+
+use crate::engine::scheduler::Scheduler;
+use crate::engine::{TaskInfo, TaskStatus};
+use crate::intake::{category, host_matches, url_host};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// 创建时间
+    #[default]
+    Added,
+    /// 文件名（不区分大小写）
+    Name,
+    /// 总大小；未知的无论升降序都排在最后
+    Size,
+    /// 下载进度（已下载 / 总大小）
+    Progress,
+    Status,
+    /// 当前速度
+    Speed,
+}
+
+/// 检索条件；均可省略，省略时为全部任务按创建时间升序
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskQuery {
+    /// 文件名或地址包含，空白分隔的多个词须都包含（不区分大小写）
+    pub text: Option<String>,
+    /// 任一状态；空为不限
+    pub status: Vec<TaskStatus>,
+    /// 文件分类标识（program、document、video、archive、other）
+    pub category: Option<String>,
+    /// 主机名；`*.example.com` 匹配 example.com 及其子域名
+    pub host: Option<String>,
+    /// 创建时间范围（Unix 秒，含两端）
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub sort: SortKey,
+    pub descending: bool,
+    pub offset: usize,
+    /// 省略时不分页
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskPage {
+    pub tasks: Vec<TaskInfo>,
+    /// 符合条件的总数（分页前）
+    pub total: usize,
+}
+
+impl TaskQuery {
+    pub fn matches(&self, t: &TaskInfo) -> bool {
+        if !self.status.is_empty() && !self.status.contains(&t.status) {
+            return false;
+        }
+        if let Some(text) = self.text.as_deref().filter(|s| !s.trim().is_empty()) {
+            let filename = t.filename.to_lowercase();
+            let url = t.url.to_lowercase();
+            if !text
+                .to_lowercase()
+                .split_whitespace()
+                .all(|word| filename.contains(word) || url.contains(word))
+            {
+                return false;
+            }
+        }
+        if let Some(pattern) = self.host.as_deref().filter(|h| !h.trim().is_empty()) {
+            if !url_host(&t.url).is_some_and(|host| host_matches(pattern, &host)) {
+                return false;
+            }
+        }
+        self.category.as_deref().map_or(true, |c| c.is_empty() || category(&t.filename).0 == c)
+            && self.from.map_or(true, |from| t.created_at >= from)
+            && self.to.map_or(true, |to| t.created_at <= to)
+    }
+
+    /// 筛选、排序并分页
+    pub fn apply(&self, tasks: Vec<TaskInfo>) -> TaskPage {
+        let mut matched: Vec<TaskInfo> = tasks.into_iter().filter(|t| self.matches(t)).collect();
+        matched.sort_by(|a, b| {
+            compare(self.sort, self.descending, a, b)
+                .then_with(|| a.created_at.cmp(&b.created_at))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        let total = matched.len();
+        let tasks = matched
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        TaskPage { tasks, total }
+    }
+}
+
+pub async fn query(scheduler: &Scheduler, query: &TaskQuery) -> TaskPage {
+    query.apply(scheduler.list_downloads().await)
+}
+
+fn compare(key: SortKey, descending: bool, a: &TaskInfo, b: &TaskInfo) -> Ordering {
+    let ord = match key {
+        SortKey::Added => a.created_at.cmp(&b.created_at),
+        SortKey::Name => a.filename.to_lowercase().cmp(&b.filename.to_lowercase()),
+        SortKey::Size => match (a.total_bytes, b.total_bytes) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            // 大小未知的无论升降序都在最后
+            (x, y) => return y.is_some().cmp(&x.is_some()),
+        },
+        SortKey::Progress => progress(a).total_cmp(&progress(b)),
+        SortKey::Status => status_rank(a.status).cmp(&status_rank(b.status)),
+        SortKey::Speed => a.speed_bps.unwrap_or(0).cmp(&b.speed_bps.unwrap_or(0)),
+    };
+    if descending {
+        ord.reverse()
+    } else {
+        ord
+    }
+}
+
+fn progress(t: &TaskInfo) -> f64 {
+    match t.total_bytes {
+        _ if t.status == TaskStatus::Completed => 1.0,
+        Some(total) if total > 0 => t.downloaded_bytes as f64 / total as f64,
+        _ => 0.0,
+    }
+}
+
+/// 进行中的在前，结束的在后
+fn status_rank(s: TaskStatus) -> u8 {
+    match s {
+        TaskStatus::Downloading => 0,
+        TaskStatus::NeedsRefresh => 1,
+        TaskStatus::Pending => 2,
+        TaskStatus::Paused => 3,
+        TaskStatus::Failed => 4,
+        TaskStatus::Completed => 5,
+        TaskStatus::Cancelled => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, filename: &str, status: TaskStatus, total: Option<u64>, created_at: i64) -> TaskInfo {
+        TaskInfo {
+            id: id.to_string(),
+            url: format!("https://dl.example.com/files/{}", filename),
+            filename: filename.to_string(),
+            save_path: format!("/downloads/{}", filename),
+            total_bytes: total,
+            downloaded_bytes: 0,
+            status,
+            error_message: None,
+            speed_bps: None,
+            created_at,
+            kind: Default::default(),
+            sources: Vec::new(),
+            source: Default::default(),
+        }
+    }
+
+    fn ids(page: &TaskPage) -> Vec<&str> {
+        page.tasks.iter().map(|t| t.id.as_str()).collect()
+    }
+
+    fn sample() -> Vec<TaskInfo> {
+        let mut other = task("e", "Report Final.PDF", TaskStatus::Failed, None, 50);
+        other.url = "ftp://mirror.example.org/pub/report.pdf".to_string();
+        vec![
+            task("c", "movie.mkv", TaskStatus::Downloading, Some(700), 30),
+            task("a", "setup.exe", TaskStatus::Completed, Some(100), 10),
+            task("d", "notes.txt", TaskStatus::Paused, Some(100), 30),
+            task("b", "archive.zip", TaskStatus::Completed, None, 20),
+            other,
+        ]
+    }
+
+    #[test]
+    fn filters_by_text_status_category_host_and_time() {
+        let run = |query: TaskQuery| ids(&query.apply(sample())).into_iter().map(String::from).collect::<Vec<_>>();
+        assert_eq!(run(TaskQuery::default()), ["a", "b", "c", "d", "e"]);
+        let text = |s: &str| TaskQuery { text: Some(s.to_string()), ..TaskQuery::default() };
+        assert_eq!(run(text("REPORT pub")), ["e"]);
+        assert_eq!(run(text("dl.example files")), ["a", "b", "c", "d"]);
+        assert_eq!(run(text("   ")).len(), 5);
+        assert_eq!(
+            run(TaskQuery { status: vec![TaskStatus::Completed, TaskStatus::Paused], ..TaskQuery::default() }),
+            ["a", "b", "d"]
+        );
+        assert_eq!(run(TaskQuery { category: Some("document".into()), ..TaskQuery::default() }), ["d", "e"]);
+        assert_eq!(run(TaskQuery { host: Some("*.example.org".into()), ..TaskQuery::default() }), ["e"]);
+        assert!(run(TaskQuery { host: Some("example.com".into()), ..TaskQuery::default() }).is_empty());
+        assert_eq!(run(TaskQuery { from: Some(20), to: Some(30), ..TaskQuery::default() }), ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn sort_keeps_ties_in_creation_order() {
+        let sorted = |sort: SortKey, descending: bool| {
+            let query = TaskQuery { sort, descending, ..TaskQuery::default() };
+            ids(&query.apply(sample())).into_iter().map(String::from).collect::<Vec<_>>()
+        };
+        assert_eq!(sorted(SortKey::Name, false), ["b", "c", "d", "e", "a"]);
+        // c 与 d 创建时间相同，按 id 排；降序不影响相同键之间的顺序
+        assert_eq!(sorted(SortKey::Added, true), ["e", "c", "d", "b", "a"]);
+        // 大小未知的无论升降序都在最后；a 与 d 同为 100
+        assert_eq!(sorted(SortKey::Size, false), ["a", "d", "c", "b", "e"]);
+        assert_eq!(sorted(SortKey::Size, true), ["c", "a", "d", "b", "e"]);
+        assert_eq!(sorted(SortKey::Status, false), ["c", "d", "e", "a", "b"]);
+        // 已完成的进度为 1，无论是否知道大小
+        assert_eq!(sorted(SortKey::Progress, true), ["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn pages_cover_every_task_once() {
+        let tasks: Vec<TaskInfo> = (0..23)
+            .map(|i| task(&format!("t{:02}", (i * 7) % 23), "same.bin", TaskStatus::Paused, Some(1), i % 3))
+            .collect();
+        let full = TaskQuery { sort: SortKey::Size, descending: true, ..TaskQuery::default() }.apply(tasks.clone());
+        assert_eq!(full.total, 23);
+        let mut paged = Vec::new();
+        for offset in (0..30).step_by(5) {
+            let query = TaskQuery {
+                sort: SortKey::Size,
+                descending: true,
+                offset,
+                limit: Some(5),
+                ..TaskQuery::default()
+            };
+            let page = query.apply(tasks.clone());
+            assert_eq!(page.total, 23);
+            assert!(page.tasks.len() <= 5);
+            paged.extend(page.tasks.into_iter().map(|t| t.id));
+        }
+        assert_eq!(paged, ids(&full));
+        let mut unique = paged.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 23);
+    }
+
+    #[test]
+    fn query_rejects_unknown_fields() {
+        let query: TaskQuery = serde_json::from_str(r#"{"sort":"size","descending":true,"limit":10}"#).unwrap();
+        assert_eq!((query.sort, query.descending, query.limit), (SortKey::Size, true, Some(10)));
+        assert!(serde_json::from_str::<TaskQuery>(r#"{"sort_by":"size"}"#).is_err());
+    }
+}
@@ -1,18 +1,19 @@
 //! 应用设置：持久化与加载
 
+use crate::network::{BypassList, NetworkOptions, ProxyConfig, ProxyMode, SystemProxy};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
 const SETTINGS_FILENAME: &str = "multidown_settings.json";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case", default)]
 pub struct AppSettings {
     /// 默认保存路径（空则使用系统下载目录）
     pub default_save_path: String,
     /// 每任务最大连接数
     pub max_connections_per_task: u32,
-    /// 全局最大并发任务数（暂未用）
+    /// 同时下载的任务数上限，超出的自动开始任务排队（0 表示不限；手动开始不受限）
     pub max_concurrent_tasks: u32,
     /// 系统启动时运行
     pub run_at_startup: bool,
@@ -24,16 +25,30 @@ pub struct AppSettings {
     pub show_complete_dialog: bool,
     /// 重复链接：ask | skip | overwrite | rename
     pub duplicate_action: String,
+    /// 未指定保存目录的任务按文件类型保存到默认目录下的分类子目录（程序、文档、视频、压缩包、其他）
+    pub categorize_downloads: bool,
+    /// 按站点指定保存目录与连接数
+    pub site_rules: Vec<SiteRule>,
     /// 手动添加任务时的 User-Agent
     pub user_agent: String,
     /// 使用上次的保存路径
     pub use_last_save_path: bool,
-    /// 代理类型：none | system | manual
+    /// 代理类型：none（沿用环境变量）| direct（直连）| system | manual | pac
     pub proxy_type: String,
+    /// 手动代理协议：http | https | socks5 | socks5h
+    pub proxy_scheme: String,
     /// 手动代理地址
     pub proxy_host: String,
     /// 手动代理端口
     pub proxy_port: u16,
+    /// 代理用户名（留空表示无需认证）
+    pub proxy_username: String,
+    /// 代理密码
+    pub proxy_password: String,
+    /// 不使用代理的地址，逗号或分号分隔，如 "localhost, *.lan, 10.0.0.0/8, <local>"
+    pub proxy_bypass: String,
+    /// PAC 脚本地址（http(s) URL 或本地路径）
+    pub proxy_pac_url: String,
     /// 下载完成时通知
     pub notification_on_complete: bool,
     /// 下载失败时通知
@@ -42,6 +57,22 @@ pub struct AppSettings {
     pub timeout_secs: u64,
     /// 下载中周期保存进度间隔（秒），0 表示不周期保存
     pub save_progress_interval_secs: u64,
+    /// 本地控制接口（HTTP/JSON-RPC，仅监听 127.0.0.1），重启后生效
+    pub control_api_enabled: bool,
+    /// 控制接口端口
+    pub control_api_port: u16,
+    /// 控制接口访问令牌；为空时启用接口会自动生成
+    pub control_api_token: String,
+    /// 在控制接口上开放 aria2 兼容的 /jsonrpc（令牌即 rpc-secret）
+    pub aria2_rpc_enabled: bool,
+    /// 浏览器扩展可指定的保存目录（默认保存路径与系统下载目录始终允许）
+    pub browser_allowed_dirs: Vec<String>,
+    /// 除本机 IPC 外再监听 TCP 端口并写入端口文件，供无法使用 IPC 的环境回退
+    pub browser_tcp_fallback: bool,
+    /// 下载历史保留天数（0 表示不限）
+    pub history_retention_days: u32,
+    /// 下载历史最多保留的条数，超出时删除最早的（0 表示不限）
+    pub history_max_entries: u32,
 }
 
 impl Default for AppSettings {
@@ -55,30 +86,98 @@ impl Default for AppSettings {
             show_start_dialog: true,
             show_complete_dialog: true,
             duplicate_action: "ask".to_string(),
+            categorize_downloads: false,
+            site_rules: Vec::new(),
             user_agent: "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36".to_string(),
             use_last_save_path: true,
             proxy_type: "none".to_string(),
+            proxy_scheme: "http".to_string(),
             proxy_host: String::new(),
             proxy_port: 8080,
+            proxy_username: String::new(),
+            proxy_password: String::new(),
+            proxy_bypass: "localhost, 127.0.0.1, <local>".to_string(),
+            proxy_pac_url: String::new(),
             notification_on_complete: true,
             notification_on_fail: true,
             timeout_secs: 30,
             save_progress_interval_secs: 30,
+            control_api_enabled: false,
+            control_api_port: 16800,
+            control_api_token: String::new(),
+            aria2_rpc_enabled: false,
+            browser_allowed_dirs: Vec::new(),
+            browser_tcp_fallback: false,
+            history_retention_days: 365,
+            history_max_entries: 10000,
         }
     }
 }
 
+/// 站点规则
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct SiteRule {
+    /// 主机名；`*.example.com` 匹配 example.com 及其子域名
+    pub host: String,
+    /// 保存目录，空则不改变
+    pub save_dir: String,
+    /// 每任务连接数，0 则使用全局设置
+    pub max_connections: u32,
+}
+
 impl AppSettings {
-    /// 若为手动代理且配置了 host，返回 "http://host:port"
-    pub fn proxy_url(&self) -> Option<String> {
-        if self.proxy_type != "manual" || self.proxy_host.is_empty() {
-            return None;
-        }
-        let host = self.proxy_host.trim();
-        if host.is_empty() {
-            return None;
-        }
-        Some(format!("http://{}:{}", host, self.proxy_port))
+    /// 按代理设置生成代理配置；系统代理设置与 PAC 脚本需再经 `network_options` 加载。
+    /// 手动代理地址无效、PAC 地址为空或代理类型未知时返回错误，不会悄悄改为直连
+    pub fn proxy_config(&self) -> Result<ProxyConfig, String> {
+        let mode = match self.proxy_type.as_str() {
+            "" | "none" => ProxyMode::Env,
+            "direct" => ProxyMode::Direct,
+            "system" => ProxyMode::System(SystemProxy::from_env()),
+            "manual" => {
+                let scheme = if self.proxy_scheme.is_empty() { "http" } else { self.proxy_scheme.as_str() };
+                crate::network::proxy::build_proxy_url(
+                    scheme,
+                    &self.proxy_host,
+                    self.proxy_port,
+                    &self.proxy_username,
+                    &self.proxy_password,
+                )
+                .map(ProxyMode::Manual)
+                .ok_or_else(|| {
+                    format!("代理设置无效: {}://{}:{}", scheme, self.proxy_host.trim(), self.proxy_port)
+                })?
+            }
+            "pac" => {
+                let location = self.proxy_pac_url.trim();
+                if location.is_empty() {
+                    return Err("代理设置无效: 未填写 PAC 脚本地址".to_string());
+                }
+                ProxyMode::Pac {
+                    location: location.to_string(),
+                    script: None,
+                }
+            }
+            other => return Err(format!("未知的代理类型: {}", other)),
+        };
+        Ok(ProxyConfig {
+            mode,
+            bypass: BypassList::parse(&self.proxy_bypass),
+        })
+    }
+
+    /// 生成下载使用的网络选项；代理设置无效或 PAC 加载失败时返回错误
+    pub async fn network_options(&self) -> Result<NetworkOptions, String> {
+        let proxy = self
+            .proxy_config()?
+            .load()
+            .await
+            .map_err(|e| format!("加载 PAC 失败: {}", e))?;
+        Ok(NetworkOptions {
+            proxy,
+            timeout_secs: self.timeout_secs,
+            headers: Vec::new(),
+        })
     }
 }
 
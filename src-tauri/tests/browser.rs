@@ -0,0 +1,38 @@
+//! 浏览器集成协议：list 分页，回复不超过 Native Messaging 的消息上限
+
+use multidown_lib::browser::{list_page, LIST_PAGE_MAX};
+use multidown_lib::engine::scheduler::Scheduler;
+
+#[tokio::test]
+async fn list_is_paginated_and_capped() {
+    let sched = Scheduler::new(None);
+    let dir = std::env::temp_dir().to_string_lossy().into_owned();
+    let mut ids = Vec::new();
+    for i in 0..250 {
+        let url = format!("https://example.com/{}/{}", "a".repeat(1500), i);
+        ids.push(sched.create_deferred_task(url, dir.clone(), format!("{}.bin", i)).await.unwrap());
+    }
+    let tasks = sched.list_downloads().await;
+
+    let first = list_page(tasks.clone(), false, 0, None);
+    assert_eq!(first.total, Some(250));
+    let first = first.tasks.unwrap();
+    assert_eq!(first.len(), LIST_PAGE_MAX);
+    let line = serde_json::to_string(&list_page(tasks.clone(), false, 0, Some(10_000))).unwrap();
+    assert!(line.len() < 1024 * 1024, "{}", line.len());
+
+    // 逐页取完，不重复也不遗漏
+    let mut seen = Vec::new();
+    let mut offset = 0;
+    loop {
+        let page = list_page(tasks.clone(), true, offset, Some(70)).tasks.unwrap();
+        if page.is_empty() {
+            break;
+        }
+        offset += page.len();
+        seen.extend(page.into_iter().map(|t| t.id));
+    }
+    seen.sort();
+    ids.sort();
+    assert_eq!(seen, ids);
+}
@@ -0,0 +1,62 @@
+//! 集成测试共用：临时目录与等待任务状态（各测试文件只用到其中一部分）
+#![allow(dead_code)]
+
+use multidown_lib::engine::scheduler::Scheduler;
+use multidown_lib::engine::{TaskInfo, TaskStatus};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 按测试名与进程号命名的空目录，离开作用域时连同内容删除
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("multidown_test_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn to_str(&self) -> String {
+        self.0.to_string_lossy().into_owned()
+    }
+}
+
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 等待任务满足条件，最多 10 秒
+pub async fn wait_for(sched: &Scheduler, id: &str, done: impl Fn(&TaskInfo) -> bool) -> TaskInfo {
+    for _ in 0..500 {
+        let info = sched.get_task(id).await.expect("task");
+        if done(&info) {
+            return info;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    sched.get_task(id).await.expect("task")
+}
+
+pub fn finished(info: &TaskInfo) -> bool {
+    !matches!(info.status, TaskStatus::Pending | TaskStatus::Downloading)
+}
+
+/// 等待任务结束（不再是等待或下载中）
+pub async fn wait_finished(sched: &Scheduler, id: &str) -> TaskInfo {
+    wait_for(sched, id, finished).await
+}
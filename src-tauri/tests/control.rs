@@ -0,0 +1,85 @@
+//! 本地控制接口：经 HTTP 调用 JSON-RPC 方法
+
+mod common;
+
+use multidown_lib::control::{self, ControlContext};
+use multidown_lib::engine::scheduler::Scheduler;
+use multidown_lib::engine::BroadcastEvents;
+use multidown_lib::intake::Intake;
+use multidown_lib::settings::{load_settings, save_settings, AppSettings};
+use common::TempDir;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const TOKEN: &str = "test-token";
+
+/// 启动控制接口，返回 /rpc 地址
+async fn serve(settings_path: PathBuf) -> String {
+    let events = Arc::new(BroadcastEvents::new(64));
+    let scheduler = Arc::new(Scheduler::new(None).with_events(events.clone()));
+    let intake = Arc::new(Intake::new(scheduler.clone(), settings_path.clone(), ".".to_string()));
+    let listener = control::bind(0).await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let ctx = Arc::new(ControlContext {
+        scheduler,
+        intake,
+        events,
+        token: TOKEN.to_string(),
+        aria2_rpc: false,
+    });
+    tokio::spawn(control::serve(listener, ctx));
+    format!("http://127.0.0.1:{}/rpc", port)
+}
+
+async fn call(endpoint: &str, method: &str, params: Value) -> Value {
+    let resp: Value = reqwest::Client::builder()
+        .no_proxy()
+        .build()
+        .unwrap()
+        .post(endpoint)
+        .bearer_auth(TOKEN)
+        .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(resp.get("error").is_none(), "{}", resp);
+    resp["result"].clone()
+}
+
+#[tokio::test]
+async fn proxy_password_is_never_returned() {
+    let dir = TempDir::new("control_settings");
+    let path = dir.join("settings.json");
+    let settings = AppSettings {
+        proxy_type: "manual".to_string(),
+        proxy_host: "127.0.0.1".to_string(),
+        proxy_port: 8080,
+        proxy_username: "user".to_string(),
+        proxy_password: "secret".to_string(),
+        ..AppSettings::default()
+    };
+    save_settings(&path, &settings).await.unwrap();
+    let rpc = serve(path.clone()).await;
+
+    let got = call(&rpc, "settings.get", json!({})).await;
+    assert_eq!(got["proxy_password"], "********");
+    assert_eq!(got["proxy_username"], "user");
+
+    // 省略密码或原样传回占位符时保留已保存的密码
+    call(&rpc, "settings.set", got).await;
+    let set = call(&rpc, "settings.set", json!({ "timeout_secs": 60 })).await;
+    assert_eq!(set["proxy_password"], "********");
+    let stored = load_settings(&path).unwrap();
+    assert_eq!(stored.proxy_password, "secret");
+    assert_eq!(stored.timeout_secs, 60);
+
+    call(&rpc, "settings.set", json!({ "proxy_password": "changed" })).await;
+    assert_eq!(load_settings(&path).unwrap().proxy_password, "changed");
+    call(&rpc, "settings.set", json!({ "proxy_password": "" })).await;
+    assert_eq!(load_settings(&path).unwrap().proxy_password, "");
+    assert_eq!(call(&rpc, "settings.get", json!({})).await["proxy_password"], "");
+}
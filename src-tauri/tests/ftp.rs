@@ -0,0 +1,191 @@
+//! 对本地 FTP 服务器替身探测与按 REST 分段读取，并确认配置了代理时拒绝 FTP
+
+use futures_util::StreamExt;
+use multidown_lib::network::{BypassList, NetworkOptions, ProtocolRegistry, ProxyConfig, ProxyMode};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// 只提供一个文件的被动模式 FTP 服务器；返回地址与收到的命令
+async fn ftp_server(path: &'static str, content: Vec<u8>) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    ftp_server_with(path, content, usize::MAX).await
+}
+
+/// 同上，但每条控制连接完成 `transfers` 次传输后由服务器关闭（模拟空闲超时）
+async fn ftp_server_with(
+    path: &'static str,
+    content: Vec<u8>,
+    transfers: usize,
+) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let commands = Arc::new(Mutex::new(Vec::new()));
+    let log = commands.clone();
+    let content = Arc::new(content);
+    tokio::spawn(async move {
+        while let Ok((sock, _)) = listener.accept().await {
+            let (log, content) = (log.clone(), content.clone());
+            tokio::spawn(async move {
+                let (read, mut write) = sock.into_split();
+                let mut lines = BufReader::new(read).lines();
+                let _ = write.write_all(b"220 ready\r\n").await;
+                let mut offset = 0usize;
+                let mut done = 0usize;
+                let mut passive: Option<TcpListener> = None;
+                while let Ok(Some(line)) = lines.next_line().await {
+                    log.lock().unwrap().push(line.clone());
+                    let (cmd, arg) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+                    let reply = match cmd.to_ascii_uppercase().as_str() {
+                        "USER" => "331 password please".to_string(),
+                        "PASS" => "230 logged in".to_string(),
+                        "TYPE" => "200 binary".to_string(),
+                        "SIZE" if arg == path => format!("213 {}", content.len()),
+                        "MDTM" if arg == path => "213 20240102030405".to_string(),
+                        "SIZE" | "MDTM" => "550 not found".to_string(),
+                        "REST" => {
+                            offset = arg.parse().unwrap_or(0);
+                            format!("350 restarting at {}", offset)
+                        }
+                        "EPSV" => {
+                            let data = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                            let port = data.local_addr().unwrap().port();
+                            passive = Some(data);
+                            format!("229 Entering Extended Passive Mode (|||{}|)", port)
+                        }
+                        "RETR" => {
+                            let Some(data) = passive.take() else {
+                                let _ = write.write_all(b"425 no data connection\r\n").await;
+                                continue;
+                            };
+                            let _ = write.write_all(b"150 opening data connection\r\n").await;
+                            let (mut conn, _) = data.accept().await.unwrap();
+                            let complete = conn.write_all(&content[offset.min(content.len())..]).await.is_ok();
+                            drop(conn);
+                            offset = 0;
+                            done += 1;
+                            if complete { "226 transfer complete" } else { "426 connection closed" }.to_string()
+                        }
+                        "QUIT" => {
+                            let _ = write.write_all(b"221 bye\r\n").await;
+                            return;
+                        }
+                        _ => "502 not implemented".to_string(),
+                    };
+                    if write.write_all(format!("{}\r\n", reply).as_bytes()).await.is_err() || done >= transfers {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    (addr, commands)
+}
+
+fn content() -> Vec<u8> {
+    (0..100_000u32).map(|i| (i % 251) as u8).collect()
+}
+
+fn direct() -> NetworkOptions {
+    NetworkOptions {
+        proxy: ProxyConfig {
+            mode: ProxyMode::Direct,
+            bypass: BypassList::default(),
+        },
+        timeout_secs: 5,
+        headers: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn probe_reports_size_date_and_range_support() {
+    let (addr, _) = ftp_server("/pub/data.bin", content()).await;
+    let url = format!("ftp://{}/pub/data.bin", addr);
+    let handler = ProtocolRegistry::default().handler_for(&url, &direct()).unwrap();
+    let probe = handler.probe(&url).await.unwrap();
+    assert_eq!(probe.total_bytes, Some(100_000));
+    assert!(probe.supports_range);
+    assert_eq!(probe.suggested_filename, "data.bin");
+    assert_eq!(probe.last_modified.as_deref(), Some("Tue, 02 Jan 2024 03:04:05 GMT"));
+
+    let missing = format!("ftp://{}/pub/missing.bin", addr);
+    assert!(handler.probe(&missing).await.is_err());
+}
+
+#[tokio::test]
+async fn ranged_reads_use_rest() {
+    let data = content();
+    let (addr, commands) = ftp_server("/pub/data.bin", data.clone()).await;
+    let url = format!("ftp://user:secret@{}/pub/data.bin", addr);
+    let handler = ProtocolRegistry::default().handler_for(&url, &direct()).unwrap();
+
+    let middle = handler.fetch_range(&url, 40_000, 40_999).await.unwrap();
+    assert_eq!(&middle[..], &data[40_000..41_000]);
+    let tail = handler.fetch_range(&url, 99_990, 99_999).await.unwrap();
+    assert_eq!(&tail[..], &data[99_990..]);
+    let whole = handler.fetch(&url).await.unwrap();
+    assert_eq!(&whole[..], &data[..]);
+
+    let commands = commands.lock().unwrap();
+    assert!(commands.iter().any(|c| c == "USER user"));
+    assert!(commands.iter().any(|c| c == "REST 40000"));
+    assert!(commands.iter().any(|c| c == "REST 99990"));
+    // 空闲控制连接被复用，只登录一次
+    assert_eq!(commands.iter().filter(|c| c.starts_with("PASS")).count(), 1);
+}
+
+#[tokio::test]
+async fn configured_proxy_rejects_ftp_unless_bypassed() {
+    let (addr, _) = ftp_server("/pub/data.bin", content()).await;
+    let url = format!("ftp://{}/pub/data.bin", addr);
+    let mut options = direct();
+    options.proxy.mode = ProxyMode::Manual("socks5h://127.0.0.1:9".parse().unwrap());
+    let handler = ProtocolRegistry::default().handler_for(&url, &options).unwrap();
+    let err = handler.probe(&url).await.unwrap_err();
+    assert!(err.to_string().contains("FTP"), "{}", err);
+
+    options.proxy.bypass = BypassList::parse("127.0.0.1");
+    let handler = ProtocolRegistry::default().handler_for(&url, &options).unwrap();
+    assert_eq!(handler.probe(&url).await.unwrap().total_bytes, Some(100_000));
+}
+
+#[tokio::test]
+async fn ranges_stream_in_chunks_and_survive_early_drop() {
+    let data = content();
+    let (addr, commands) = ftp_server("/pub/data.bin", data.clone()).await;
+    let url = format!("ftp://{}/pub/data.bin", addr);
+    let handler = ProtocolRegistry::default().handler_for(&url, &direct()).unwrap();
+
+    let mut stream = handler.open_range(&url, 0, 99_999).await.unwrap();
+    let mut chunks = 0;
+    let mut body = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        body.extend_from_slice(&chunk.unwrap());
+        chunks += 1;
+    }
+    assert_eq!(body, data);
+    assert!(chunks > 1, "整段只产出了 {} 块", chunks);
+
+    // 读到一半丢弃：连接随之关闭，后续请求照常
+    let mut partial = handler.open_range(&url, 0, 99_999).await.unwrap();
+    assert!(partial.next().await.unwrap().is_ok());
+    drop(partial);
+    let tail = handler.fetch_range(&url, 99_000, 99_999).await.unwrap();
+    assert_eq!(&tail[..], &data[99_000..]);
+    assert_eq!(commands.lock().unwrap().iter().filter(|c| c.starts_with("PASS")).count(), 2);
+}
+
+#[tokio::test]
+async fn dead_idle_session_is_replaced() {
+    let data = content();
+    let (addr, commands) = ftp_server_with("/pub/data.bin", data.clone(), 1).await;
+    let url = format!("ftp://{}/pub/data.bin", addr);
+    let handler = ProtocolRegistry::default().handler_for(&url, &direct()).unwrap();
+
+    let head = handler.fetch_range(&url, 0, 999).await.unwrap();
+    assert_eq!(&head[..], &data[..1000]);
+    // 服务器已关闭池中的控制连接，换新连接重试而不是报错
+    let middle = handler.fetch_range(&url, 50_000, 50_999).await.unwrap();
+    assert_eq!(&middle[..], &data[50_000..51_000]);
+    assert_eq!(commands.lock().unwrap().iter().filter(|c| c.starts_with("PASS")).count(), 2);
+}
@@ -0,0 +1,32 @@
+//! 站点抓取：取消时放弃进行中的请求
+
+use multidown_lib::grabber::{grab, GrabOptions};
+use multidown_lib::network::{MemoryHandler, NetworkOptions, ProtocolRegistry};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancel_aborts_in_flight_page_fetch() {
+    // 64 块、每块 200 毫秒：不取消时要十几秒才能读完
+    let mem = Arc::new(MemoryHandler::new(true).with_chunk_size(1024).with_delay(Duration::from_millis(200)));
+    let page = format!("<a href=\"a.zip\">a</a>{}", " ".repeat(64 * 1024));
+    mem.insert("mem://site/index.html", page);
+    let mut protocols = ProtocolRegistry::empty();
+    protocols.register_handler("mem", mem.clone());
+    let options = GrabOptions {
+        url: "mem://site/index.html".into(),
+        ..GrabOptions::default()
+    };
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        flag.store(true, Ordering::Relaxed);
+    });
+    let started = Instant::now();
+    let result = grab(&protocols, &NetworkOptions::default(), &options, &cancel, &|_| {}).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(3), "{:?}", started.elapsed());
+    assert!(result.cancelled);
+    assert_eq!(result.pages, 0);
+}
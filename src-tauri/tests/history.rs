@@ -0,0 +1,98 @@
+//! 下载历史：事件再多也不漏记；晚写入的记录按结束时间排序后再清理
+
+mod common;
+
+use multidown_lib::engine::scheduler::Scheduler;
+use multidown_lib::engine::{ChannelEvents, TaskStatus};
+use multidown_lib::history::{History, HistoryEntry, HistoryQuery, Outcome};
+use multidown_lib::network::{MemoryHandler, ProtocolRegistry};
+use multidown_lib::settings::AppSettings;
+use common::{wait_for, TempDir};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn history_records_every_task_after_a_burst_of_events() {
+    let dir = TempDir::new("history_burst");
+    // 小块读取产生大量进度事件
+    let mem = Arc::new(MemoryHandler::new(true).with_chunk_size(256));
+    let mut registry = ProtocolRegistry::empty();
+    registry.register_handler("mem", mem.clone());
+    let (events, rx) = ChannelEvents::channel();
+    let sched = Arc::new(Scheduler::new(None).with_protocols(registry).with_events(events));
+    let mut ids = Vec::new();
+    for i in 0..20 {
+        let url = format!("mem://host/f{}.bin", i);
+        mem.insert(url.clone(), vec![i as u8; 64 * 1024]);
+        let id = sched.create_task(url, dir.to_str(), None, None).await.unwrap();
+        sched.start_download(&id, None, Some(2), None).await.unwrap();
+        ids.push(id);
+    }
+    for id in &ids {
+        wait_for(&sched, id, |t| t.status == TaskStatus::Completed).await;
+    }
+
+    // 全部结束后才开始处理积压的事件
+    let history = Arc::new(History::load(dir.join("history.jsonl"), &AppSettings::default()));
+    tokio::spawn(history.clone().run(sched.clone(), rx));
+    let query = HistoryQuery {
+        outcome: Some(Outcome::Completed),
+        ..HistoryQuery::default()
+    };
+    let mut total = 0;
+    for _ in 0..250 {
+        total = history.query(&query).await.total;
+        if total == ids.len() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(total, ids.len());
+}
+
+fn entry(task_id: &str, finished_at: i64) -> HistoryEntry {
+    HistoryEntry {
+        id: 0,
+        task_id: task_id.to_string(),
+        url: format!("https://example.com/{}", task_id),
+        host: "example.com".to_string(),
+        filename: task_id.to_string(),
+        save_path: format!("/downloads/{}", task_id),
+        category: "other".to_string(),
+        size: 1,
+        started_at: finished_at - 10,
+        finished_at,
+        duration_secs: 10,
+        average_speed: 0,
+        sha256: None,
+        source: Default::default(),
+        outcome: Outcome::Completed,
+        error: None,
+    }
+}
+
+#[tokio::test]
+async fn late_records_are_ordered_by_finish_time_before_pruning() {
+    let dir = TempDir::new("history_order");
+    let path = dir.join("history.jsonl");
+    let settings = AppSettings {
+        history_retention_days: 0,
+        history_max_entries: 2,
+        ..AppSettings::default()
+    };
+    let history = History::load(path.clone(), &settings);
+    let now = chrono::Utc::now().timestamp();
+    // 先结束的任务因计算哈希最后才写入
+    history.record(entry("second", now - 20)).await.unwrap();
+    history.record(entry("third", now - 10)).await.unwrap();
+    history.record(entry("first", now - 30)).await.unwrap();
+    let names = |page: multidown_lib::history::HistoryPage| page.entries.into_iter().map(|e| e.task_id).collect::<Vec<_>>();
+    assert_eq!(names(history.query(&HistoryQuery::default()).await), ["third", "second"]);
+
+    history.record(entry("fourth", now)).await.unwrap();
+    history.record(entry("between", now - 15)).await.unwrap();
+    assert_eq!(names(history.query(&HistoryQuery::default()).await), ["fourth", "third"]);
+    // 文件中的顺序与内存中一致，重新读出结果相同
+    let reloaded = History::load(path, &settings);
+    assert_eq!(names(reloaded.query(&HistoryQuery::default()).await), ["fourth", "third"]);
+}
@@ -0,0 +1,147 @@
+//! 任务入口：排队、Metalink / HLS 任务的新建与设置缓存
+
+mod common;
+
+use multidown_lib::engine::scheduler::Scheduler;
+use multidown_lib::engine::{BroadcastEvents, HlsOptions, TaskStatus};
+use multidown_lib::intake::{self, Intake, IntakeItem, IntakeKind, IntakeOptions, IntakeResult};
+use multidown_lib::network::{MemoryHandler, ProtocolRegistry};
+use multidown_lib::settings::{load_settings, save_settings, AppSettings};
+use common::{wait_for, TempDir};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 只允许同时下载一个任务的入口，已在后台排队补位
+async fn intake(mem: &Arc<MemoryHandler>, dir: &std::path::Path) -> Arc<Intake> {
+    let events = Arc::new(BroadcastEvents::new(256));
+    let mut registry = ProtocolRegistry::empty();
+    registry.register_handler("mem", mem.clone());
+    let sched = Arc::new(Scheduler::new(None).with_protocols(registry).with_events(events.clone()));
+    let settings_path = dir.join("settings.json");
+    let settings = AppSettings {
+        max_concurrent_tasks: 1,
+        default_save_path: dir.to_string_lossy().into_owned(),
+        ..AppSettings::default()
+    };
+    save_settings(&settings_path, &settings).await.unwrap();
+    let intake = Arc::new(Intake::new(sched, settings_path, dir.to_string_lossy().into_owned()));
+    tokio::spawn(intake.clone().run_queue(events.subscribe()));
+    intake
+}
+
+fn start() -> IntakeOptions {
+    IntakeOptions {
+        start: true,
+        ..IntakeOptions::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn queued_task_starts_when_slot_frees() {
+    let mem = Arc::new(MemoryHandler::new(true).with_delay(Duration::from_millis(5)));
+    mem.insert("mem://host/a.bin", vec![1u8; 200_000]);
+    mem.insert("mem://host/b.bin", vec![2u8; 200_000]);
+    let dir = TempDir::new("intake_queue");
+    let intake = intake(&mem, &dir).await;
+    let a = intake.submit_one(IntakeItem::new("mem://host/a.bin"), start()).await;
+    let b = intake.submit_one(IntakeItem::new("mem://host/b.bin"), start()).await;
+    assert!(matches!(a, IntakeResult::Started { .. }), "{:?}", a);
+    let IntakeResult::Queued { id } = b else {
+        panic!("{:?}", b);
+    };
+    let info = wait_for(intake.scheduler(), &id, |t| t.status == TaskStatus::Completed).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn paused_queued_task_is_not_started() {
+    let mem = Arc::new(MemoryHandler::new(true).with_delay(Duration::from_millis(5)));
+    mem.insert("mem://host/a.bin", vec![1u8; 200_000]);
+    mem.insert("mem://host/b.bin", vec![2u8; 200_000]);
+    let dir = TempDir::new("intake_pause");
+    let intake = intake(&mem, &dir).await;
+    let a = intake.submit_one(IntakeItem::new("mem://host/a.bin"), start()).await.into_id().unwrap();
+    let b = intake.submit_one(IntakeItem::new("mem://host/b.bin"), start()).await;
+    let IntakeResult::Queued { id } = b else {
+        panic!("{:?}", b);
+    };
+    // 尚未开始的排队任务也可暂停
+    intake.scheduler().pause_task(&id).await.unwrap();
+    assert_eq!(intake.scheduler().get_task(&id).await.unwrap().status, TaskStatus::Paused);
+
+    let first = wait_for(intake.scheduler(), &a, |t| t.status == TaskStatus::Completed).await;
+    assert_eq!(first.status, TaskStatus::Completed, "{:?}", first);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let info = intake.scheduler().get_task(&id).await.unwrap();
+    assert_eq!(info.status, TaskStatus::Paused, "{:?}", info);
+    assert!(mem.requested_ranges("mem://host/b.bin").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn metalink_items_start_through_intake() {
+    let mem = Arc::new(MemoryHandler::new(true));
+    mem.insert("mem://mirror/a.bin", vec![7u8; 50_000]);
+    let dir = TempDir::new("intake_metalink");
+    let intake = intake(&mem, &dir).await;
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+ <file name="a.bin">
+  <size>50000</size>
+  <url priority="1">ftp://unsupported/a.bin</url>
+  <url priority="2">mem://mirror/a.bin</url>
+ </file>
+ <file name="b.bin">
+  <url>ftp://unsupported/b.bin</url>
+ </file>
+</metalink>"#;
+    let protocols = intake.scheduler().protocols();
+    let items = intake::metalink_items(xml, None, |u| protocols.supports(u)).unwrap();
+    // 没有受支持地址的文件跳过，主地址取受支持的那个
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].url, "mem://mirror/a.bin");
+    let outcomes = intake.submit(items, start()).await;
+    let id = outcomes[0].result.task_id().cloned().unwrap_or_else(|| panic!("{:?}", outcomes[0]));
+    let info = wait_for(intake.scheduler(), &id, |t| t.status == TaskStatus::Completed).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+    assert_eq!(info.filename, "a.bin");
+    assert_eq!(std::fs::read(dir.join("a.bin")).unwrap(), vec![7u8; 50_000]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hls_item_is_created_without_probe() {
+    let mem = Arc::new(MemoryHandler::new(true));
+    mem.insert("mem://host/v/seg0.ts", vec![1u8; 1000]);
+    mem.insert(
+        "mem://host/v/index.m3u8",
+        "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXTINF:4.0,\nseg0.ts\n#EXT-X-ENDLIST\n",
+    );
+    let dir = TempDir::new("intake_hls");
+    let intake = intake(&mem, &dir).await;
+    let item = IntakeItem {
+        kind: IntakeKind::Hls(HlsOptions::default()),
+        ..IntakeItem::new("mem://host/v/index.m3u8")
+    };
+    let id = intake.submit_one(item, start()).await.into_id().unwrap();
+    let info = wait_for(intake.scheduler(), &id, |t| t.status == TaskStatus::Completed).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+    // 同一地址再次提交按重复处理
+    let again = IntakeItem {
+        kind: IntakeKind::Hls(HlsOptions::default()),
+        ..IntakeItem::new("mem://host/v/index.m3u8")
+    };
+    let result = intake.submit_one(again, IntakeOptions::default()).await;
+    assert!(matches!(result, IntakeResult::Duplicate { .. }), "{:?}", result);
+}
+
+#[tokio::test]
+async fn save_settings_updates_cache_and_file() {
+    let mem = Arc::new(MemoryHandler::new(true));
+    let dir = TempDir::new("intake_settings");
+    let intake = intake(&mem, &dir).await;
+    let mut settings = intake.settings();
+    settings.max_concurrent_tasks = 4;
+    intake.save_settings(&settings).await.unwrap();
+    assert_eq!(intake.settings().max_concurrent_tasks, 4);
+    let stored = load_settings(&dir.join("settings.json")).unwrap();
+    assert_eq!(stored.max_concurrent_tasks, 4);
+}
@@ -0,0 +1,149 @@
+//! HLS / DASH 任务：遇到过期链接时转为等待新地址而不是失败；不支持 Range 的单文件轨
+
+mod common;
+
+use multidown_lib::engine::scheduler::Scheduler;
+use multidown_lib::engine::{ChannelEvents, DashOptions, EngineEvent, HlsOptions, TaskStatus};
+use multidown_lib::network::{MemoryHandler, ProtocolRegistry};
+use common::{wait_finished, TempDir};
+use std::sync::Arc;
+
+fn registry(mem: &Arc<MemoryHandler>) -> ProtocolRegistry {
+    let mut registry = ProtocolRegistry::empty();
+    registry.register_handler("mem", mem.clone());
+    registry
+}
+
+fn scheduler(mem: &Arc<MemoryHandler>) -> Arc<Scheduler> {
+    Arc::new(Scheduler::new(None).with_protocols(registry(mem)))
+}
+
+fn hls_fixture() -> Arc<MemoryHandler> {
+    let mem = Arc::new(MemoryHandler::new(true));
+    let mut playlist = String::from("#EXTM3U\n#EXT-X-TARGETDURATION:4\n");
+    for i in 0..4 {
+        mem.insert(format!("mem://host/v/seg{}.ts", i), vec![i as u8; 1000]);
+        playlist.push_str(&format!("#EXTINF:4.0,\nseg{}.ts\n", i));
+    }
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    mem.insert("mem://host/v/index.m3u8", playlist);
+    mem
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hls_expired_segment_needs_refresh() {
+    let mem = hls_fixture();
+    mem.expire("mem://host/v/seg2.ts");
+    let (events, mut rx) = ChannelEvents::channel();
+    let sched = Arc::new(Scheduler::new(None).with_protocols(registry(&mem)).with_events(events));
+    let dir = TempDir::new("hls_expired");
+    let id = sched
+        .create_hls_task("mem://host/v/index.m3u8".into(), dir.to_str(), None, HlsOptions::default())
+        .await
+        .unwrap();
+    sched.start_download(&id, None, Some(1), None).await.unwrap();
+    let info = wait_finished(&sched, &id).await;
+    assert_eq!(info.status, TaskStatus::NeedsRefresh, "{:?}", info);
+    let mut notified = false;
+    while let Ok(event) = rx.try_recv() {
+        if let EngineEvent::NeedsRefresh { id: ev_id, url, .. } = event {
+            assert_eq!(ev_id, id);
+            assert_eq!(url, "mem://host/v/index.m3u8");
+            notified = true;
+        }
+    }
+    assert!(notified);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hls_expired_playlist_needs_refresh() {
+    let mem = hls_fixture();
+    mem.expire("mem://host/v/index.m3u8");
+    let sched = scheduler(&mem);
+    let dir = TempDir::new("hls_playlist_expired");
+    let id = sched
+        .create_hls_task("mem://host/v/index.m3u8".into(), dir.to_str(), None, HlsOptions::default())
+        .await
+        .unwrap();
+    sched.start_download(&id, None, Some(2), None).await.unwrap();
+    let info = wait_finished(&sched, &id).await;
+    assert_eq!(info.status, TaskStatus::NeedsRefresh, "{:?}", info);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hls_other_errors_still_fail() {
+    let mem = hls_fixture();
+    mem.remove("mem://host/v/seg1.ts");
+    let sched = scheduler(&mem);
+    let dir = TempDir::new("hls_missing");
+    let id = sched
+        .create_hls_task("mem://host/v/index.m3u8".into(), dir.to_str(), None, HlsOptions::default())
+        .await
+        .unwrap();
+    sched.start_download(&id, None, Some(2), None).await.unwrap();
+    // 缺失的分片退避重试后失败
+    let info = wait_finished(&sched, &id).await;
+    assert_eq!(info.status, TaskStatus::Failed, "{:?}", info);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dash_expired_segment_needs_refresh() {
+    let mem = Arc::new(MemoryHandler::new(true));
+    mem.insert("mem://host/d/v1/init.mp4", b"INIT".to_vec());
+    for t in [0u64, 90000, 180000] {
+        mem.insert(format!("mem://host/d/v1/{}.m4s", t), format!("seg-{}", t).into_bytes());
+    }
+    mem.expire("mem://host/d/v1/90000.m4s");
+    let mpd = r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT3S">
+ <Period>
+  <BaseURL>d/</BaseURL>
+  <AdaptationSet contentType="video" mimeType="video/mp4">
+   <SegmentTemplate timescale="90000" initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/$Time$.m4s">
+    <SegmentTimeline><S t="0" d="90000" r="-1"/></SegmentTimeline>
+   </SegmentTemplate>
+   <Representation id="v1" bandwidth="3000000" width="1280" height="720"/>
+  </AdaptationSet>
+ </Period>
+</MPD>"#;
+    mem.insert("mem://host/movie.mpd", mpd);
+    let sched = scheduler(&mem);
+    let dir = TempDir::new("dash_expired");
+    let id = sched
+        .create_dash_task("mem://host/movie.mpd".into(), dir.to_str(), None, DashOptions::default())
+        .await
+        .unwrap();
+    sched.start_download(&id, None, Some(1), None).await.unwrap();
+    let info = wait_finished(&sched, &id).await;
+    assert_eq!(info.status, TaskStatus::NeedsRefresh, "{:?}", info);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dash_single_file_without_range_is_streamed() {
+    let mem = Arc::new(MemoryHandler::new(false).with_chunk_size(4096));
+    let video: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+    mem.insert("mem://host/d/video.mp4", video.clone());
+    let mpd = r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT3S">
+ <Period>
+  <AdaptationSet contentType="video" mimeType="video/mp4">
+   <Representation id="v1" bandwidth="3000000" height="720">
+    <BaseURL>d/video.mp4</BaseURL>
+    <SegmentBase indexRange="0-99"/>
+   </Representation>
+  </AdaptationSet>
+ </Period>
+</MPD>"#;
+    mem.insert("mem://host/movie.mpd", mpd);
+    let sched = scheduler(&mem);
+    let dir = TempDir::new("dash_single_stream");
+    let id = sched
+        .create_dash_task("mem://host/movie.mpd".into(), dir.to_str(), None, DashOptions::default())
+        .await
+        .unwrap();
+    sched.start_download(&id, None, Some(2), None).await.unwrap();
+    let info = wait_finished(&sched, &id).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+    assert_eq!(info.downloaded_bytes, video.len() as u64);
+    assert_eq!(std::fs::read(dir.join("movie.mp4")).unwrap(), video);
+}
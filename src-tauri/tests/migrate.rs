@@ -0,0 +1,88 @@
+//! 恢复导出的任务：映射后的保存路径须在允许的目录之内
+
+mod common;
+
+use multidown_lib::migrate::{prepare_restore, write_archive, DirMapping, ExportData};
+use common::TempDir;
+use std::path::PathBuf;
+
+/// 已完成的任务，文件在 save_path
+fn export(tasks: &[(&str, &str)]) -> ExportData {
+    let tasks: Vec<serde_json::Value> = tasks
+        .iter()
+        .map(|(id, save_path)| {
+            let state = serde_json::json!({
+                "id": id,
+                "url": format!("https://example.com/{}", id),
+                "save_path": save_path,
+                "filename": id,
+                "total_bytes": 100,
+                "downloaded_bytes": 100,
+                "status": "completed",
+                "pending_segments": [],
+                "supports_range": true,
+                "created_at": 0,
+            });
+            serde_json::json!({ "url": format!("https://example.com/{}", id), "state": state })
+        })
+        .collect();
+    serde_json::from_value(serde_json::json!({ "version": 2, "tasks": tasks })).unwrap()
+}
+
+#[test]
+fn restore_refuses_paths_outside_allowed_dirs() {
+    let source = TempDir::new("restore_source");
+    let outside = TempDir::new("restore_outside");
+    let allowed = TempDir::new("restore_allowed");
+    let a = source.join("a.bin");
+    let b = outside.join("b.bin");
+    std::fs::write(&a, [1u8; 100]).unwrap();
+    std::fs::write(&b, [2u8; 100]).unwrap();
+    let data = export(&[
+        ("a.bin", &a.to_string_lossy()),
+        ("b.bin", &b.to_string_lossy()),
+        ("c.bin", &source.join("../etc/c.bin").to_string_lossy()),
+    ]);
+    let archive = source.join("tasks.zip");
+    write_archive(&data, &archive).unwrap();
+    std::fs::remove_file(&b).unwrap();
+
+    // 只映射了 a 所在的目录；b 仍指向允许范围之外，c 含 ..
+    let mappings = [DirMapping {
+        from: source.to_string_lossy().into_owned(),
+        to: allowed.to_string_lossy().into_owned(),
+    }];
+    let allowed_dirs = vec![allowed.to_path_buf()];
+    let (tasks, report) = prepare_restore(data, None, &mappings, &allowed_dirs, Some(&archive), &[]).unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(PathBuf::from(&tasks[0].save_path), allowed.join("a.bin"));
+    assert_eq!(std::fs::read(allowed.join("a.bin")).unwrap(), vec![1u8; 100]);
+    assert_eq!(report.skipped.len(), 2, "{:?}", report.skipped);
+    assert!(!b.exists());
+}
+
+#[test]
+fn restore_does_not_touch_existing_files() {
+    let source = TempDir::new("restore_clash_source");
+    let allowed = TempDir::new("restore_clash_allowed");
+    let a = source.join("a.bin");
+    std::fs::write(&a, [1u8; 100]).unwrap();
+    let data = export(&[("a.bin", &a.to_string_lossy())]);
+    let archive = source.join("tasks.zip");
+    write_archive(&data, &archive).unwrap();
+    // 目标目录里已有一个无关的同名同大小文件
+    std::fs::write(allowed.join("a.bin"), [9u8; 100]).unwrap();
+
+    let mappings = [DirMapping {
+        from: source.to_string_lossy().into_owned(),
+        to: allowed.to_string_lossy().into_owned(),
+    }];
+    let (tasks, report) = prepare_restore(data, None, &mappings, &[allowed.to_path_buf()], Some(&archive), &[]).unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(PathBuf::from(&tasks[0].save_path), allowed.join("a (1).bin"));
+    assert_eq!(tasks[0].filename, "a (1).bin");
+    assert_eq!(std::fs::read(allowed.join("a (1).bin")).unwrap(), vec![1u8; 100]);
+    assert_eq!(std::fs::read(allowed.join("a.bin")).unwrap(), vec![9u8; 100]);
+    assert_eq!(report.renamed.len(), 1, "{:?}", report.renamed);
+    assert_eq!(report.reset, 0);
+}
@@ -0,0 +1,168 @@
+//! 经本地 SOCKS5 代理替身发出请求，确认代理、绕过列表与 PAC 候选切换在真实连接上生效
+
+use multidown_lib::network::{build_client_from_options, BypassList, NetworkOptions, ProxyConfig, ProxyMode};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 只会回复 "ok" 的 HTTP 源站
+async fn origin() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut sock, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 4096];
+                let mut req = Vec::new();
+                while !req.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match sock.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => req.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let _ = sock
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                    .await;
+            });
+        }
+    });
+    addr
+}
+
+/// 无认证、只支持 CONNECT 的 SOCKS5 代理替身；返回地址与已转发的连接数
+async fn socks5() -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let count = Arc::new(AtomicUsize::new(0));
+    let counter = count.clone();
+    tokio::spawn(async move {
+        while let Ok((sock, _)) = listener.accept().await {
+            let counter = counter.clone();
+            tokio::spawn(async move {
+                if let Ok(mut upstream) = socks5_handshake(sock).await {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let _ = tokio::io::copy_bidirectional(&mut upstream.0, &mut upstream.1).await;
+                }
+            });
+        }
+    });
+    (addr, count)
+}
+
+async fn socks5_handshake(mut sock: TcpStream) -> std::io::Result<(TcpStream, TcpStream)> {
+    let mut head = [0u8; 2];
+    sock.read_exact(&mut head).await?;
+    let mut methods = vec![0u8; head[1] as usize];
+    sock.read_exact(&mut methods).await?;
+    sock.write_all(&[5, 0]).await?;
+    let mut req = [0u8; 4];
+    sock.read_exact(&mut req).await?;
+    let host = match req[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            sock.read_exact(&mut ip).await?;
+            std::net::Ipv4Addr::from(ip).to_string()
+        }
+        3 => {
+            let len = sock.read_u8().await? as usize;
+            let mut name = vec![0u8; len];
+            sock.read_exact(&mut name).await?;
+            String::from_utf8_lossy(&name).into_owned()
+        }
+        _ => return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "address type")),
+    };
+    let port = sock.read_u16().await?;
+    let upstream = TcpStream::connect((host.as_str(), port)).await?;
+    sock.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+    Ok((sock, upstream))
+}
+
+fn options(proxy: SocketAddr, bypass: &str) -> NetworkOptions {
+    NetworkOptions {
+        proxy: ProxyConfig {
+            mode: ProxyMode::Manual(format!("socks5h://{}", proxy).parse().unwrap()),
+            bypass: BypassList::parse(bypass),
+        },
+        timeout_secs: 5,
+        headers: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn request_goes_through_socks5_proxy() {
+    let origin = origin().await;
+    let (proxy, count) = socks5().await;
+    let client = build_client_from_options(&options(proxy, "")).unwrap();
+    let body = client
+        .get(format!("http://localhost:{}/file", origin.port()))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(body, "ok");
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn bypassed_host_connects_directly() {
+    let origin = origin().await;
+    let (proxy, count) = socks5().await;
+    let client = build_client_from_options(&options(proxy, "localhost")).unwrap();
+    let body = client
+        .get(format!("http://localhost:{}/file", origin.port()))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(body, "ok");
+    assert_eq!(count.load(Ordering::SeqCst), 0);
+}
+
+/// PAC 的第一个代理连不上时换用下一个，不悄悄直连
+#[cfg(feature = "pac")]
+#[tokio::test(flavor = "multi_thread")]
+async fn pac_fails_over_to_next_proxy_on_connect_error() {
+    use multidown_lib::network::{HttpHandler, ProtocolHandler};
+
+    let origin = origin().await;
+    let (proxy, count) = socks5().await;
+    let dead = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let pac = std::env::temp_dir().join(format!("multidown_test_failover_{}.pac", std::process::id()));
+    std::fs::write(
+        &pac,
+        format!(
+            "function FindProxyForURL(url, host) {{ return \"SOCKS5 {}; SOCKS5 {}\"; }}",
+            dead, proxy
+        ),
+    )
+    .unwrap();
+    let config = ProxyConfig {
+        mode: ProxyMode::Pac {
+            location: pac.to_string_lossy().into_owned(),
+            script: None,
+        },
+        bypass: BypassList::default(),
+    }
+    .load()
+    .await
+    .unwrap();
+    let handler = HttpHandler::new(&NetworkOptions {
+        proxy: config,
+        timeout_secs: 5,
+        headers: Vec::new(),
+    })
+    .unwrap();
+    let url = format!("http://localhost:{}/file", origin.port());
+    assert_eq!(&handler.fetch(&url).await.unwrap()[..], b"ok");
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    // 之后的请求直接用可用的代理
+    assert_eq!(&handler.fetch(&url).await.unwrap()[..], b"ok");
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+    let _ = std::fs::remove_file(&pac);
+}
@@ -0,0 +1,252 @@
+//! 以内存协议驱动调度器：分段下载、暂停续传、镜像切换与链接过期
+
+mod common;
+
+use multidown_lib::engine::scheduler::Scheduler;
+use multidown_lib::engine::{ChannelEvents, EngineEvent, TaskStatus};
+use multidown_lib::network::{MemoryHandler, NetworkOptions, ProtocolRegistry};
+use common::{finished, wait_for, TempDir};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn data(len: u32, seed: u32) -> Vec<u8> {
+    (0..len).map(|i| (i.wrapping_mul(seed) % 251) as u8).collect()
+}
+
+fn scheduler(mem: &Arc<MemoryHandler>) -> Arc<Scheduler> {
+    let mut registry = ProtocolRegistry::empty();
+    registry.register_handler("mem", mem.clone());
+    Arc::new(Scheduler::new(None).with_protocols(registry))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn segmented_download() {
+    let mem = Arc::new(MemoryHandler::new(true).with_chunk_size(4096));
+    let content = data(1_000_000, 7);
+    mem.insert("mem://host/file.bin", content.clone());
+    let sched = scheduler(&mem);
+    let dir = TempDir::new("segmented");
+    let id = sched
+        .create_task("mem://host/file.bin".into(), dir.to_string_lossy().into(), None, None)
+        .await
+        .unwrap();
+    sched.start_download(&id, None, Some(4), None).await.unwrap();
+    let info = wait_for(&sched, &id, finished).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+    assert_eq!(info.downloaded_bytes, content.len() as u64);
+    assert!(std::fs::read(dir.join("file.bin")).unwrap() == content);
+    let ranges = mem.requested_ranges("mem://host/file.bin");
+    assert!(ranges.len() >= 4, "{:?}", ranges);
+    assert!(ranges.iter().any(|&(start, _)| start > 0), "{:?}", ranges);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pause_and_resume_keeps_downloaded_bytes() {
+    let mem = Arc::new(
+        MemoryHandler::new(true)
+            .with_chunk_size(16 * 1024)
+            .with_delay(Duration::from_millis(10)),
+    );
+    let content = data(2_000_000, 11);
+    mem.insert("mem://host/big.bin", content.clone());
+    let sched = scheduler(&mem);
+    let dir = TempDir::new("resume");
+    let id = sched
+        .create_task("mem://host/big.bin".into(), dir.to_string_lossy().into(), None, None)
+        .await
+        .unwrap();
+    sched.start_download(&id, None, Some(4), None).await.unwrap();
+    wait_for(&sched, &id, |t| t.downloaded_bytes > 0).await;
+    sched.pause_task(&id).await.unwrap();
+    // 等进行中的分段退出
+    sched.wait_idle(&id).await;
+    let paused = sched.get_task(&id).await.unwrap();
+    assert_eq!(paused.status, TaskStatus::Paused);
+    assert!(paused.downloaded_bytes > 0 && paused.downloaded_bytes < content.len() as u64, "{:?}", paused);
+    let started = mem.requested_ranges("mem://host/big.bin");
+
+    sched.resume_task(&id, None, Some(4), None).await.unwrap();
+    let info = wait_for(&sched, &id, finished).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+    assert!(std::fs::read(dir.join("big.bin")).unwrap() == content);
+    // 暂停前开始过的分段从已下载处续传，不从头重下
+    let resumed = &mem.requested_ranges("mem://host/big.bin")[started.len()..];
+    assert!(!resumed.is_empty());
+    for &(start, end) in &started {
+        assert!(
+            resumed.iter().all(|&(s, e)| e < start || s > end || s > start),
+            "{:?} restarted in {:?}",
+            (start, end),
+            resumed
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mirror_failover() {
+    let mem = Arc::new(MemoryHandler::new(true).with_chunk_size(4096));
+    let content = data(2_000_000, 13);
+    mem.insert("mem://a/f.bin", content.clone());
+    mem.insert("mem://b/f.bin", content.clone());
+    let sched = scheduler(&mem);
+    let dir = TempDir::new("mirror");
+    let id = sched
+        .create_task_with_mirrors(
+            "mem://a/f.bin".into(),
+            vec!["mem://b/f.bin".into(), "mem://gone/f.bin".into()],
+            dir.to_string_lossy().into(),
+            None,
+            None,
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+    // 无法访问的镜像不采用，只留原因
+    let sources = sched.get_task(&id).await.unwrap().sources;
+    assert_eq!(sources.len(), 3, "{:?}", sources);
+    assert!(sources[2].disabled.as_deref().is_some_and(|r| r.contains("无法访问")), "{:?}", sources);
+    // 主地址在开始前失效，各段切换到镜像
+    mem.remove("mem://a/f.bin");
+    sched.start_download(&id, None, Some(4), None).await.unwrap();
+    let info = wait_for(&sched, &id, finished).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+    assert!(info.sources[0].disabled.is_some(), "{:?}", info.sources);
+    assert!(info.sources[1].disabled.is_none(), "{:?}", info.sources);
+    assert!(std::fs::read(dir.join("f.bin")).unwrap() == content);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mismatching_mirror_is_dropped_with_reason() {
+    let mem = Arc::new(MemoryHandler::new(true).with_chunk_size(4096));
+    let content = data(300_000, 17);
+    mem.insert("mem://a/f.bin", content.clone());
+    mem.insert("mem://b/f.bin", content.clone());
+    mem.insert("mem://c/f.bin", vec![0u8; 10]);
+    let sched = scheduler(&mem);
+    let dir = TempDir::new("mismatch");
+    let id = sched
+        .create_task_with_mirrors(
+            "mem://a/f.bin".into(),
+            vec!["mem://c/f.bin".into(), "mem://b/f.bin".into()],
+            dir.to_string_lossy().into(),
+            None,
+            None,
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+    let sources = sched.get_task(&id).await.unwrap().sources;
+    let rejected = sources.iter().find(|s| s.url == "mem://c/f.bin").expect("rejected mirror listed");
+    assert!(rejected.disabled.as_deref().is_some_and(|r| r.contains("不一致")), "{:?}", sources);
+
+    // 未采用的镜像不参与下载，重启后也不会恢复使用
+    let snapshot = sched.snapshot_tasks().await;
+    assert_eq!(snapshot[0].sources, vec!["mem://a/f.bin".to_string(), "mem://b/f.bin".to_string()]);
+    assert_eq!(snapshot[0].rejected_sources.len(), 1);
+    sched.start_download(&id, None, Some(4), None).await.unwrap();
+    let info = wait_for(&sched, &id, finished).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+    assert!(mem.requested_ranges("mem://c/f.bin").is_empty());
+    assert!(std::fs::read(dir.join("f.bin")).unwrap() == content);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn expired_link_needs_refresh() {
+    let mem = Arc::new(MemoryHandler::new(true).with_chunk_size(4096));
+    let content = data(500_000, 3);
+    mem.insert("mem://a/f.bin", content.clone());
+    let (events, mut rx) = ChannelEvents::channel();
+    let mut registry = ProtocolRegistry::empty();
+    registry.register_handler("mem", mem.clone());
+    let sched = Arc::new(Scheduler::new(None).with_protocols(registry).with_events(events));
+    let dir = TempDir::new("expired");
+    let id = sched
+        .create_task("mem://a/f.bin".into(), dir.to_string_lossy().into(), None, None)
+        .await
+        .unwrap();
+    mem.expire("mem://a/f.bin");
+    sched.start_download(&id, None, Some(4), None).await.unwrap();
+    let info = wait_for(&sched, &id, finished).await;
+    assert_eq!(info.status, TaskStatus::NeedsRefresh, "{:?}", info);
+    let mut notified = false;
+    while let Ok(event) = rx.try_recv() {
+        if let EngineEvent::NeedsRefresh { id: ev_id, url, .. } = event {
+            assert_eq!(ev_id, id);
+            assert_eq!(url, "mem://a/f.bin");
+            notified = true;
+        }
+    }
+    assert!(notified);
+
+    // 换用新地址后继续下载
+    mem.insert("mem://b/f.bin", content.clone());
+    sched
+        .rebind_task_url(&id, "mem://b/f.bin".into(), &Default::default())
+        .await
+        .unwrap();
+    sched.start_download(&id, None, Some(4), None).await.unwrap();
+    let info = wait_for(&sched, &id, finished).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+    assert!(std::fs::read(dir.join("f.bin")).unwrap() == content);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resume_without_range_support_restarts_from_zero() {
+    let mem = Arc::new(
+        MemoryHandler::new(false)
+            .with_chunk_size(16 * 1024)
+            .with_delay(Duration::from_millis(10)),
+    );
+    let content = data(1_000_000, 19);
+    mem.insert("mem://host/plain.bin", content.clone());
+    let sched = scheduler(&mem);
+    let dir = TempDir::new("no_range");
+    let id = sched
+        .create_task("mem://host/plain.bin".into(), dir.to_string_lossy().into(), None, None)
+        .await
+        .unwrap();
+    sched.start_download(&id, None, Some(4), None).await.unwrap();
+    wait_for(&sched, &id, |t| t.downloaded_bytes > 0).await;
+    sched.pause_task(&id).await.unwrap();
+    sched.wait_idle(&id).await;
+    assert!(sched.get_task(&id).await.unwrap().downloaded_bytes > 0);
+
+    // 服务器忽略区间总是从头返回，续传只能整体重下，否则数据会写错位置
+    sched.resume_task(&id, None, Some(4), None).await.unwrap();
+    let info = wait_for(&sched, &id, finished).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+    assert_eq!(info.downloaded_bytes, content.len() as u64);
+    assert!(std::fs::read(dir.join("plain.bin")).unwrap() == content);
+    let ranges = mem.requested_ranges("mem://host/plain.bin");
+    assert!(ranges.iter().all(|&r| r == (0, content.len() as u64 - 1)), "{:?}", ranges);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rebind_right_after_pause_keeps_in_flight_segments() {
+    let mem = Arc::new(
+        MemoryHandler::new(true)
+            .with_chunk_size(16 * 1024)
+            .with_delay(Duration::from_millis(30)),
+    );
+    let content = data(2_000_000, 23);
+    mem.insert("mem://host/big.bin", content.clone());
+    mem.insert("mem://mirror/big.bin", content.clone());
+    let sched = scheduler(&mem);
+    let dir = TempDir::new("rebind_pause");
+    let id = sched
+        .create_task("mem://host/big.bin".into(), dir.to_string_lossy().into(), None, None)
+        .await
+        .unwrap();
+    sched.start_download(&id, None, Some(4), None).await.unwrap();
+    wait_for(&sched, &id, |t| t.downloaded_bytes > 0).await;
+    // 不等 worker 退出就换地址：进行中的分段须交回新任务，否则文件会有空洞
+    sched.pause_task(&id).await.unwrap();
+    sched
+        .rebind_task_url(&id, "mem://mirror/big.bin".into(), &NetworkOptions::default())
+        .await
+        .unwrap();
+    sched.resume_task(&id, None, Some(4), None).await.unwrap();
+    let info = wait_for(&sched, &id, finished).await;
+    assert_eq!(info.status, TaskStatus::Completed, "{:?}", info);
+    assert!(std::fs::read(dir.join("big.bin")).unwrap() == content);
+}
@@ -8,7 +8,7 @@ import {
   sendNotification,
 } from "@tauri-apps/plugin-notification";
 import { useState, useCallback, useEffect, useMemo, useRef } from "react";
-import type { TaskInfo } from "./types/download";
+import type { TaskInfo, TaskPage } from "./types/download";
 import { TaskList } from "./components/TaskList";
 import { AddTask } from "./components/AddTask";
 import { Toolbar } from "./components/Toolbar";
@@ -17,12 +17,15 @@ import { TitleBar } from "./components/TitleBar";
 import { OptionsModal } from "./components/OptionsModal";
 import { ContextMenu } from "./components/ContextMenu";
 import { BatchAdd } from "./components/BatchAdd";
+import { SiteGrabber } from "./components/SiteGrabber";
+import { ImportModal, type ImportSource } from "./components/ImportModal";
 import { DownloadFileInfo } from "./components/DownloadFileInfo";
 import { PropertiesModal } from "./components/PropertiesModal";
 import { MoveRenameModal } from "./components/MoveRenameModal";
 import { AboutModal } from "./components/AboutModal";
+import { HistoryModal } from "./components/HistoryModal";
 import { Toast, useToast } from "./components/Toast";
-import type { AppSettings } from "./types/download";
+import type { AppSettings, ArchiveSummary } from "./types/download";
 import "./index.css";
 
 function isHttpUrl(s: string): boolean {
@@ -35,10 +38,14 @@ function App() {
   const [selectedId, setSelectedId] = useState<string | null>(null);
   const [addTaskOpen, setAddTaskOpen] = useState(false);
   const [batchAddOpen, setBatchAddOpen] = useState(false);
+  const [siteGrabberOpen, setSiteGrabberOpen] = useState(false);
+  /** 待导入的文件内容（导出的 zip 另带其路径），非 null 时显示导入预览 */
+  const [importSource, setImportSource] = useState<ImportSource | null>(null);
   const [downloadFileInfoOpen, setDownloadFileInfoOpen] = useState(false);
   const [downloadFileInfoUrl, setDownloadFileInfoUrl] = useState("");
   const [optionsOpen, setOptionsOpen] = useState(false);
   const [aboutOpen, setAboutOpen] = useState(false);
+  const [historyOpen, setHistoryOpen] = useState(false);
 
   const [scheduleOpen, setScheduleOpen] = useState(false);
   const [findVisible, setFindVisible] = useState(false);
@@ -105,6 +112,31 @@ function App() {
     };
   }, []);
 
+  useEffect(() => {
+    const unlisten = listen<[string, string, string]>("download-needs-refresh", async (e) => {
+      const [_, filename] = e.payload;
+      refreshTasks();
+      try {
+        const s = await invoke<AppSettings>("get_settings");
+        if (!s.notification_on_fail) return;
+        let granted = await isPermissionGranted();
+        if (!granted) {
+          const perm = await requestPermission();
+          granted = perm === "granted";
+        }
+        if (granted) {
+          sendNotification({
+            title: "下载链接已过期",
+            body: `${filename}：请在浏览器中重新下载或更换地址，已下载部分会保留`,
+          });
+        }
+      } catch (_) {}
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [refreshTasks]);
+
   useEffect(() => {
     try {
       localStorage.setItem("multidown-dark", darkMode ? "1" : "0");
@@ -114,7 +146,7 @@ function App() {
   const lastClipboardUrlRef = useRef<string | null>(null);
 
   useEffect(() => {
-    if (!downloadFileInfoOpen && !addTaskOpen && !batchAddOpen && !optionsOpen) {
+    if (!downloadFileInfoOpen && !addTaskOpen && !batchAddOpen && !siteGrabberOpen && importSource === null && !optionsOpen) {
       const onFocus = async () => {
         try {
           const settings = await invoke<AppSettings>("get_settings");
@@ -137,23 +169,35 @@ function App() {
       window.addEventListener("focus", onFocus);
       return () => window.removeEventListener("focus", onFocus);
     }
-  }, [downloadFileInfoOpen, addTaskOpen, batchAddOpen, optionsOpen]);
+  }, [downloadFileInfoOpen, addTaskOpen, batchAddOpen, siteGrabberOpen, importSource, optionsOpen]);
 
   const selectedTask = useMemo(
     () => tasks.find((t) => t.id === selectedId) ?? null,
     [tasks, selectedId]
   );
 
-  const displayTasks = useMemo(() => {
-    if (!findQuery.trim()) return tasks;
-    const q = findQuery.trim().toLowerCase();
-    return tasks.filter(
-      (t) =>
-        (t.filename || "").toLowerCase().includes(q) ||
-        (t.url || "").toLowerCase().includes(q)
-    );
+  /** 查找时由后端筛选的任务；为 null 时显示全部 */
+  const [foundTasks, setFoundTasks] = useState<TaskInfo[] | null>(null);
+
+  useEffect(() => {
+    const text = findQuery.trim();
+    if (!text) {
+      setFoundTasks(null);
+      return;
+    }
+    let stale = false;
+    invoke<TaskPage>("query_downloads", { query: { text } })
+      .then((page) => {
+        if (!stale) setFoundTasks(page.tasks);
+      })
+      .catch(console.error);
+    return () => {
+      stale = true;
+    };
   }, [tasks, findQuery]);
 
+  const displayTasks = foundTasks ?? tasks;
+
   const handleFindNext = useCallback(() => {
     if (!findQuery.trim() || displayTasks.length === 0) return;
     const q = findQuery.trim().toLowerCase();
@@ -310,17 +354,26 @@ function App() {
 
   const handleExport = useCallback(async () => {
     try {
-      const json = await invoke<string>("export_tasks");
       const filePath = await save({
-        defaultPath: "multidown-tasks.json",
+        defaultPath: "multidown-tasks.mdtasks",
         filters: [
           {
-            name: "JSON文件",
-            extensions: ["json"]
+            name: "任务列表",
+            extensions: ["mdtasks", "json"]
+          },
+          {
+            name: "任务列表与已下载的文件（迁移用）",
+            extensions: ["zip"]
           }
         ]
       });
-      if (filePath) {
+      if (!filePath) return;
+      if (filePath.toLowerCase().endsWith(".zip")) {
+        const summary = await invoke<ArchiveSummary>("export_task_archive", { path: filePath });
+        const skipped = summary.skipped.length > 0 ? `，${summary.skipped.length} 个正在下载的任务未打包文件` : "";
+        showToast(`已导出 ${summary.tasks} 个任务，含 ${summary.files} 个文件${skipped}`);
+      } else {
+        const json = await invoke<string>("export_tasks");
         await writeTextFile(filePath, json);
         showToast("任务列表已导出到文件");
       }
@@ -335,31 +388,34 @@ function App() {
       const filePath = await open({
         filters: [
           {
-            name: "JSON文件",
-            extensions: ["json"]
+            name: "任务列表",
+            extensions: ["mdtasks", "json", "txt", "har", "ef2", "zip"]
           },
           {
-            name: "文本文件",
-            extensions: ["txt"]
+            name: "所有文件",
+            extensions: ["*"]
           }
         ],
         multiple: false
       });
       if (filePath) {
-        const text = await readTextFile(filePath as string);
+        const path = filePath as string;
+        if (path.toLowerCase().endsWith(".zip")) {
+          setImportSource({ text: await invoke<string>("read_task_archive", { path }), archive: path });
+          return;
+        }
+        const text = await readTextFile(path);
         if (!text.trim()) {
           showToast("文件为空，请选择包含任务列表或 URL 列表的文件");
           return;
         }
-        const count = await invoke<number>("import_tasks", { text });
-        refreshTasks();
-        showToast(`已导入 ${count} 个任务`);
+        setImportSource({ text });
       }
     } catch (e) {
       console.error(e);
       showToast("导入失败");
     }
-  }, [refreshTasks, showToast]);
+  }, [showToast]);
 
   const handleTaskContextMenu = useCallback((e: React.MouseEvent, task: TaskInfo) => {
     setContextMenu({ x: e.clientX, y: e.clientY, task });
@@ -427,7 +483,7 @@ function App() {
           label: "继续下载",
           onClick: () =>
             invoke("resume_download", { taskId: contextMenu.task.id }).then(refreshTasks).catch(console.error),
-          disabled: contextMenu.task.status !== "paused" && contextMenu.task.status !== "pending",
+          disabled: !["paused", "pending", "needs_refresh"].includes(contextMenu.task.status),
         },
         {
           type: "item",
@@ -523,6 +579,7 @@ function App() {
             setBatchAddInitialUrls("");
             setBatchAddOpen(true);
           }}
+          onSiteGrabber={() => setSiteGrabberOpen(true)}
           onBatchAddFromClipboard={async () => {
             try {
               const text = await invoke<string>("read_clipboard_text");
@@ -559,6 +616,7 @@ function App() {
           onRefresh={refreshTasks}
           onOpenOptions={() => setOptionsOpen(true)}
           onOpenSchedule={() => setScheduleOpen(true)}
+          onOpenHistory={() => setHistoryOpen(true)}
           onOpenAbout={() => setAboutOpen(true)}
           onInstallExtension={async () => {
             try {
@@ -639,6 +697,16 @@ function App() {
         onAdded={refreshTasks}
       />
 
+      <SiteGrabber open={siteGrabberOpen} onClose={() => setSiteGrabberOpen(false)} onAdded={refreshTasks} />
+      <ImportModal
+        source={importSource}
+        onClose={() => setImportSource(null)}
+        onImported={(added) => {
+          refreshTasks();
+          showToast(`已导入 ${added} 个任务`);
+        }}
+      />
+
       <DownloadFileInfo
         open={downloadFileInfoOpen}
         initialUrl={downloadFileInfoUrl}
@@ -652,6 +720,8 @@ function App() {
 
       <OptionsModal open={optionsOpen} onClose={() => setOptionsOpen(false)} />
 
+      <HistoryModal open={historyOpen} onClose={() => setHistoryOpen(false)} />
+
       <AboutModal open={aboutOpen} onClose={() => setAboutOpen(false)} version="0.1.0" />
 
       {toast && (
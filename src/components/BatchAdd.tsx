@@ -1,5 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
 import { useState, useEffect } from "react";
+import type { IntakeItem, IntakeOutcome } from "../types/download";
 
 interface BatchAddProps {
   open: boolean;
@@ -13,39 +14,66 @@ export function BatchAdd({ open, initialUrls = "", onClose, onAdded }: BatchAddP
   const [saveDir, setSaveDir] = useState("");
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [lazyProbe, setLazyProbe] = useState(false);
+  const [preview, setPreview] = useState<string[] | null>(null);
 
   useEffect(() => {
     if (open) {
       if (initialUrls.trim()) setUrlsText(initialUrls.trim());
       else setUrlsText("");
+      setPreview(null);
       invoke<string>("get_default_download_dir")
         .then(setSaveDir)
         .catch(() => {});
     }
   }, [open, initialUrls]);
 
+  /** 展开 [001-120]、[a-z]、{a,b} 等模式；出错时显示原因并返回 null */
+  const expand = async (): Promise<string[] | null> => {
+    try {
+      const urls = await invoke<string[]>("expand_batch_urls", { text: urlsText });
+      if (urls.length === 0) {
+        setError("请输入至少一个有效的 HTTP(S) 链接，每行一个。");
+        return null;
+      }
+      setError(null);
+      return urls;
+    } catch (e) {
+      setError(String(e));
+      return null;
+    }
+  };
+
+  const handlePreview = async () => {
+    setPreview(await expand());
+  };
+
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
-    const lines = urlsText
-      .split(/\n/)
-      .map((s) => s.trim())
-      .filter((s) => s.length > 0 && (s.startsWith("http://") || s.startsWith("https://")));
-    if (lines.length === 0) {
-      setError("请输入至少一个有效的 HTTP(S) 链接，每行一个。");
-      return;
-    }
-    setError(null);
+    const lines = await expand();
+    if (!lines) return;
     setLoading(true);
     try {
-      const ids = await invoke<string[]>("create_batch_download", {
-        urls: lines,
-        saveDir: saveDir.trim() || ".",
+      const items: IntakeItem[] = lines.map((url) => ({ url, save_dir: saveDir.trim() || null }));
+      const outcomes = await invoke<IntakeOutcome[]>("add_downloads", {
+        items,
+        options: { start: true, lazy_probe: lazyProbe },
       });
-      for (const id of ids) {
-        await invoke("start_download", { taskId: id });
+      const problems = outcomes.flatMap((o) =>
+        o.result === "rejected" || o.result === "failed"
+          ? [`${o.url}：${o.error}`]
+          : o.result === "skipped" || o.result === "duplicate"
+            ? [`${o.url}：已有相同的任务`]
+            : []
+      );
+      onAdded();
+      setPreview(null);
+      if (problems.length > 0) {
+        setUrlsText(outcomes.filter((o) => !("id" in o)).map((o) => o.url).join("\n"));
+        setError(`${outcomes.length - problems.length} 个已添加，以下未添加：\n${problems.join("\n")}`);
+        return;
       }
       setUrlsText("");
-      onAdded();
       onClose();
     } catch (e) {
       setError(String(e));
@@ -67,15 +95,29 @@ export function BatchAdd({ open, initialUrls = "", onClose, onAdded }: BatchAddP
         <form onSubmit={handleSubmit}>
           <div className="modal-body">
             <div className="form-group">
-              <label>下载链接（每行一个）</label>
+              <label>下载链接（每行一个，可用 [001-120]、[a-z]、[0-100:5]、{"{a,b,c}"} 批量生成）</label>
               <textarea
                 value={urlsText}
-                onChange={(e) => setUrlsText(e.target.value)}
-                placeholder={"https://example.com/file1.zip\nhttps://example.com/file2.zip"}
+                onChange={(e) => {
+                  setUrlsText(e.target.value);
+                  setPreview(null);
+                }}
+                placeholder={"https://example.com/file1.zip\nhttps://example.com/img[001-120].jpg"}
                 rows={8}
                 style={{ width: "100%", padding: 8, fontSize: 13, resize: "vertical", fontFamily: "inherit" }}
               />
             </div>
+            {preview && (
+              <div className="form-group">
+                <label>将添加 {preview.length} 个链接</label>
+                <div
+                  style={{ maxHeight: 120, overflow: "auto", fontSize: 12, color: "#555", whiteSpace: "pre", marginTop: 4 }}
+                >
+                  {preview.slice(0, 200).join("\n")}
+                  {preview.length > 200 ? `\n…另有 ${preview.length - 200} 个` : ""}
+                </div>
+              </div>
+            )}
             <div className="form-group">
               <label>保存目录</label>
               <input
@@ -85,12 +127,17 @@ export function BatchAdd({ open, initialUrls = "", onClose, onAdded }: BatchAddP
                 placeholder="留空则使用默认下载目录"
               />
             </div>
+            <label className="form-check-row">
+              <input type="checkbox" checked={lazyProbe} onChange={(e) => setLazyProbe(e.target.checked)} />
+              <span>开始下载时再探测（链接较多时添加更快，文件名取自链接）</span>
+            </label>
             {error && (
-              <div style={{ color: "#c00", fontSize: 13, marginBottom: 8 }}>{error}</div>
+              <div style={{ color: "#c00", fontSize: 13, marginBottom: 8, whiteSpace: "pre-wrap" }}>{error}</div>
             )}
           </div>
           <div className="modal-footer">
             <button type="button" className="btn" onClick={onClose}>取消</button>
+            <button type="button" className="btn" onClick={handlePreview} disabled={loading}>预览</button>
             <button type="submit" className="btn btn-primary" disabled={loading}>
               {loading ? "添加中…" : "添加并开始下载"}
             </button>
@@ -0,0 +1,241 @@
+import { invoke } from "@tauri-apps/api/core";
+import { useState, useEffect, useCallback } from "react";
+import type { HistoryEntry, HistoryOutcome, HistoryPage, HistoryQuery, HistoryStats, TaskSource } from "../types/download";
+import { formatBytes } from "./TaskList";
+
+interface HistoryModalProps {
+  open: boolean;
+  onClose: () => void;
+}
+
+const PAGE_SIZE = 100;
+
+const OUTCOME_LABELS: Record<HistoryOutcome, string> = {
+  completed: "完成",
+  failed: "失败",
+  cancelled: "取消",
+};
+
+const SOURCE_LABELS: Record<TaskSource, string> = {
+  manual: "手动",
+  browser: "浏览器",
+  import: "导入",
+  control: "控制接口",
+};
+
+const CATEGORY_LABELS: Record<string, string> = {
+  program: "程序",
+  document: "文档",
+  video: "视频",
+  archive: "压缩包",
+  other: "其他",
+};
+
+/** 日期输入框的值（本地日期）转为 Unix 秒；endOfDay 时取当天最后一秒 */
+function dateToSecs(value: string, endOfDay: boolean): number | undefined {
+  if (!value) return undefined;
+  const t = new Date(`${value}T${endOfDay ? "23:59:59" : "00:00:00"}`).getTime();
+  return Number.isFinite(t) ? Math.floor(t / 1000) : undefined;
+}
+
+function formatDuration(secs: number): string {
+  if (secs < 60) return `${secs} 秒`;
+  if (secs < 3600) return `${Math.floor(secs / 60)} 分 ${secs % 60} 秒`;
+  return `${Math.floor(secs / 3600)} 时 ${Math.floor((secs % 3600) / 60)} 分`;
+}
+
+export function HistoryModal({ open, onClose }: HistoryModalProps) {
+  const [text, setText] = useState("");
+  const [host, setHost] = useState("");
+  const [category, setCategory] = useState("");
+  const [outcome, setOutcome] = useState<HistoryOutcome | "">("");
+  const [source, setSource] = useState<TaskSource | "">("");
+  const [from, setFrom] = useState("");
+  const [to, setTo] = useState("");
+  const [offset, setOffset] = useState(0);
+  const [page, setPage] = useState<HistoryPage | null>(null);
+  const [stats, setStats] = useState<HistoryStats | null>(null);
+  const [detail, setDetail] = useState<HistoryEntry | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  const load = useCallback(async () => {
+    const query: HistoryQuery = {
+      text: text.trim() || undefined,
+      host: host.trim() || undefined,
+      category: category || undefined,
+      outcome: outcome || undefined,
+      source: source || undefined,
+      from: dateToSecs(from, false),
+      to: dateToSecs(to, true),
+    };
+    try {
+      const [p, s] = await Promise.all([
+        invoke<HistoryPage>("query_history", { query: { ...query, offset, limit: PAGE_SIZE } }),
+        invoke<HistoryStats>("history_stats", { query }),
+      ]);
+      setPage(p);
+      setStats(s);
+      setError(null);
+    } catch (e) {
+      setError(String(e));
+    }
+  }, [text, host, category, outcome, source, from, to, offset]);
+
+  useEffect(() => {
+    if (open) load();
+    else setDetail(null);
+  }, [open, load]);
+
+  // 条件变化后回到第一页
+  useEffect(() => {
+    setOffset(0);
+  }, [text, host, category, outcome, source, from, to]);
+
+  const remove = async (id: number) => {
+    try {
+      await invoke<number>("delete_history", { ids: [id] });
+      setDetail(null);
+      load();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const clearAll = async () => {
+    if (!window.confirm("清空全部下载历史？")) return;
+    try {
+      await invoke("clear_history");
+      load();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  if (!open) return null;
+
+  const total = page?.total ?? 0;
+  const days = stats ? Object.entries(stats.per_day).slice(-14) : [];
+  const maxDay = Math.max(1, ...days.map(([, b]) => b.bytes));
+  const hosts = stats
+    ? Object.entries(stats.per_host)
+        .sort((a, b) => b[1].bytes - a[1].bytes || b[1].count - a[1].count)
+        .slice(0, 8)
+    : [];
+
+  return (
+    <div className="modal-overlay" onClick={(e) => e.target === e.currentTarget && onClose()}>
+      <div className="modal" onClick={(e) => e.stopPropagation()} style={{ minWidth: 760, maxWidth: "90vw" }}>
+        <div className="modal-title">下载历史</div>
+        <div className="modal-body">
+          <div style={{ display: "flex", flexWrap: "wrap", gap: 6, marginBottom: 8 }}>
+            <input type="text" placeholder="文件名或地址" value={text} onChange={(e) => setText(e.target.value)} />
+            <input type="text" placeholder="站点，如 *.example.com" value={host} onChange={(e) => setHost(e.target.value)} />
+            <select value={category} onChange={(e) => setCategory(e.target.value)}>
+              <option value="">全部分类</option>
+              {Object.entries(CATEGORY_LABELS).map(([id, label]) => (
+                <option key={id} value={id}>{label}</option>
+              ))}
+            </select>
+            <select value={outcome} onChange={(e) => setOutcome(e.target.value as HistoryOutcome | "")}>
+              <option value="">全部结果</option>
+              {(Object.keys(OUTCOME_LABELS) as HistoryOutcome[]).map((o) => (
+                <option key={o} value={o}>{OUTCOME_LABELS[o]}</option>
+              ))}
+            </select>
+            <select value={source} onChange={(e) => setSource(e.target.value as TaskSource | "")}>
+              <option value="">全部来源</option>
+              {(Object.keys(SOURCE_LABELS) as TaskSource[]).map((s) => (
+                <option key={s} value={s}>{SOURCE_LABELS[s]}</option>
+              ))}
+            </select>
+            <input type="date" value={from} onChange={(e) => setFrom(e.target.value)} title="开始日期" />
+            <input type="date" value={to} onChange={(e) => setTo(e.target.value)} title="结束日期" />
+          </div>
+          {stats && (
+            <div style={{ fontSize: 12, marginBottom: 8 }}>
+              <div>
+                共 {stats.total} 条：完成 {stats.completed}，失败 {stats.failed}，取消 {stats.cancelled}；
+                成功率 {(stats.success_rate * 100).toFixed(1)}%；已下载 {formatBytes(stats.bytes)}
+              </div>
+              {days.length > 0 && (
+                <div style={{ display: "flex", gap: 16, marginTop: 6 }}>
+                  <div style={{ flex: 1 }}>
+                    {days.map(([day, b]) => (
+                      <div key={day} style={{ display: "flex", alignItems: "center", gap: 6 }}>
+                        <span style={{ width: 80 }}>{day}</span>
+                        <div style={{ flex: 1, background: "#eee", height: 8 }}>
+                          <div style={{ width: `${(b.bytes / maxDay) * 100}%`, background: "#4a90d9", height: 8 }} />
+                        </div>
+                        <span style={{ width: 70, textAlign: "right" }}>{formatBytes(b.bytes)}</span>
+                      </div>
+                    ))}
+                  </div>
+                  <div style={{ flex: 1 }}>
+                    {hosts.map(([h, b]) => (
+                      <div key={h} style={{ display: "flex", justifyContent: "space-between" }} title={h}>
+                        <span style={{ overflow: "hidden", textOverflow: "ellipsis", whiteSpace: "nowrap" }}>{h || "—"}</span>
+                        <span>{b.count} 个 · {formatBytes(b.bytes)}</span>
+                      </div>
+                    ))}
+                  </div>
+                </div>
+              )}
+            </div>
+          )}
+          <div style={{ maxHeight: 280, overflow: "auto", border: "1px solid #ddd", fontSize: 12 }}>
+            <table className="properties-table" style={{ width: "100%" }}>
+              <tbody>
+                {page?.entries.map((e) => (
+                  <tr key={e.id} onClick={() => setDetail(e)} style={{ cursor: "pointer" }} title={e.url}>
+                    <td>{new Date(e.finished_at * 1000).toLocaleString()}</td>
+                    <td style={{ maxWidth: 260, overflow: "hidden", textOverflow: "ellipsis", whiteSpace: "nowrap" }}>{e.filename}</td>
+                    <td>{formatBytes(e.size)}</td>
+                    <td>{e.average_speed > 0 ? `${formatBytes(e.average_speed)}/s` : "—"}</td>
+                    <td>{OUTCOME_LABELS[e.outcome]}</td>
+                    <td>{SOURCE_LABELS[e.source]}</td>
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          </div>
+          {total > PAGE_SIZE && (
+            <div style={{ fontSize: 12, marginTop: 6, display: "flex", alignItems: "center", gap: 8 }}>
+              <button type="button" className="btn" disabled={offset === 0} onClick={() => setOffset(Math.max(0, offset - PAGE_SIZE))}>
+                上一页
+              </button>
+              <span>
+                {offset + 1}–{Math.min(offset + PAGE_SIZE, total)} / {total}
+              </span>
+              <button type="button" className="btn" disabled={offset + PAGE_SIZE >= total} onClick={() => setOffset(offset + PAGE_SIZE)}>
+                下一页
+              </button>
+            </div>
+          )}
+          {detail && (
+            <div style={{ fontSize: 12, marginTop: 8, wordBreak: "break-all" }}>
+              <div>地址：{detail.url}</div>
+              <div>保存到：{detail.save_path}</div>
+              <div>
+                下载时长：{formatDuration(detail.duration_secs)}
+                {detail.error ? `；错误：${detail.error}` : ""}
+              </div>
+              {detail.sha256 && <div>SHA-256：{detail.sha256}</div>}
+              <button type="button" className="btn" style={{ marginTop: 4 }} onClick={() => remove(detail.id)}>
+                删除此记录
+              </button>
+            </div>
+          )}
+          {error && <div style={{ color: "#c00", fontSize: 13, marginTop: 8 }}>{error}</div>}
+        </div>
+        <div className="modal-footer">
+          <button type="button" className="btn" onClick={clearAll} disabled={total === 0 && !text && !host}>
+            清空历史
+          </button>
+          <button type="button" className="btn btn-primary" onClick={onClose}>
+            关闭
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
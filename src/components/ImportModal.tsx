@@ -0,0 +1,289 @@
+import { invoke } from "@tauri-apps/api/core";
+import { open as openDialog } from "@tauri-apps/plugin-dialog";
+import { readTextFile } from "@tauri-apps/plugin-fs";
+import { useState, useEffect } from "react";
+import type { DirMapping, ImportFormat, ImportPreview, IntakeOutcome, RestoreReport } from "../types/download";
+
+/** 要导入的文件内容；导出的 zip 另带路径，恢复时从中解出已下载的文件 */
+export interface ImportSource {
+  text: string;
+  archive?: string;
+}
+
+interface ImportModalProps {
+  /** 为 null 时不显示 */
+  source: ImportSource | null;
+  onClose: () => void;
+  onImported: (added: number) => void;
+}
+
+const FORMAT_LABELS: Record<ImportFormat, string> = {
+  multidown: "Multidown 任务列表",
+  url_list: "URL 列表（wget -i）",
+  aria2: "aria2 输入文件",
+  har: "HAR（浏览器网络记录）",
+  ef2: "IDM 导出（.ef2）",
+};
+
+/** 地址的最后一段，作为未指定文件名时的显示名 */
+function displayName(url: string, filename?: string | null): string {
+  if (filename) return filename;
+  const path = url.split(/[?#]/)[0].replace(/\/+$/, "");
+  return path.slice(path.lastIndexOf("/") + 1) || url;
+}
+
+export function ImportModal({ source, onClose, onImported }: ImportModalProps) {
+  const text = source?.text ?? null;
+  const [format, setFormat] = useState<ImportFormat | "">("");
+  const [cookies, setCookies] = useState<string | null>(null);
+  const [cookieFile, setCookieFile] = useState("");
+  const [preview, setPreview] = useState<ImportPreview | null>(null);
+  const [selected, setSelected] = useState<Set<number>>(new Set());
+  const [importing, setImporting] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  /** 按导出的完整状态恢复进度，而不是重新新建 */
+  const [restore, setRestore] = useState(true);
+  /** 原目录 → 新目录 */
+  const [targets, setTargets] = useState<Record<string, string>>({});
+
+  useEffect(() => {
+    if (text === null) {
+      setFormat("");
+      setCookies(null);
+      setCookieFile("");
+      setPreview(null);
+      setError(null);
+      setRestore(true);
+      setTargets({});
+    }
+  }, [text]);
+
+  useEffect(() => {
+    if (text === null) return;
+    let stale = false;
+    invoke<ImportPreview>("preview_import", { text, format: format || null, cookies })
+      .then((p) => {
+        if (stale) return;
+        setPreview(p);
+        setSelected(new Set(p.items.map((_, i) => i)));
+        setTargets((prev) => Object.fromEntries(p.directories.map((d) => [d, prev[d] ?? d])));
+        setError(null);
+      })
+      .catch((e) => {
+        if (stale) return;
+        setPreview(null);
+        setError(String(e));
+      });
+    return () => {
+      stale = true;
+    };
+  }, [text, format, cookies]);
+
+  const pickCookies = async () => {
+    try {
+      const path = await openDialog({
+        filters: [{ name: "Cookie 文件", extensions: ["txt"] }],
+        multiple: false,
+      });
+      if (!path) return;
+      setCookies(await readTextFile(path as string));
+      setCookieFile((path as string).split(/[\\/]/).pop() || "");
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const toggle = (i: number) => {
+    setSelected((prev) => {
+      const next = new Set(prev);
+      if (next.has(i)) next.delete(i);
+      else next.add(i);
+      return next;
+    });
+  };
+
+  const restoring = restore && (preview?.restorable ?? 0) > 0;
+
+  const handleRestore = async () => {
+    if (!source || selected.size === 0) return;
+    setImporting(true);
+    setError(null);
+    try {
+      const mappings: DirMapping[] = Object.entries(targets)
+        .filter(([from, to]) => to.trim() && to.trim() !== from)
+        .map(([from, to]) => ({ from, to: to.trim() }));
+      const report = await invoke<RestoreReport>("restore_tasks", {
+        text: source.text,
+        archive: source.archive ?? null,
+        selected: [...selected].sort((a, b) => a - b),
+        mappings,
+      });
+      onImported(report.restored.length);
+      const notes = [
+        report.reset > 0 ? `${report.reset} 个任务的文件不在或大小不符，将从头下载` : "",
+        ...report.renamed,
+        ...report.skipped,
+      ].filter(Boolean);
+      if (notes.length > 0) {
+        setError(notes.join("\n"));
+        return;
+      }
+      onClose();
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setImporting(false);
+    }
+  };
+
+  const handleImport = async () => {
+    if (!preview) return;
+    if (restoring) return handleRestore();
+    const items = preview.items.filter((_, i) => selected.has(i));
+    if (items.length === 0) return;
+    setImporting(true);
+    setError(null);
+    try {
+      const outcomes = await invoke<IntakeOutcome[]>("add_downloads", { items, options: { source: "import" } });
+      const added = outcomes.filter((o) => "id" in o).length;
+      onImported(added);
+      const problems = outcomes.filter((o) => o.result === "rejected" || o.result === "failed");
+      if (problems.length > 0) {
+        setError(problems.map((o) => `${o.url}: ${"error" in o ? o.error : ""}`).join("\n"));
+        return;
+      }
+      onClose();
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setImporting(false);
+    }
+  };
+
+  if (text === null) return null;
+
+  const total = preview?.items.length ?? 0;
+
+  return (
+    <div className="modal-overlay" onClick={(e) => e.target === e.currentTarget && !importing && onClose()}>
+      <div className="modal" onClick={(e) => e.stopPropagation()} style={{ minWidth: 560 }}>
+        <div className="modal-title">导入任务</div>
+        <div className="modal-body">
+          <div className="form-group">
+            <label>格式</label>
+            <select
+              style={{ padding: "6px 10px", minWidth: 200, marginLeft: 8 }}
+              value={format}
+              onChange={(e) => setFormat(e.target.value as ImportFormat | "")}
+            >
+              <option value="">自动识别{preview && !format ? `（${FORMAT_LABELS[preview.format]}）` : ""}</option>
+              {(Object.keys(FORMAT_LABELS) as ImportFormat[]).map((f) => (
+                <option key={f} value={f}>{FORMAT_LABELS[f]}</option>
+              ))}
+            </select>
+          </div>
+          {!restoring && (
+            <div className="form-group">
+              <label>Cookie 文件（Netscape 格式 cookies.txt）</label>
+              <div style={{ display: "flex", alignItems: "center", gap: 8 }}>
+                <button type="button" className="btn" onClick={pickCookies}>选择…</button>
+                {cookieFile && (
+                  <>
+                    <span style={{ fontSize: 12 }}>
+                      {cookieFile}
+                      {preview ? `，${preview.cookies_attached} 个任务带上了 Cookie` : ""}
+                    </span>
+                    <button
+                      type="button"
+                      className="btn"
+                      onClick={() => {
+                        setCookies(null);
+                        setCookieFile("");
+                      }}
+                    >
+                      移除
+                    </button>
+                  </>
+                )}
+              </div>
+            </div>
+          )}
+          {preview && preview.restorable > 0 && (
+            <>
+              <label className="form-check-row">
+                <input type="checkbox" checked={restore} onChange={(e) => setRestore(e.target.checked)} />
+                <span>恢复下载进度与状态（迁移到本机）</span>
+              </label>
+              {restore &&
+                preview.directories.map((dir) => (
+                  <div className="form-group" key={dir}>
+                    <label title={dir} style={{ overflow: "hidden", textOverflow: "ellipsis", whiteSpace: "nowrap" }}>
+                      {dir} →
+                    </label>
+                    <input
+                      type="text"
+                      value={targets[dir] ?? dir}
+                      onChange={(e) => setTargets((prev) => ({ ...prev, [dir]: e.target.value }))}
+                    />
+                  </div>
+                ))}
+            </>
+          )}
+          {preview && (
+            <>
+              <div style={{ fontSize: 13, marginBottom: 6 }}>
+                共 {total} 个任务
+                {total > 0 && (
+                  <button
+                    type="button"
+                    className="btn"
+                    style={{ marginLeft: 8 }}
+                    onClick={() => setSelected(selected.size === total ? new Set() : new Set(preview.items.map((_, i) => i)))}
+                  >
+                    {selected.size === total ? "全不选" : "全选"}
+                  </button>
+                )}
+              </div>
+              <div style={{ maxHeight: 240, overflow: "auto", border: "1px solid #ddd", fontSize: 12 }}>
+                {preview.items.map((item, i) => (
+                  <label key={i} className="form-check-row" title={item.url} style={{ padding: "2px 6px" }}>
+                    <input type="checkbox" checked={selected.has(i)} onChange={() => toggle(i)} />
+                    <span style={{ flex: 1, overflow: "hidden", textOverflow: "ellipsis", whiteSpace: "nowrap" }}>
+                      {displayName(item.url, item.filename)}
+                    </span>
+                    {item.headers && item.headers.length > 0 && (
+                      <span style={{ color: "#666", marginLeft: 8 }} title={item.headers.map(([k]) => k).join(", ")}>
+                        {item.headers.length} 个请求头
+                      </span>
+                    )}
+                  </label>
+                ))}
+              </div>
+              {preview.warnings.length > 0 && (
+                <div style={{ color: "#a60", fontSize: 12, marginTop: 6, maxHeight: 80, overflow: "auto", whiteSpace: "pre-wrap" }}>
+                  {preview.warnings.join("\n")}
+                </div>
+              )}
+            </>
+          )}
+          {error && (
+            <div style={{ color: "#c00", fontSize: 13, marginTop: 8, whiteSpace: "pre-wrap" }}>{error}</div>
+          )}
+        </div>
+        <div className="modal-footer">
+          <button type="button" className="btn" onClick={onClose} disabled={importing}>
+            取消
+          </button>
+          <button
+            type="button"
+            className="btn btn-primary"
+            onClick={handleImport}
+            disabled={importing || !preview || selected.size === 0}
+          >
+            {importing ? "导入中…" : `${restoring ? "恢复" : "导入"}选中的 ${selected.size} 个任务`}
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -8,10 +8,12 @@ interface MenuBarProps {
   onNewTask: () => void;
   onBatchAdd: () => void;
   onBatchAddFromClipboard?: () => void;
+  onSiteGrabber?: () => void;
   onOpenFromClipboard: () => void;
   onRefresh: () => void;
   onOpenOptions: () => void;
   onOpenSchedule: () => void;
+  onOpenHistory?: () => void;
   onPauseAll: () => void;
   onStopAll: () => void;
   onStartQueue?: () => void;
@@ -38,10 +40,12 @@ export function MenuBar({
   onNewTask,
   onBatchAdd,
   onBatchAddFromClipboard,
+  onSiteGrabber,
   onOpenFromClipboard,
   onRefresh: _onRefresh,
   onOpenOptions,
   onOpenSchedule,
+  onOpenHistory,
   onPauseAll,
   onStopAll,
   onStartQueue,
@@ -127,7 +131,7 @@ export function MenuBar({
               onBatchAddFromClipboard,
               !onBatchAddFromClipboard
             )}
-            {menuItem("运行站点抓取", undefined, true)}
+            {menuItem("运行站点抓取", onSiteGrabber, !onSiteGrabber)}
             {sep()}
             {menuItem("显示悬浮窗", undefined, true)}
             {sep()}
@@ -174,6 +178,7 @@ export function MenuBar({
             {sep()}
             {menuItem("查找 (Ctrl+F)", onFind)}
             {menuItem("查找下一个 (F3)", onFindNext, !onFindNext)}
+            {menuItem("下载历史", onOpenHistory, !onOpenHistory)}
             {sep()}
             {menuItem("计划任务", onOpenSchedule)}
             {menuItem("开始队列", onStartQueue, !hasPausedOrPending || !onStartQueue)}
@@ -28,12 +28,26 @@ const defaultSettings: AppSettings = {
   user_agent: "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
   use_last_save_path: true,
   proxy_type: "none",
+  proxy_scheme: "http",
   proxy_host: "",
   proxy_port: 8080,
+  proxy_username: "",
+  proxy_password: "",
+  proxy_bypass: "localhost, 127.0.0.1, <local>",
+  proxy_pac_url: "",
   notification_on_complete: true,
   notification_on_fail: true,
   timeout_secs: 30,
   save_progress_interval_secs: 30,
+  control_api_enabled: false,
+  control_api_port: 16800,
+  control_api_token: "",
+  aria2_rpc_enabled: false,
+  browser_tcp_fallback: false,
+  history_retention_days: 365,
+  history_max_entries: 10000,
+  categorize_downloads: false,
+  site_rules: [],
 };
 
 export function OptionsModal({ open, onClose }: OptionsModalProps) {
@@ -41,9 +55,11 @@ export function OptionsModal({ open, onClose }: OptionsModalProps) {
   const [settings, setSettings] = useState<AppSettings>(defaultSettings);
   const [loading, setLoading] = useState(false);
   const [saving, setSaving] = useState(false);
+  const [saveError, setSaveError] = useState<string | null>(null);
 
   useEffect(() => {
     if (open) {
+      setSaveError(null);
       setLoading(true);
       invoke<AppSettings>("get_settings")
         .then((s) => setSettings({ ...defaultSettings, ...s }))
@@ -62,7 +78,7 @@ export function OptionsModal({ open, onClose }: OptionsModalProps) {
       await invoke("set_settings", { settings });
       onClose();
     } catch (e) {
-      console.error(e);
+      setSaveError(String(e));
     } finally {
       setSaving(false);
     }
@@ -176,6 +192,14 @@ export function OptionsModal({ open, onClose }: OptionsModalProps) {
                     />
                     <span>使用上次的保存路径</span>
                   </label>
+                  <label className="form-check-row">
+                    <input
+                      type="checkbox"
+                      checked={settings.categorize_downloads ?? false}
+                      onChange={(e) => update({ categorize_downloads: e.target.checked })}
+                    />
+                    <span>按文件类型保存到子目录（程序、文档、视频、压缩包、其他）</span>
+                  </label>
                 </div>
               )}
               {tab === "connection" && (
@@ -233,6 +257,79 @@ export function OptionsModal({ open, onClose }: OptionsModalProps) {
                       <span style={{ color: "#666", fontSize: 12 }}>0 表示不周期保存</span>
                     </div>
                   </div>
+                  <div className="form-group">
+                    <label>下载历史保留</label>
+                    <div style={{ display: "flex", alignItems: "center", gap: 8, marginTop: 6 }}>
+                      <input
+                        type="number"
+                        min={0}
+                        value={settings.history_retention_days ?? 365}
+                        onChange={(e) => update({ history_retention_days: Number(e.target.value) || 0 })}
+                        style={{ width: 80, padding: "6px 10px" }}
+                      />
+                      <span>天，最多</span>
+                      <input
+                        type="number"
+                        min={0}
+                        value={settings.history_max_entries ?? 10000}
+                        onChange={(e) => update({ history_max_entries: Number(e.target.value) || 0 })}
+                        style={{ width: 100, padding: "6px 10px" }}
+                      />
+                      <span>条</span>
+                      <span style={{ color: "#666", fontSize: 12 }}>0 表示不限</span>
+                    </div>
+                  </div>
+                  <div className="form-group">
+                    <label className="form-check-row">
+                      <input
+                        type="checkbox"
+                        checked={settings.control_api_enabled ?? false}
+                        onChange={(e) => update({ control_api_enabled: e.target.checked })}
+                      />
+                      <span>启用本地控制接口（HTTP/JSON-RPC，仅本机可访问，重启后生效）</span>
+                    </label>
+                    {settings.control_api_enabled && (
+                      <div style={{ display: "flex", alignItems: "center", gap: 8, marginTop: 6 }}>
+                        <span>端口</span>
+                        <input
+                          type="number"
+                          min={1}
+                          max={65535}
+                          value={settings.control_api_port ?? 16800}
+                          onChange={(e) => update({ control_api_port: Number(e.target.value) || 16800 })}
+                          style={{ width: 90, padding: "6px 10px" }}
+                        />
+                        <span>令牌</span>
+                        <input
+                          type="text"
+                          value={settings.control_api_token ?? ""}
+                          placeholder="留空则启动时自动生成"
+                          onChange={(e) => update({ control_api_token: e.target.value.trim() })}
+                          style={{ flex: 1, padding: "6px 10px" }}
+                        />
+                      </div>
+                    )}
+                    {settings.control_api_enabled && (
+                      <label className="form-check-row" style={{ marginTop: 6 }}>
+                        <input
+                          type="checkbox"
+                          checked={settings.aria2_rpc_enabled ?? false}
+                          onChange={(e) => update({ aria2_rpc_enabled: e.target.checked })}
+                        />
+                        <span>兼容 aria2 JSON-RPC（/jsonrpc，令牌即 rpc-secret，可供 AriaNg 等工具使用）</span>
+                      </label>
+                    )}
+                  </div>
+                  <div className="form-group">
+                    <label className="form-check-row">
+                      <input
+                        type="checkbox"
+                        checked={settings.browser_tcp_fallback ?? false}
+                        onChange={(e) => update({ browser_tcp_fallback: e.target.checked })}
+                      />
+                      <span>浏览器扩展另走本机 TCP 端口（仅在本机 IPC 不可用时开启，重启后生效）</span>
+                    </label>
+                  </div>
                 </div>
               )}
               {tab === "proxy" && (
@@ -248,7 +345,16 @@ export function OptionsModal({ open, onClose }: OptionsModalProps) {
                           checked={settings.proxy_type === "none"}
                           onChange={() => update({ proxy_type: "none" })}
                         />
-                        <span>不使用代理</span>
+                        <span>不另设代理（沿用环境变量 HTTP_PROXY 等）</span>
+                      </label>
+                      <label className="form-check-row" style={{ marginBottom: 8 }}>
+                        <input
+                          type="radio"
+                          name="proxy"
+                          checked={settings.proxy_type === "direct"}
+                          onChange={() => update({ proxy_type: "direct" })}
+                        />
+                        <span>直接连接（忽略环境变量中的代理）</span>
                       </label>
                       <label className="form-check-row" style={{ marginBottom: 8 }}>
                         <input
@@ -259,7 +365,7 @@ export function OptionsModal({ open, onClose }: OptionsModalProps) {
                         />
                         <span>使用系统设置</span>
                       </label>
-                      <label className="form-check-row">
+                      <label className="form-check-row" style={{ marginBottom: 8 }}>
                         <input
                           type="radio"
                           name="proxy"
@@ -268,10 +374,47 @@ export function OptionsModal({ open, onClose }: OptionsModalProps) {
                         />
                         <span>手动配置</span>
                       </label>
+                      <label className="form-check-row">
+                        <input
+                          type="radio"
+                          name="proxy"
+                          checked={settings.proxy_type === "pac"}
+                          onChange={() => update({ proxy_type: "pac" })}
+                        />
+                        <span>使用自动配置脚本（PAC）</span>
+                      </label>
+                    </div>
+                    <div style={{ color: "#666", fontSize: 12, marginTop: 6 }}>
+                      代理只用于 HTTP(S)。FTP 下载不经代理，按上述设置需走代理的 FTP 地址会报错；如需直连，请把其主机加入“不使用代理的地址”。
                     </div>
                   </div>
+                  {settings.proxy_type === "pac" && (
+                    <div className="form-group">
+                      <label>PAC 脚本地址</label>
+                      <input
+                        type="text"
+                        value={settings.proxy_pac_url}
+                        onChange={(e) => update({ proxy_pac_url: e.target.value })}
+                        placeholder="例如 http://wpad/wpad.dat 或本地文件路径"
+                        style={{ marginTop: 6 }}
+                      />
+                    </div>
+                  )}
                   {settings.proxy_type === "manual" && (
                     <>
+                      <div className="form-group">
+                        <label>代理类型</label>
+                        <select
+                          value={settings.proxy_scheme}
+                          onChange={(e) => update({ proxy_scheme: e.target.value })}
+                          style={{ marginTop: 6 }}
+                        >
+                          <option value="http">HTTP</option>
+                          <option value="https">HTTPS</option>
+                          <option value="socks5">SOCKS5</option>
+                          <option value="socks5h">SOCKS5（远程解析域名）</option>
+                        </select>
+                      </div>
                       <div className="form-group">
                         <label>代理地址</label>
                         <input
@@ -291,8 +434,39 @@ export function OptionsModal({ open, onClose }: OptionsModalProps) {
                           style={{ marginTop: 6, width: 100 }}
                         />
                       </div>
+                      <div className="form-group">
+                        <label>用户名</label>
+                        <input
+                          type="text"
+                          value={settings.proxy_username}
+                          onChange={(e) => update({ proxy_username: e.target.value })}
+                          placeholder="无需认证时留空"
+                          style={{ marginTop: 6 }}
+                        />
+                      </div>
+                      <div className="form-group">
+                        <label>密码</label>
+                        <input
+                          type="password"
+                          value={settings.proxy_password}
+                          onChange={(e) => update({ proxy_password: e.target.value })}
+                          style={{ marginTop: 6 }}
+                        />
+                      </div>
                     </>
                   )}
+                  {settings.proxy_type !== "none" && settings.proxy_type !== "direct" && (
+                    <div className="form-group">
+                      <label>不使用代理的地址</label>
+                      <input
+                        type="text"
+                        value={settings.proxy_bypass}
+                        onChange={(e) => update({ proxy_bypass: e.target.value })}
+                        placeholder="逗号分隔，如 localhost, *.lan, 10.0.0.0/8, <local>"
+                        style={{ marginTop: 6 }}
+                      />
+                    </div>
+                  )}
                 </div>
               )}
               {tab === "sounds" && (
@@ -320,6 +494,7 @@ export function OptionsModal({ open, onClose }: OptionsModalProps) {
           )}
         </div>
         <div className="modal-footer">
+          {saveError && <span style={{ color: "#c00", fontSize: 13, marginRight: "auto" }}>{saveError}</span>}
           <button type="button" className="btn" onClick={onClose}>取消</button>
           <button type="button" className="btn btn-primary" onClick={handleSave} disabled={saving}>
             {saving ? "保存中…" : "确定"}
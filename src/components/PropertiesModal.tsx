@@ -22,6 +22,7 @@ function formatStatus(s: string): string {
     completed: "已完成",
     failed: "失败",
     cancelled: "已取消",
+    needs_refresh: "链接过期",
   };
   return map[s] ?? s;
 }
@@ -0,0 +1,292 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { useState, useEffect, useRef } from "react";
+import type { GrabOptions, GrabProgressEvent, GrabResult, IntakeItem, IntakeOutcome } from "../types/download";
+import { formatBytes } from "./TaskList";
+
+interface SiteGrabberProps {
+  open: boolean;
+  onClose: () => void;
+  onAdded: () => void;
+}
+
+/** 逗号或空白分隔的列表 */
+function splitList(text: string): string[] {
+  return text
+    .split(/[\s,，]+/)
+    .map((s) => s.trim())
+    .filter((s) => s.length > 0);
+}
+
+/** 以 MB 输入的大小，留空为不限 */
+function parseMegabytes(text: string): number | null {
+  const n = Number(text);
+  return text.trim() && Number.isFinite(n) && n >= 0 ? Math.round(n * 1024 * 1024) : null;
+}
+
+export function SiteGrabber({ open, onClose, onAdded }: SiteGrabberProps) {
+  const [url, setUrl] = useState("");
+  const [depth, setDepth] = useState(1);
+  const [sameHost, setSameHost] = useState(true);
+  const [respectRobots, setRespectRobots] = useState(true);
+  const [extensions, setExtensions] = useState("");
+  const [mimeTypes, setMimeTypes] = useState("");
+  const [pattern, setPattern] = useState("");
+  const [minSize, setMinSize] = useState("");
+  const [maxSize, setMaxSize] = useState("");
+  const [saveDir, setSaveDir] = useState("");
+  const [grabId, setGrabId] = useState<string | null>(null);
+  const [progress, setProgress] = useState<GrabProgressEvent["progress"] | null>(null);
+  const [result, setResult] = useState<GrabResult | null>(null);
+  const [selected, setSelected] = useState<Set<string>>(new Set());
+  const [adding, setAdding] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  const grabIdRef = useRef<string | null>(null);
+
+  useEffect(() => {
+    if (open) {
+      setResult(null);
+      setProgress(null);
+      setError(null);
+      invoke<string>("get_default_download_dir")
+        .then(setSaveDir)
+        .catch(() => {});
+    }
+  }, [open]);
+
+  useEffect(() => {
+    const unlisten = listen<GrabProgressEvent>("grab-progress", (event) => {
+      if (event.payload.id === grabIdRef.current) setProgress(event.payload.progress);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const handleGrab = async (e: React.FormEvent) => {
+    e.preventDefault();
+    if (!url.trim()) return;
+    const id = `grab-${Date.now()}`;
+    grabIdRef.current = id;
+    setGrabId(id);
+    setError(null);
+    setResult(null);
+    setProgress(null);
+    const options: GrabOptions = {
+      url: url.trim(),
+      depth,
+      same_host: sameHost,
+      respect_robots: respectRobots,
+      extensions: splitList(extensions),
+      mime_types: splitList(mimeTypes),
+      pattern: pattern.trim() || null,
+      min_size: parseMegabytes(minSize),
+      max_size: parseMegabytes(maxSize),
+    };
+    try {
+      const r = await invoke<GrabResult>("grab_site", { id, options });
+      setResult(r);
+      setSelected(new Set(r.files.map((f) => f.url)));
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      grabIdRef.current = null;
+      setGrabId(null);
+    }
+  };
+
+  const handleCancel = () => {
+    if (grabId) invoke("cancel_grab", { id: grabId }).catch(() => {});
+    else onClose();
+  };
+
+  const toggle = (fileUrl: string) => {
+    setSelected((prev) => {
+      const next = new Set(prev);
+      if (next.has(fileUrl)) next.delete(fileUrl);
+      else next.add(fileUrl);
+      return next;
+    });
+  };
+
+  const handleAdd = async () => {
+    if (!result) return;
+    const files = result.files.filter((f) => selected.has(f.url));
+    if (files.length === 0) return;
+    setAdding(true);
+    setError(null);
+    try {
+      const items: IntakeItem[] = files.map((f) => ({
+        url: f.url,
+        filename: f.filename || null,
+        save_dir: saveDir.trim() || null,
+      }));
+      const outcomes = await invoke<IntakeOutcome[]>("add_downloads", { items, options: { start: true } });
+      onAdded();
+      const problems = outcomes.filter((o) => !("id" in o));
+      if (problems.length > 0) {
+        setError(`${outcomes.length - problems.length} 个已添加，${problems.length} 个未添加（重复或无法访问）`);
+        return;
+      }
+      onClose();
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setAdding(false);
+    }
+  };
+
+  const handleOverlayClick = (e: React.MouseEvent) => {
+    if (e.target === e.currentTarget && !grabId) onClose();
+  };
+
+  if (!open) return null;
+
+  const running = grabId !== null;
+
+  return (
+    <div className="modal-overlay" onClick={handleOverlayClick}>
+      <div className="modal" onClick={(e) => e.stopPropagation()} style={{ minWidth: 560 }}>
+        <div className="modal-title">站点抓取</div>
+        <form onSubmit={handleGrab}>
+          <div className="modal-body">
+            <div className="form-group">
+              <label>起始页面</label>
+              <input
+                type="text"
+                value={url}
+                onChange={(e) => setUrl(e.target.value)}
+                placeholder="https://example.com/gallery/"
+                disabled={running}
+              />
+            </div>
+            <div className="form-group">
+              <label>跟随层数</label>
+              <select
+                style={{ padding: "6px 10px", minWidth: 80, marginLeft: 8 }}
+                value={depth}
+                onChange={(e) => setDepth(Number(e.target.value))}
+                disabled={running}
+              >
+                {[1, 2, 3, 4, 5].map((n) => (
+                  <option key={n} value={n}>{n}</option>
+                ))}
+              </select>
+              <span style={{ color: "#666", fontSize: 12, marginLeft: 8 }}>1 只下载起始页面上链接的文件</span>
+            </div>
+            <label className="form-check-row">
+              <input type="checkbox" checked={sameHost} onChange={(e) => setSameHost(e.target.checked)} disabled={running} />
+              <span>只进入同一站点的页面</span>
+            </label>
+            <label className="form-check-row">
+              <input
+                type="checkbox"
+                checked={respectRobots}
+                onChange={(e) => setRespectRobots(e.target.checked)}
+                disabled={running}
+              />
+              <span>遵守 robots.txt</span>
+            </label>
+            <div className="form-group">
+              <label>文件扩展名（逗号分隔，留空不限）</label>
+              <input type="text" value={extensions} onChange={(e) => setExtensions(e.target.value)} placeholder="jpg, png, zip" disabled={running} />
+            </div>
+            <div className="form-group">
+              <label>MIME 类型（逗号分隔，可写前缀）</label>
+              <input type="text" value={mimeTypes} onChange={(e) => setMimeTypes(e.target.value)} placeholder="image/, application/pdf" disabled={running} />
+            </div>
+            <div className="form-group">
+              <label>地址须匹配的正则表达式</label>
+              <input type="text" value={pattern} onChange={(e) => setPattern(e.target.value)} placeholder="/uploads/" disabled={running} />
+            </div>
+            <div className="form-group">
+              <label>大小（MB）</label>
+              <input
+                type="number"
+                min={0}
+                value={minSize}
+                onChange={(e) => setMinSize(e.target.value)}
+                placeholder="最小"
+                style={{ width: 100, marginLeft: 8 }}
+                disabled={running}
+              />
+              <span style={{ margin: "0 6px" }}>–</span>
+              <input
+                type="number"
+                min={0}
+                value={maxSize}
+                onChange={(e) => setMaxSize(e.target.value)}
+                placeholder="最大"
+                style={{ width: 100 }}
+                disabled={running}
+              />
+            </div>
+            {running && (
+              <div style={{ fontSize: 13, marginBottom: 8 }}>
+                抓取中… 已解析 {progress?.pages ?? 0} 个页面，找到 {progress?.files ?? 0} 个文件
+              </div>
+            )}
+            {result && (
+              <>
+                <div style={{ fontSize: 13, marginBottom: 6 }}>
+                  {result.cancelled ? "已取消，" : ""}解析 {result.pages} 个页面，找到 {result.files.length} 个文件，排除 {result.skipped} 个链接
+                  {result.errors.length > 0 ? `，${result.errors.length} 个无法访问` : ""}
+                  {result.files.length > 0 && (
+                    <button
+                      type="button"
+                      className="btn"
+                      style={{ marginLeft: 8 }}
+                      onClick={() =>
+                        setSelected(
+                          selected.size === result.files.length ? new Set() : new Set(result.files.map((f) => f.url))
+                        )
+                      }
+                    >
+                      {selected.size === result.files.length ? "全不选" : "全选"}
+                    </button>
+                  )}
+                </div>
+                <div style={{ maxHeight: 220, overflow: "auto", border: "1px solid #ddd", fontSize: 12 }}>
+                  {result.files.map((f) => (
+                    <label key={f.url} className="form-check-row" title={f.url} style={{ padding: "2px 6px" }}>
+                      <input type="checkbox" checked={selected.has(f.url)} onChange={() => toggle(f.url)} />
+                      <span style={{ flex: 1, overflow: "hidden", textOverflow: "ellipsis", whiteSpace: "nowrap" }}>
+                        {f.filename}
+                      </span>
+                      <span style={{ color: "#666", marginLeft: 8 }}>{f.size != null ? formatBytes(f.size) : "未知"}</span>
+                    </label>
+                  ))}
+                </div>
+                <div className="form-group" style={{ marginTop: 8 }}>
+                  <label>保存目录</label>
+                  <input
+                    type="text"
+                    value={saveDir}
+                    onChange={(e) => setSaveDir(e.target.value)}
+                    placeholder="留空则使用默认下载目录"
+                  />
+                </div>
+              </>
+            )}
+            {error && (
+              <div style={{ color: "#c00", fontSize: 13, marginBottom: 8, whiteSpace: "pre-wrap" }}>{error}</div>
+            )}
+          </div>
+          <div className="modal-footer">
+            <button type="button" className="btn" onClick={handleCancel}>
+              {running ? "停止" : "关闭"}
+            </button>
+            <button type="submit" className="btn" disabled={running || !url.trim()}>
+              {result ? "重新抓取" : "开始抓取"}
+            </button>
+            {result && (
+              <button type="button" className="btn btn-primary" onClick={handleAdd} disabled={adding || running || selected.size === 0}>
+                {adding ? "添加中…" : `下载选中的 ${selected.size} 个文件`}
+              </button>
+            )}
+          </div>
+        </form>
+      </div>
+    </div>
+  );
+}
@@ -17,6 +17,7 @@ const statusText: Record<string, string> = {
   completed: "完成",
   failed: "失败",
   cancelled: "已取消",
+  needs_refresh: "链接过期",
 };
 
 export function formatBytes(n: number): string {
@@ -24,7 +24,9 @@ export function Toolbar({
   onStopQueue,
 }: ToolbarProps) {
   const selected = tasks.find((t) => t.id === selectedId);
-  const canResume = selected && (selected.status === "paused" || selected.status === "pending");
+  const canResume =
+    selected &&
+    (selected.status === "paused" || selected.status === "pending" || selected.status === "needs_refresh");
   const canPause = selected && selected.status === "downloading";
   const hasDownloading = tasks.some((t) => t.status === "downloading");
   const hasPausedOrPending = tasks.some(
@@ -4,7 +4,8 @@ export type TaskStatus =
   | "paused"
   | "completed"
   | "failed"
-  | "cancelled";
+  | "cancelled"
+  | "needs_refresh";
 
 export interface TaskInfo {
   id: string;
@@ -17,6 +18,77 @@ export interface TaskInfo {
   error_message: string | null;
   speed_bps: number | null;
   created_at: number;
+  kind: TaskKind;
+  sources: SourceInfo[];
+  source: TaskSource;
+}
+
+/** query_downloads 的排序键 */
+export type TaskSortKey = "added" | "name" | "size" | "progress" | "status" | "speed";
+
+/** 任务检索条件（query_downloads），均可省略；from / to 为创建时间的 Unix 秒 */
+export interface TaskQuery {
+  text?: string;
+  status?: TaskStatus[];
+  category?: string;
+  host?: string;
+  from?: number;
+  to?: number;
+  sort?: TaskSortKey;
+  descending?: boolean;
+  offset?: number;
+  limit?: number;
+}
+
+export interface TaskPage {
+  tasks: TaskInfo[];
+  total: number;
+}
+
+/** 任务从哪里添加，记入下载历史 */
+export type TaskSource = "manual" | "browser" | "import" | "control";
+
+export interface SourceInfo {
+  url: string;
+  priority: number;
+  speed_bps: number | null;
+  failures: number;
+  disabled: string | null;
+}
+
+export interface HlsOptions {
+  max_bandwidth?: number | null;
+  max_height?: number | null;
+}
+
+export interface DashOptions {
+  video_id?: string | null;
+  audio_id?: string | null;
+  max_height?: number | null;
+  no_audio?: boolean;
+}
+
+export type TaskKind =
+  | { type: "file" }
+  | ({ type: "hls" } & HlsOptions)
+  | ({ type: "dash" } & DashOptions);
+
+export interface HlsVariant {
+  url: string;
+  bandwidth: number;
+  resolution: [number, number] | null;
+  codecs: string | null;
+}
+
+export interface DashRepresentation {
+  id: string;
+  track: "video" | "audio" | "text" | "other";
+  mime_type: string | null;
+  codecs: string | null;
+  bandwidth: number;
+  width: number | null;
+  height: number | null;
+  lang: string | null;
 }
 
 export interface ProbeResult {
@@ -24,8 +96,47 @@ export interface ProbeResult {
   total_bytes: number | null;
   suggested_filename: string;
   final_url: string;
+  last_modified?: string | null;
+  etag?: string | null;
+  content_type?: string | null;
+}
+
+export interface SiteRule {
+  /** 主机名，`*.example.com` 匹配其子域名 */
+  host: string;
+  save_dir: string;
+  /** 0 表示使用全局设置 */
+  max_connections: number;
+}
+
+/** 新建任务请求（add_downloads），除 url 外均可省略 */
+export interface IntakeItem {
+  url: string;
+  mirrors?: string[];
+  save_dir?: string | null;
+  filename?: string | null;
+  probe?: ProbeResult | null;
+  /** 探测与下载时附带的请求头，如 [["Cookie", "a=1"]] */
+  headers?: [string, string][];
+}
+
+export interface IntakeOptions {
+  /** 省略时按设置 duplicate_action */
+  duplicate?: "ask" | "skip" | "overwrite" | "rename";
+  start?: boolean;
+  /** 开始下载时再探测，文件名取自地址 */
+  lazy_probe?: boolean;
+  /** 记入下载历史的来源，默认 manual */
+  source?: TaskSource;
 }
 
+/** add_downloads 每条请求的结果 */
+export type IntakeOutcome = { url: string } & (
+  | { result: "added" | "started" | "queued"; id: string }
+  | { result: "skipped" | "duplicate"; existing: string }
+  | { result: "rejected" | "failed"; error: string }
+);
+
 export interface AppSettings {
   default_save_path: string;
   max_connections_per_task: number;
@@ -35,13 +146,164 @@ export interface AppSettings {
   show_start_dialog: boolean;
   show_complete_dialog: boolean;
   duplicate_action: string;
+  categorize_downloads?: boolean;
+  site_rules?: SiteRule[];
   user_agent: string;
   use_last_save_path: boolean;
   proxy_type: string;
+  proxy_scheme: string;
   proxy_host: string;
   proxy_port: number;
+  proxy_username: string;
+  proxy_password: string;
+  proxy_bypass: string;
+  proxy_pac_url: string;
   notification_on_complete: boolean;
   notification_on_fail: boolean;
   timeout_secs: number;
   save_progress_interval_secs?: number;
+  control_api_enabled?: boolean;
+  control_api_port?: number;
+  control_api_token?: string;
+  aria2_rpc_enabled?: boolean;
+  browser_allowed_dirs?: string[];
+  browser_tcp_fallback?: boolean;
+  /** 下载历史保留天数，0 表示不限 */
+  history_retention_days?: number;
+  /** 下载历史最多条数，0 表示不限 */
+  history_max_entries?: number;
+}
+
+/** 站点抓取选项（grab_site），除 url 外均可省略 */
+export interface GrabOptions {
+  url: string;
+  /** 跟随链接的层数，1 只看起始页面上的链接 */
+  depth?: number;
+  same_host?: boolean;
+  respect_robots?: boolean;
+  extensions?: string[];
+  mime_types?: string[];
+  pattern?: string | null;
+  min_size?: number | null;
+  max_size?: number | null;
+  max_pages?: number;
+  max_files?: number;
+  concurrency?: number;
+}
+
+export interface GrabbedFile {
+  url: string;
+  referer: string;
+  filename: string;
+  size: number | null;
+  content_type: string | null;
+}
+
+export interface GrabResult {
+  files: GrabbedFile[];
+  pages: number;
+  skipped: number;
+  errors: string[];
+  cancelled: boolean;
+}
+
+/** grab-progress 事件 */
+export interface GrabProgressEvent {
+  id: string;
+  progress: { pages: number; files: number; pending: number };
+}
+
+/** 导入文件的格式；省略时按内容判断 */
+export type ImportFormat = "multidown" | "url_list" | "aria2" | "har" | "ef2";
+
+/** preview_import 的结果：尚未新建任务 */
+export interface ImportPreview {
+  format: ImportFormat;
+  items: IntakeItem[];
+  warnings: string[];
+  cookies_attached: number;
+  /** 带完整下载状态、可以迁移恢复的条目数 */
+  restorable: number;
+  /** 可迁移任务所在的目录 */
+  directories: string[];
+}
+
+/** export_task_archive 的结果 */
+export interface ArchiveSummary {
+  tasks: number;
+  files: number;
+  /** 正在下载、未打包文件的任务 */
+  skipped: string[];
+}
+
+/** 恢复时的目录映射 */
+export interface DirMapping {
+  from: string;
+  to: string;
+}
+
+/** restore_tasks 的结果 */
+export interface RestoreReport {
+  restored: string[];
+  /** 文件不在或大小不符、将从头下载的任务数 */
+  reset: number;
+  /** 目标位置已有其他文件、改名恢复的任务 */
+  renamed: string[];
+  skipped: string[];
+}
+
+export type HistoryOutcome = "completed" | "failed" | "cancelled";
+
+/** 下载历史中的一条记录 */
+export interface HistoryEntry {
+  id: number;
+  task_id: string;
+  url: string;
+  host: string;
+  filename: string;
+  save_path: string;
+  category: string;
+  size: number;
+  started_at: number;
+  finished_at: number;
+  duration_secs: number;
+  average_speed: number;
+  sha256: string | null;
+  source: TaskSource;
+  outcome: HistoryOutcome;
+  error: string | null;
+}
+
+/** 历史检索条件；from / to 为 Unix 秒 */
+export interface HistoryQuery {
+  text?: string;
+  host?: string;
+  category?: string;
+  outcome?: HistoryOutcome;
+  source?: TaskSource;
+  from?: number;
+  to?: number;
+  offset?: number;
+  limit?: number;
+}
+
+export interface HistoryPage {
+  entries: HistoryEntry[];
+  total: number;
+}
+
+export interface HistoryBucket {
+  count: number;
+  bytes: number;
+}
+
+export interface HistoryStats {
+  total: number;
+  completed: number;
+  failed: number;
+  cancelled: number;
+  success_rate: number;
+  bytes: number;
+  per_day: Record<string, HistoryBucket>;
+  per_host: Record<string, HistoryBucket>;
 }